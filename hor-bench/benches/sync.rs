@@ -0,0 +1,44 @@
+//! Sync throughput and latency for growing fleets against a mock GitHub.
+
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use hor_bench::Fleet;
+use hor_core::Priority;
+
+const FLEET_SIZES: [usize; 3] = [10, 100, 1000];
+
+fn sync(c: &mut Criterion) {
+    let runtime = tokio::runtime::Runtime::new().expect("unable to start runtime");
+    // One fleet for every size, each syncing the first projects of it
+    let fleet = runtime
+        .block_on(Fleet::start(FLEET_SIZES[FLEET_SIZES.len() - 1]))
+        .expect("unable to start fleet");
+
+    let mut group = c.benchmark_group("sync");
+    group.sample_size(10);
+    for size in FLEET_SIZES {
+        let ids = fleet.ids(size);
+        group.throughput(Throughput::Elements(size as u64));
+        group.bench_with_input(BenchmarkId::from_parameter(size), &ids, |b, ids| {
+            b.to_async(&runtime).iter(|| async {
+                let report = fleet
+                    .system
+                    .sync_ids(ids, Priority::Poll)
+                    .await
+                    .expect("sync failed");
+                // A failing project returns early and would flatter the numbers
+                assert_eq!(report.failures().count(), 0, "projects failed to sync");
+                report
+            })
+        });
+    }
+    group.finish();
+
+    let connections = fleet.system.github_connections();
+    println!(
+        "{} requests over {} connections",
+        connections.requests, connections.opened
+    );
+}
+
+criterion_group!(benches, sync);
+criterion_main!(benches);
@@ -0,0 +1,54 @@
+//! Fixtures for measuring syncs without GitHub: a system over a generated
+//! fleet pointed at the fake GitHub of `hor-test`.
+
+use std::collections::HashSet;
+
+use hor_core::{HorSystem, InitializedState};
+use hor_registry::{ProjectId, SourceProject};
+use hor_test::{fixtures::SHA, github_project, MockGithub, StaticRegistry};
+
+const OWNER: &str = "bench";
+const ENV: &str = "prod";
+
+/// `size` projects that are all released already, so a sync exercises the
+/// read path every project goes through on every cycle.
+pub struct Fleet {
+    pub system: HorSystem<InitializedState>,
+    ids: Vec<ProjectId>,
+    /// Kept alive for as long as the system talks to it
+    _github: MockGithub,
+}
+
+impl Fleet {
+    /// Starts the mock and initializes a system against it.
+    pub async fn start(size: usize) -> anyhow::Result<Fleet> {
+        let github = MockGithub::start().await;
+        let projects = (0..size)
+            .map(|index| {
+                let repo = format!("repo-{index}");
+                github.add_repo(OWNER, &repo, SHA);
+                github.set_ref(OWNER, &repo, &format!("tags/{ENV}"), Some(SHA));
+                github_project(OWNER, &repo, ENV)
+            })
+            .collect::<anyhow::Result<Vec<_>>>()?;
+        let ids = projects
+            .iter()
+            .map(|project| match project {
+                SourceProject::Github(project) => project.id(),
+                _ => unreachable!("only GitHub projects are generated"),
+            })
+            .collect();
+
+        let system = hor_test::system(StaticRegistry(projects), &github, serde_json::json!({}))?;
+        Ok(Fleet {
+            system,
+            ids,
+            _github: github,
+        })
+    }
+
+    /// Ids of the first `size` projects.
+    pub fn ids(&self, size: usize) -> HashSet<ProjectId> {
+        self.ids.iter().take(size).cloned().collect()
+    }
+}
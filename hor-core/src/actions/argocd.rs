@@ -0,0 +1,52 @@
+use hor_registry::ArgoCdAction;
+use serde::Deserialize;
+use serde_json::json;
+
+use super::Promotion;
+
+#[derive(Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct ArgoCdConfig {
+    /// Base URL of the ArgoCD server, e.g. `https://argocd.example.com`
+    url: String,
+    /// API token of an account allowed to update the applications
+    token: String,
+}
+
+impl ArgoCdConfig {
+    /// The API token, which errors may echo.
+    pub(crate) fn secrets(&self) -> impl Iterator<Item = &str> {
+        std::iter::once(self.token.as_str())
+    }
+}
+
+/// Sets the Application's `spec.source.targetRevision`.
+pub(super) async fn run(
+    config: &ArgoCdConfig,
+    http: &reqwest::Client,
+    action: &ArgoCdAction,
+    promotion: &Promotion<'_>,
+) -> anyhow::Result<()> {
+    let revision = action
+        .revision
+        .resolve(&promotion.project.env, promotion.to);
+    let patch = json!({ "spec": { "source": { "targetRevision": revision } } });
+
+    let mut request = http
+        .patch(format!(
+            "{}/api/v1/applications/{}",
+            config.url.trim_end_matches('/'),
+            action.application
+        ))
+        .bearer_auth(&config.token)
+        .json(&json!({
+            "name": action.application,
+            "patch": patch.to_string(),
+            "patchType": "merge",
+        }));
+    if let Some(namespace) = &action.app_namespace {
+        request = request.query(&[("appNamespace", namespace)]);
+    }
+    request.send().await?.error_for_status()?;
+    Ok(())
+}
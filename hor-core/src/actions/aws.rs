@@ -0,0 +1,219 @@
+use serde::Deserialize;
+
+/// Credentials of an AWS account, used to sign requests with Signature
+/// Version 4.
+#[derive(Deserialize, Debug, Clone)]
+#[serde(rename_all = "kebab-case")]
+#[cfg_attr(not(feature = "aws"), allow(dead_code))]
+pub struct AwsAccount {
+    region: String,
+    access_key_id: String,
+    secret_access_key: String,
+    /// For temporary credentials, e.g. of an assumed role
+    #[serde(default)]
+    session_token: Option<String>,
+}
+
+impl AwsAccount {
+    /// The secret key and session token, which errors may echo.
+    pub(crate) fn secrets(&self) -> impl Iterator<Item = &str> {
+        std::iter::once(self.secret_access_key.as_str()).chain(self.session_token.as_deref())
+    }
+}
+
+/// Puts one event on `bus`, an EventBridge bus's name or ARN.
+#[cfg(feature = "aws")]
+pub(crate) async fn put_event(
+    account: &AwsAccount,
+    http: &reqwest::Client,
+    bus: &str,
+    source: &str,
+    detail_type: &str,
+    detail: &serde_json::Value,
+) -> anyhow::Result<()> {
+    #[derive(Deserialize)]
+    #[serde(rename_all = "PascalCase")]
+    struct PutEventsOutput {
+        failed_entry_count: usize,
+        entries: Vec<PutEventsEntry>,
+    }
+
+    #[derive(Deserialize)]
+    #[serde(rename_all = "PascalCase")]
+    struct PutEventsEntry {
+        #[serde(default)]
+        error_message: Option<String>,
+    }
+
+    let body = serde_json::json!({
+        "Entries": [{
+            "EventBusName": bus,
+            "Source": source,
+            "DetailType": detail_type,
+            // EventBridge takes the detail as a string of JSON
+            "Detail": detail.to_string(),
+        }],
+    });
+    let output: PutEventsOutput = post(
+        account,
+        http,
+        "events",
+        &[
+            ("content-type", "application/x-amz-json-1.1"),
+            ("x-amz-target", "AWSEvents.PutEvents"),
+        ],
+        body.to_string(),
+    )
+    .await?
+    .json()
+    .await?;
+    // Rejected entries still answer 200
+    if output.failed_entry_count > 0 {
+        let reason = output
+            .entries
+            .into_iter()
+            .find_map(|entry| entry.error_message)
+            .unwrap_or_default();
+        anyhow::bail!("EventBridge rejected the event: {reason}");
+    }
+    Ok(())
+}
+
+/// Publishes `message` to the SNS topic `topic_arn`, with string message
+/// attributes subscriptions can filter on.
+#[cfg(feature = "aws")]
+pub(crate) async fn publish(
+    account: &AwsAccount,
+    http: &reqwest::Client,
+    topic_arn: &str,
+    message: &str,
+    attributes: &[(&str, &str)],
+) -> anyhow::Result<()> {
+    // The serializer isn't Send, so it's done with before awaiting
+    let body = {
+        let mut form = url::form_urlencoded::Serializer::new(String::new());
+        form.append_pair("Action", "Publish")
+            .append_pair("Version", "2010-03-31")
+            .append_pair("TopicArn", topic_arn)
+            .append_pair("Message", message);
+        for (index, (name, value)) in attributes.iter().enumerate() {
+            let entry = format!("MessageAttributes.entry.{}", index + 1);
+            form.append_pair(&format!("{entry}.Name"), name)
+                .append_pair(&format!("{entry}.Value.DataType"), "String")
+                .append_pair(&format!("{entry}.Value.StringValue"), value);
+        }
+        form.finish()
+    };
+    post(
+        account,
+        http,
+        "sns",
+        &[("content-type", "application/x-www-form-urlencoded")],
+        body,
+    )
+    .await?;
+    Ok(())
+}
+
+/// Signs and sends a `POST /` to `service` in the account's region.
+#[cfg(feature = "aws")]
+async fn post(
+    account: &AwsAccount,
+    http: &reqwest::Client,
+    service: &str,
+    headers: &[(&str, &str)],
+    body: String,
+) -> anyhow::Result<reqwest::Response> {
+    let host = format!("{service}.{}.amazonaws.com", account.region);
+    let headers = sign(
+        account,
+        service,
+        &host,
+        headers,
+        body.as_bytes(),
+        chrono::Utc::now(),
+    );
+    let mut request = http.post(format!("https://{host}/")).body(body);
+    for (name, value) in headers {
+        request = request.header(name, value);
+    }
+    Ok(request.send().await?.error_for_status()?)
+}
+
+/// Every header of a `POST /` to `host`: `headers`, which must be
+/// lowercase and sorted, and those of the signature.
+#[cfg(feature = "aws")]
+fn sign(
+    account: &AwsAccount,
+    service: &str,
+    host: &str,
+    headers: &[(&str, &str)],
+    body: &[u8],
+    now: chrono::DateTime<chrono::Utc>,
+) -> Vec<(String, String)> {
+    use ring::{digest, hmac};
+
+    let hex = |bytes: &[u8]| {
+        bytes
+            .iter()
+            .map(|byte| format!("{byte:02x}"))
+            .collect::<String>()
+    };
+    let mac = |key: &[u8], data: &str| {
+        hmac::sign(&hmac::Key::new(hmac::HMAC_SHA256, key), data.as_bytes())
+            .as_ref()
+            .to_vec()
+    };
+
+    let amz_date = now.format("%Y%m%dT%H%M%SZ").to_string();
+    let date = now.format("%Y%m%d").to_string();
+    let mut signed: Vec<(String, String)> = headers
+        .iter()
+        .map(|(name, value)| (name.to_string(), value.to_string()))
+        .collect();
+    signed.push(("host".to_string(), host.to_string()));
+    signed.push(("x-amz-date".to_string(), amz_date.clone()));
+    if let Some(token) = &account.session_token {
+        signed.push(("x-amz-security-token".to_string(), token.clone()));
+    }
+    signed.sort();
+
+    let names = signed
+        .iter()
+        .map(|(name, _)| name.as_str())
+        .collect::<Vec<_>>()
+        .join(";");
+    let canonical_headers: String = signed
+        .iter()
+        .map(|(name, value)| format!("{name}:{}\n", value.trim()))
+        .collect();
+    let canonical = format!(
+        "POST\n/\n\n{canonical_headers}\n{names}\n{}",
+        hex(digest::digest(&digest::SHA256, body).as_ref())
+    );
+    let scope = format!("{date}/{}/{service}/aws4_request", account.region);
+    let to_sign = format!(
+        "AWS4-HMAC-SHA256\n{amz_date}\n{scope}\n{}",
+        hex(digest::digest(&digest::SHA256, canonical.as_bytes()).as_ref())
+    );
+    let key = [account.region.as_str(), service, "aws4_request"]
+        .iter()
+        .fold(
+            mac(
+                format!("AWS4{}", account.secret_access_key).as_bytes(),
+                &date,
+            ),
+            |key, part| mac(&key, part),
+        );
+    let signature = hex(&mac(&key, &to_sign));
+
+    signed.retain(|(name, _)| name != "host");
+    signed.push((
+        "authorization".to_string(),
+        format!(
+            "AWS4-HMAC-SHA256 Credential={}/{scope}, SignedHeaders={names}, Signature={signature}",
+            account.access_key_id
+        ),
+    ));
+    signed
+}
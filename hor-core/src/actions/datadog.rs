@@ -0,0 +1,57 @@
+use hor_registry::DatadogAction;
+use serde::Deserialize;
+use serde_json::json;
+
+use super::Promotion;
+
+#[derive(Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct DatadogAccount {
+    api_key: String,
+    /// e.g. `datadoghq.eu` or `us5.datadoghq.com`
+    #[serde(default = "DatadogAccount::default_site")]
+    site: String,
+}
+
+impl DatadogAccount {
+    /// The API key, which errors may echo.
+    pub(crate) fn secrets(&self) -> impl Iterator<Item = &str> {
+        std::iter::once(self.api_key.as_str())
+    }
+
+    fn default_site() -> String {
+        "datadoghq.com".to_string()
+    }
+}
+
+/// Posts an event whose `version` tag matches the one APM reports, so
+/// deployment tracking lines up with the release.
+pub(super) async fn run(
+    account: &DatadogAccount,
+    http: &reqwest::Client,
+    action: &DatadogAction,
+    promotion: &Promotion<'_>,
+) -> anyhow::Result<()> {
+    let project = promotion.project;
+    let mut tags = vec![
+        format!("service:{}", action.service),
+        format!("env:{}", project.env),
+        format!("version:{}", promotion.to),
+        "source:hands-off-release".to_string(),
+    ];
+    tags.extend(action.tags.iter().cloned());
+
+    http.post(format!("https://api.{}/api/v1/events", account.site))
+        .header("DD-API-KEY", &account.api_key)
+        .json(&json!({
+            "title": format!("Released {} to {}", action.service, project.env),
+            "text": promotion.render("{owner}/{repo} moved to {sha} in {env}"),
+            "tags": tags,
+            "alert_type": "info",
+            "aggregation_key": format!("{}-{}", action.service, project.env),
+        }))
+        .send()
+        .await?
+        .error_for_status()?;
+    Ok(())
+}
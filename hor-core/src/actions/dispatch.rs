@@ -0,0 +1,36 @@
+use anyhow::Context;
+use hor_registry::WorkflowDispatchAction;
+use octocrab::Octocrab;
+use serde_json::{Map, Value};
+
+use super::Promotion;
+
+pub(super) async fn run(
+    octo: &Octocrab,
+    action: &WorkflowDispatchAction,
+    promotion: &Promotion<'_>,
+) -> anyhow::Result<()> {
+    let (owner, repo) = (action.owner.as_str(), action.repo.as_str());
+    let git_ref = match &action.git_ref {
+        Some(git_ref) => promotion.render(git_ref),
+        None => octo
+            .repos(owner, repo)
+            .get()
+            .await
+            .with_context(|| format!("Unable to read {owner}/{repo}"))?
+            .default_branch
+            .with_context(|| format!("{owner}/{repo} has no default branch"))?,
+    };
+    let inputs: Map<String, Value> = action
+        .inputs
+        .iter()
+        .map(|(name, value)| (name.clone(), Value::String(promotion.render(value))))
+        .collect();
+    octo.actions()
+        .create_workflow_dispatch(owner, repo, &action.workflow, git_ref)
+        .inputs(Value::Object(inputs))
+        .send()
+        .await
+        .with_context(|| format!("Unable to dispatch {} of {owner}/{repo}", action.workflow))?;
+    Ok(())
+}
@@ -0,0 +1,24 @@
+use hor_registry::EventBridgeAction;
+
+use super::{
+    aws::{self, AwsAccount},
+    Promotion,
+};
+
+pub(super) async fn run(
+    account: &AwsAccount,
+    http: &reqwest::Client,
+    action: &EventBridgeAction,
+    promotion: &Promotion<'_>,
+) -> anyhow::Result<()> {
+    let detail = promotion.payload(action.detail.as_ref());
+    aws::put_event(
+        account,
+        http,
+        &action.event_bus,
+        &action.source,
+        &action.detail_type,
+        &detail,
+    )
+    .await
+}
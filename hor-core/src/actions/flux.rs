@@ -0,0 +1,56 @@
+use chrono::{DateTime, Utc};
+use hor_registry::{FluxAction, Revision};
+use serde_json::{json, Value};
+
+use super::Promotion;
+
+/// Merge patch pinning the GitRepository to the release and asking Flux,
+/// as of `now`, to reconcile right away instead of at its next interval.
+fn patch(action: &FluxAction, promotion: &Promotion<'_>, now: DateTime<Utc>) -> Value {
+    // Flux picks the most specific ref field set, so clear the others
+    let reference = match action.revision {
+        Revision::Sha => {
+            json!({ "commit": promotion.to, "tag": null, "semver": null, "name": null })
+        }
+        Revision::Tag => json!({
+            "tag": promotion.project.env,
+            "commit": null,
+            "semver": null,
+            "name": null,
+        }),
+    };
+    json!({
+        "metadata": {
+            "annotations": {
+                "reconcile.fluxcd.io/requestedAt": now.to_rfc3339(),
+            },
+        },
+        "spec": { "ref": reference },
+    })
+}
+
+#[cfg(feature = "kubernetes")]
+pub(super) async fn run(
+    client: kube::Client,
+    action: &FluxAction,
+    promotion: &Promotion<'_>,
+    now: DateTime<Utc>,
+) -> anyhow::Result<()> {
+    use kube::{
+        api::{ApiResource, DynamicObject, GroupVersionKind, Patch, PatchParams},
+        Api,
+    };
+
+    let resource = ApiResource::from_gvk_with_plural(
+        &GroupVersionKind::gvk("source.toolkit.fluxcd.io", "v1", "GitRepository"),
+        "gitrepositories",
+    );
+    let api: Api<DynamicObject> = Api::namespaced_with(client, &action.namespace, &resource);
+    api.patch(
+        &action.git_repository,
+        &PatchParams::default(),
+        &Patch::Merge(patch(action, promotion, now)),
+    )
+    .await?;
+    Ok(())
+}
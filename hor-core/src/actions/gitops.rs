@@ -0,0 +1,174 @@
+use anyhow::{bail, Context};
+use hor_registry::GitopsPullRequestAction;
+use octocrab::{
+    models::repos::{Object, Ref},
+    params::repos::Reference,
+    Octocrab,
+};
+use regex::{Captures, Regex};
+use serde_json::json;
+use tracing::info;
+
+use super::Promotion;
+
+/// A file as it is on the branch a pull request will target.
+pub(super) struct BaseFile {
+    pub base: String,
+    pub base_sha: String,
+    pub path: String,
+    /// Blob sha, needed to update the file
+    pub sha: String,
+    pub content: String,
+}
+
+/// A change of a [`BaseFile`] to propose.
+pub(super) struct FileChange<'a> {
+    pub branch: &'a str,
+    pub message: &'a str,
+    pub content: &'a str,
+    pub body: String,
+}
+
+pub(super) async fn run(
+    octo: &Octocrab,
+    action: &GitopsPullRequestAction,
+    promotion: &Promotion<'_>,
+) -> anyhow::Result<()> {
+    let (owner, repo) = (action.owner.as_str(), action.repo.as_str());
+    let pattern = Regex::new(&action.pattern).context("Invalid pattern")?;
+    let revision = action
+        .revision
+        .resolve(&promotion.project.env, promotion.to);
+    let path = promotion.render(&action.path);
+    let file = read_base_file(octo, owner, repo, action.base.as_deref(), &path).await?;
+    let content = &file.content;
+
+    let mut matched = false;
+    let updated = pattern.replace_all(content, |captures: &Captures<'_>| {
+        let whole = &captures[0];
+        match (captures.get(0), captures.get(1)) {
+            (Some(outer), Some(inner)) => {
+                matched = true;
+                let (start, end) = (inner.start() - outer.start(), inner.end() - outer.start());
+                format!("{}{revision}{}", &whole[..start], &whole[end..])
+            }
+            _ => whole.to_string(),
+        }
+    });
+    if !matched {
+        bail!("Pattern does not match anything in {path}");
+    }
+    if updated == *content {
+        info!(path, "GitOps file already at the released revision");
+        return Ok(());
+    }
+
+    let branch = promotion.render(&action.branch);
+    let message = promotion.render(&action.commit_message);
+    let change = FileChange {
+        branch: &branch,
+        message: &message,
+        content: &updated,
+        body: format!(
+            "Released `{}/{}` at `{}` to `{}`.",
+            promotion.project.owner, promotion.project.repo, promotion.to, promotion.project.env
+        ),
+    };
+    let number = open_pull_request(octo, owner, repo, &file, &change).await?;
+    info!(number, "Opened GitOps pull request");
+    Ok(())
+}
+
+/// Reads `path` from `base`, the repository's default branch if `None`.
+pub(super) async fn read_base_file(
+    octo: &Octocrab,
+    owner: &str,
+    repo: &str,
+    base: Option<&str>,
+    path: &str,
+) -> anyhow::Result<BaseFile> {
+    let (base, base_sha) = read_base(octo, owner, repo, base).await?;
+    let file = octo
+        .repos(owner, repo)
+        .get_content()
+        .path(path)
+        .r#ref(&base)
+        .send()
+        .await
+        .with_context(|| format!("Unable to read {path}"))?
+        .take_items()
+        .into_iter()
+        .next()
+        .with_context(|| format!("{path} is not a file"))?;
+    let content = file
+        .decoded_content()
+        .with_context(|| format!("{path} has no content"))?;
+    Ok(BaseFile {
+        base,
+        base_sha,
+        path: path.to_string(),
+        sha: file.sha,
+        content,
+    })
+}
+
+/// The name of `base`, the repository's default branch if `None`, and
+/// the commit it is at.
+pub(super) async fn read_base(
+    octo: &Octocrab,
+    owner: &str,
+    repo: &str,
+    base: Option<&str>,
+) -> anyhow::Result<(String, String)> {
+    let repo_handler = octo.repos(owner, repo);
+    let base = match base {
+        Some(base) => base.to_string(),
+        None => repo_handler
+            .get()
+            .await?
+            .default_branch
+            .with_context(|| format!("{owner}/{repo} has no default branch"))?,
+    };
+    match repo_handler
+        .get_ref(&Reference::Branch(base.clone()))
+        .await?
+        .object
+    {
+        Object::Commit { sha, .. } => Ok((base, sha)),
+        _ => bail!("Base branch {base} does not point at a commit"),
+    }
+}
+
+/// Commits `change` to a new branch off the file's base and opens a pull
+/// request of it, returning its number.
+pub(super) async fn open_pull_request(
+    octo: &Octocrab,
+    owner: &str,
+    repo: &str,
+    file: &BaseFile,
+    change: &FileChange<'_>,
+) -> anyhow::Result<u64> {
+    let (branch, message, path) = (change.branch, change.message, &file.path);
+    octo.post::<_, Ref>(
+        format!("/repos/{owner}/{repo}/git/refs"),
+        Some(&json!({ "ref": format!("refs/heads/{branch}"), "sha": file.base_sha })),
+    )
+    .await
+    .with_context(|| format!("Unable to create branch {branch}"))?;
+    octo.repos(owner, repo)
+        .update_file(path, message, change.content.as_bytes(), &file.sha)
+        .branch(branch)
+        .send()
+        .await
+        .with_context(|| format!("Unable to commit {path}"))?;
+
+    let title = message.lines().next().unwrap_or(message);
+    let pull = octo
+        .pulls(owner, repo)
+        .create(title, branch, &file.base)
+        .body(&change.body)
+        .send()
+        .await
+        .context("Unable to open pull request")?;
+    Ok(pull.number)
+}
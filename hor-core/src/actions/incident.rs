@@ -0,0 +1,131 @@
+use std::collections::BTreeSet;
+
+use hor_registry::{GithubIssueIncident, PagerDutyIncident};
+use octocrab::Octocrab;
+use serde::Deserialize;
+use serde_json::json;
+
+use super::Promotion;
+use crate::github::GitCommit;
+
+const PAGERDUTY_EVENTS: &str = "https://events.pagerduty.com/v2/enqueue";
+
+#[derive(Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct PagerDutyService {
+    /// Integration key of the service's Events API v2 integration
+    routing_key: String,
+}
+
+impl PagerDutyService {
+    /// The routing key, which errors may echo.
+    pub(crate) fn secrets(&self) -> impl Iterator<Item = &str> {
+        std::iter::once(self.routing_key.as_str())
+    }
+}
+
+/// What was rolled back, for the templates.
+pub(super) struct Rollback<'a> {
+    /// The release that was rolled back
+    pub promotion: &'a Promotion<'a>,
+    /// What the env runs again
+    pub from: &'a str,
+    pub reason: &'a str,
+    pub commits: &'a [GitCommit],
+}
+
+impl Rollback<'_> {
+    fn render(&self, template: &str) -> String {
+        // Substituted last, so placeholders quoted by commit summaries stay
+        let rendered = self
+            .promotion
+            .render(template)
+            .replace("{from}", self.from)
+            .replace("{reason}", self.reason);
+        rendered.replace("{commits}", &self.commit_list())
+    }
+
+    fn commit_list(&self) -> String {
+        self.commits
+            .iter()
+            .map(|commit| {
+                let summary = commit.commit.message.lines().next().unwrap_or_default();
+                let sha = commit.sha.get(..7).unwrap_or(&commit.sha);
+                match &commit.author {
+                    Some(author) => format!("- {sha} {summary} (@{})", author.login),
+                    None => format!("- {sha} {summary}"),
+                }
+            })
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+
+    fn authors(&self) -> BTreeSet<&str> {
+        self.commits
+            .iter()
+            .filter_map(|commit| commit.author.as_ref())
+            .map(|author| author.login.as_str())
+            .collect()
+    }
+}
+
+/// Opens an issue in the project's repository, returning its number.
+pub(super) async fn open_issue(
+    github: &Octocrab,
+    incident: &GithubIssueIncident,
+    rollback: &Rollback<'_>,
+) -> anyhow::Result<u64> {
+    let project = rollback.promotion.project;
+    let issue = github
+        .issues(&project.owner, &project.repo)
+        .create(rollback.render(&incident.title))
+        .body(rollback.render(&incident.body))
+        .labels(incident.labels.clone())
+        .send()
+        .await?;
+    Ok(issue.number)
+}
+
+/// Triggers a PagerDuty incident, deduplicated by the rolled back release.
+pub(super) async fn trigger_pagerduty(
+    service: &PagerDutyService,
+    http: &reqwest::Client,
+    incident: &PagerDutyIncident,
+    rollback: &Rollback<'_>,
+) -> anyhow::Result<()> {
+    let promotion = rollback.promotion;
+    let commits: Vec<_> = rollback
+        .commits
+        .iter()
+        .map(|commit| {
+            json!({
+                "sha": commit.sha,
+                "summary": commit.commit.message.lines().next().unwrap_or_default(),
+                "author": commit.author.as_ref().map(|author| &author.login),
+            })
+        })
+        .collect();
+    http.post(PAGERDUTY_EVENTS)
+        .json(&json!({
+            "routing_key": service.routing_key,
+            "event_action": "trigger",
+            "dedup_key": promotion.render("hands-off-release/{owner}/{repo}/{env}/{sha}"),
+            "payload": {
+                "summary": rollback.render("Rolled back {env} of {owner}/{repo} from {short-sha}: {reason}"),
+                "source": promotion.render("{owner}/{repo}"),
+                "severity": incident.severity,
+                "custom_details": {
+                    "env": promotion.project.env,
+                    "rolled-back": promotion.to,
+                    "rolled-back-to": rollback.from,
+                    "reason": rollback.reason,
+                    "commits": commits,
+                    "authors": rollback.authors(),
+                },
+            },
+        }))
+        .send()
+        .await?
+        .error_for_status()?;
+    Ok(())
+}
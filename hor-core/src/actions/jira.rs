@@ -0,0 +1,94 @@
+use anyhow::bail;
+use hor_registry::JiraAction;
+use serde::Deserialize;
+use serde_json::json;
+use tracing::{debug, info};
+
+use super::issue_keys;
+
+#[derive(Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct JiraSite {
+    /// e.g. `https://acme.atlassian.net`
+    url: String,
+    email: String,
+    /// API token of `email`
+    token: String,
+}
+
+impl JiraSite {
+    /// The API token, which errors may echo.
+    pub(crate) fn secrets(&self) -> impl Iterator<Item = &str> {
+        std::iter::once(self.token.as_str())
+    }
+}
+
+#[derive(Deserialize)]
+struct Transitions {
+    transitions: Vec<Transition>,
+}
+
+#[derive(Deserialize)]
+struct Transition {
+    id: String,
+    name: String,
+    to: Status,
+}
+
+#[derive(Deserialize)]
+struct Status {
+    name: String,
+}
+
+/// Applies the transition to every issue offering it; issues that don't
+/// (e.g. because they're already there) are left alone.
+pub(super) async fn run(
+    site: &JiraSite,
+    http: &reqwest::Client,
+    action: &JiraAction,
+    messages: &[String],
+) -> anyhow::Result<()> {
+    let base = site.url.trim_end_matches('/');
+    let mut failures = Vec::new();
+    for issue in issue_keys(messages, &action.projects) {
+        let url = format!("{base}/rest/api/3/issue/{issue}/transitions");
+        let result = async {
+            let available: Transitions = http
+                .get(&url)
+                .basic_auth(&site.email, Some(&site.token))
+                .send()
+                .await?
+                .error_for_status()?
+                .json()
+                .await?;
+            let Some(transition) = available.transitions.into_iter().find(|transition| {
+                transition.name.eq_ignore_ascii_case(&action.transition)
+                    || transition.to.name.eq_ignore_ascii_case(&action.transition)
+            }) else {
+                debug!(issue, "Issue does not offer the transition, skipping");
+                return Ok(());
+            };
+            http.post(&url)
+                .basic_auth(&site.email, Some(&site.token))
+                .json(&json!({ "transition": { "id": transition.id } }))
+                .send()
+                .await?
+                .error_for_status()?;
+            info!(
+                issue,
+                transition = action.transition,
+                "Transitioned Jira issue"
+            );
+            anyhow::Ok(())
+        }
+        .await;
+        if let Err(err) = result {
+            failures.push(format!("{issue}: {err:#}"));
+        }
+    }
+
+    if !failures.is_empty() {
+        bail!("Unable to transition {}", failures.join(", "));
+    }
+    Ok(())
+}
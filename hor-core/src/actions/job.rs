@@ -0,0 +1,65 @@
+use anyhow::Context;
+use hor_registry::KubernetesJobAction;
+use k8s_openapi::api::{batch::v1::Job, core::v1::EnvVar};
+use kube::{
+    api::{Api, PostParams},
+    Client,
+};
+use tracing::info;
+
+use super::Promotion;
+
+/// Renders the template, exposes the release to every container as
+/// `HOR_*` variables and creates the Job.
+pub(super) async fn run(
+    client: Client,
+    action: &KubernetesJobAction,
+    promotion: &Promotion<'_>,
+) -> anyhow::Result<()> {
+    let template = tokio::fs::read_to_string(&action.template)
+        .await
+        .with_context(|| format!("Unable to read {}", action.template))?;
+    let mut job: Job = serde_yaml::from_str(&promotion.render(&template))
+        .with_context(|| format!("{} is not a valid Job", action.template))?;
+
+    let project = promotion.project;
+    let metadata = &mut job.metadata;
+    // A fixed name would make every release after the first collide
+    if metadata.name.is_none() && metadata.generate_name.is_none() {
+        metadata.generate_name = Some(format!("{}-{}-", project.repo, project.env));
+    }
+    metadata.labels.get_or_insert_with(Default::default).insert(
+        "app.kubernetes.io/managed-by".to_string(),
+        "hands-off-release".to_string(),
+    );
+
+    let variables = [
+        ("HOR_OWNER", project.owner.as_str()),
+        ("HOR_REPO", project.repo.as_str()),
+        ("HOR_ENV", project.env.as_str()),
+        ("HOR_SHA", promotion.to),
+    ];
+    if let Some(spec) = job
+        .spec
+        .as_mut()
+        .and_then(|spec| spec.template.spec.as_mut())
+    {
+        for container in &mut spec.containers {
+            let env = container.env.get_or_insert_with(Vec::new);
+            env.extend(variables.iter().map(|(name, value)| EnvVar {
+                name: name.to_string(),
+                value: Some(value.to_string()),
+                ..EnvVar::default()
+            }));
+        }
+    }
+
+    let jobs: Api<Job> = Api::namespaced(client, &action.namespace);
+    let created = jobs.create(&PostParams::default(), &job).await?;
+    info!(
+        namespace = action.namespace,
+        job = created.metadata.name,
+        "Created Kubernetes Job"
+    );
+    Ok(())
+}
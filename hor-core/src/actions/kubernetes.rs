@@ -0,0 +1,29 @@
+use serde::Deserialize;
+
+/// Cluster access for Kubernetes-backed actions. Uses the in-cluster
+/// service account, or the local kubeconfig outside a cluster.
+#[derive(Deserialize, Default)]
+#[serde(rename_all = "kebab-case")]
+#[cfg_attr(not(feature = "kubernetes"), allow(dead_code))]
+pub struct KubernetesConfig {
+    /// Kubeconfig context to use instead of the current one
+    #[serde(default)]
+    context: Option<String>,
+}
+
+#[cfg(feature = "kubernetes")]
+pub(super) async fn client(config: &KubernetesConfig) -> anyhow::Result<kube::Client> {
+    use kube::config::{Config, KubeConfigOptions};
+
+    let config = match &config.context {
+        Some(context) => {
+            Config::from_kubeconfig(&KubeConfigOptions {
+                context: Some(context.clone()),
+                ..KubeConfigOptions::default()
+            })
+            .await?
+        }
+        None => Config::infer().await?,
+    };
+    Ok(kube::Client::try_from(config)?)
+}
@@ -0,0 +1,154 @@
+use anyhow::{bail, Context};
+use hor_registry::LinearAction;
+use serde::{de::DeserializeOwned, Deserialize};
+use serde_json::{json, Value};
+use tracing::{debug, info};
+
+use super::{issue_keys, Promotion};
+
+const API: &str = "https://api.linear.app/graphql";
+
+#[derive(Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct LinearConfig {
+    /// Personal API key or OAuth token
+    api_key: String,
+}
+
+impl LinearConfig {
+    /// The API key, which errors may echo.
+    pub(crate) fn secrets(&self) -> impl Iterator<Item = &str> {
+        std::iter::once(self.api_key.as_str())
+    }
+}
+
+#[derive(Deserialize)]
+struct Response<T> {
+    data: Option<T>,
+    #[serde(default)]
+    errors: Vec<Value>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct IssueData {
+    issue: Option<Issue>,
+    issue_labels: Nodes<Node>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Issue {
+    id: String,
+    label_ids: Vec<String>,
+}
+
+#[derive(Deserialize)]
+struct Nodes<T> {
+    nodes: Vec<T>,
+}
+
+#[derive(Deserialize)]
+struct Node {
+    id: String,
+}
+
+pub(super) async fn run(
+    config: &LinearConfig,
+    http: &reqwest::Client,
+    action: &LinearAction,
+    promotion: &Promotion<'_>,
+    messages: &[String],
+) -> anyhow::Result<()> {
+    let mut failures = Vec::new();
+    for key in issue_keys(messages, &action.teams) {
+        if let Err(err) = update_issue(config, http, action, promotion, &key).await {
+            failures.push(format!("{key}: {err:#}"));
+        }
+    }
+    if !failures.is_empty() {
+        bail!("Unable to update {}", failures.join(", "));
+    }
+    Ok(())
+}
+
+async fn update_issue(
+    config: &LinearConfig,
+    http: &reqwest::Client,
+    action: &LinearAction,
+    promotion: &Promotion<'_>,
+    key: &str,
+) -> anyhow::Result<()> {
+    let data: IssueData = graphql(
+        config,
+        http,
+        "query($id: String!, $label: String!) { \
+            issue(id: $id) { id labelIds } \
+            issueLabels(filter: { name: { eqIgnoreCase: $label } }) { nodes { id } } \
+        }",
+        json!({ "id": key, "label": action.label.as_deref().unwrap_or_default() }),
+    )
+    .await?;
+    let Some(issue) = data.issue else {
+        debug!(key, "No such Linear issue, skipping");
+        return Ok(());
+    };
+
+    if let Some(label) = &action.label {
+        let label_id = data
+            .issue_labels
+            .nodes
+            .into_iter()
+            .next()
+            .map(|label| label.id)
+            .with_context(|| format!("No Linear label {label}"))?;
+        if !issue.label_ids.contains(&label_id) {
+            let mut label_ids = issue.label_ids.clone();
+            label_ids.push(label_id);
+            graphql::<Value>(
+                config,
+                http,
+                "mutation($id: String!, $labelIds: [String!]) { \
+                    issueUpdate(id: $id, input: { labelIds: $labelIds }) { success } \
+                }",
+                json!({ "id": issue.id, "labelIds": label_ids }),
+            )
+            .await?;
+        }
+    }
+
+    if let Some(comment) = &action.comment {
+        graphql::<Value>(
+            config,
+            http,
+            "mutation($issueId: String!, $body: String!) { \
+                commentCreate(input: { issueId: $issueId, body: $body }) { success } \
+            }",
+            json!({ "issueId": issue.id, "body": promotion.render(comment) }),
+        )
+        .await?;
+    }
+    info!(key, "Updated Linear issue");
+    Ok(())
+}
+
+async fn graphql<T: DeserializeOwned>(
+    config: &LinearConfig,
+    http: &reqwest::Client,
+    query: &str,
+    variables: Value,
+) -> anyhow::Result<T> {
+    let response: Response<T> = http
+        .post(API)
+        .header(reqwest::header::AUTHORIZATION, &config.api_key)
+        .json(&json!({ "query": query, "variables": variables }))
+        .send()
+        .await?
+        .error_for_status()?
+        .json()
+        .await?;
+    if !response.errors.is_empty() {
+        bail!("Linear API errors: {}", Value::from(response.errors));
+    }
+    response.data.context("Linear API returned no data")
+}
@@ -0,0 +1,463 @@
+mod argocd;
+pub(crate) mod aws;
+mod datadog;
+mod dispatch;
+#[cfg(feature = "aws")]
+mod eventbridge;
+#[cfg(feature = "kubernetes")]
+mod flux;
+mod gitops;
+mod incident;
+mod jira;
+#[cfg(feature = "kubernetes")]
+mod job;
+mod kubernetes;
+mod linear;
+mod pubsub;
+mod release_notes;
+mod statuspage;
+mod submodule;
+mod terraform;
+mod verification;
+mod version;
+mod webhook;
+
+use std::collections::{BTreeSet, HashMap};
+
+use anyhow::{bail, Context};
+use chrono::{DateTime, Utc};
+use hor_registry::{
+    GithubProject, PostSyncAction, RollbackIncident, StatuspageMaintenance, Verification,
+};
+use octocrab::Octocrab;
+use regex::Regex;
+use serde::Deserialize;
+use serde_json::{json, Value};
+
+use crate::{
+    github::{GitCommit, HorOctocrabExtension},
+    launchdarkly::{self, LaunchDarklyConfig},
+    oci::{self, RegistryCredentials},
+    Clock, Redactor,
+};
+
+pub use argocd::ArgoCdConfig;
+pub use aws::AwsAccount;
+pub use datadog::DatadogAccount;
+pub use incident::PagerDutyService;
+pub use jira::JiraSite;
+pub use kubernetes::KubernetesConfig;
+pub use linear::LinearConfig;
+pub use pubsub::GcpConfig;
+pub use release_notes::{ConfluenceSite, NotionConfig};
+pub use statuspage::StatuspageConfig;
+pub use terraform::TerraformCloudConfig;
+pub use webhook::WebhookTarget;
+
+/// Endpoints and credentials of the systems post-sync actions talk to.
+#[derive(Deserialize, Default)]
+#[serde(rename_all = "kebab-case")]
+pub struct IntegrationsConfig {
+    #[serde(default)]
+    argocd: Option<ArgoCdConfig>,
+    #[serde(default)]
+    terraform_cloud: Option<TerraformCloudConfig>,
+    /// Jira sites by name
+    #[serde(default)]
+    jira: HashMap<String, JiraSite>,
+    #[serde(default)]
+    launchdarkly: Option<LaunchDarklyConfig>,
+    #[serde(default)]
+    linear: Option<LinearConfig>,
+    /// Confluence sites by name
+    #[serde(default)]
+    confluence: HashMap<String, ConfluenceSite>,
+    #[serde(default)]
+    notion: Option<NotionConfig>,
+    /// Datadog accounts by name
+    #[serde(default)]
+    datadog: HashMap<String, DatadogAccount>,
+    #[serde(default)]
+    statuspage: Option<StatuspageConfig>,
+    /// Container registry credentials by host, e.g. `ghcr.io`
+    #[serde(default)]
+    registries: HashMap<String, RegistryCredentials>,
+    #[serde(default)]
+    #[cfg_attr(not(feature = "kubernetes"), allow(dead_code))]
+    kubernetes: KubernetesConfig,
+    /// Webhooks by name
+    #[serde(default)]
+    webhooks: HashMap<String, WebhookTarget>,
+    /// AWS accounts by name
+    #[serde(default)]
+    #[cfg_attr(not(feature = "aws"), allow(dead_code))]
+    aws: HashMap<String, AwsAccount>,
+    #[serde(default)]
+    gcp: GcpConfig,
+    /// PagerDuty services by name
+    #[serde(default)]
+    pagerduty: HashMap<String, PagerDutyService>,
+}
+
+impl IntegrationsConfig {
+    /// Every configured credential, which errors may echo.
+    pub(crate) fn secrets(&self) -> impl Iterator<Item = String> + '_ {
+        let IntegrationsConfig {
+            argocd,
+            terraform_cloud,
+            jira,
+            launchdarkly,
+            linear,
+            confluence,
+            notion,
+            datadog,
+            statuspage,
+            registries,
+            kubernetes: _,
+            webhooks,
+            aws,
+            gcp,
+            pagerduty,
+        } = self;
+        argocd
+            .iter()
+            .flat_map(ArgoCdConfig::secrets)
+            .chain(
+                terraform_cloud
+                    .iter()
+                    .flat_map(TerraformCloudConfig::secrets),
+            )
+            .chain(jira.values().flat_map(JiraSite::secrets))
+            .chain(launchdarkly.iter().flat_map(LaunchDarklyConfig::secrets))
+            .chain(linear.iter().flat_map(LinearConfig::secrets))
+            .chain(confluence.values().flat_map(ConfluenceSite::secrets))
+            .chain(notion.iter().flat_map(NotionConfig::secrets))
+            .chain(datadog.values().flat_map(DatadogAccount::secrets))
+            .chain(statuspage.iter().flat_map(StatuspageConfig::secrets))
+            .chain(registries.values().flat_map(RegistryCredentials::secrets))
+            .chain(webhooks.values().flat_map(WebhookTarget::secrets))
+            .chain(aws.values().flat_map(AwsAccount::secrets))
+            .chain(gcp.secrets())
+            .chain(pagerduty.values().flat_map(PagerDutyService::secrets))
+            .map(str::to_string)
+    }
+}
+
+/// The ref move an action reacts to.
+pub(crate) struct Promotion<'a> {
+    pub project: &'a GithubProject,
+    /// Previously released commit, if the env ref existed
+    pub from: Option<&'a str>,
+    pub to: &'a str,
+}
+
+impl Promotion<'_> {
+    /// Substitutes `{owner}`, `{repo}`, `{env}`, `{sha}` and `{short-sha}`.
+    pub fn render(&self, template: &str) -> String {
+        template
+            .replace("{owner}", &self.project.owner)
+            .replace("{repo}", &self.project.repo)
+            .replace("{env}", &self.project.env)
+            .replace("{sha}", self.to)
+            .replace("{short-sha}", self.to.get(..7).unwrap_or(self.to))
+    }
+
+    /// `template` with every string in it rendered, or the promotion
+    /// itself if there's none.
+    pub fn payload(&self, template: Option<&Value>) -> Value {
+        match template {
+            Some(template) => self.render_value(template),
+            None => json!({
+                "owner": self.project.owner,
+                "repo": self.project.repo,
+                "env": self.project.env,
+                "from": self.from,
+                "to": self.to,
+            }),
+        }
+    }
+
+    fn render_value(&self, template: &Value) -> Value {
+        match template {
+            Value::String(template) => Value::String(self.render(template)),
+            Value::Array(items) => items.iter().map(|item| self.render_value(item)).collect(),
+            Value::Object(fields) => Value::Object(
+                fields
+                    .iter()
+                    .map(|(name, value)| (name.clone(), self.render_value(value)))
+                    .collect(),
+            ),
+            other => other.clone(),
+        }
+    }
+}
+
+pub(crate) struct Integrations {
+    http: reqwest::Client,
+    /// The write client, for actions changing other repositories
+    github: Octocrab,
+    config: IntegrationsConfig,
+    /// Connected on first use, as only some deployments talk to a cluster
+    #[cfg(feature = "kubernetes")]
+    kube: tokio::sync::OnceCell<kube::Client>,
+}
+
+impl Integrations {
+    pub fn new(config: IntegrationsConfig, http: reqwest::Client, github: Octocrab) -> Self {
+        Self {
+            http,
+            github,
+            config,
+            #[cfg(feature = "kubernetes")]
+            kube: tokio::sync::OnceCell::new(),
+        }
+    }
+
+    /// Whether `registry/repository:tag` has been pushed.
+    pub async fn image_exists(
+        &self,
+        registry: &str,
+        repository: &str,
+        tag: &str,
+    ) -> anyhow::Result<bool> {
+        let credentials = self.config.registries.get(registry);
+        oci::manifest_exists(&self.http, credentials, registry, repository, tag).await
+    }
+
+    /// The digest `registry/repository:tag` was pushed as, `None` if it
+    /// hasn't been.
+    pub async fn image_digest(
+        &self,
+        registry: &str,
+        repository: &str,
+        tag: &str,
+    ) -> anyhow::Result<Option<String>> {
+        let credentials = self.config.registries.get(registry);
+        oci::manifest_digest(&self.http, credentials, registry, repository, tag).await
+    }
+
+    pub async fn flag_on(
+        &self,
+        project: &str,
+        flag: &str,
+        environment: &str,
+    ) -> anyhow::Result<bool> {
+        let config = configured(&self.config.launchdarkly, "launchdarkly")?;
+        launchdarkly::flag_on(config, &self.http, project, flag, environment).await
+    }
+
+    /// Polls the project's health URL for the verification window.
+    pub async fn verify(
+        &self,
+        verification: &Verification,
+        promotion: &Promotion<'_>,
+        redactor: &Redactor,
+        clock: &dyn Clock,
+    ) -> anyhow::Result<()> {
+        verification::verify(&self.http, verification, promotion, redactor, clock).await
+    }
+
+    /// Opens the project's maintenance at `now`, returning the incident to
+    /// close.
+    pub async fn open_maintenance(
+        &self,
+        maintenance: &StatuspageMaintenance,
+        promotion: &Promotion<'_>,
+        now: DateTime<Utc>,
+    ) -> anyhow::Result<String> {
+        let config = configured(&self.config.statuspage, "statuspage")?;
+        statuspage::open(config, &self.http, maintenance, promotion, now).await
+    }
+
+    pub async fn close_maintenance(
+        &self,
+        maintenance: &StatuspageMaintenance,
+        incident: &str,
+        promotion: &Promotion<'_>,
+        succeeded: bool,
+    ) -> anyhow::Result<()> {
+        let config = configured(&self.config.statuspage, "statuspage")?;
+        statuspage::close(
+            config,
+            &self.http,
+            maintenance,
+            incident,
+            promotion,
+            succeeded,
+        )
+        .await
+    }
+
+    /// Opens `incident` for `promotion` having been rolled back to `from`,
+    /// saying where it was recorded.
+    pub async fn open_incident(
+        &self,
+        incident: &RollbackIncident,
+        promotion: &Promotion<'_>,
+        from: &str,
+        reason: &str,
+    ) -> anyhow::Result<String> {
+        let commits = self.released_commits(promotion).await?;
+        let rollback = incident::Rollback {
+            promotion,
+            from,
+            reason,
+            commits: &commits,
+        };
+        match incident {
+            RollbackIncident::GithubIssue(issue) => {
+                let number = incident::open_issue(&self.github, issue, &rollback).await?;
+                Ok(format!("issue #{number}"))
+            }
+            RollbackIncident::PagerDuty(pagerduty) => {
+                let service = named(
+                    &self.config.pagerduty,
+                    "PagerDuty service",
+                    &pagerduty.service,
+                )?;
+                incident::trigger_pagerduty(service, &self.http, pagerduty, &rollback).await?;
+                Ok(format!("PagerDuty service {}", pagerduty.service))
+            }
+            _ => bail!("Unsupported rollback incident"),
+        }
+    }
+
+    /// The commits a promotion released, oldest first.
+    async fn released_commits(&self, promotion: &Promotion<'_>) -> anyhow::Result<Vec<GitCommit>> {
+        let (owner, repo) = (&promotion.project.owner, &promotion.project.repo);
+        Ok(match promotion.from {
+            Some(from) => {
+                self.github
+                    .compare(owner, repo, from, promotion.to)
+                    .await?
+                    .commits
+            }
+            None => vec![self.github.commit(owner, repo, promotion.to).await?],
+        })
+    }
+
+    /// Messages of the commits a promotion released.
+    async fn released_messages(&self, promotion: &Promotion<'_>) -> anyhow::Result<Vec<String>> {
+        Ok(self
+            .released_commits(promotion)
+            .await?
+            .into_iter()
+            .map(|commit| commit.commit.message)
+            .collect())
+    }
+
+    /// Runs `action` for `promotion`, released at `now`.
+    pub async fn run(
+        &self,
+        action: &PostSyncAction,
+        promotion: &Promotion<'_>,
+        #[cfg_attr(not(feature = "kubernetes"), allow(unused_variables))] now: DateTime<Utc>,
+    ) -> anyhow::Result<()> {
+        match action {
+            PostSyncAction::Argocd(action) => {
+                let config = configured(&self.config.argocd, "argocd")?;
+                argocd::run(config, &self.http, action, promotion).await
+            }
+            PostSyncAction::GitopsPullRequest(action) => {
+                gitops::run(&self.github, action, promotion).await
+            }
+            PostSyncAction::VersionBump(action) => {
+                let messages = self.released_messages(promotion).await?;
+                version::run(&self.github, action, promotion, &messages).await
+            }
+            PostSyncAction::SubmoduleBump(action) => {
+                submodule::run(&self.github, action, promotion).await
+            }
+            PostSyncAction::TerraformCloud(action) => {
+                let config = configured(&self.config.terraform_cloud, "terraform-cloud")?;
+                terraform::run(config, &self.http, action, promotion).await
+            }
+            PostSyncAction::Jira(action) => {
+                let site = named(&self.config.jira, "Jira site", &action.site)?;
+                let messages = self.released_messages(promotion).await?;
+                jira::run(site, &self.http, action, &messages).await
+            }
+            PostSyncAction::Linear(action) => {
+                let config = configured(&self.config.linear, "linear")?;
+                let messages = self.released_messages(promotion).await?;
+                linear::run(config, &self.http, action, promotion, &messages).await
+            }
+            PostSyncAction::Datadog(action) => {
+                let account = named(&self.config.datadog, "Datadog account", &action.account)?;
+                datadog::run(account, &self.http, action, promotion).await
+            }
+            PostSyncAction::ReleaseNotes(action) => {
+                let commits = self.released_commits(promotion).await?;
+                release_notes::run(&self.config, &self.http, action, promotion, &commits).await
+            }
+            PostSyncAction::WorkflowDispatch(action) => {
+                dispatch::run(&self.github, action, promotion).await
+            }
+            PostSyncAction::Webhook(action) => {
+                let target = named(&self.config.webhooks, "webhook", &action.webhook)?;
+                webhook::run(target, &self.http, action, promotion).await
+            }
+            PostSyncAction::PubSub(action) => {
+                pubsub::run(&self.config.gcp, &self.http, action, promotion).await
+            }
+            #[cfg(feature = "aws")]
+            PostSyncAction::Eventbridge(action) => {
+                let account = named(&self.config.aws, "AWS account", &action.account)?;
+                eventbridge::run(account, &self.http, action, promotion).await
+            }
+            #[cfg(not(feature = "aws"))]
+            PostSyncAction::Eventbridge(_) => bail!("EventBridge actions need the aws feature"),
+            #[cfg(feature = "kubernetes")]
+            PostSyncAction::Flux(action) => {
+                flux::run(self.kube().await?, action, promotion, now).await
+            }
+            #[cfg(not(feature = "kubernetes"))]
+            PostSyncAction::Flux(_) => bail!("Flux actions need the kubernetes feature"),
+            #[cfg(feature = "kubernetes")]
+            PostSyncAction::KubernetesJob(action) => {
+                job::run(self.kube().await?, action, promotion).await
+            }
+            #[cfg(not(feature = "kubernetes"))]
+            PostSyncAction::KubernetesJob(_) => {
+                bail!("Kubernetes Job actions need the kubernetes feature")
+            }
+            other => bail!("Post-sync action {} is not supported", other.name()),
+        }
+    }
+}
+
+#[cfg(feature = "kubernetes")]
+impl Integrations {
+    async fn kube(&self) -> anyhow::Result<kube::Client> {
+        self.kube
+            .get_or_try_init(|| kubernetes::client(&self.config.kubernetes))
+            .await
+            .cloned()
+    }
+}
+
+/// Issue keys such as `PAY-123` (Jira and Linear share the format)
+/// mentioned in `messages`, limited to `prefixes` unless it's empty.
+fn issue_keys(messages: &[String], prefixes: &[String]) -> BTreeSet<String> {
+    let key = Regex::new(r"\b([A-Z][A-Z0-9_]+)-[0-9]+\b").expect("valid regex");
+    messages
+        .iter()
+        .flat_map(|message| key.captures_iter(message))
+        .filter(|captures| {
+            prefixes.is_empty() || prefixes.iter().any(|prefix| prefix == &captures[1])
+        })
+        .map(|captures| captures[0].to_string())
+        .collect()
+}
+
+fn configured<'a, T>(config: &'a Option<T>, name: &str) -> anyhow::Result<&'a T> {
+    config
+        .as_ref()
+        .with_context(|| format!("No {name} integration is configured"))
+}
+
+fn named<'a, T>(configs: &'a HashMap<String, T>, what: &str, name: &str) -> anyhow::Result<&'a T> {
+    configs
+        .get(name)
+        .with_context(|| format!("No {what} {name} is configured"))
+}
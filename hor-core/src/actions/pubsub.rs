@@ -0,0 +1,73 @@
+use anyhow::Context;
+use base64::{engine::general_purpose::STANDARD, Engine};
+use hor_registry::PubSubAction;
+use serde::Deserialize;
+use serde_json::json;
+
+use super::Promotion;
+
+/// Token of the instance's service account, on GCE, GKE and Cloud Run.
+const METADATA_TOKEN_URL: &str =
+    "http://metadata.google.internal/computeMetadata/v1/instance/service-accounts/default/token";
+
+#[derive(Deserialize, Default)]
+#[serde(rename_all = "kebab-case")]
+pub struct GcpConfig {
+    /// OAuth access token allowed to publish; the metadata server's if
+    /// unset
+    #[serde(default)]
+    access_token: Option<String>,
+}
+
+impl GcpConfig {
+    /// The access token, which errors may echo.
+    pub(crate) fn secrets(&self) -> impl Iterator<Item = &str> {
+        self.access_token.as_deref().into_iter()
+    }
+}
+
+#[derive(Deserialize)]
+struct MetadataToken {
+    access_token: String,
+}
+
+pub(super) async fn run(
+    config: &GcpConfig,
+    http: &reqwest::Client,
+    action: &PubSubAction,
+    promotion: &Promotion<'_>,
+) -> anyhow::Result<()> {
+    let token = match &config.access_token {
+        Some(token) => token.clone(),
+        None => {
+            let token: MetadataToken = http
+                .get(METADATA_TOKEN_URL)
+                .header("Metadata-Flavor", "Google")
+                .send()
+                .await
+                .and_then(|response| response.error_for_status())
+                .context("Unable to get a token from the metadata server")?
+                .json()
+                .await?;
+            token.access_token
+        }
+    };
+    let data = promotion.payload(action.data.as_ref()).to_string();
+    let attributes: serde_json::Map<_, _> = action
+        .attributes
+        .iter()
+        .map(|(name, value)| (name.clone(), json!(promotion.render(value))))
+        .collect();
+    http.post(format!(
+        "https://pubsub.googleapis.com/v1/projects/{}/topics/{}:publish",
+        action.project, action.topic
+    ))
+    .bearer_auth(token)
+    .json(&json!({
+        "messages": [{ "data": STANDARD.encode(data), "attributes": attributes }],
+    }))
+    .send()
+    .await?
+    .error_for_status()?;
+    Ok(())
+}
@@ -0,0 +1,165 @@
+use hor_registry::{ReleaseNotesAction, ReleaseNotesDestination};
+use serde::Deserialize;
+use serde_json::{json, Value};
+use tracing::info;
+
+use super::{configured, named, IntegrationsConfig, Promotion};
+use crate::github::GitCommit;
+
+const NOTION_API: &str = "https://api.notion.com/v1";
+const NOTION_VERSION: &str = "2022-06-28";
+/// Notion takes at most this many blocks per request
+const NOTION_MAX_BLOCKS: usize = 100;
+
+#[derive(Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct ConfluenceSite {
+    /// e.g. `https://acme.atlassian.net`
+    url: String,
+    email: String,
+    /// API token of `email`
+    token: String,
+}
+
+impl ConfluenceSite {
+    /// The API token, which errors may echo.
+    pub(crate) fn secrets(&self) -> impl Iterator<Item = &str> {
+        std::iter::once(self.token.as_str())
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct NotionConfig {
+    /// Internal integration secret
+    token: String,
+}
+
+impl NotionConfig {
+    /// The integration secret, which errors may echo.
+    pub(crate) fn secrets(&self) -> impl Iterator<Item = &str> {
+        std::iter::once(self.token.as_str())
+    }
+}
+
+/// One line of the notes: a released commit.
+struct Entry {
+    short_sha: String,
+    summary: String,
+    author: Option<String>,
+}
+
+impl Entry {
+    fn text(&self) -> String {
+        match &self.author {
+            Some(author) => format!("{} ({}, @{author})", self.summary, self.short_sha),
+            None => format!("{} ({})", self.summary, self.short_sha),
+        }
+    }
+}
+
+pub(super) async fn run(
+    config: &IntegrationsConfig,
+    http: &reqwest::Client,
+    action: &ReleaseNotesAction,
+    promotion: &Promotion<'_>,
+    commits: &[GitCommit],
+) -> anyhow::Result<()> {
+    let title = promotion.render(&action.title);
+    let intro = promotion.render("Released {owner}/{repo} at {sha} to {env}.");
+    // Newest first, as changelogs read
+    let entries: Vec<_> = commits
+        .iter()
+        .rev()
+        .map(|commit| Entry {
+            short_sha: commit.sha.get(..7).unwrap_or(&commit.sha).to_string(),
+            summary: commit
+                .commit
+                .message
+                .lines()
+                .next()
+                .unwrap_or_default()
+                .to_string(),
+            author: commit.author.as_ref().map(|account| account.login.clone()),
+        })
+        .collect();
+
+    match &action.destination {
+        ReleaseNotesDestination::Confluence {
+            site,
+            space,
+            parent,
+        } => {
+            let site = named(&config.confluence, "Confluence site", site)?;
+            let mut body = format!("<p>{}</p><ul>", escape(&intro));
+            for entry in &entries {
+                body.push_str(&format!("<li>{}</li>", escape(&entry.text())));
+            }
+            body.push_str("</ul>");
+            let mut page = json!({
+                "type": "page",
+                "title": title,
+                "space": { "key": space },
+                "body": { "storage": { "value": body, "representation": "storage" } },
+            });
+            if let Some(parent) = parent {
+                page["ancestors"] = json!([{ "id": parent }]);
+            }
+            http.post(format!(
+                "{}/wiki/rest/api/content",
+                site.url.trim_end_matches('/')
+            ))
+            .basic_auth(&site.email, Some(&site.token))
+            .json(&page)
+            .send()
+            .await?
+            .error_for_status()?;
+            info!(title, space, "Published release notes to Confluence");
+        }
+        ReleaseNotesDestination::Notion {
+            database,
+            title_property,
+        } => {
+            let notion = configured(&config.notion, "notion")?;
+            let block = |kind: &str, text: &str| -> Value {
+                json!({
+                    "object": "block",
+                    "type": kind,
+                    kind: { "rich_text": [{ "type": "text", "text": { "content": text } }] },
+                })
+            };
+            let mut children = vec![block("paragraph", &intro)];
+            children.extend(
+                entries
+                    .iter()
+                    .take(NOTION_MAX_BLOCKS - 1)
+                    .map(|entry| block("bulleted_list_item", &entry.text())),
+            );
+            http.post(format!("{NOTION_API}/pages"))
+                .bearer_auth(&notion.token)
+                .header("Notion-Version", NOTION_VERSION)
+                .json(&json!({
+                    "parent": { "database_id": database },
+                    "properties": {
+                        title_property.as_str(): {
+                            "title": [{ "type": "text", "text": { "content": title } }],
+                        },
+                    },
+                    "children": children,
+                }))
+                .send()
+                .await?
+                .error_for_status()?;
+            info!(title, database, "Published release notes to Notion");
+        }
+    }
+    Ok(())
+}
+
+/// Escapes `text` for Confluence's XHTML storage format.
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
@@ -0,0 +1,112 @@
+use std::collections::HashMap;
+
+use chrono::{DateTime, Duration, Utc};
+use hor_registry::StatuspageMaintenance;
+use serde::Deserialize;
+use serde_json::json;
+
+use super::Promotion;
+
+const API: &str = "https://api.statuspage.io/v1";
+
+/// How long a maintenance is scheduled for; it's closed as soon as the
+/// ref has moved.
+const EXPECTED_DURATION: Duration = Duration::minutes(30);
+
+#[derive(Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct StatuspageConfig {
+    api_key: String,
+}
+
+impl StatuspageConfig {
+    /// The API key, which errors may echo.
+    pub(crate) fn secrets(&self) -> impl Iterator<Item = &str> {
+        std::iter::once(self.api_key.as_str())
+    }
+}
+
+#[derive(Deserialize)]
+struct Incident {
+    id: String,
+}
+
+/// Opens an in-progress maintenance starting `now`, returning its
+/// incident id.
+pub(super) async fn open(
+    config: &StatuspageConfig,
+    http: &reqwest::Client,
+    maintenance: &StatuspageMaintenance,
+    promotion: &Promotion<'_>,
+    now: DateTime<Utc>,
+) -> anyhow::Result<String> {
+    let incident: Incident = http
+        .post(format!("{API}/pages/{}/incidents", maintenance.page_id))
+        .header(
+            reqwest::header::AUTHORIZATION,
+            format!("OAuth {}", config.api_key),
+        )
+        .json(&json!({
+            "incident": {
+                "name": promotion.render(&maintenance.name),
+                "status": "in_progress",
+                "impact_override": "maintenance",
+                "scheduled_for": now,
+                "scheduled_until": now + EXPECTED_DURATION,
+                "body": promotion.render("Releasing {owner}/{repo} {short-sha} to {env}."),
+                "component_ids": maintenance.component_ids,
+                "components": statuses(maintenance, "under_maintenance"),
+            }
+        }))
+        .send()
+        .await?
+        .error_for_status()?
+        .json()
+        .await?;
+    Ok(incident.id)
+}
+
+/// Completes the maintenance and puts its components back in service.
+pub(super) async fn close(
+    config: &StatuspageConfig,
+    http: &reqwest::Client,
+    maintenance: &StatuspageMaintenance,
+    incident: &str,
+    promotion: &Promotion<'_>,
+    succeeded: bool,
+) -> anyhow::Result<()> {
+    let body = match succeeded {
+        true => promotion.render("Released {owner}/{repo} {short-sha} to {env}."),
+        false => promotion.render("Release of {owner}/{repo} {short-sha} to {env} failed."),
+    };
+    http.patch(format!(
+        "{API}/pages/{}/incidents/{incident}",
+        maintenance.page_id
+    ))
+    .header(
+        reqwest::header::AUTHORIZATION,
+        format!("OAuth {}", config.api_key),
+    )
+    .json(&json!({
+        "incident": {
+            "status": "completed",
+            "body": body,
+            "components": statuses(maintenance, "operational"),
+        }
+    }))
+    .send()
+    .await?
+    .error_for_status()?;
+    Ok(())
+}
+
+fn statuses<'a>(
+    maintenance: &'a StatuspageMaintenance,
+    status: &'a str,
+) -> HashMap<&'a str, &'a str> {
+    maintenance
+        .component_ids
+        .iter()
+        .map(|id| (id.as_str(), status))
+        .collect()
+}
@@ -0,0 +1,115 @@
+use anyhow::{bail, Context};
+use hor_registry::SubmoduleBumpAction;
+use octocrab::{models::repos::Ref, Octocrab};
+use serde::Deserialize;
+use serde_json::json;
+use tracing::info;
+
+use super::{gitops, Promotion};
+
+/// Git's file mode of a submodule, a commit in the tree.
+const GITLINK: &str = "160000";
+
+#[derive(Deserialize)]
+struct Content {
+    r#type: String,
+    sha: String,
+}
+
+#[derive(Deserialize)]
+struct Sha {
+    sha: String,
+}
+
+#[derive(Deserialize)]
+struct GitCommit {
+    tree: Sha,
+}
+
+pub(super) async fn run(
+    octo: &Octocrab,
+    action: &SubmoduleBumpAction,
+    promotion: &Promotion<'_>,
+) -> anyhow::Result<()> {
+    let (owner, repo, path) = (
+        action.owner.as_str(),
+        action.repo.as_str(),
+        action.path.as_str(),
+    );
+    let (base, base_sha) = gitops::read_base(octo, owner, repo, action.base.as_deref()).await?;
+    let current: Content = octo
+        .get(
+            format!("/repos/{owner}/{repo}/contents/{path}"),
+            Some(&json!({ "ref": base })),
+        )
+        .await
+        .with_context(|| format!("Unable to read {path}"))?;
+    if current.r#type != "submodule" {
+        bail!(
+            "{path} of {owner}/{repo} is a {}, not a submodule",
+            current.r#type
+        );
+    }
+    if current.sha == promotion.to {
+        info!(path, "Submodule already at the released commit");
+        return Ok(());
+    }
+
+    let base_commit: GitCommit = octo
+        .get(
+            format!("/repos/{owner}/{repo}/git/commits/{base_sha}"),
+            None::<&()>,
+        )
+        .await
+        .context("Unable to read the base commit")?;
+    let tree: Sha = octo
+        .post(
+            format!("/repos/{owner}/{repo}/git/trees"),
+            Some(&json!({
+                "base_tree": base_commit.tree.sha,
+                "tree": [{ "path": path, "mode": GITLINK, "type": "commit", "sha": promotion.to }],
+            })),
+        )
+        .await
+        .context("Unable to create tree")?;
+    let message = promotion.render(&action.commit_message);
+    let commit: Sha = octo
+        .post(
+            format!("/repos/{owner}/{repo}/git/commits"),
+            Some(&json!({ "message": message, "tree": tree.sha, "parents": [base_sha] })),
+        )
+        .await
+        .context("Unable to create commit")?;
+
+    if action.push {
+        octo.patch::<Ref, _, _>(
+            format!("/repos/{owner}/{repo}/git/refs/heads/{base}"),
+            Some(&json!({ "sha": commit.sha, "force": false })),
+        )
+        .await
+        .with_context(|| format!("Unable to push to {base}"))?;
+        info!(base, sha = commit.sha, "Pushed submodule bump");
+        return Ok(());
+    }
+
+    let branch = promotion.render(&action.branch);
+    octo.post::<_, Ref>(
+        format!("/repos/{owner}/{repo}/git/refs"),
+        Some(&json!({ "ref": format!("refs/heads/{branch}"), "sha": commit.sha })),
+    )
+    .await
+    .with_context(|| format!("Unable to create branch {branch}"))?;
+    let title = message.lines().next().unwrap_or(&message);
+    let pull = octo
+        .pulls(owner, repo)
+        .create(title, &branch, &base)
+        .body(format!(
+            "Points `{path}` at `{}`, as released to `{}`.",
+            promotion.to, promotion.project.env
+        ))
+        .send()
+        .await
+        .context("Unable to open pull request")?;
+    info!(number = pull.number, "Opened submodule bump pull request");
+    Ok(())
+}
@@ -0,0 +1,59 @@
+use hor_registry::TerraformCloudAction;
+use serde::Deserialize;
+use serde_json::json;
+
+use super::Promotion;
+
+#[derive(Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct TerraformCloudConfig {
+    /// Terraform Enterprise base URL
+    #[serde(default = "TerraformCloudConfig::default_url")]
+    url: String,
+    /// Team or user token allowed to queue runs
+    token: String,
+}
+
+impl TerraformCloudConfig {
+    /// The API token, which errors may echo.
+    pub(crate) fn secrets(&self) -> impl Iterator<Item = &str> {
+        std::iter::once(self.token.as_str())
+    }
+
+    fn default_url() -> String {
+        "https://app.terraform.io".to_string()
+    }
+}
+
+pub(super) async fn run(
+    config: &TerraformCloudConfig,
+    http: &reqwest::Client,
+    action: &TerraformCloudAction,
+    promotion: &Promotion<'_>,
+) -> anyhow::Result<()> {
+    let revision = action
+        .revision
+        .resolve(&promotion.project.env, promotion.to);
+    let body = json!({
+        "data": {
+            "type": "runs",
+            "attributes": {
+                "message": promotion.render("Released {owner}/{repo} {short-sha} to {env}"),
+                // Run variable values are HCL, so strings need quoting
+                "variables": [{ "key": action.variable, "value": json!(revision).to_string() }],
+            },
+            "relationships": {
+                "workspace": { "data": { "type": "workspaces", "id": action.workspace_id } },
+            },
+        },
+    });
+
+    http.post(format!("{}/api/v2/runs", config.url.trim_end_matches('/')))
+        .bearer_auth(&config.token)
+        .header(reqwest::header::CONTENT_TYPE, "application/vnd.api+json")
+        .body(body.to_string())
+        .send()
+        .await?
+        .error_for_status()?;
+    Ok(())
+}
@@ -0,0 +1,51 @@
+use std::time::Duration;
+
+use anyhow::bail;
+use hor_registry::Verification;
+use tracing::{debug, info};
+
+use super::Promotion;
+use crate::{Clock, Redactor};
+
+/// Polls the health URL until the window is over, failing once it was
+/// unhealthy `failures` times in a row.
+pub(super) async fn verify(
+    http: &reqwest::Client,
+    verification: &Verification,
+    promotion: &Promotion<'_>,
+    redactor: &Redactor,
+    clock: &dyn Clock,
+) -> anyhow::Result<()> {
+    let url = promotion.render(&verification.url);
+    let interval = Duration::from_secs(verification.interval_secs.max(1));
+    let step = chrono::Duration::seconds(interval.as_secs() as i64);
+    let deadline = clock.now() + chrono::Duration::seconds(verification.window_secs as i64);
+    let mut failures = 0;
+    loop {
+        match http
+            .get(&url)
+            .send()
+            .await
+            .and_then(|response| response.error_for_status())
+        {
+            Ok(_) => failures = 0,
+            Err(err) => {
+                failures += 1;
+                debug!(
+                    url = redactor.redact(&url),
+                    failures,
+                    err = %redactor.debug(&err),
+                    "Health check failed"
+                );
+                if failures >= verification.failures {
+                    bail!("{url} was unhealthy {failures} times in a row: {err}");
+                }
+            }
+        }
+        if clock.now() + step > deadline {
+            info!(url = redactor.redact(&url), "Release verified");
+            return Ok(());
+        }
+        clock.sleep(interval).await;
+    }
+}
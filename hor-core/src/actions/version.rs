@@ -0,0 +1,85 @@
+use std::ops::Range;
+
+use anyhow::Context;
+use hor_registry::{VersionBumpAction, VersionFormat};
+use octocrab::Octocrab;
+use regex::Regex;
+use tracing::info;
+
+use super::{
+    gitops::{self, FileChange},
+    Promotion,
+};
+use crate::versions::{conventional_bump, Version};
+
+pub(super) async fn run(
+    octo: &Octocrab,
+    action: &VersionBumpAction,
+    promotion: &Promotion<'_>,
+    messages: &[String],
+) -> anyhow::Result<()> {
+    let Some(bump) = conventional_bump(messages) else {
+        info!("No released commit calls for a new version");
+        return Ok(());
+    };
+    let (owner, repo) = (&promotion.project.owner, &promotion.project.repo);
+    let format = action.format.unwrap_or_else(|| guess_format(&action.path));
+    let file =
+        gitops::read_base_file(octo, owner, repo, action.base.as_deref(), &action.path).await?;
+    let range = locate(format, &file.content)
+        .with_context(|| format!("No version found in {}", action.path))?;
+    let current = Version::parse(&file.content[range.clone()])?;
+    let next = current.bump(bump);
+
+    let mut content = file.content.clone();
+    content.replace_range(range, &next.to_string());
+    let render =
+        |template: &str| promotion.render(&template.replace("{version}", &next.to_string()));
+    let (branch, message) = (render(&action.branch), render(&action.commit_message));
+    let change = FileChange {
+        branch: &branch,
+        message: &message,
+        content: &content,
+        body: format!(
+            "Bumps `{}` from {current} to {next}, as released to `{}` at `{}`.",
+            action.path, promotion.project.env, promotion.to
+        ),
+    };
+    let number = gitops::open_pull_request(octo, owner, repo, &file, &change).await?;
+    info!(number, %current, %next, "Opened version bump pull request");
+    Ok(())
+}
+
+fn guess_format(path: &str) -> VersionFormat {
+    let name = path.rsplit('/').next().unwrap_or(path);
+    match name {
+        "Cargo.toml" => VersionFormat::Cargo,
+        "package.json" => VersionFormat::PackageJson,
+        _ => VersionFormat::Plain,
+    }
+}
+
+/// Where the version is written in `content`.
+fn locate(format: VersionFormat, content: &str) -> Option<Range<usize>> {
+    match format {
+        VersionFormat::Cargo => {
+            let start = content.find("[package]")?;
+            let table = &content[start..];
+            // Up to the next table
+            let end = table[1..].find("\n[").map_or(table.len(), |end| end + 1);
+            let version = Regex::new(r#"(?m)^\s*version\s*=\s*"([^"]*)""#).expect("valid regex");
+            let found = version.captures(&table[..end])?.get(1)?;
+            Some(start + found.start()..start + found.end())
+        }
+        VersionFormat::PackageJson => {
+            let version = Regex::new(r#""version"\s*:\s*"([^"]*)""#).expect("valid regex");
+            let found = version.captures(content)?.get(1)?;
+            Some(found.range())
+        }
+        VersionFormat::Plain => {
+            let start = content.len() - content.trim_start().len();
+            let end = content.trim_end().len();
+            (start < end).then_some(start..end)
+        }
+    }
+}
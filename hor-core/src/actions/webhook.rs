@@ -0,0 +1,38 @@
+use std::collections::HashMap;
+
+use hor_registry::WebhookAction;
+use serde::Deserialize;
+
+use super::Promotion;
+
+#[derive(Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct WebhookTarget {
+    url: String,
+    /// Sent with every request, e.g. `Authorization`
+    #[serde(default)]
+    headers: HashMap<String, String>,
+}
+
+impl WebhookTarget {
+    /// The header values, which errors may echo.
+    pub(crate) fn secrets(&self) -> impl Iterator<Item = &str> {
+        self.headers.values().map(String::as_str)
+    }
+}
+
+pub(super) async fn run(
+    target: &WebhookTarget,
+    http: &reqwest::Client,
+    action: &WebhookAction,
+    promotion: &Promotion<'_>,
+) -> anyhow::Result<()> {
+    let mut request = http
+        .post(&target.url)
+        .json(&promotion.payload(action.payload.as_ref()));
+    for (name, value) in &target.headers {
+        request = request.header(name, value);
+    }
+    request.send().await?.error_for_status()?;
+    Ok(())
+}
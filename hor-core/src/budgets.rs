@@ -0,0 +1,205 @@
+//! Promotion budgets: how often a project, or every project of an env
+//! together, may be promoted. Promotions over budget are held back until
+//! there's room again.
+
+use chrono::{DateTime, Duration, Utc};
+use hor_registry::{GithubProject, ProjectId, PromotionBudget, Registry};
+use hor_state::{ActionReport, PROMOTION_RETENTION_DAYS};
+use tracing::error;
+
+use crate::{HorSystem, InitializedState};
+
+/// Slots taken by syncs that haven't finished yet, so concurrent syncs
+/// don't spend the same slot twice.
+#[derive(Default)]
+pub(crate) struct BudgetClaims {
+    claims: tokio::sync::Mutex<Vec<Claim>>,
+}
+
+struct Claim {
+    id: ProjectId,
+    env: String,
+    at: DateTime<Utc>,
+}
+
+impl<R: Registry + ?Sized> HorSystem<InitializedState, R> {
+    /// Claims a slot of the budgets of `project` and its env, or tells why
+    /// there is none. The claim is settled by [`Self::settle_budget`].
+    pub(crate) async fn claim_budget(
+        &self,
+        id: &ProjectId,
+        project: &GithubProject,
+    ) -> anyhow::Result<Result<(), String>> {
+        let env = project.env.as_str();
+        let env_budget = self.state.promotion_budgets.get(env);
+        if project.budget.is_none() && env_budget.is_none() {
+            return Ok(Ok(()));
+        }
+
+        let mut claims = self.state.budget_claims.claims.lock().await;
+        let now = self.state.clock.now();
+        let since = now - Duration::days(PROMOTION_RETENTION_DAYS);
+        let mut promotions = self.state.store.promotions_since(env, since).await?;
+        promotions.extend(
+            claims
+                .iter()
+                .filter(|claim| claim.env == env)
+                .map(|claim| (claim.id.clone(), claim.at)),
+        );
+        promotions.sort_by_key(|(_, at)| *at);
+        let all: Vec<_> = promotions.iter().map(|(_, at)| *at).collect();
+        let own: Vec<_> = promotions
+            .iter()
+            .filter(|(project, _)| project == id)
+            .map(|(_, at)| *at)
+            .collect();
+
+        let budgets = [
+            (project.budget.as_ref(), &own, "project"),
+            (env_budget, &all, "env"),
+        ];
+        for (budget, promotions, scope) in budgets {
+            let Some(budget) = budget else { continue };
+            if let Some(until) = next_slot(budget, promotions, now) {
+                return Ok(Err(format!(
+                    "{scope} budget of {env} is spent until {}",
+                    until.format("%Y-%m-%d %H:%M UTC")
+                )));
+            }
+        }
+        claims.push(Claim {
+            id: id.clone(),
+            env: env.to_string(),
+            at: now,
+        });
+        Ok(Ok(()))
+    }
+
+    /// Spends the claim of `id`, if it has one, when the env ref moved,
+    /// and gives it back otherwise. Shadow syncs spend nothing. Reported
+    /// on if the promotion couldn't be recorded, as the budget then no
+    /// longer counts it.
+    pub(crate) async fn settle_budget(
+        &self,
+        id: &ProjectId,
+        env: &str,
+        moved: bool,
+    ) -> Option<ActionReport> {
+        let mut claims = self.state.budget_claims.claims.lock().await;
+        let index = claims
+            .iter()
+            .position(|claim| &claim.id == id && claim.env == env)?;
+        let claim = claims.remove(index);
+        if !moved || self.state.shadow {
+            return None;
+        }
+        let err = self
+            .state
+            .store
+            .record_promotion(id, env, claim.at)
+            .await
+            .err()?;
+        error!(%id, err = %self.state.redactor.debug(&err), "Unable to record promotion");
+        Some(ActionReport {
+            action: "record promotion".to_string(),
+            error: Some(format!("{:#}", anyhow::Error::new(err))),
+            simulated: false,
+        })
+    }
+}
+
+/// When `budget` has room again after `promotions`, oldest first; `None`
+/// if it has room at `now`.
+fn next_slot(
+    budget: &PromotionBudget,
+    promotions: &[DateTime<Utc>],
+    now: DateTime<Utc>,
+) -> Option<DateTime<Utc>> {
+    let mut next = None;
+    if let (Some(interval), Some(last)) = (budget.min_interval_secs, promotions.last()) {
+        let at = *last + Duration::seconds(interval as i64);
+        if at > now {
+            next = Some(at);
+        }
+    }
+    if let Some(max) = budget.max_per_day {
+        let day: Vec<_> = promotions
+            .iter()
+            .filter(|at| **at > now - Duration::days(1))
+            .collect();
+        if day.len() >= max {
+            // Room again once enough of the day's promotions age out
+            let at = day.get(day.len() - max).map_or(now, |at| **at) + Duration::days(1);
+            next = next.max(Some(at));
+        }
+    }
+    next
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::TimeZone;
+
+    use super::*;
+
+    fn now() -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(2024, 1, 15, 12, 0, 0).unwrap()
+    }
+
+    fn hours_ago(hours: &[i64]) -> Vec<DateTime<Utc>> {
+        hours
+            .iter()
+            .map(|hours| now() - Duration::hours(*hours))
+            .collect()
+    }
+
+    fn budget(max_per_day: Option<usize>, min_interval_secs: Option<u64>) -> PromotionBudget {
+        PromotionBudget {
+            max_per_day,
+            min_interval_secs,
+        }
+    }
+
+    #[test]
+    fn no_budget_always_has_room() {
+        assert_eq!(
+            next_slot(&budget(None, None), &hours_ago(&[2, 1]), now()),
+            None
+        );
+        assert_eq!(next_slot(&budget(Some(1), Some(60)), &[], now()), None);
+    }
+
+    #[test]
+    fn waits_the_interval_after_the_last_promotion() {
+        let budget = budget(None, Some(2 * 60 * 60));
+        assert_eq!(
+            next_slot(&budget, &hours_ago(&[5, 1]), now()),
+            Some(now() + Duration::hours(1))
+        );
+        assert_eq!(next_slot(&budget, &hours_ago(&[5, 3]), now()), None);
+    }
+
+    #[test]
+    fn waits_for_enough_of_the_day_to_age_out() {
+        let budget = budget(Some(2), None);
+        assert_eq!(
+            next_slot(&budget, &hours_ago(&[20, 10, 1]), now()),
+            Some(now() + Duration::hours(14))
+        );
+        assert_eq!(
+            next_slot(&budget, &hours_ago(&[10, 1]), now()),
+            Some(now() + Duration::hours(14))
+        );
+        // Promotions older than a day don't count
+        assert_eq!(next_slot(&budget, &hours_ago(&[30, 1]), now()), None);
+    }
+
+    #[test]
+    fn takes_the_later_of_both_limits() {
+        let budget = budget(Some(2), Some(20 * 60 * 60));
+        assert_eq!(
+            next_slot(&budget, &hours_ago(&[23, 1]), now()),
+            Some(now() + Duration::hours(19))
+        );
+    }
+}
@@ -0,0 +1,132 @@
+use glob::{MatchOptions, Pattern};
+
+/// Where GitHub looks for the file, in order of precedence.
+pub(crate) const LOCATIONS: &[&str] = &[".github/CODEOWNERS", "CODEOWNERS", "docs/CODEOWNERS"];
+
+const OPTIONS: MatchOptions = MatchOptions {
+    case_sensitive: true,
+    require_literal_separator: true,
+    require_literal_leading_dot: false,
+};
+
+/// A parsed CODEOWNERS file.
+pub(crate) struct CodeOwners {
+    rules: Vec<Rule>,
+}
+
+struct Rule {
+    patterns: Vec<Pattern>,
+    owners: Vec<String>,
+}
+
+impl CodeOwners {
+    /// Lines with invalid patterns are skipped, as GitHub does.
+    pub fn parse(content: &str) -> Self {
+        let rules = content
+            .lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty() && !line.starts_with('#'))
+            .filter_map(|line| {
+                let line = line.split(" #").next().unwrap_or(line);
+                let mut fields = line.split_whitespace();
+                let patterns = patterns(fields.next()?)?;
+                Some(Rule {
+                    patterns,
+                    owners: fields.map(str::to_string).collect(),
+                })
+            })
+            .collect();
+        Self { rules }
+    }
+
+    /// Owners of `path`; the last matching rule wins.
+    pub fn owners_of(&self, path: &str) -> &[String] {
+        self.rules
+            .iter()
+            .rev()
+            .find(|rule| {
+                rule.patterns
+                    .iter()
+                    .any(|pattern| pattern.matches_with(path, OPTIONS))
+            })
+            .map_or(&[], |rule| &rule.owners)
+    }
+}
+
+/// Translates a gitignore-style pattern into globs matching the path
+/// itself and everything below it.
+fn patterns(raw: &str) -> Option<Vec<Pattern>> {
+    let anchored = raw.starts_with('/') || raw.trim_end_matches('/').contains('/');
+    let trimmed = raw.trim_start_matches('/').trim_end_matches('/');
+    let base = match anchored {
+        true => trimmed.to_string(),
+        false => format!("**/{trimmed}"),
+    };
+    let mut globs = vec![format!("{base}/**")];
+    if !raw.ends_with('/') {
+        globs.push(base);
+    }
+    globs.iter().map(|glob| Pattern::new(glob).ok()).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CODEOWNERS: &str = "\
+# Everything falls back to the platform team
+*               @acme/platform
+*.rs            @acme/rust # Inline comment
+/docs/          @acme/docs
+apps/web        @acme/web @octocat
+build/          @acme/build
+[invalid        @nobody
+/unowned.txt
+";
+
+    fn owners(path: &str) -> Vec<String> {
+        CodeOwners::parse(CODEOWNERS).owners_of(path).to_vec()
+    }
+
+    #[test]
+    fn last_matching_rule_wins() {
+        assert_eq!(owners("README.md"), ["@acme/platform"]);
+        assert_eq!(owners("src/main.rs"), ["@acme/rust"]);
+        assert_eq!(owners("docs/guide.rs"), ["@acme/docs"]);
+    }
+
+    #[test]
+    fn unanchored_patterns_match_at_any_depth() {
+        assert_eq!(owners("tools/build/run.sh"), ["@acme/build"]);
+        assert_eq!(owners("build/run.sh"), ["@acme/build"]);
+    }
+
+    #[test]
+    fn patterns_with_a_slash_are_anchored() {
+        assert_eq!(owners("apps/web"), ["@acme/web", "@octocat"]);
+        assert_eq!(owners("apps/web/index.html"), ["@acme/web", "@octocat"]);
+        assert_eq!(owners("vendor/apps/web/index.html"), ["@acme/platform"]);
+        assert_eq!(owners("vendor/docs/index.html"), ["@acme/platform"]);
+    }
+
+    #[test]
+    fn directory_patterns_only_match_below_them() {
+        // `build/` is a directory, so a file of that name isn't covered
+        assert_eq!(owners("build"), ["@acme/platform"]);
+    }
+
+    #[test]
+    fn rules_without_owners_unown_their_paths() {
+        assert!(owners("unowned.txt").is_empty());
+    }
+
+    #[test]
+    fn skips_invalid_patterns() {
+        assert_eq!(owners("[invalid"), ["@acme/platform"]);
+    }
+
+    #[test]
+    fn nothing_is_owned_by_an_empty_file() {
+        assert!(CodeOwners::parse("").owners_of("src/main.rs").is_empty());
+    }
+}
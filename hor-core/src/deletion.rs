@@ -0,0 +1,112 @@
+//! Soft-deleted projects: no longer synced, but kept with their history so
+//! an accidental deletion can be undone, until they're purged once the
+//! retention period has passed.
+
+use anyhow::{bail, Context};
+use hor_registry::{ProjectId, Registry, SourceProject};
+use hor_state::Deletion;
+use tracing::{error, info};
+
+use crate::{HorSystem, InitializedState};
+
+impl<R: Registry + ?Sized> HorSystem<InitializedState, R> {
+    /// Soft-deletes `id`, which must be registered: its syncs are skipped
+    /// from now on, and its history is purged after the retention period
+    /// unless it's restored first. False if it already was deleted, which
+    /// keeps the original deletion and its retention.
+    pub async fn delete_project(
+        &self,
+        id: &ProjectId,
+        reason: Option<&str>,
+    ) -> anyhow::Result<bool> {
+        if !self.is_registered(id).await? {
+            bail!("{id} is not a registered project");
+        }
+        let deletion = Deletion {
+            reason: reason.map(str::to_string),
+            deleted_at: self.state.clock.now(),
+            purged_at: None,
+        };
+        let deleted = self
+            .state
+            .store
+            .insert_deletion(id, &deletion)
+            .await
+            .context("Unable to delete project")?;
+        if deleted {
+            info!(%id, reason, "Deleted project");
+        }
+        Ok(deleted)
+    }
+
+    /// Restores a soft-deleted project, synced again from the next sync on;
+    /// from scratch if it was purged. False if it wasn't deleted.
+    pub async fn restore_project(&self, id: &ProjectId) -> anyhow::Result<bool> {
+        let restored = self
+            .state
+            .store
+            .remove_deletion(id)
+            .await
+            .context("Unable to restore project")?;
+        if restored {
+            info!(%id, "Restored project");
+        }
+        Ok(restored)
+    }
+
+    /// Whether `id` is one of the registry's projects. Owners' repositories
+    /// are only listed if it isn't one registered on its own.
+    async fn is_registered(&self, id: &ProjectId) -> anyhow::Result<bool> {
+        let mut owners = Vec::new();
+        let mut registered = Vec::new();
+        for project in self.registry.get_projects() {
+            match project {
+                SourceProject::Github(project) => registered.extend(project.expand_regions()),
+                SourceProject::GithubOwner(owner) => owners.push(owner),
+                _ => {}
+            }
+        }
+        if registered.iter().any(|project| project.id() == *id) {
+            return Ok(true);
+        }
+        for owner in owners {
+            let expanded = self.expand_github_owner(owner).await?;
+            if expanded.iter().any(|project| project.id() == *id) {
+                return Ok(true);
+            }
+        }
+        Ok(false)
+    }
+
+    /// Every soft-deleted project, oldest deletion first.
+    pub async fn deleted_projects(&self) -> anyhow::Result<Vec<(ProjectId, Deletion)>> {
+        Ok(self.state.store.deletions().await?)
+    }
+
+    /// Purges the history of the projects deleted longer than the
+    /// retention period ago. They stay deleted, so a project still in the
+    /// registry isn't synced again until it's restored.
+    pub(crate) async fn purge_deleted(&self) -> anyhow::Result<()> {
+        let store = &self.state.store;
+        let now = self.state.clock.now();
+        let due = store
+            .deletions()
+            .await?
+            .into_iter()
+            .filter(|(_, deletion)| deletion.purged_at.is_none())
+            .filter(|(_, deletion)| now - deletion.deleted_at >= self.state.deletion_retention);
+        for (id, _) in due {
+            match store.purge_project(&id, now).await {
+                Ok(()) => info!(%id, "Purged deleted project"),
+                Err(err) => {
+                    error!(
+                        %id,
+                        err = %self.state.redactor.debug(&err),
+                        "Unable to purge deleted project"
+                    )
+                }
+            }
+        }
+        Ok(())
+    }
+}
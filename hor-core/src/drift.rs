@@ -0,0 +1,147 @@
+//! Daily drift reports: a digest of the projects whose env ref isn't where
+//! their releases should have taken it, and since when, delivered to the
+//! notification sinks apart from the events of each sync.
+
+use std::collections::{hash_map::Entry, HashMap};
+
+use anyhow::Context;
+use chrono::{DateTime, Duration, NaiveTime, TimeZone, Utc};
+use hor_registry::{ProjectId, Registry};
+use hor_state::{DriftReport, DriftedProject, Event, ProjectOutcome};
+use serde::Deserialize;
+use tracing::info;
+
+use crate::{events, HorSystem, InitializedState};
+
+/// Runs looked back on at most, however many a day had.
+const REPORT_RUNS: usize = 1000;
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(rename_all = "kebab-case")]
+pub(crate) struct DriftReports {
+    /// Hour of the day, UTC, the report is compiled at
+    #[serde(default = "DriftReports::default_hour_utc")]
+    pub hour_utc: u32,
+    /// Names of the sinks the report goes to, every sink if empty
+    #[serde(default)]
+    pub sinks: Vec<String>,
+}
+
+impl DriftReports {
+    fn default_hour_utc() -> u32 {
+        8
+    }
+
+    /// When the first report after `now` is due.
+    pub fn next_after(&self, now: DateTime<Utc>) -> DateTime<Utc> {
+        let today = Utc.from_utc_datetime(&now.date_naive().and_time(NaiveTime::MIN))
+            + Duration::hours(self.hour_utc.into());
+        if today > now {
+            today
+        } else {
+            today + Duration::days(1)
+        }
+    }
+}
+
+/// The last sync of a project, and how long it's been drifted before it.
+struct Latest {
+    env: String,
+    outcome: ProjectOutcome,
+    drifted_since: DateTime<Utc>,
+    /// An earlier run found the project where it should be
+    settled: bool,
+}
+
+impl<R: Registry + ?Sized> HorSystem<InitializedState, R> {
+    /// The drift report of the last day's runs: every project synced in
+    /// them, by how its last sync came out. Only live runs count, or only
+    /// simulated ones in shadow mode.
+    pub async fn drift_report(&self) -> anyhow::Result<DriftReport> {
+        let at = self.state.clock.now();
+        let since = at - Duration::days(1);
+        let runs = self
+            .state
+            .store
+            .recent_runs(REPORT_RUNS)
+            .await
+            .context("Unable to read run history")?;
+
+        // Newest first, so a project's first report is its last sync
+        let mut latest: HashMap<ProjectId, Latest> = HashMap::new();
+        let runs = runs
+            .iter()
+            .map(|(_, run)| run)
+            .take_while(|run| run.finished_at >= since)
+            .filter(|run| run.simulated == self.state.shadow);
+        for run in runs {
+            for project in &run.projects {
+                let drifted = project.outcome.drifted();
+                match latest.entry(project.id.clone()) {
+                    Entry::Vacant(entry) => {
+                        entry.insert(Latest {
+                            env: project.env.clone(),
+                            outcome: project.outcome.clone(),
+                            drifted_since: run.started_at,
+                            settled: !drifted,
+                        });
+                    }
+                    Entry::Occupied(mut entry) => {
+                        let latest = entry.get_mut();
+                        if latest.settled {
+                            continue;
+                        }
+                        if drifted {
+                            latest.drifted_since = run.started_at;
+                        } else {
+                            latest.settled = true;
+                        }
+                    }
+                }
+            }
+        }
+
+        let mut converged = 0;
+        let mut drifted = Vec::new();
+        for (id, latest) in latest {
+            if !latest.outcome.drifted() {
+                converged += 1;
+                continue;
+            }
+            drifted.push(DriftedProject {
+                id,
+                env: latest.env,
+                outcome: latest.outcome,
+                drifted_since: latest.drifted_since,
+            });
+        }
+        drifted.sort_by(|a, b| (a.drifted_since, &a.id).cmp(&(b.drifted_since, &b.id)));
+        Ok(DriftReport {
+            since,
+            at,
+            converged,
+            drifted,
+            simulated: self.state.shadow,
+        })
+    }
+
+    /// Compiles the drift report and queues it for the sinks `reports`
+    /// names.
+    pub(crate) async fn send_drift_report(&self, reports: &DriftReports) -> anyhow::Result<()> {
+        let report = self.drift_report().await?;
+        info!(
+            converged = report.converged,
+            drifted = report.drifted.len(),
+            "Compiled drift report"
+        );
+        events::enqueue_to(
+            self.state.store.as_ref(),
+            &self.state.sinks,
+            &reports.sinks,
+            self.state.clock.now(),
+            Event::DriftReport { report },
+        )
+        .await
+        .context("Unable to queue drift report")
+    }
+}
@@ -0,0 +1,504 @@
+use std::{collections::HashMap, sync::Arc, time::Duration};
+
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use hor_state::{Event, OutboxEntry, OutboxId, StateStore};
+use serde::Deserialize;
+use serde_json::json;
+use tracing::{info, warn};
+
+#[cfg(feature = "aws")]
+use crate::actions::{aws, AwsAccount};
+use crate::{
+    nats::{self, NatsServer},
+    Clock, Redactor,
+};
+
+const OUTBOX_BATCH: usize = 50;
+const MAX_ATTEMPTS: u32 = 10;
+const MAX_BACKOFF: Duration = Duration::from_secs(60 * 60);
+
+/// A destination for [`Event`]s, fed from the outbox.
+#[async_trait]
+pub trait EventSink: Send + Sync {
+    async fn deliver(&self, event: &Event) -> anyhow::Result<()>;
+
+    /// Whether `event` is queued for the sink at all. Sync summaries, one
+    /// per sync, are only for sinks that stream everything.
+    fn accepts(&self, event: &Event) -> bool {
+        !matches!(event, Event::SyncFinished { .. })
+    }
+}
+
+pub type EventSinks = HashMap<String, Arc<dyn EventSink>>;
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(rename_all = "kebab-case")]
+pub struct SinkConfig {
+    /// Outbox entries are addressed to sinks by name
+    pub name: String,
+    #[serde(flatten)]
+    pub kind: SinkKind,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(tag = "kind", rename_all = "kebab-case")]
+pub enum SinkKind {
+    /// POSTs each event as JSON
+    Webhook {
+        url: String,
+        #[serde(default)]
+        headers: HashMap<String, String>,
+    },
+    /// Adds an annotation tagged with project, env and SHA
+    Grafana {
+        url: String,
+        /// Service account token allowed to write annotations
+        token: String,
+        /// Limit annotations to one dashboard rather than the organization
+        #[serde(default, rename = "dashboard-uid")]
+        dashboard_uid: Option<String>,
+        #[serde(default)]
+        tags: Vec<String>,
+    },
+    /// Publishes every event, sync summaries included, to
+    /// `{subject-prefix}.{kind}`, e.g. `hands-off-release.ref-moved`
+    Nats {
+        #[serde(flatten)]
+        server: NatsServer,
+        #[serde(default = "default_nats_prefix", rename = "subject-prefix")]
+        subject_prefix: String,
+    },
+    /// Produces every event, sync summaries included, to a Kafka topic
+    /// through a Confluent REST Proxy, keyed by project
+    KafkaRest {
+        /// Base URL of the proxy
+        url: String,
+        topic: String,
+        #[serde(default)]
+        headers: HashMap<String, String>,
+    },
+    /// Puts each event on an AWS EventBridge bus, its kind as the detail
+    /// type, e.g. `ref-moved`
+    #[cfg(feature = "aws")]
+    Eventbridge {
+        #[serde(flatten)]
+        account: AwsAccount,
+        /// Name or ARN of the event bus
+        #[serde(default = "default_event_bus", rename = "event-bus")]
+        event_bus: String,
+        #[serde(default = "default_event_source")]
+        source: String,
+    },
+    /// Publishes each event as JSON to an SNS topic, with its kind as the
+    /// `kind` message attribute for subscriptions to filter on
+    #[cfg(feature = "aws")]
+    Sns {
+        #[serde(rename = "topic-arn")]
+        topic_arn: String,
+        #[serde(flatten)]
+        account: AwsAccount,
+    },
+}
+
+fn default_nats_prefix() -> String {
+    "hands-off-release".to_string()
+}
+
+#[cfg(feature = "aws")]
+fn default_event_bus() -> String {
+    "default".to_string()
+}
+
+#[cfg(feature = "aws")]
+fn default_event_source() -> String {
+    "hands-off-release".to_string()
+}
+
+/// Tokens and header values of the sinks, which their errors may echo.
+pub(crate) fn sink_secrets(configs: &[SinkConfig]) -> impl Iterator<Item = String> + '_ {
+    configs.iter().flat_map(|config| match &config.kind {
+        SinkKind::Webhook { headers, .. } => headers.values().cloned().collect::<Vec<_>>(),
+        SinkKind::Grafana { token, .. } => vec![token.clone()],
+        SinkKind::Nats { server, .. } => server.secrets().cloned().collect(),
+        SinkKind::KafkaRest { headers, .. } => headers.values().cloned().collect(),
+        #[cfg(feature = "aws")]
+        SinkKind::Eventbridge { account, .. } | SinkKind::Sns { account, .. } => {
+            account.secrets().map(str::to_string).collect()
+        }
+    })
+}
+
+pub(crate) fn build_sinks(configs: &[SinkConfig], http: &reqwest::Client) -> EventSinks {
+    configs
+        .iter()
+        .map(|config| {
+            let sink: Arc<dyn EventSink> = match &config.kind {
+                SinkKind::Webhook { url, headers } => Arc::new(WebhookSink {
+                    http: http.clone(),
+                    url: url.clone(),
+                    headers: headers.clone(),
+                }),
+                SinkKind::Grafana {
+                    url,
+                    token,
+                    dashboard_uid,
+                    tags,
+                } => Arc::new(GrafanaSink {
+                    http: http.clone(),
+                    url: url.clone(),
+                    token: token.clone(),
+                    dashboard_uid: dashboard_uid.clone(),
+                    tags: tags.clone(),
+                }),
+                SinkKind::Nats {
+                    server,
+                    subject_prefix,
+                } => Arc::new(NatsSink {
+                    server: server.clone(),
+                    subject_prefix: subject_prefix.clone(),
+                }),
+                SinkKind::KafkaRest {
+                    url,
+                    topic,
+                    headers,
+                } => Arc::new(KafkaRestSink {
+                    http: http.clone(),
+                    url: url.clone(),
+                    topic: topic.clone(),
+                    headers: headers.clone(),
+                }),
+                #[cfg(feature = "aws")]
+                SinkKind::Eventbridge {
+                    account,
+                    event_bus,
+                    source,
+                } => Arc::new(EventBridgeSink {
+                    http: http.clone(),
+                    account: account.clone(),
+                    event_bus: event_bus.clone(),
+                    source: source.clone(),
+                }),
+                #[cfg(feature = "aws")]
+                SinkKind::Sns { topic_arn, account } => Arc::new(SnsSink {
+                    http: http.clone(),
+                    account: account.clone(),
+                    topic_arn: topic_arn.clone(),
+                }),
+            };
+            (config.name.clone(), sink)
+        })
+        .collect()
+}
+
+/// Persists one outbox entry per sink for `event`, due at `now`.
+pub(crate) async fn enqueue(
+    store: &dyn StateStore,
+    sinks: &EventSinks,
+    now: DateTime<Utc>,
+    event: Event,
+) -> anyhow::Result<()> {
+    enqueue_to(store, sinks, &[], now, event).await
+}
+
+/// Like [`enqueue`], but only for the sinks named in `only`, unless it's
+/// empty.
+pub(crate) async fn enqueue_to(
+    store: &dyn StateStore,
+    sinks: &EventSinks,
+    only: &[String],
+    now: DateTime<Utc>,
+    event: Event,
+) -> anyhow::Result<()> {
+    let entries: Vec<_> = sinks
+        .iter()
+        .filter(|(name, _)| only.is_empty() || only.contains(name))
+        .filter(|(_, sink)| sink.accepts(&event))
+        .map(|(sink, _)| OutboxEntry::new(sink, event.clone(), now))
+        .collect();
+    if !entries.is_empty() {
+        store.enqueue_outbox(&entries).await?;
+    }
+    Ok(())
+}
+
+/// Persists one pending outbox entry per sink for `event`, ahead of the
+/// mutation it announces: delivered once [`release`]d, dropped with
+/// [`discard`] if the mutation didn't happen. Entries a crash leaves
+/// pending are settled by the next sync of the project.
+pub(crate) async fn hold(
+    store: &dyn StateStore,
+    sinks: &EventSinks,
+    event: Event,
+) -> anyhow::Result<Vec<OutboxId>> {
+    let entries: Vec<_> = sinks
+        .iter()
+        .filter(|(_, sink)| sink.accepts(&event))
+        .map(|(sink, _)| OutboxEntry::pending(sink, event.clone()))
+        .collect();
+    if entries.is_empty() {
+        return Ok(Vec::new());
+    }
+    Ok(store.enqueue_outbox(&entries).await?)
+}
+
+/// Makes held entries due for delivery at `now`.
+pub(crate) async fn release(
+    store: &dyn StateStore,
+    held: &[OutboxId],
+    now: DateTime<Utc>,
+) -> anyhow::Result<()> {
+    if !held.is_empty() {
+        store.release_outbox(held, now).await?;
+    }
+    Ok(())
+}
+
+/// Drops held entries, as what they announce didn't happen.
+pub(crate) async fn discard(store: &dyn StateStore, held: &[OutboxId]) -> anyhow::Result<()> {
+    for id in held {
+        store.complete_outbox(*id).await?;
+    }
+    Ok(())
+}
+
+/// Attempts every due outbox entry once, rescheduling failures with
+/// exponential backoff until [`MAX_ATTEMPTS`] is reached.
+pub(crate) async fn deliver_due(
+    store: &dyn StateStore,
+    sinks: &EventSinks,
+    redactor: &Redactor,
+    clock: &dyn Clock,
+) -> anyhow::Result<()> {
+    for (id, entry) in store.due_outbox(clock.now(), OUTBOX_BATCH).await? {
+        let Some(sink) = sinks.get(&entry.sink) else {
+            warn!(
+                sink = entry.sink,
+                "outbox entry addressed to unknown sink, dropping"
+            );
+            store
+                .retry_outbox(id, None, "sink is no longer configured")
+                .await?;
+            continue;
+        };
+
+        let event = redactor.redact_value(entry.event);
+        match sink.deliver(&event).await {
+            Ok(()) => store.complete_outbox(id).await?,
+            Err(err) => {
+                let attempts = entry.attempts + 1;
+                let next_attempt_at = (attempts < MAX_ATTEMPTS).then(|| {
+                    let backoff = Duration::from_secs(10 << attempts.min(12)).min(MAX_BACKOFF);
+                    clock.now() + chrono::Duration::from_std(backoff).unwrap_or_default()
+                });
+                if next_attempt_at.is_none() {
+                    warn!(
+                        sink = entry.sink,
+                        err = %redactor.debug(&err),
+                        "giving up on event delivery"
+                    );
+                } else {
+                    info!(
+                        sink = entry.sink,
+                        attempts,
+                        err = %redactor.debug(&err),
+                        "event delivery failed, will retry"
+                    );
+                }
+                store
+                    .retry_outbox(id, next_attempt_at, &redactor.redact(&format!("{err:#}")))
+                    .await?;
+            }
+        }
+    }
+    Ok(())
+}
+
+struct WebhookSink {
+    http: reqwest::Client,
+    url: String,
+    headers: HashMap<String, String>,
+}
+
+#[async_trait]
+impl EventSink for WebhookSink {
+    async fn deliver(&self, event: &Event) -> anyhow::Result<()> {
+        let mut request = self.http.post(&self.url).json(event);
+        for (name, value) in &self.headers {
+            request = request.header(name, value);
+        }
+        request.send().await?.error_for_status()?;
+        Ok(())
+    }
+}
+
+struct GrafanaSink {
+    http: reqwest::Client,
+    url: String,
+    token: String,
+    dashboard_uid: Option<String>,
+    tags: Vec<String>,
+}
+
+#[async_trait]
+impl EventSink for GrafanaSink {
+    async fn deliver(&self, event: &Event) -> anyhow::Result<()> {
+        let Event::RefMoved {
+            project,
+            owner,
+            repo,
+            env,
+            to,
+            at,
+            simulated,
+            risk,
+            ..
+        } = event
+        else {
+            return Ok(());
+        };
+
+        let mut tags = vec![
+            "hands-off-release".to_string(),
+            format!("project:{owner}/{repo}"),
+            format!("env:{env}"),
+            format!("sha:{to}"),
+        ];
+        if *simulated {
+            tags.push("simulated".to_string());
+        }
+        tags.extend(self.tags.iter().cloned());
+        let mut text = format!("Released {owner}/{repo} ({project}) at {to} to {env}");
+        if let Some(risk) = risk {
+            text.push_str(&format!(", risk {risk}"));
+        }
+        let mut annotation = json!({
+            "time": at.timestamp_millis(),
+            "tags": tags,
+            "text": text,
+        });
+        if let Some(uid) = &self.dashboard_uid {
+            annotation["dashboardUID"] = json!(uid);
+        }
+
+        self.http
+            .post(format!(
+                "{}/api/annotations",
+                self.url.trim_end_matches('/')
+            ))
+            .bearer_auth(&self.token)
+            .json(&annotation)
+            .send()
+            .await?
+            .error_for_status()?;
+        Ok(())
+    }
+}
+
+/// The `kind` an event is tagged with when serialized, e.g. `ref-moved`.
+fn event_kind(event: &serde_json::Value) -> &str {
+    event["kind"].as_str().unwrap_or("event")
+}
+
+struct NatsSink {
+    server: NatsServer,
+    subject_prefix: String,
+}
+
+#[async_trait]
+impl EventSink for NatsSink {
+    async fn deliver(&self, event: &Event) -> anyhow::Result<()> {
+        let payload = serde_json::to_value(event)?;
+        let subject = format!("{}.{}", self.subject_prefix, event_kind(&payload));
+        nats::publish(&self.server, &subject, payload.to_string().as_bytes()).await
+    }
+
+    fn accepts(&self, _event: &Event) -> bool {
+        true
+    }
+}
+
+struct KafkaRestSink {
+    http: reqwest::Client,
+    url: String,
+    topic: String,
+    headers: HashMap<String, String>,
+}
+
+#[async_trait]
+impl EventSink for KafkaRestSink {
+    async fn deliver(&self, event: &Event) -> anyhow::Result<()> {
+        let value = serde_json::to_value(event)?;
+        // Keeps each project's events in order, on one partition
+        let key = value.get("project").cloned().unwrap_or_default();
+        let mut request = self
+            .http
+            .post(format!(
+                "{}/topics/{}",
+                self.url.trim_end_matches('/'),
+                self.topic
+            ))
+            .header(
+                reqwest::header::CONTENT_TYPE,
+                "application/vnd.kafka.json.v2+json",
+            )
+            .body(json!({ "records": [{ "key": key, "value": value }] }).to_string());
+        for (name, value) in &self.headers {
+            request = request.header(name, value);
+        }
+        request.send().await?.error_for_status()?;
+        Ok(())
+    }
+
+    fn accepts(&self, _event: &Event) -> bool {
+        true
+    }
+}
+
+#[cfg(feature = "aws")]
+struct EventBridgeSink {
+    http: reqwest::Client,
+    account: AwsAccount,
+    event_bus: String,
+    source: String,
+}
+
+#[cfg(feature = "aws")]
+#[async_trait]
+impl EventSink for EventBridgeSink {
+    async fn deliver(&self, event: &Event) -> anyhow::Result<()> {
+        let detail = serde_json::to_value(event)?;
+        aws::put_event(
+            &self.account,
+            &self.http,
+            &self.event_bus,
+            &self.source,
+            event_kind(&detail),
+            &detail,
+        )
+        .await
+    }
+}
+
+#[cfg(feature = "aws")]
+struct SnsSink {
+    http: reqwest::Client,
+    account: AwsAccount,
+    topic_arn: String,
+}
+
+#[cfg(feature = "aws")]
+#[async_trait]
+impl EventSink for SnsSink {
+    async fn deliver(&self, event: &Event) -> anyhow::Result<()> {
+        let message = serde_json::to_value(event)?;
+        aws::publish(
+            &self.account,
+            &self.http,
+            &self.topic_arn,
+            &message.to_string(),
+            &[("kind", event_kind(&message))],
+        )
+        .await
+    }
+}
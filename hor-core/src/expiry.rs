@@ -0,0 +1,65 @@
+//! Ephemeral envs, e.g. the preview of a pull request, torn down once
+//! their TTL passes without a release.
+
+use anyhow::Context;
+use chrono::Duration;
+use hor_registry::{GithubProject, ProjectId, Registry};
+use hor_state::{Freeze, FreezeScope};
+use tracing::{info, warn};
+
+use crate::{github::HorOctocrabExtension, HorSystem, InitializedState};
+
+impl<R: Registry + ?Sized> HorSystem<InitializedState, R> {
+    /// Expires `project` if its last release is more than `ttl_secs` ago:
+    /// the env tag is deleted and the project frozen, so the next sync
+    /// doesn't create the tag again. Why it expired, if it did.
+    pub(crate) async fn expire(
+        &self,
+        id: &ProjectId,
+        project: &GithubProject,
+        ttl_secs: u64,
+    ) -> anyhow::Result<Option<String>> {
+        let store = &self.state.store;
+        let (owner, repo, env) = (&project.owner, &project.repo, &project.env);
+        // Counted from the first release on, not from registration
+        let Some(deployed) = store.last_deployment(id, env).await? else {
+            return Ok(None);
+        };
+        let now = self.state.clock.now();
+        if now - deployed.deployed_at < Duration::seconds(ttl_secs as i64) {
+            return Ok(None);
+        }
+        let detail = format!("{env} expired, {}m after its last release", ttl_secs / 60);
+        if self.state.shadow {
+            info!(env, "Shadow mode, not expiring env");
+            return Ok(Some(detail));
+        }
+
+        let git_ref = format!("tags/{env}");
+        self.ensure_unprotected(&git_ref)?;
+        let deleted = self
+            .state
+            .writer(env)
+            .delete_ref(owner, repo, &git_ref)
+            .await
+            .context("Unable to delete env tag")?;
+        if !deleted {
+            warn!(git_ref, "Env tag was already gone");
+        }
+        store
+            .set_freeze(
+                &FreezeScope::Project(id.clone()),
+                Some(&Freeze {
+                    reason: Some(format!(
+                        "{detail}; lift the freeze to bring it back, or deregister the project"
+                    )),
+                    frozen_at: now,
+                }),
+            )
+            .await
+            .context("Unable to freeze expired project")?;
+        self.registry.project_expired(id);
+        info!(env, "Env expired");
+        Ok(Some(detail))
+    }
+}
@@ -0,0 +1,74 @@
+//! Refs other than the env tag moved along with it, for downstream systems
+//! that watch branches or differently named tags.
+
+use anyhow::bail;
+use hor_registry::{GithubProject, ProjectId, Registry};
+use hor_state::{ActionReport, ProjectOutcome};
+use tracing::{info, warn};
+
+use crate::{HorSystem, InitializedState};
+
+impl<R: Registry + ?Sized> HorSystem<InitializedState, R> {
+    /// Points the extra refs of `project` at `sha`, one after the other.
+    /// The first failure stops the rest, so a ref later in the list is
+    /// never ahead of one before it. Reports the refs that moved or failed
+    /// to.
+    pub(crate) async fn sync_extra_refs(
+        &self,
+        id: &ProjectId,
+        project: &GithubProject,
+        sha: &str,
+    ) -> Vec<ActionReport> {
+        let mut reports = Vec::new();
+        for extra in &project.extra_refs {
+            // Accepted with or without the `refs/` prefix
+            let git_ref = extra.strip_prefix("refs/").unwrap_or(extra);
+            let error = match self.sync_extra_ref(id, project, git_ref, sha).await {
+                Ok(false) => continue,
+                Ok(true) => None,
+                Err(err) => {
+                    warn!(
+                        git_ref,
+                        err = %self.state.redactor.debug(&err),
+                        "Unable to move extra ref"
+                    );
+                    Some(format!("{err:#}"))
+                }
+            };
+            let failed = error.is_some();
+            reports.push(ActionReport {
+                action: format!("ref {git_ref}"),
+                error,
+                simulated: self.state.shadow,
+            });
+            if failed {
+                break;
+            }
+        }
+        reports
+    }
+
+    /// Whether the ref moved.
+    async fn sync_extra_ref(
+        &self,
+        id: &ProjectId,
+        project: &GithubProject,
+        git_ref: &str,
+        sha: &str,
+    ) -> anyhow::Result<bool> {
+        let (owner, repo) = (&project.owner, &project.repo);
+        let current = self.observe_ref(id, owner, repo, git_ref).await?;
+        if current.as_deref() == Some(sha) {
+            return Ok(false);
+        }
+        match self.move_ref(id, project, git_ref, current, sha).await? {
+            ProjectOutcome::Conflict { expected, actual } => {
+                bail!("{git_ref} moved concurrently, expected at {expected:?}, found at {actual:?}")
+            }
+            _ => {
+                info!(git_ref, sha, "Moved extra ref");
+                Ok(true)
+            }
+        }
+    }
+}
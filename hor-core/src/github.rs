@@ -0,0 +1,602 @@
+use std::str::FromStr;
+
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use http::{header::LOCATION, HeaderMap, StatusCode};
+use octocrab::{
+    etag::EntityTag,
+    models::{
+        repos::{ContentItems, Object, Ref, Release},
+        Repository,
+    },
+    FromResponse, Octocrab, Page,
+};
+use serde::{de::IgnoredAny, Deserialize};
+use serde_json::json;
+
+/// Calls octocrab has no (suitable) typed API for.
+#[async_trait]
+pub(crate) trait HorOctocrabExtension {
+    /// Force-moves `reference` (e.g. `tags/prod`) to `sha`, `None` if
+    /// GitHub refused, most likely because the ref doesn't exist.
+    async fn update_ref(
+        &self,
+        owner: &str,
+        repo: &str,
+        reference: &str,
+        sha: &str,
+    ) -> Result<Option<Ref>, RefWriteError>;
+
+    /// Creates `reference` (e.g. `refs/tags/prod`) at `sha`, `None` if it
+    /// already exists.
+    async fn create_ref(
+        &self,
+        owner: &str,
+        repo: &str,
+        reference: &str,
+        sha: &str,
+    ) -> Result<Option<Ref>, RefWriteError>;
+
+    /// Deletes `reference` (e.g. `tags/prod`), `false` if it didn't exist.
+    async fn delete_ref(
+        &self,
+        owner: &str,
+        repo: &str,
+        reference: &str,
+    ) -> Result<bool, RefWriteError>;
+
+    /// Names of the active rulesets of `owner/repo` covering tags, those
+    /// of its organization included.
+    async fn tag_rulesets(&self, owner: &str, repo: &str) -> octocrab::Result<Vec<String>>;
+
+    /// `owner/repo` as the token sees it, `None` if it doesn't exist or
+    /// isn't visible. A renamed or transferred repository is followed to
+    /// its new name.
+    async fn repository(&self, owner: &str, repo: &str) -> octocrab::Result<Option<Repository>>;
+
+    /// Commits reachable from `head` but not from `base`.
+    async fn compare(
+        &self,
+        owner: &str,
+        repo: &str,
+        base: &str,
+        head: &str,
+    ) -> octocrab::Result<Comparison>;
+
+    async fn commit(&self, owner: &str, repo: &str, sha: &str) -> octocrab::Result<GitCommit>;
+
+    /// The annotated tag object `sha`.
+    async fn tag_object(&self, owner: &str, repo: &str, sha: &str) -> octocrab::Result<TagObject>;
+
+    /// Looks up `git_ref` (e.g. `tags/prod`), revalidating against `etag`
+    /// if given. Unchanged answers don't count against the rate limit.
+    async fn lookup_ref(
+        &self,
+        owner: &str,
+        repo: &str,
+        git_ref: &str,
+        etag: Option<&str>,
+    ) -> octocrab::Result<RefLookup>;
+
+    /// Contents of `path` at `reference`, `None` if there is no such file.
+    async fn file_content(
+        &self,
+        owner: &str,
+        repo: &str,
+        path: &str,
+        reference: &str,
+    ) -> octocrab::Result<Option<String>>;
+
+    /// Every ref starting with `prefix`, e.g. `tags/v`.
+    async fn matching_refs(
+        &self,
+        owner: &str,
+        repo: &str,
+        prefix: &str,
+    ) -> octocrab::Result<Vec<Ref>>;
+
+    /// Logins of every member of the team, including child teams.
+    async fn team_members(&self, org: &str, team: &str) -> octocrab::Result<Vec<String>>;
+
+    /// The last 100 commits of `branch`, newest first.
+    async fn recent_commits(
+        &self,
+        owner: &str,
+        repo: &str,
+        branch: &str,
+    ) -> octocrab::Result<Vec<GitCommit>>;
+
+    /// Whether the repository has artifact attestations of `digest`, e.g.
+    /// `sha256:…` of an image.
+    async fn has_attestations(
+        &self,
+        owner: &str,
+        repo: &str,
+        digest: &str,
+    ) -> octocrab::Result<bool>;
+
+    /// Latest check run per check name on `sha`.
+    async fn check_runs(
+        &self,
+        owner: &str,
+        repo: &str,
+        sha: &str,
+    ) -> octocrab::Result<Vec<CheckRun>>;
+
+    /// The release of `tag`, `None` if it has none.
+    async fn release_by_tag(
+        &self,
+        owner: &str,
+        repo: &str,
+        tag: &str,
+    ) -> octocrab::Result<Option<Release>>;
+
+    /// Attaches `asset` to `release` as JSON file `name`, `false` if it
+    /// already has an asset of that name.
+    async fn upload_json_asset(
+        &self,
+        release: &Release,
+        name: &str,
+        asset: &serde_json::Value,
+    ) -> octocrab::Result<bool>;
+}
+
+/// Why a ref couldn't be written.
+#[derive(thiserror::Error, Debug)]
+pub(crate) enum RefWriteError {
+    #[error(transparent)]
+    Github(#[from] octocrab::Error),
+    /// A repository ruleset forbids the write; `rules` are the violations
+    /// as GitHub describes them
+    #[error("blocked by repository rules: {}", .rules.join("; "))]
+    Ruleset { rules: Vec<String> },
+}
+
+#[derive(Deserialize, Debug)]
+pub(crate) struct TagObject {
+    /// What the tag points at, possibly another tag
+    pub object: Object,
+}
+
+/// Outcome of a ref lookup that isn't a failure.
+pub(crate) enum RefLookup {
+    Found {
+        git_ref: Box<Ref>,
+        etag: Option<String>,
+    },
+    /// Still matches the ETag it was looked up with
+    Unchanged,
+    /// No such ref, told by the status code since GHES and localized
+    /// messages vary
+    Missing,
+}
+
+#[derive(Deserialize, Debug)]
+pub(crate) struct Comparison {
+    /// `ahead`, `behind`, `identical` or `diverged`, head against base
+    pub status: String,
+    pub total_commits: usize,
+    pub commits: Vec<GitCommit>,
+    #[serde(default)]
+    pub files: Vec<ChangedFile>,
+}
+
+#[derive(Deserialize, Debug)]
+pub(crate) struct GitCommit {
+    pub sha: String,
+    pub commit: CommitDetails,
+    /// The linked GitHub account, absent for unknown emails
+    pub author: Option<Account>,
+    pub parents: Vec<IgnoredAny>,
+    /// Only present when fetching a single commit
+    #[serde(default)]
+    pub files: Vec<ChangedFile>,
+}
+
+#[derive(Deserialize, Debug)]
+pub(crate) struct ChangedFile {
+    pub filename: String,
+    /// Where a renamed file used to be
+    #[serde(default)]
+    pub previous_filename: Option<String>,
+}
+
+impl ChangedFile {
+    /// Files GitHub lists at most for a comparison or a single commit, the
+    /// rest being silently left out.
+    pub const LISTED_AT_MOST: usize = 300;
+
+    /// Every path touched, the old one of a renamed file included.
+    pub fn paths(&self) -> impl Iterator<Item = &str> {
+        std::iter::once(self.filename.as_str()).chain(self.previous_filename.as_deref())
+    }
+}
+
+#[derive(Deserialize, Debug)]
+pub(crate) struct CommitDetails {
+    pub message: String,
+    #[serde(default)]
+    pub committer: Option<Signature>,
+}
+
+#[derive(Deserialize, Debug)]
+pub(crate) struct Signature {
+    pub date: DateTime<Utc>,
+}
+
+#[derive(Deserialize, Debug)]
+pub(crate) struct Account {
+    pub login: String,
+}
+
+#[derive(Deserialize, Debug)]
+pub(crate) struct CheckRun {
+    pub name: String,
+    pub status: String,
+    pub conclusion: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct Attestations {
+    attestations: Vec<IgnoredAny>,
+}
+
+#[derive(Deserialize)]
+struct Ruleset {
+    name: String,
+    enforcement: String,
+}
+
+#[derive(Deserialize)]
+struct RejectedWrite {
+    message: String,
+}
+
+/// The rules a 422 to a ref write says were violated, as the
+/// `Repository rule violations found` message lists them one per line;
+/// `None` if it was refused for another reason.
+fn rule_violations(body: &str) -> Option<Vec<String>> {
+    let message = serde_json::from_str::<RejectedWrite>(body).ok()?.message;
+    let mut lines = message
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty());
+    if !lines.next()?.starts_with("Repository rule violations") {
+        return None;
+    }
+    Some(lines.map(str::to_string).collect())
+}
+
+#[derive(Deserialize)]
+struct CheckRuns {
+    total_count: usize,
+    check_runs: Vec<CheckRun>,
+}
+
+#[async_trait]
+impl HorOctocrabExtension for Octocrab {
+    async fn update_ref(
+        &self,
+        owner: &str,
+        repo: &str,
+        reference: &str,
+        sha: &str,
+    ) -> Result<Option<Ref>, RefWriteError> {
+        let response = self
+            ._patch(
+                format!("/repos/{owner}/{repo}/git/refs/{reference}"),
+                Some(&json!({
+                    "sha": sha,
+                    "force": true
+                })),
+            )
+            .await?;
+        // "Reference does not exist", unless a ruleset forbids the update
+        if response.status() == StatusCode::UNPROCESSABLE_ENTITY {
+            return match rule_violations(&self.body_to_string(response).await?) {
+                Some(rules) => Err(RefWriteError::Ruleset { rules }),
+                None => Ok(None),
+            };
+        }
+        let updated = Ref::from_response(octocrab::map_github_error(response).await?).await?;
+        Ok(Some(updated))
+    }
+
+    async fn create_ref(
+        &self,
+        owner: &str,
+        repo: &str,
+        reference: &str,
+        sha: &str,
+    ) -> Result<Option<Ref>, RefWriteError> {
+        let response = self
+            ._post(
+                format!("/repos/{owner}/{repo}/git/refs"),
+                Some(&json!({
+                    "ref": reference,
+                    "sha": sha,
+                })),
+            )
+            .await?;
+        // "Reference already exists", unless a ruleset forbids creating it
+        if response.status() == StatusCode::UNPROCESSABLE_ENTITY {
+            return match rule_violations(&self.body_to_string(response).await?) {
+                Some(rules) => Err(RefWriteError::Ruleset { rules }),
+                None => Ok(None),
+            };
+        }
+        let created = Ref::from_response(octocrab::map_github_error(response).await?).await?;
+        Ok(Some(created))
+    }
+
+    async fn delete_ref(
+        &self,
+        owner: &str,
+        repo: &str,
+        reference: &str,
+    ) -> Result<bool, RefWriteError> {
+        let response = self
+            ._delete(
+                format!("/repos/{owner}/{repo}/git/refs/{reference}"),
+                None::<&()>,
+            )
+            .await?;
+        // "Reference does not exist", unless a ruleset forbids deleting it
+        if response.status() == StatusCode::UNPROCESSABLE_ENTITY {
+            return match rule_violations(&self.body_to_string(response).await?) {
+                Some(rules) => Err(RefWriteError::Ruleset { rules }),
+                None => Ok(false),
+            };
+        }
+        octocrab::map_github_error(response).await?;
+        Ok(true)
+    }
+
+    async fn tag_rulesets(&self, owner: &str, repo: &str) -> octocrab::Result<Vec<String>> {
+        let rulesets: Vec<Ruleset> = self
+            .get(
+                format!("/repos/{owner}/{repo}/rulesets"),
+                Some(&json!({
+                    "targets": "tag",
+                    "includes_parents": true,
+                    "per_page": 100,
+                })),
+            )
+            .await?;
+        Ok(rulesets
+            .into_iter()
+            .filter(|ruleset| ruleset.enforcement == "active")
+            .map(|ruleset| ruleset.name)
+            .collect())
+    }
+
+    async fn repository(&self, owner: &str, repo: &str) -> octocrab::Result<Option<Repository>> {
+        let response = self._get(format!("/repos/{owner}/{repo}")).await?;
+        // GitHub redirects the old name to `/repositories/{id}`; hyper
+        // doesn't follow redirects by itself
+        let location = match response.status() {
+            StatusCode::MOVED_PERMANENTLY
+            | StatusCode::FOUND
+            | StatusCode::TEMPORARY_REDIRECT
+            | StatusCode::PERMANENT_REDIRECT => response
+                .headers()
+                .get(LOCATION)
+                .and_then(|location| location.to_str().ok())
+                .map(str::to_string),
+            _ => None,
+        };
+        let response = match location {
+            Some(location) => self._get(location).await?,
+            None => response,
+        };
+        if response.status() == StatusCode::NOT_FOUND {
+            return Ok(None);
+        }
+        let repository =
+            Repository::from_response(octocrab::map_github_error(response).await?).await?;
+        Ok(Some(repository))
+    }
+
+    async fn compare(
+        &self,
+        owner: &str,
+        repo: &str,
+        base: &str,
+        head: &str,
+    ) -> octocrab::Result<Comparison> {
+        self.get(
+            format!("/repos/{owner}/{repo}/compare/{base}...{head}"),
+            None::<&()>,
+        )
+        .await
+    }
+
+    async fn commit(&self, owner: &str, repo: &str, sha: &str) -> octocrab::Result<GitCommit> {
+        self.get(format!("/repos/{owner}/{repo}/commits/{sha}"), None::<&()>)
+            .await
+    }
+
+    async fn tag_object(&self, owner: &str, repo: &str, sha: &str) -> octocrab::Result<TagObject> {
+        self.get(format!("/repos/{owner}/{repo}/git/tags/{sha}"), None::<&()>)
+            .await
+    }
+
+    async fn lookup_ref(
+        &self,
+        owner: &str,
+        repo: &str,
+        git_ref: &str,
+        etag: Option<&str>,
+    ) -> octocrab::Result<RefLookup> {
+        let mut headers = HeaderMap::new();
+        // A validator that no longer parses just means a full fetch
+        if let Some(etag) = etag.and_then(|etag| EntityTag::from_str(etag).ok()) {
+            EntityTag::insert_if_none_match_header(&mut headers, etag)?;
+        }
+        let response = self
+            ._get_with_headers(
+                format!("/repos/{owner}/{repo}/git/ref/{git_ref}"),
+                Some(headers),
+            )
+            .await?;
+        match response.status() {
+            StatusCode::NOT_MODIFIED => return Ok(RefLookup::Unchanged),
+            // "Git Repository is empty", which has no refs at all
+            StatusCode::NOT_FOUND | StatusCode::CONFLICT => return Ok(RefLookup::Missing),
+            _ => {}
+        }
+
+        let etag = EntityTag::extract_from_response(&response).map(|etag| etag.to_string());
+        let git_ref = Ref::from_response(octocrab::map_github_error(response).await?).await?;
+        Ok(RefLookup::Found {
+            git_ref: Box::new(git_ref),
+            etag,
+        })
+    }
+
+    async fn file_content(
+        &self,
+        owner: &str,
+        repo: &str,
+        path: &str,
+        reference: &str,
+    ) -> octocrab::Result<Option<String>> {
+        let path = path.trim_start_matches('/');
+        let response = self
+            ._get(format!(
+                "/repos/{owner}/{repo}/contents/{path}?ref={reference}"
+            ))
+            .await?;
+        if response.status() == StatusCode::NOT_FOUND {
+            return Ok(None);
+        }
+        let content =
+            ContentItems::from_response(octocrab::map_github_error(response).await?).await?;
+        Ok(content
+            .items
+            .first()
+            .and_then(|item| item.decoded_content()))
+    }
+
+    async fn matching_refs(
+        &self,
+        owner: &str,
+        repo: &str,
+        prefix: &str,
+    ) -> octocrab::Result<Vec<Ref>> {
+        let first_page: Page<Ref> = self
+            .get(
+                format!("/repos/{owner}/{repo}/git/matching-refs/{prefix}"),
+                Some(&json!({ "per_page": 100 })),
+            )
+            .await?;
+        self.all_pages(first_page).await
+    }
+
+    async fn team_members(&self, org: &str, team: &str) -> octocrab::Result<Vec<String>> {
+        let first_page: Page<Account> = self
+            .get(
+                format!("/orgs/{org}/teams/{team}/members"),
+                Some(&json!({ "per_page": 100 })),
+            )
+            .await?;
+        let members = self.all_pages(first_page).await?;
+        Ok(members.into_iter().map(|member| member.login).collect())
+    }
+
+    async fn recent_commits(
+        &self,
+        owner: &str,
+        repo: &str,
+        branch: &str,
+    ) -> octocrab::Result<Vec<GitCommit>> {
+        self.get(
+            format!("/repos/{owner}/{repo}/commits"),
+            Some(&json!({ "sha": branch, "per_page": 100 })),
+        )
+        .await
+    }
+
+    async fn has_attestations(
+        &self,
+        owner: &str,
+        repo: &str,
+        digest: &str,
+    ) -> octocrab::Result<bool> {
+        let response = self
+            ._get(format!("/repos/{owner}/{repo}/attestations/{digest}"))
+            .await?;
+        if response.status() == StatusCode::NOT_FOUND {
+            return Ok(false);
+        }
+        let found =
+            Attestations::from_response(octocrab::map_github_error(response).await?).await?;
+        Ok(!found.attestations.is_empty())
+    }
+
+    async fn check_runs(
+        &self,
+        owner: &str,
+        repo: &str,
+        sha: &str,
+    ) -> octocrab::Result<Vec<CheckRun>> {
+        // `Page` doesn't know the object the runs come wrapped in, so pages
+        // are walked by number instead
+        let mut check_runs = Vec::new();
+        for page in 1.. {
+            let runs: CheckRuns = self
+                .get(
+                    format!("/repos/{owner}/{repo}/commits/{sha}/check-runs"),
+                    Some(&json!({ "filter": "latest", "per_page": 100, "page": page })),
+                )
+                .await?;
+            let last = runs.check_runs.is_empty()
+                || check_runs.len() + runs.check_runs.len() >= runs.total_count;
+            check_runs.extend(runs.check_runs);
+            if last {
+                break;
+            }
+        }
+        Ok(check_runs)
+    }
+
+    async fn release_by_tag(
+        &self,
+        owner: &str,
+        repo: &str,
+        tag: &str,
+    ) -> octocrab::Result<Option<Release>> {
+        let response = self
+            ._get(format!("/repos/{owner}/{repo}/releases/tags/{tag}"))
+            .await?;
+        if response.status() == StatusCode::NOT_FOUND {
+            return Ok(None);
+        }
+        let release = Release::from_response(octocrab::map_github_error(response).await?).await?;
+        Ok(Some(release))
+    }
+
+    async fn upload_json_asset(
+        &self,
+        release: &Release,
+        name: &str,
+        asset: &serde_json::Value,
+    ) -> octocrab::Result<bool> {
+        // Uploads go to their own host, e.g. `uploads.github.com`; the URL
+        // is a template ending in `{?name,label}`
+        let upload_url = release
+            .upload_url
+            .split('{')
+            .next()
+            .unwrap_or(&release.upload_url);
+        let response = self
+            ._post(format!("{upload_url}?name={name}"), Some(asset))
+            .await?;
+        // "already_exists"
+        if response.status() == StatusCode::UNPROCESSABLE_ENTITY {
+            return Ok(false);
+        }
+        octocrab::map_github_error(response).await?;
+        Ok(true)
+    }
+}
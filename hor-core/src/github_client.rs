@@ -0,0 +1,544 @@
+//! The HTTP stack under the GitHub clients. Every token shares one
+//! connection pool, negotiating HTTP/2 where the server offers it, so a
+//! sync over a large fleet pays for a handful of TLS handshakes rather
+//! than one per request.
+//!
+//! Once GitHub rate-limits a token, be it the primary limit or a
+//! secondary (abuse) limit, every request of that token waits out the
+//! given delay instead of being retried straight away, which GitHub
+//! answers by banning the token for longer.
+
+use std::{
+    collections::HashMap,
+    future::Future,
+    path::PathBuf,
+    pin::Pin,
+    str::FromStr,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Mutex,
+    },
+    task::{Context, Poll},
+    time::Duration,
+};
+
+use chrono::Utc;
+use http::{
+    header::{AUTHORIZATION, RETRY_AFTER, USER_AGENT},
+    HeaderMap, HeaderValue, Method, Request, Response, StatusCode, Uri,
+};
+use hyper::{client::HttpConnector, service::Service, Body};
+use hyper_rustls::{HttpsConnector, HttpsConnectorBuilder};
+use jsonwebtoken::EncodingKey;
+use octocrab::{
+    auth::AppAuth,
+    models::{AppId, InstallationId},
+    service::middleware::{base_uri::BaseUriLayer, extra_headers::ExtraHeadersLayer},
+    AuthState, Octocrab, OctocrabBuilder,
+};
+use serde::Deserialize;
+use tokio::time::Instant;
+use tower::retry::{Policy, RetryLayer};
+use tracing::warn;
+
+use crate::{usage, HorSystemInitializationError};
+
+const GITHUB_API: &str = "https://api.github.com";
+
+/// Retries of a request that failed to connect, got a 5xx or was rate
+/// limited.
+const RETRIES: usize = 3;
+
+/// Wait before the first retry of a failed request, doubled on each
+/// further one.
+const RETRY_BACKOFF: Duration = Duration::from_millis(500);
+
+/// How long a token is paused after a secondary rate limit that doesn't
+/// say for how long, as GitHub recommends.
+const DEFAULT_PAUSE: Duration = Duration::from_secs(60);
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(rename_all = "kebab-case")]
+pub struct GithubClientConfig {
+    /// Idle connections kept open per host
+    #[serde(default = "GithubClientConfig::default_max_idle_per_host")]
+    max_idle_per_host: usize,
+    /// Seconds an idle connection is kept before being closed
+    #[serde(default = "GithubClientConfig::default_idle_timeout_secs")]
+    idle_timeout_secs: u64,
+    /// Seconds between keep-alive pings on HTTP/2 connections, and of TCP
+    /// keep-alive
+    #[serde(default = "GithubClientConfig::default_keep_alive_secs")]
+    keep_alive_secs: u64,
+    /// Offers HTTP/2 during the TLS handshake; HTTP/1.1 connections are
+    /// pooled too, but carry one request at a time
+    #[serde(default = "GithubClientConfig::default_http2")]
+    http2: bool,
+    #[serde(default)]
+    connect_timeout_secs: Option<u64>,
+}
+
+impl GithubClientConfig {
+    fn default_max_idle_per_host() -> usize {
+        32
+    }
+
+    fn default_idle_timeout_secs() -> u64 {
+        90
+    }
+
+    fn default_keep_alive_secs() -> u64 {
+        30
+    }
+
+    fn default_http2() -> bool {
+        true
+    }
+}
+
+impl Default for GithubClientConfig {
+    fn default() -> Self {
+        Self {
+            max_idle_per_host: GithubClientConfig::default_max_idle_per_host(),
+            idle_timeout_secs: GithubClientConfig::default_idle_timeout_secs(),
+            keep_alive_secs: GithubClientConfig::default_keep_alive_secs(),
+            http2: GithubClientConfig::default_http2(),
+            connect_timeout_secs: None,
+        }
+    }
+}
+
+/// Authentication as a GitHub App, each mutation going through the
+/// installation of its environment.
+#[derive(Deserialize, Debug, Clone)]
+#[serde(rename_all = "kebab-case")]
+pub struct GithubAppConfig {
+    app_id: u64,
+    /// PEM file of the app's private key
+    private_key_path: PathBuf,
+    /// Installation used for reads, and for mutating environments not in
+    /// `environments`
+    installation_id: u64,
+    /// Installations mutating specific environments, by env; e.g. the one
+    /// of a locked-down organization holding the prod repositories
+    #[serde(default)]
+    environments: HashMap<String, u64>,
+}
+
+/// Clients of a GitHub App's installations.
+pub(crate) struct GithubAppClients {
+    pub default: Octocrab,
+    /// By env, for those with their own installation
+    pub environments: HashMap<String, Octocrab>,
+}
+
+/// Counters of the shared connection pool since startup. Requests per
+/// connection is the reuse rate.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ConnectionStats {
+    /// Connections opened, each costing a TCP and TLS handshake
+    pub opened: u64,
+    /// Requests sent, retries included
+    pub requests: u64,
+}
+
+#[derive(Default)]
+struct Counters {
+    opened: AtomicU64,
+    requests: AtomicU64,
+}
+
+type HttpClient = hyper::Client<Counted<HttpsConnector<HttpConnector>>, String>;
+
+pub(crate) struct GithubClient {
+    http: HttpClient,
+    counters: Arc<Counters>,
+}
+
+impl GithubClient {
+    pub fn new(config: &GithubClientConfig) -> Self {
+        let keep_alive = Duration::from_secs(config.keep_alive_secs);
+        let mut connector = HttpConnector::new();
+        // TLS is negotiated by the wrapping connector
+        connector.enforce_http(false);
+        connector.set_keepalive(Some(keep_alive));
+        connector.set_connect_timeout(config.connect_timeout_secs.map(Duration::from_secs));
+
+        let https = HttpsConnectorBuilder::new()
+            .with_native_roots()
+            // Plain HTTP is only ever configured for stand-ins
+            .https_or_http();
+        let https = match config.http2 {
+            true => https.enable_all_versions().wrap_connector(connector),
+            false => https.enable_http1().wrap_connector(connector),
+        };
+
+        let counters = Arc::new(Counters::default());
+        let http = hyper::Client::builder()
+            .pool_max_idle_per_host(config.max_idle_per_host)
+            .pool_idle_timeout(Duration::from_secs(config.idle_timeout_secs))
+            .http2_keep_alive_interval(keep_alive)
+            .http2_keep_alive_while_idle(true)
+            .build(Counted {
+                inner: https,
+                counters: counters.clone(),
+            });
+        Self { http, counters }
+    }
+
+    /// A client authenticating with `token`, on the shared pool.
+    pub fn octocrab(
+        &self,
+        token: &str,
+        api_url: Option<&str>,
+    ) -> Result<Octocrab, HorSystemInitializationError> {
+        let authorization = HeaderValue::from_str(&format!("Bearer {token}"))
+            .map_err(HorSystemInitializationError::GithubToken)?;
+        self.build(Some(authorization), AuthState::None, api_url)
+    }
+
+    /// Clients of the installations of the app in `config`, on the shared
+    /// pool. Installation tokens are fetched and renewed by octocrab.
+    pub fn app(
+        &self,
+        config: &GithubAppConfig,
+        api_url: Option<&str>,
+    ) -> Result<GithubAppClients, HorSystemInitializationError> {
+        let pem = std::fs::read(&config.private_key_path)
+            .map_err(HorSystemInitializationError::GithubAppKeyFile)?;
+        let key =
+            EncodingKey::from_rsa_pem(&pem).map_err(HorSystemInitializationError::GithubAppKey)?;
+        // Installations share the app's pause, which errs on the side of
+        // waiting when only one of them is limited
+        let app = self.build(
+            None,
+            AuthState::App(AppAuth {
+                app_id: AppId(config.app_id),
+                key,
+            }),
+            api_url,
+        )?;
+        Ok(GithubAppClients {
+            default: app.installation(InstallationId(config.installation_id)),
+            environments: config
+                .environments
+                .iter()
+                .map(|(env, id)| (env.clone(), app.installation(InstallationId(*id))))
+                .collect(),
+        })
+    }
+
+    fn build(
+        &self,
+        authorization: Option<HeaderValue>,
+        auth: AuthState,
+        api_url: Option<&str>,
+    ) -> Result<Octocrab, HorSystemInitializationError> {
+        let base_uri = Uri::from_str(api_url.unwrap_or(GITHUB_API))
+            .map_err(HorSystemInitializationError::GithubApiUrl)?;
+        let mut headers = vec![(USER_AGENT, HeaderValue::from_static("hands-off-release"))];
+        headers.extend(authorization.map(|authorization| (AUTHORIZATION, authorization)));
+
+        // Each token is limited on its own, so each gets its own pause
+        let service = Paced {
+            inner: Counted {
+                inner: self.http.clone(),
+                counters: self.counters.clone(),
+            },
+            pause: Arc::default(),
+        };
+        Ok(OctocrabBuilder::new_empty()
+            .with_service(service)
+            .with_layer(&RetryLayer::new(Retry::new()))
+            .with_layer(&ExtraHeadersLayer::new(Arc::new(headers)))
+            .with_layer(&BaseUriLayer::new(base_uri))
+            .with_auth(auth)
+            .build()
+            .unwrap_or_else(|never| match never {}))
+    }
+
+    pub fn stats(&self) -> ConnectionStats {
+        ConnectionStats {
+            opened: self.counters.opened.load(Ordering::Relaxed),
+            requests: self.counters.requests.load(Ordering::Relaxed),
+        }
+    }
+}
+
+/// Counts calls into the wrapped service: as a connector, connections
+/// opened; as a client, requests sent.
+#[derive(Clone)]
+struct Counted<S> {
+    inner: S,
+    counters: Arc<Counters>,
+}
+
+impl<C: Service<Uri>> Service<Uri> for Counted<C> {
+    type Response = C::Response;
+    type Error = C::Error;
+    type Future = C::Future;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, uri: Uri) -> Self::Future {
+        self.counters.opened.fetch_add(1, Ordering::Relaxed);
+        self.inner.call(uri)
+    }
+}
+
+impl Service<Request<String>> for Counted<HttpClient> {
+    type Response = Response<Body>;
+    type Error = hyper::Error;
+    type Future = Pin<Box<dyn Future<Output = Result<Response<Body>, hyper::Error>> + Send>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Service::<Request<String>>::poll_ready(&mut self.inner, cx)
+    }
+
+    fn call(&mut self, request: Request<String>) -> Self::Future {
+        self.counters.requests.fetch_add(1, Ordering::Relaxed);
+        Box::pin(self.inner.call(request))
+    }
+}
+
+/// Until when a token's requests are held back.
+#[derive(Default)]
+struct Pause {
+    until: Mutex<Option<Instant>>,
+}
+
+impl Pause {
+    async fn wait(&self) {
+        // Looped since the pause may be extended while sleeping
+        while let Some(until) = self.until() {
+            if until <= Instant::now() {
+                return;
+            }
+            tokio::time::sleep_until(until).await;
+        }
+    }
+
+    fn extend(&self, delay: Duration) {
+        let until = Instant::now() + delay;
+        let mut current = self
+            .until
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        // `None` sorts first, so a running pause is only ever extended
+        *current = (*current).max(Some(until));
+    }
+
+    fn until(&self) -> Option<Instant> {
+        *self
+            .until
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+/// Marks a response that paused its token, for the retry policy.
+#[derive(Clone, Copy)]
+struct RateLimited;
+
+/// Holds requests back while the token is paused, and pauses it when a
+/// response says it's rate limited.
+#[derive(Clone)]
+struct Paced<S> {
+    inner: S,
+    pause: Arc<Pause>,
+}
+
+impl Service<Request<String>> for Paced<Counted<HttpClient>> {
+    type Response = Response<Body>;
+    type Error = hyper::Error;
+    type Future = Pin<Box<dyn Future<Output = Result<Response<Body>, hyper::Error>> + Send>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, request: Request<String>) -> Self::Future {
+        let mut inner = self.inner.clone();
+        let pause = self.pause.clone();
+        Box::pin(async move {
+            pause.wait().await;
+            let response = inner.call(request).await;
+            usage::record(response.as_ref().ok().map(Response::status));
+            let response = response?;
+            let status = response.status();
+            if !limiting(status) {
+                return Ok(response);
+            }
+            // Only the body tells a secondary limit from a permission problem
+            let (mut parts, body) = response.into_parts();
+            let body = hyper::body::to_bytes(body).await?;
+            if let Some(delay) = rate_limited(status, &parts.headers, &body) {
+                warn!(?delay, %status, "GitHub rate limit hit, pausing the token");
+                pause.extend(delay);
+                parts.extensions.insert(RateLimited);
+            }
+            Ok(Response::from_parts(parts, Body::from(body)))
+        })
+    }
+}
+
+/// Whether GitHub may answer a rate limit with `status`.
+fn limiting(status: StatusCode) -> bool {
+    status == StatusCode::FORBIDDEN || status == StatusCode::TOO_MANY_REQUESTS
+}
+
+/// How long to hold off if the response is a rate limit. GitHub answers
+/// 403 or 429: a `Retry-After` says how long, an exhausted
+/// `x-ratelimit-remaining` until when, and otherwise a secondary limit
+/// is waited out for at least a minute.
+fn rate_limited(status: StatusCode, headers: &HeaderMap, body: &[u8]) -> Option<Duration> {
+    if !limiting(status) {
+        return None;
+    }
+    let header = |name| headers.get(name).and_then(|value| value.to_str().ok());
+    if let Some(seconds) = header(RETRY_AFTER.as_str()).and_then(|value| value.parse().ok()) {
+        return Some(Duration::from_secs(seconds));
+    }
+    if header("x-ratelimit-remaining") == Some("0") {
+        let reset = header("x-ratelimit-reset").and_then(|reset| reset.parse::<i64>().ok());
+        return Some(reset.map_or(DEFAULT_PAUSE, |reset| {
+            Duration::from_secs((reset - Utc::now().timestamp()).max(1) as u64)
+        }));
+    }
+    let message = String::from_utf8_lossy(body).to_lowercase();
+    // Older GitHub Enterprise servers still call it abuse detection
+    let secondary = message.contains("secondary rate limit") || message.contains("abuse detection");
+    // A bare 403 is a permission problem; a bare 429 still a limit
+    (secondary || status == StatusCode::TOO_MANY_REQUESTS).then_some(DEFAULT_PAUSE)
+}
+
+/// Whether sending `request` twice does no more than sending it once.
+/// Ref updates are, as they set the ref to a given SHA.
+fn idempotent(request: &Request<String>) -> bool {
+    match *request.method() {
+        Method::GET | Method::HEAD | Method::PUT | Method::DELETE | Method::OPTIONS => true,
+        Method::PATCH => request.uri().path().contains("/git/refs/"),
+        _ => false,
+    }
+}
+
+/// Retries idempotent requests that failed to connect or got a 5xx, with
+/// a doubling backoff, and any rate-limited request once the pause it
+/// caused is over: GitHub didn't act on it.
+#[derive(Clone)]
+struct Retry {
+    attempts: usize,
+    backoff: Duration,
+}
+
+impl Retry {
+    fn new() -> Self {
+        Self {
+            attempts: RETRIES,
+            backoff: RETRY_BACKOFF,
+        }
+    }
+}
+
+impl Policy<Request<String>, Response<Body>, hyper::Error> for Retry {
+    type Future = Pin<Box<dyn Future<Output = Self> + Send>>;
+
+    fn retry(
+        &self,
+        request: &Request<String>,
+        result: Result<&Response<Body>, &hyper::Error>,
+    ) -> Option<Self::Future> {
+        let retry = match result {
+            Ok(response) if response.extensions().get::<RateLimited>().is_some() => true,
+            Ok(response) => response.status().is_server_error() && idempotent(request),
+            Err(_) => idempotent(request),
+        };
+        if !retry || self.attempts == 0 {
+            return None;
+        }
+        let next = Retry {
+            attempts: self.attempts - 1,
+            backoff: self.backoff * 2,
+        };
+        let backoff = self.backoff;
+        Some(Box::pin(async move {
+            tokio::time::sleep(backoff).await;
+            next
+        }))
+    }
+
+    fn clone_request(&self, request: &Request<String>) -> Option<Request<String>> {
+        let mut clone = Request::builder()
+            .uri(request.uri())
+            .method(request.method())
+            .version(request.version())
+            .body(request.body().clone())
+            .ok()?;
+        *clone.headers_mut() = request.headers().clone();
+        Some(clone)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn headers(pairs: &[(&'static str, &str)]) -> HeaderMap {
+        pairs
+            .iter()
+            .map(|(name, value)| (http::HeaderName::from_static(name), value.parse().unwrap()))
+            .collect()
+    }
+
+    #[test]
+    fn secondary_limits_pause_for_a_minute() {
+        let body = br#"{"message": "You have exceeded a secondary rate limit."}"#;
+        let with_quota = headers(&[("x-ratelimit-remaining", "4000")]);
+        assert_eq!(
+            rate_limited(StatusCode::FORBIDDEN, &with_quota, body),
+            Some(DEFAULT_PAUSE)
+        );
+        let exhausted = headers(&[("x-ratelimit-remaining", "0")]);
+        assert_eq!(
+            rate_limited(StatusCode::FORBIDDEN, &exhausted, b""),
+            Some(DEFAULT_PAUSE)
+        );
+        let retry_after = headers(&[("retry-after", "120")]);
+        assert_eq!(
+            rate_limited(StatusCode::FORBIDDEN, &retry_after, body),
+            Some(Duration::from_secs(120))
+        );
+    }
+
+    #[test]
+    fn permission_denials_are_not_limits() {
+        let body = br#"{"message": "Resource not accessible by integration"}"#;
+        let with_quota = headers(&[("x-ratelimit-remaining", "4000")]);
+        assert_eq!(rate_limited(StatusCode::FORBIDDEN, &with_quota, body), None);
+        let body = br#"{"message": "secondary rate limit"}"#;
+        assert_eq!(
+            rate_limited(StatusCode::NOT_FOUND, &HeaderMap::new(), body),
+            None
+        );
+    }
+
+    #[test]
+    fn only_idempotent_requests_are_retried_on_errors() {
+        let request = |method, path: &str| {
+            Request::builder()
+                .method(method)
+                .uri(format!("https://api.github.com{path}"))
+                .body(String::new())
+                .unwrap()
+        };
+        assert!(idempotent(&request(Method::GET, "/repos/o/r")));
+        assert!(idempotent(&request(
+            Method::PATCH,
+            "/repos/o/r/git/refs/heads/prod"
+        )));
+        assert!(!idempotent(&request(Method::PATCH, "/repos/o/r")));
+        assert!(!idempotent(&request(Method::POST, "/repos/o/r/git/refs")));
+    }
+}
@@ -0,0 +1,236 @@
+//! All-or-nothing releases of project groups, e.g. the services of a
+//! checkout stack that have to move together.
+
+use futures::future::join_all;
+use hor_registry::{GithubProject, ProjectId, Registry};
+use hor_state::{
+    ApiUsage, BlockReason, PolicyDecision, ProjectOutcome, ProjectReport, ReleaseInputs, SkipReason,
+};
+use tracing::{error, info, info_span, warn, Instrument};
+
+use crate::{actions::Promotion, usage, HorSystem, InitializedState, Priority, Release};
+
+struct Member {
+    id: ProjectId,
+    project: GithubProject,
+    decisions: Vec<PolicyDecision>,
+    inputs: Option<ReleaseInputs>,
+    release: Option<Release>,
+    outcome: Option<ProjectOutcome>,
+    /// Of every step of the member so far
+    api_usage: ApiUsage,
+}
+
+impl<R: Registry + ?Sized> HorSystem<InitializedState, R> {
+    /// Plans every project of the group first and only moves any if none
+    /// is held back. If moving one fails, the ones already moved are moved
+    /// back and the whole group reports the failure.
+    pub(crate) async fn update_group(
+        &self,
+        group: &str,
+        projects: &[&GithubProject],
+        priority: Priority,
+    ) -> Vec<ProjectReport> {
+        let mut ids: Vec<_> = projects.iter().map(|project| project.id()).collect();
+        ids.sort();
+        ids.dedup();
+        // Taken in id order, so overlapping syncs can't deadlock
+        let mut guards = Vec::new();
+        for id in &ids {
+            guards.push(self.state.project_locks.lock(id).await);
+        }
+        // One slot for the group, as its projects only ever move together
+        let mut permit = self.state.scheduler.acquire(priority).await;
+
+        let mut members = Vec::new();
+        for project in projects {
+            let id = project.id();
+            let mut decisions = Vec::new();
+            let mut inputs = None;
+            let ((project, planned), api_usage) = usage::metered(async {
+                let project = self
+                    .follow_move(project)
+                    .await
+                    .unwrap_or_else(|| (*project).clone());
+                let planned = self
+                    .plan_github(
+                        &id,
+                        &project,
+                        &mut decisions,
+                        &mut inputs,
+                        &mut permit,
+                        priority,
+                    )
+                    .instrument(info_span!("plan Github project", %id, group))
+                    .await;
+                (project, planned)
+            })
+            .await;
+            let (release, outcome) = match planned {
+                Ok(Ok(release)) => (Some(release), None),
+                Ok(Err(outcome)) => (None, Some(outcome)),
+                Err(err) => {
+                    error!(%id, err = %self.state.redactor.debug(&err), "Unable to plan project");
+                    let error = format!("{err:#}");
+                    (None, Some(ProjectOutcome::Failed { error }))
+                }
+            };
+            members.push(Member {
+                id,
+                project,
+                decisions,
+                inputs,
+                release,
+                outcome,
+                api_usage,
+            });
+        }
+
+        // Members with nothing to release don't hold the others back; a
+        // frozen one does, as does any failure or block
+        let holding: Vec<_> = members
+            .iter()
+            .filter(|member| match &member.outcome {
+                None | Some(ProjectOutcome::Unchanged { .. }) => false,
+                Some(ProjectOutcome::Skipped { reason, .. }) => *reason == SkipReason::Frozen,
+                Some(_) => true,
+            })
+            .map(|member| member.id.to_string())
+            .collect();
+        if holding.is_empty() {
+            self.release_group(group, &mut members).await;
+        } else {
+            info!(group, ?holding, "Group held back");
+            let detail = format!("group {group} is held back by {}", holding.join(", "));
+            for member in &mut members {
+                if member.release.is_some() {
+                    member.outcome = Some(ProjectOutcome::Blocked {
+                        reason: BlockReason::Group,
+                        detail: detail.clone(),
+                    });
+                }
+            }
+        }
+
+        drop(permit);
+        let reports = join_all(members.into_iter().map(|member| async move {
+            let outcome = member.outcome.unwrap_or_else(|| ProjectOutcome::Failed {
+                error: "project was never released".to_string(),
+            });
+            let (mut report, api_usage) = usage::metered(self.finish(
+                member.id,
+                &member.project,
+                outcome,
+                member.decisions,
+                member.inputs,
+                None,
+            ))
+            .await;
+            report.api_usage = member.api_usage;
+            report.api_usage += api_usage;
+            report
+        }))
+        .await;
+        drop(guards);
+        reports
+    }
+
+    /// Moves each planned project in turn, moving everything back as soon
+    /// as one doesn't move.
+    async fn release_group(&self, group: &str, members: &mut [Member]) {
+        let mut failed = None;
+        for member in members.iter_mut() {
+            let Some(release) = member.release.clone() else {
+                continue;
+            };
+            let id = &member.id;
+            let (outcome, api_usage) = usage::metered(
+                self.release_github(id, &member.project, release)
+                    .instrument(info_span!("update Github project", %id, group)),
+            )
+            .await;
+            member.api_usage += api_usage;
+            let outcome = outcome.unwrap_or_else(|err| {
+                error!(%id, err = %self.state.redactor.debug(&err), "Unable to sync project");
+                ProjectOutcome::Failed {
+                    error: format!("{err:#}"),
+                }
+            });
+            let moved = matches!(
+                outcome,
+                ProjectOutcome::Created { .. }
+                    | ProjectOutcome::Updated { .. }
+                    | ProjectOutcome::Recreated { .. }
+            );
+            member.outcome = Some(outcome);
+            if !moved {
+                failed = Some(member.id.clone());
+                break;
+            }
+        }
+        let Some(failed) = failed else {
+            return;
+        };
+
+        let reason = format!("{failed} of group {group} did not move");
+        warn!(group, %failed, "Group release failed, moving it back");
+        for member in members.iter_mut() {
+            let Some(release) = &member.release else {
+                continue;
+            };
+            member.outcome = match member.outcome.take() {
+                Some(
+                    ProjectOutcome::Created { .. }
+                    | ProjectOutcome::Updated { .. }
+                    | ProjectOutcome::Recreated { .. },
+                ) => {
+                    let (outcome, api_usage) =
+                        usage::metered(self.roll_back_member(member, release, &reason)).await;
+                    member.api_usage += api_usage;
+                    Some(outcome)
+                }
+                // Never attempted
+                None => Some(ProjectOutcome::Blocked {
+                    reason: BlockReason::Group,
+                    detail: reason.clone(),
+                }),
+                outcome => outcome,
+            };
+        }
+    }
+
+    async fn roll_back_member(
+        &self,
+        member: &Member,
+        release: &Release,
+        reason: &str,
+    ) -> ProjectOutcome {
+        let Some(from) = &release.tag_sha else {
+            return ProjectOutcome::Failed {
+                error: format!("{reason}; the created env tag was left in place"),
+            };
+        };
+        let promotion = Promotion {
+            project: &member.project,
+            from: Some(from),
+            to: &release.target_sha,
+        };
+        match self.roll_back(&member.id, &promotion, from).await {
+            Ok(()) => ProjectOutcome::RolledBack {
+                from: from.clone(),
+                to: release.target_sha.clone(),
+                reason: reason.to_string(),
+            },
+            Err(err) => {
+                error!(
+                    id = %member.id,
+                    err = %self.state.redactor.debug(&err),
+                    "Unable to roll back group member"
+                );
+                ProjectOutcome::Failed {
+                    error: format!("{reason}; moving it back failed: {err:#}"),
+                }
+            }
+        }
+    }
+}
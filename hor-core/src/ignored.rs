@@ -0,0 +1,75 @@
+//! Commits that on their own don't warrant releasing the branch, e.g.
+//! Dependabot's or docs-only ones.
+
+use anyhow::Context;
+use glob::Pattern;
+use hor_registry::{IgnoredChanges, Registry};
+
+use crate::{
+    github::{ChangedFile, HorOctocrabExtension},
+    HorSystem, InitializedState,
+};
+
+impl<R: Registry + ?Sized> HorSystem<InitializedState, R> {
+    /// Why moving the env tag from `from` to `to` isn't warranted, if
+    /// every commit in between is ignored. Ranges that aren't a plain
+    /// move forward, or with commits too long to list whole, are always
+    /// released.
+    pub(crate) async fn unwarranted(
+        &self,
+        ignore: &IgnoredChanges,
+        owner: &str,
+        repo: &str,
+        from: &str,
+        to: &str,
+    ) -> anyhow::Result<Option<String>> {
+        let paths = ignore
+            .paths
+            .iter()
+            .map(|glob| Pattern::new(glob))
+            .collect::<Result<Vec<_>, _>>()
+            .context("Invalid ignored path")?;
+        let octo = &self.state.read_octo;
+        let comparison = octo
+            .compare(owner, repo, from, to)
+            .await
+            .context("Unable to compare with the release")?;
+        if comparison.status != "ahead" || comparison.commits.len() < comparison.total_commits {
+            return Ok(None);
+        }
+
+        for commit in &comparison.commits {
+            let by_ignored = commit
+                .author
+                .as_ref()
+                .is_some_and(|account| ignore.authors.contains(&account.login));
+            if by_ignored {
+                continue;
+            }
+            if paths.is_empty() {
+                return Ok(None);
+            }
+            // Compared commits come without their files
+            let files = octo
+                .commit(owner, repo, &commit.sha)
+                .await
+                .with_context(|| format!("Unable to read commit {}", commit.sha))?
+                .files;
+            // Empty commits are pushed on purpose, e.g. to force a release
+            let ignored = !files.is_empty()
+                && files.len() < ChangedFile::LISTED_AT_MOST
+                && files
+                    .iter()
+                    .flat_map(ChangedFile::paths)
+                    .all(|path| paths.iter().any(|pattern| pattern.matches(path)));
+            if !ignored {
+                return Ok(None);
+            }
+        }
+        Ok(Some(format!(
+            "all {} commit(s) since {} are ignored",
+            comparison.total_commits,
+            from.get(..7).unwrap_or(from)
+        )))
+    }
+}
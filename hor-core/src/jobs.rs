@@ -0,0 +1,114 @@
+//! Syncs queued in the state store, so a trigger survives a restart and
+//! runs once however many instances share the store.
+
+use std::{collections::HashSet, str::FromStr};
+
+use anyhow::Context;
+use chrono::Duration;
+use hor_registry::{LabelSelector, Registry};
+use hor_state::{JobId, RunId, SyncJob, SyncReport, SyncTarget};
+use tracing::{error, info, warn};
+
+use crate::{HorSystem, InitializedState, Priority};
+
+/// How often a running job's heartbeat is recorded.
+const JOB_HEARTBEAT_SECS: u64 = 60;
+
+/// Without a heartbeat this long, a running job is taken to be abandoned
+/// by an instance that went away, and is claimed again.
+const JOB_TIMEOUT_SECS: i64 = 5 * 60;
+
+impl<R: Registry + ?Sized> HorSystem<InitializedState, R> {
+    /// Queues a sync of `target`, run by the next instance to claim it.
+    /// Queuing again under the same `idempotency_key`, e.g. when a client
+    /// retries, hands back the job queued first.
+    pub async fn queue_sync(
+        &self,
+        target: &SyncTarget,
+        idempotency_key: Option<&str>,
+    ) -> anyhow::Result<JobId> {
+        if let SyncTarget::Selector { selector } = target {
+            LabelSelector::from_str(selector)?;
+        }
+        let id = self
+            .state
+            .store
+            .enqueue_job(target, idempotency_key, self.state.clock.now())
+            .await
+            .context("Unable to queue sync")?;
+        info!(%id, ?target, "Queued sync");
+        Ok(id)
+    }
+
+    /// The most recent queued syncs, newest first.
+    pub async fn recent_jobs(&self, limit: usize) -> anyhow::Result<Vec<(JobId, SyncJob)>> {
+        Ok(self.state.store.recent_jobs(limit).await?)
+    }
+
+    /// Claims the oldest queued sync and runs it; `None` if nothing was
+    /// queued. The report comes with the job unless the sync failed, which
+    /// is recorded with the job instead.
+    pub(crate) async fn run_next_job(&self) -> anyhow::Result<Option<(JobId, Option<SyncReport>)>> {
+        let store = &self.state.store;
+        let now = self.state.clock.now();
+        let Some((id, job)) = store
+            .claim_job(now, now - Duration::seconds(JOB_TIMEOUT_SECS))
+            .await
+            .context("Unable to claim queued sync")?
+        else {
+            return Ok(None);
+        };
+        info!(%id, target = ?job.target, "Running queued sync");
+        let sync = self.run_job(&job.target);
+        tokio::pin!(sync);
+        let mut heartbeat =
+            tokio::time::interval(std::time::Duration::from_secs(JOB_HEARTBEAT_SECS));
+        // The claim counts as the first one
+        heartbeat.tick().await;
+        let result = loop {
+            tokio::select! {
+                result = &mut sync => break result,
+                _ = heartbeat.tick() => {
+                    if let Err(err) = store.heartbeat_job(id, self.state.clock.now()).await {
+                        let err = self.state.redactor.debug(&err);
+                        warn!(%id, err, "Unable to record job heartbeat");
+                    }
+                }
+            }
+        };
+        let finished_at = self.state.clock.now();
+        match result {
+            Ok((run, report)) => {
+                store.finish_job(id, Some(run), None, finished_at).await?;
+                Ok(Some((id, Some(report))))
+            }
+            Err(err) => {
+                let message = self.state.redactor.redact(&format!("{err:#}"));
+                error!(%id, error = message, "Queued sync failed");
+                store
+                    .finish_job(id, None, Some(&message), finished_at)
+                    .await?;
+                Ok(Some((id, None)))
+            }
+        }
+    }
+
+    /// Syncs the projects `target` covers.
+    async fn run_job(&self, target: &SyncTarget) -> anyhow::Result<(RunId, SyncReport)> {
+        match target {
+            SyncTarget::Selector { selector } => match LabelSelector::from_str(selector) {
+                Ok(selector) => {
+                    self.sync_projects(&selector, None, Priority::Triggered)
+                        .await
+                }
+                Err(err) => Err(err.into()),
+            },
+            SyncTarget::Projects { ids } => {
+                let ids: HashSet<_> = ids.iter().cloned().collect();
+                self.sync_projects(&LabelSelector::default(), Some(&ids), Priority::Triggered)
+                    .await
+            }
+            other => Err(anyhow::anyhow!("Unsupported sync target {other:?}")),
+        }
+    }
+}
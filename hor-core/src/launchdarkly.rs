@@ -0,0 +1,56 @@
+//! Flag state lookups against the LaunchDarkly REST API.
+
+use std::collections::HashMap;
+
+use anyhow::Context;
+use serde::Deserialize;
+
+const API: &str = "https://app.launchdarkly.com/api/v2";
+
+#[derive(Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct LaunchDarklyConfig {
+    /// Access token with read access to the gated flags
+    api_key: String,
+}
+
+impl LaunchDarklyConfig {
+    /// The access token, which errors may echo.
+    pub(crate) fn secrets(&self) -> impl Iterator<Item = &str> {
+        std::iter::once(self.api_key.as_str())
+    }
+}
+
+#[derive(Deserialize)]
+struct Flag {
+    environments: HashMap<String, FlagEnvironment>,
+}
+
+#[derive(Deserialize)]
+struct FlagEnvironment {
+    on: bool,
+}
+
+/// Whether targeting of `flag` is on in `environment`.
+pub(crate) async fn flag_on(
+    config: &LaunchDarklyConfig,
+    http: &reqwest::Client,
+    project: &str,
+    flag: &str,
+    environment: &str,
+) -> anyhow::Result<bool> {
+    let flag_state: Flag = http
+        .get(format!("{API}/flags/{project}/{flag}"))
+        .query(&[("env", environment)])
+        .header(reqwest::header::AUTHORIZATION, &config.api_key)
+        .send()
+        .await?
+        .error_for_status()?
+        .json()
+        .await?;
+    flag_state
+        .environments
+        .get(environment)
+        .map(|state| state.on)
+        .with_context(|| format!("Flag {flag} has no environment {environment}"))
+}
@@ -1,38 +1,194 @@
+pub mod actions;
+mod budgets;
+mod clock;
+mod codeowners;
+mod deletion;
+mod drift;
+pub mod events;
+mod expiry;
+mod extra_refs;
+mod github;
+mod github_client;
+mod groups;
+mod ignored;
+mod jobs;
+mod launchdarkly;
+mod locks;
+mod manifest;
+mod mirrors;
+mod nats;
+mod oci;
+pub mod policy;
+mod polling;
+mod previews;
+mod redaction;
+mod release_tags;
+mod replay;
+mod repos;
+mod rollouts;
+mod running;
+mod scheduler;
+mod supervisor;
+mod trains;
+mod usage;
+mod validation;
+mod versions;
+
+use std::{
+    collections::{BTreeMap, HashMap, HashSet},
+    sync::{Arc, Mutex, MutexGuard, Once},
+    time::Duration,
+};
+
+use actions::{Integrations, IntegrationsConfig, Promotion};
 use anyhow::{bail, Context};
-use async_trait::async_trait;
+use budgets::BudgetClaims;
+use chrono::{DateTime, Utc};
 use config::{Config, ConfigError, File};
-use hor_registry::{GithubProject, Registry, SourceProject};
+use drift::DriftReports;
+use events::{EventSinks, SinkConfig};
+use futures::future::join_all;
+use github::{HorOctocrabExtension, RefLookup, RefWriteError};
+use github_client::GithubClient;
+use glob::Pattern;
+use hor_registry::{
+    AutoPromotion, BlueGreen, GithubOwnerProject, GithubProject, LabelSelector, ProjectId,
+    PromotionBudget, Registry, RollbackIncident, SourceProject, Verification,
+};
+use hor_state::{
+    ActionReport, ApiUsage, BlockReason, Deployment, Event, FreezeScope, LeaderElectionConfig,
+    LeaderLeaseRef, PolicyDecision, ProjectOutcome, ProjectReport, RefState, ReleaseInputs,
+    ReleaseSource, RunId, SkipReason, StateStoreConfig, StateStoreError, StateStoreRef, SyncReport,
+    SyncedProject,
+};
+use locks::ProjectLocks;
 use mediator::{ConfigParseErr, ConfigProvider, Mediate};
 use mediator_config::configrs::ConfigRsAdapter;
 use mediator_tracing::TracingModule;
-use octocrab::{
-    models::repos::{Object, Ref},
-    params::repos::Reference,
-    GitHubError, Octocrab, OctocrabBuilder,
-};
+use octocrab::{models::repos::Object, Octocrab};
+use polling::AdaptivePolling;
+use repos::RepoCache;
+use scheduler::{Permit, Scheduler};
 use serde::Deserialize;
-use serde_json::json;
-use tracing::{info, info_span};
+use tracing::{debug, error, info, info_span, warn, Instrument};
 
-pub type RefType<T> = Box<T>;
+pub use clock::{Clock, ClockRef, ManualClock, ShiftedClock, SystemClock};
+pub use github_client::{ConnectionStats, GithubAppConfig, GithubClientConfig};
+pub use redaction::Redactor;
+pub use replay::ReplayedProject;
+pub use running::RunningState;
+pub use scheduler::{Priority, SchedulerConfig};
+pub use supervisor::{TaskHealth, TaskState};
+pub use validation::ProjectValidationError;
+
+pub type RefType<T> = Arc<T>;
+
+/// Registry type used when the registry is only known at runtime.
+pub type DynRegistry = dyn Registry + Send + Sync;
+
+const DEFAULT_SYNC_INTERVAL: Duration = Duration::from_secs(300);
+const MAX_TAG_DEPTH: usize = 8;
+const DEFAULT_REPOSITORY_CACHE_TTL: Duration = Duration::from_secs(3600);
+/// Runs searched for the last report of a promotion source
+const RECENT_RUNS: usize = 10;
+/// Longer than any env tag move takes, so a pending announcement this old
+/// was left behind by a crash
+const PENDING_GRACE: chrono::Duration = chrono::Duration::minutes(10);
+const DEFAULT_DELETED_PROJECT_RETENTION_DAYS: i64 = 30;
+const DEFAULT_STARTUP_VALIDATION_TIMEOUT: Duration = Duration::from_secs(120);
 
 pub struct UninitializedState {
     config_provider: ConfigRsAdapter,
 }
 
 pub struct InitializedState {
-    octo: Octocrab,
+    /// Client used for lookups (repository metadata, ref reads)
+    read_octo: Octocrab,
+    /// Client used for ref mutations
+    write_octo: Octocrab,
+    /// Clients mutating the refs of environments with their own GitHub App
+    /// installation, by env
+    env_octos: HashMap<String, Octocrab>,
+    store: StateStoreRef,
+    /// Only the lease holder mutates refs
+    lease: LeaderLeaseRef,
+    /// Outbox destinations, by name
+    sinks: EventSinks,
+    integrations: Integrations,
+    /// Connection pool under both GitHub clients
+    github: GithubClient,
+    /// Bounds GitHub traffic across every concurrent sync
+    scheduler: Scheduler,
+    /// Default branches and node ids, refreshed after a TTL
+    repos: RepoCache,
+    /// One sync per project at a time, whoever triggered it
+    project_locks: ProjectLocks,
+    /// Tags never moved, whatever the registry says
+    protected_refs: Vec<Pattern>,
+    /// Runs everything but the mutations
+    shadow: bool,
+    /// Scrubs credentials from reports and notifications
+    redactor: Redactor,
+    /// What windows, trains, bake times and history go by
+    clock: ClockRef,
+    /// Budgets shared by every project of an env, by env
+    promotion_budgets: HashMap<String, PromotionBudget>,
+    budget_claims: BudgetClaims,
+    /// GitHub API usage of each project since startup
+    api_usage: Mutex<BTreeMap<ProjectId, ApiUsage>>,
+    sync_interval: Duration,
+    /// Scheduled syncs in between full passes only cover dirty projects;
+    /// `None` makes every scheduled sync a full pass
+    full_sync_interval: Option<Duration>,
+    /// Scheduled syncs in between full passes also cover the projects due
+    /// by their activity
+    adaptive_polling: Option<AdaptivePolling>,
+    /// Compiled daily and queued for the notification sinks
+    drift_reports: Option<DriftReports>,
+    /// How long soft-deleted projects keep their history
+    deletion_retention: chrono::Duration,
+    /// How long startup waits for the projects to be validated
+    startup_validation_timeout: Duration,
 }
 
-pub struct HorSystem<State> {
-    registry: RefType<dyn Registry>,
+impl InitializedState {
+    /// The client mutating refs of `env`.
+    fn writer(&self, env: &str) -> &Octocrab {
+        self.env_octos.get(env).unwrap_or(&self.write_octo)
+    }
+}
+
+/// The release system, generic over its lifecycle state and registry.
+///
+/// The registry defaults to a trait object; embedders that know their
+/// registry type can use [`HorSystem::with_registry`] for static dispatch.
+pub struct HorSystem<State, R: ?Sized = DynRegistry> {
+    registry: RefType<R>,
     state: State,
 }
 
 impl HorSystem<UninitializedState> {
     pub fn new(
-        registry: RefType<dyn Registry>,
-        config_path: &'static str,
+        registry: RefType<DynRegistry>,
+        config_path: &str,
+    ) -> Result<Self, HorSystemInitializationError> {
+        Self::from_ref(registry, config_path)
+    }
+}
+
+impl<R: Registry> HorSystem<UninitializedState, R> {
+    pub fn with_registry(
+        registry: R,
+        config_path: &str,
+    ) -> Result<Self, HorSystemInitializationError> {
+        Self::from_ref(RefType::new(registry), config_path)
+    }
+}
+
+impl<R: Registry + ?Sized> HorSystem<UninitializedState, R> {
+    fn from_ref(
+        registry: RefType<R>,
+        config_path: &str,
     ) -> Result<Self, HorSystemInitializationError> {
         Ok(Self {
             registry,
@@ -41,136 +197,1870 @@ impl HorSystem<UninitializedState> {
                     Config::builder()
                         .add_source(File::with_name(config_path))
                         .build()
-                        .map_err(|err| HorSystemInitializationError::ConfigRs(err))?,
+                        .map_err(HorSystemInitializationError::ConfigRs)?,
                 ),
             },
         })
     }
 
-    pub fn init(self) -> Result<HorSystem<InitializedState>, HorSystemInitializationError> {
-        TracingModule::default().init();
+    /// Reads the configuration and wires the system up. Tracing is set up
+    /// by the first system initialized in the process, and shared by the
+    /// ones after it.
+    pub fn init(self) -> Result<HorSystem<InitializedState, R>, HorSystemInitializationError> {
+        static TRACING: Once = Once::new();
+        TRACING.call_once(|| TracingModule::default().init());
         let config = self.state.config_provider.extract("hor");
 
         let config: HorSystemConfiguration =
-            config.map_err(|err| HorSystemInitializationError::ConfigParse(err))?;
+            config.map_err(HorSystemInitializationError::ConfigParse)?;
 
         self.mediate(config)
     }
 }
 
-impl HorSystem<InitializedState> {
-    pub async fn sync(&self) -> anyhow::Result<()> {
+impl<R: Registry + ?Sized> HorSystem<InitializedState, R> {
+    pub async fn sync(&self) -> anyhow::Result<SyncReport> {
+        self.sync_filtered(&LabelSelector::default()).await
+    }
+
+    /// Syncs only the projects whose labels match `selector`.
+    pub async fn sync_filtered(&self, selector: &LabelSelector) -> anyhow::Result<SyncReport> {
+        self.sync_with(selector, Priority::Triggered).await
+    }
+
+    /// Syncs the projects whose labels match `selector`, going through the
+    /// scheduler at `priority`.
+    ///
+    /// A failing project is recorded in the report without aborting the
+    /// remaining projects; the report is persisted as run history.
+    pub async fn sync_with(
+        &self,
+        selector: &LabelSelector,
+        priority: Priority,
+    ) -> anyhow::Result<SyncReport> {
+        let (_, report) = self.sync_projects(selector, None, priority).await?;
+        Ok(report)
+    }
+
+    /// Syncs only the projects in `ids`, e.g. the ones known to have
+    /// changed.
+    pub async fn sync_ids(
+        &self,
+        ids: &HashSet<ProjectId>,
+        priority: Priority,
+    ) -> anyhow::Result<SyncReport> {
+        let (_, report) = self
+            .sync_projects(&LabelSelector::default(), Some(ids), priority)
+            .await?;
+        Ok(report)
+    }
+
+    /// The report of the sync, and the run it was recorded as.
+    pub(crate) async fn sync_projects(
+        &self,
+        selector: &LabelSelector,
+        ids: Option<&HashSet<ProjectId>>,
+        priority: Priority,
+    ) -> anyhow::Result<(RunId, SyncReport)> {
+        if !self.is_leader().await? {
+            bail!("Another instance holds the leader lease, refusing to sync");
+        }
+
+        let started_at = self.state.clock.now();
+        let mut github = Vec::new();
+        let mut owners = Vec::new();
+        let mut rollouts = Vec::new();
+        // Entries that can't be synced are reported on, not the whole sync
+        // failed for them
+        let mut unsynced = Vec::new();
         let projects = self.registry.get_projects();
         for project in projects {
+            if !selector.matches(project.labels()) {
+                continue;
+            }
             match project {
-                SourceProject::Github(project) => self.update_github(project).await?,
-                other => bail!("Project type currently not supported {:?}", other),
+                SourceProject::Github(project) => match &project.rollout {
+                    Some(rollout) if !project.regions.is_empty() => {
+                        rollouts.push((project, rollout))
+                    }
+                    _ => github.extend(project.expand_regions()),
+                },
+                SourceProject::GithubOwner(owner) => owners.push(owner),
+                other => unsynced.push(ProjectReport::bare(
+                    other.id(),
+                    other.env().to_string(),
+                    ProjectOutcome::Skipped {
+                        reason: SkipReason::Unsupported,
+                        detail: format!("project type currently not supported {other:?}"),
+                    },
+                )),
+            }
+        }
+        // Listing large organizations page by page dominates the start of a
+        // sync, so every owner is listed at once
+        let expanded = join_all(owners.iter().map(|owner| self.expand_github_owner(owner))).await;
+        for (owner, expanded) in owners.into_iter().zip(expanded) {
+            match expanded {
+                Ok(projects) => github.extend(projects),
+                Err(err) => {
+                    let error = format!("{err:#}");
+                    let err = self.state.redactor.debug(&err);
+                    error!(owner = owner.owner, err, "Unable to expand owner");
+                    let outcome = ProjectOutcome::Failed { error };
+                    unsynced.push(ProjectReport::bare(owner.id(), owner.env.clone(), outcome));
+                }
+            }
+        }
+        // Regions of a project share its previews
+        let mut previewed = HashSet::new();
+        let previews: Vec<_> = github
+            .iter()
+            .chain(rollouts.iter().map(|(project, _)| *project))
+            .filter_map(|project| Some((project, project.previews.as_ref()?)))
+            .filter(|(project, previews)| {
+                previewed.insert((
+                    project.owner.clone(),
+                    project.repo.clone(),
+                    previews.env.clone(),
+                ))
+            })
+            .map(|(project, previews)| (project.clone(), previews.clone()))
+            .collect();
+        let mut torn_down = Vec::new();
+        for (expanded, (project, previews)) in join_all(
+            previews
+                .iter()
+                .map(|(project, previews)| self.expand_previews(project, previews)),
+        )
+        .await
+        .into_iter()
+        .zip(&previews)
+        {
+            match expanded {
+                Ok((projects, reports)) => {
+                    github.extend(projects);
+                    torn_down.extend(reports);
+                }
+                Err(err) => {
+                    let error = format!("{err:#}");
+                    let err = self.state.redactor.debug(&err);
+                    error!(id = %project.id(), err, "Unable to expand previews");
+                    let id = project.id_for_env(&previews.env);
+                    let outcome = ProjectOutcome::Failed { error };
+                    unsynced.push(ProjectReport::bare(id, previews.env.clone(), outcome));
+                }
+            }
+        }
+        // Ids of expanded projects are only known past this point
+        if let Some(ids) = ids {
+            github.retain(|project| ids.contains(&project.id()));
+        }
+        let mut groups: BTreeMap<&str, Vec<&GithubProject>> = BTreeMap::new();
+        let mut single = Vec::new();
+        for project in &github {
+            match &project.group {
+                Some(group) => groups.entry(group).or_default().push(project),
+                None => single.push(project),
+            }
+        }
+        // Every project is started at once; the scheduler decides how many
+        // actually talk to GitHub
+        let single = join_all(single.into_iter().map(|project| async move {
+            // Taken first, so a project waiting on itself doesn't hold a slot
+            let _guard = self.state.project_locks.lock(&project.id()).await;
+            let permit = self.state.scheduler.acquire(priority).await;
+            self.update_github(project, permit, priority).await
+        }));
+        let groups = join_all(
+            groups
+                .iter()
+                .map(|(group, projects)| self.update_group(group, projects, priority)),
+        );
+        let rollouts = join_all(
+            rollouts
+                .iter()
+                .map(|(project, rollout)| self.update_rollout(project, rollout, ids, priority)),
+        );
+        let (mut reports, groups, rollouts) = futures::join!(single, groups, rollouts);
+        reports.extend(groups.into_iter().flatten());
+        reports.extend(rollouts.into_iter().flatten());
+        reports.extend(torn_down);
+        reports.extend(unsynced);
+
+        // Errors may quote whatever GitHub or an integration sent back
+        let report = self.state.redactor.redact_value(SyncReport {
+            simulated: self.state.shadow,
+            started_at,
+            finished_at: self.state.clock.now(),
+            projects: reports,
+        });
+        let run = self
+            .state
+            .store
+            .record_run(&report)
+            .await
+            .context("Unable to record run")?;
+        let finished = Event::SyncFinished {
+            run,
+            started_at: report.started_at,
+            finished_at: report.finished_at,
+            projects: report
+                .projects
+                .iter()
+                .map(|project| SyncedProject {
+                    id: project.id.clone(),
+                    env: project.env.clone(),
+                    outcome: project.outcome.clone(),
+                })
+                .collect(),
+            simulated: report.simulated,
+        };
+        if let Err(err) = events::enqueue(
+            self.state.store.as_ref(),
+            &self.state.sinks,
+            self.state.clock.now(),
+            finished,
+        )
+        .await
+        {
+            error!(err = %self.state.redactor.debug(&err), "Unable to queue sync finished event");
+        }
+        let mut points = 0;
+        {
+            let mut totals = self.lock_api_usage();
+            for project in &report.projects {
+                points += project.api_usage.points;
+                *totals.entry(project.id.clone()).or_default() += project.api_usage;
+            }
+        }
+        let connections = self.state.github.stats();
+        info!(
+            %run,
+            failures = report.failures().count(),
+            connections = connections.opened,
+            requests = connections.requests,
+            points,
+            "Sync finished"
+        );
+        Ok((run, report))
+    }
+
+    pub fn state_store(&self) -> &StateStoreRef {
+        &self.state.store
+    }
+
+    /// How long startup waits for [`validate_projects`](Self::validate_projects).
+    pub fn startup_validation_timeout(&self) -> Duration {
+        self.state.startup_validation_timeout
+    }
+
+    /// Connection reuse of the GitHub clients since startup.
+    pub fn github_connections(&self) -> ConnectionStats {
+        self.state.github.stats()
+    }
+
+    /// GitHub API usage of each project since startup, e.g. to find the
+    /// repos spending the rate limit.
+    pub fn api_usage(&self) -> BTreeMap<ProjectId, ApiUsage> {
+        self.lock_api_usage().clone()
+    }
+
+    fn lock_api_usage(&self) -> MutexGuard<'_, BTreeMap<ProjectId, ApiUsage>> {
+        // Totals are only ever added to in one go
+        self.state
+            .api_usage
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    /// Delivers whatever is due in the outbox.
+    pub async fn deliver_outbox(&self) -> anyhow::Result<()> {
+        events::deliver_due(
+            self.state.store.as_ref(),
+            &self.state.sinks,
+            &self.state.redactor,
+            self.state.clock.as_ref(),
+        )
+        .await
+    }
+
+    /// The redactor scrubbing reports and notifications, for anything else
+    /// shown outside, e.g. errors printed by the binary.
+    pub fn redactor(&self) -> &Redactor {
+        &self.state.redactor
+    }
+
+    /// Replaces the clock, e.g. with a [`ManualClock`] to step through
+    /// deploy windows and bake times.
+    pub fn with_clock(mut self, clock: ClockRef) -> Self {
+        self.state.scheduler.set_clock(clock.clone());
+        self.state.clock = clock;
+        self
+    }
+
+    pub fn clock(&self) -> &ClockRef {
+        &self.state.clock
+    }
+
+    /// Drops the cached metadata of `owner/repo`, e.g. after a `repository`
+    /// webhook, or of all of the owner's repositories if `repo` is `None`.
+    pub fn invalidate_repository(&self, owner: &str, repo: Option<&str>) {
+        self.state.repos.invalidate(owner, repo);
+    }
+
+    /// Drops the cached metadata of the repository with node id `node_id`,
+    /// for events about a repository that was renamed or transferred.
+    pub fn invalidate_repository_node(&self, node_id: &str) {
+        self.state.repos.invalidate_node(node_id);
+    }
+
+    /// Drops all cached repository metadata.
+    pub fn refresh_repositories(&self) {
+        self.state.repos.clear();
+    }
+
+    /// Whether this instance may mutate refs, acquiring the lease if it is
+    /// free.
+    pub async fn is_leader(&self) -> anyhow::Result<bool> {
+        self.state
+            .lease
+            .is_leader()
+            .await
+            .context("Unable to check the leader lease")
+    }
+
+    /// Lists the owner's repositories and returns a project for each one
+    /// selected by the entry's globs.
+    async fn expand_github_owner(
+        &self,
+        owner: &GithubOwnerProject,
+    ) -> anyhow::Result<Vec<GithubProject>> {
+        let octo = &self.state.read_octo;
+        let first_page = octo
+            .orgs(owner.owner.as_str())
+            .list_repos()
+            .per_page(100)
+            .send()
+            .await
+            .with_context(|| format!("Unable to list repositories of {}", owner.owner))?;
+        let repos = octo.all_pages(first_page).await?;
+
+        let mut projects = Vec::new();
+        for repo in repos {
+            if owner
+                .matches(&repo.name)
+                .context("Invalid repository glob")?
+            {
+                projects.extend(owner.project_for(repo.name).expand_regions());
+            }
+        }
+        info!(
+            owner = owner.owner,
+            count = projects.len(),
+            "Expanded owner"
+        );
+        Ok(projects)
+    }
+
+    async fn update_github(
+        &self,
+        project: &GithubProject,
+        permit: Permit,
+        priority: Priority,
+    ) -> ProjectReport {
+        let (mut report, api_usage) =
+            usage::metered(self.update_github_metered(project, permit, priority)).await;
+        report.api_usage = api_usage;
+        report
+    }
+
+    async fn update_github_metered(
+        &self,
+        project: &GithubProject,
+        mut permit: Permit,
+        priority: Priority,
+    ) -> ProjectReport {
+        let id = project.id();
+        // The id is kept, so history carries over to the new name
+        let moved = self.follow_move(project).await;
+        let project = moved.as_ref().unwrap_or(project);
+        let mut decisions = Vec::new();
+        let mut inputs = None;
+        let outcome = async {
+            self.update_github_inner(
+                &id,
+                project,
+                &mut decisions,
+                &mut inputs,
+                &mut permit,
+                priority,
+            )
+            .await
+            .unwrap_or_else(|err| {
+                error!(err = %self.state.redactor.debug(&err), "Unable to sync project");
+                ProjectOutcome::Failed {
+                    error: format!("{err:#}"),
+                }
+            })
+        }
+        .instrument(info_span!("update Github project", %id, ?project))
+        .await;
+        self.finish(id, project, outcome, decisions, inputs, Some(permit))
+            .await
+    }
+
+    /// Runs the actions and verification of a release, if `outcome` is one,
+    /// and reports on the project. `permit` is released before verifying.
+    async fn finish(
+        &self,
+        id: ProjectId,
+        project: &GithubProject,
+        outcome: ProjectOutcome,
+        decisions: Vec<PolicyDecision>,
+        inputs: Option<ReleaseInputs>,
+        permit: Option<Permit>,
+    ) -> ProjectReport {
+        let promotion = match &outcome {
+            ProjectOutcome::Created { sha } => Some(Promotion {
+                project,
+                from: None,
+                to: sha,
+            }),
+            ProjectOutcome::Updated { from, to } | ProjectOutcome::Recreated { from, to } => {
+                Some(Promotion {
+                    project,
+                    from: Some(from),
+                    to,
+                })
+            }
+            _ => None,
+        };
+        let mut actions = Vec::new();
+        actions.extend(
+            self.settle_budget(&id, &project.env, promotion.is_some())
+                .await,
+        );
+        // Mirrors and release tags follow wherever the env tag is, also
+        // when it didn't move, so they catch up after a failure
+        let released = match &outcome {
+            ProjectOutcome::Unchanged { sha } | ProjectOutcome::Created { sha } => Some(sha),
+            ProjectOutcome::Updated { to, .. } | ProjectOutcome::Recreated { to, .. } => Some(to),
+            _ => None,
+        };
+        let mut manifest = None;
+        if let Some(sha) = released {
+            actions.extend(
+                self.sync_mirrors(project, sha)
+                    .instrument(info_span!("mirrors", %id))
+                    .await,
+            );
+            actions.extend(
+                self.sync_extra_refs(&id, project, sha)
+                    .instrument(info_span!("extra refs", %id))
+                    .await,
+            );
+            actions.extend(
+                self.tag_release(project, sha)
+                    .instrument(info_span!("release tag", %id))
+                    .await,
+            );
+        }
+        if let Some(promotion) = &promotion {
+            actions.extend(
+                self.run_actions(promotion)
+                    .instrument(info_span!("post-sync actions", %id))
+                    .await,
+            );
+            let boarded = matches!(
+                inputs.as_ref().map(|inputs| &inputs.source),
+                Some(ReleaseSource::Branch { .. })
+            );
+            if let (Some(train), true) = (&project.train, boarded) {
+                if let Err(err) = self.announce_train(&id, train, promotion).await {
+                    warn!(
+                        %id,
+                        err = %self.state.redactor.debug(&err),
+                        "Unable to announce release train"
+                    );
+                }
+            }
+            if let Some(manifests) = &project.manifest {
+                match self
+                    .manifest(&id, promotion, &decisions, inputs.as_ref())
+                    .await
+                {
+                    Ok(built) => {
+                        actions.extend(
+                            self.publish_manifest(manifests, promotion, &built)
+                                .instrument(info_span!("manifest", %id))
+                                .await,
+                        );
+                        manifest = Some(built);
+                    }
+                    Err(err) => {
+                        warn!(
+                            %id,
+                            err = %self.state.redactor.debug(&err),
+                            "Unable to build release manifest"
+                        )
+                    }
+                }
+            }
+        }
+
+        // Verification only waits, so other projects get the slot meanwhile
+        drop(permit);
+        let verified = match (&project.verification, &promotion) {
+            // Nothing was released to watch
+            _ if self.state.shadow => None,
+            (Some(verification), Some(promotion)) => {
+                self.verify_release(&id, verification, promotion, &mut actions)
+                    .instrument(info_span!("verification", %id))
+                    .await
+            }
+            _ => None,
+        };
+        let outcome = verified.unwrap_or(outcome);
+
+        ProjectReport {
+            id,
+            env: project.env.clone(),
+            outcome,
+            decisions,
+            actions,
+            inputs,
+            manifest,
+            // Filled in by the caller, which meters the whole sync of the project
+            api_usage: ApiUsage::default(),
+        }
+    }
+
+    /// The project under its repository's new name if it was renamed or
+    /// transferred, which the rest of the sync and the actions then use.
+    async fn follow_move(&self, project: &GithubProject) -> Option<GithubProject> {
+        // Failures are left for the sync itself to report
+        let repo = self
+            .state
+            .repos
+            .get(&self.state.read_octo, &project.owner, &project.repo)
+            .await
+            .ok()?;
+        // GitHub names are case-insensitive
+        if repo.owner.eq_ignore_ascii_case(&project.owner)
+            && repo.name.eq_ignore_ascii_case(&project.repo)
+        {
+            return None;
+        }
+        warn!(
+            from = format!("{}/{}", project.owner, project.repo),
+            to = format!("{}/{}", repo.owner, repo.name),
+            "Repository was renamed or transferred, update the registry"
+        );
+        self.registry
+            .repository_moved(&project.owner, &project.repo, &repo.owner, &repo.name);
+        Some(GithubProject {
+            owner: repo.owner,
+            repo: repo.name,
+            ..project.clone()
+        })
+    }
+
+    /// The commit the source environment has run for long enough to be
+    /// promoted, or why there is none yet.
+    async fn promotable(
+        &self,
+        id: &ProjectId,
+        project: &GithubProject,
+        promotion: &AutoPromotion,
+    ) -> anyhow::Result<Result<String, String>> {
+        let store = &self.state.store;
+        let env = &promotion.env;
+        let source = promotion
+            .project
+            .clone()
+            .unwrap_or_else(|| project.id_for_env(env));
+        if &source == id {
+            bail!("Project promotes from itself");
+        }
+        let Some(deployed) = store.last_deployment(&source, env).await? else {
+            return Ok(Err(format!("nothing was released to {env} yet")));
+        };
+        // Someone moved the source tag by hand, so it hasn't converged
+        let current = self
+            .observe_ref(
+                &source,
+                &project.owner,
+                &project.repo,
+                &format!("tags/{env}"),
+            )
+            .await?;
+        if current.as_deref() != Some(deployed.sha.as_str()) {
+            return Ok(Err(format!("{env} is not at its last release")));
+        }
+
+        let baked = self.state.clock.now() - deployed.deployed_at;
+        let after = chrono::Duration::seconds(promotion.after_secs as i64);
+        if baked < after {
+            return Ok(Err(format!(
+                "{env} has run {} for {}m of {}m",
+                deployed.sha,
+                baked.num_minutes(),
+                after.num_minutes()
+            )));
+        }
+
+        // A rolled back release resets the clock by recording the previous
+        // one; this catches the source failing in any other way
+        let runs = store.recent_runs(RECENT_RUNS).await?;
+        let last = runs
+            .iter()
+            .flat_map(|(_, report)| &report.projects)
+            .find(|report| report.id == source);
+        if let Some(report) = last {
+            if matches!(
+                report.outcome,
+                ProjectOutcome::Failed { .. } | ProjectOutcome::RolledBack { .. }
+            ) {
+                return Ok(Err(format!("{env} reported a failure in its last sync")));
+            }
+        }
+        Ok(Ok(deployed.sha))
+    }
+
+    /// Moves the idle color of a blue/green project to `target_sha`. `None`
+    /// once it is there and confirmed, so the env tag can flip to it.
+    async fn stage_color(
+        &self,
+        id: &ProjectId,
+        project: &GithubProject,
+        blue_green: &BlueGreen,
+        tag_sha: Option<&str>,
+        target_sha: &str,
+    ) -> anyhow::Result<Option<ProjectOutcome>> {
+        let (owner, repo, env) = (&project.owner, &project.repo, &project.env);
+        let blue = self
+            .observe_ref(id, owner, repo, &format!("tags/{env}-blue"))
+            .await?;
+        let green = self
+            .observe_ref(id, owner, repo, &format!("tags/{env}-green"))
+            .await?;
+        // The color the env tag is on serves traffic, the other one is idle
+        let (color, idle_sha) = match tag_sha.is_some() && tag_sha == blue.as_deref() {
+            true => ("green", green),
+            false => ("blue", blue),
+        };
+
+        if idle_sha.as_deref() != Some(target_sha) {
+            let idle_env = format!("{env}-{color}");
+            let outcome = self
+                .move_ref(
+                    id,
+                    project,
+                    &format!("tags/{idle_env}"),
+                    idle_sha,
+                    target_sha,
+                )
+                .await?;
+            if let ProjectOutcome::Conflict { .. } = outcome {
+                return Ok(Some(outcome));
+            }
+            info!(color, "Staged release on the idle color");
+            return Ok(Some(ProjectOutcome::Staged {
+                color: color.to_string(),
+                sha: target_sha.to_string(),
+            }));
+        }
+
+        if let Some(confirm) = &blue_green.confirm {
+            let confirm = Verification {
+                url: confirm.url.replace("{color}", color),
+                ..confirm.clone()
+            };
+            let promotion = Promotion {
+                project,
+                from: tag_sha,
+                to: target_sha,
+            };
+            if let Err(err) = self
+                .state
+                .integrations
+                .verify(
+                    &confirm,
+                    &promotion,
+                    &self.state.redactor,
+                    self.state.clock.as_ref(),
+                )
+                .await
+            {
+                info!(color, err = %self.state.redactor.debug(&err), "Idle color not confirmed");
+                return Ok(Some(ProjectOutcome::Blocked {
+                    reason: BlockReason::Unconfirmed,
+                    detail: format!("{color} failed its confirmation: {err:#}"),
+                }));
+            }
+        }
+        Ok(None)
+    }
+
+    /// Watches the release's health, moving the env tag back if it fails.
+    /// `None` if it passed, otherwise what became of the release.
+    async fn verify_release(
+        &self,
+        id: &ProjectId,
+        verification: &Verification,
+        promotion: &Promotion<'_>,
+        actions: &mut Vec<ActionReport>,
+    ) -> Option<ProjectOutcome> {
+        let reason = match self
+            .state
+            .integrations
+            .verify(
+                verification,
+                promotion,
+                &self.state.redactor,
+                self.state.clock.as_ref(),
+            )
+            .await
+        {
+            Ok(()) => return None,
+            Err(err) => format!("{err:#}"),
+        };
+        error!(reason, "Release failed verification");
+
+        let Some(from) = promotion.from else {
+            self.alert_verification_failed(id, promotion, &reason, None)
+                .await;
+            return Some(ProjectOutcome::Failed {
+                error: format!(
+                    "release failed verification with nothing to roll back to: {reason}"
+                ),
+            });
+        };
+        let _permit = self.state.scheduler.acquire(Priority::Triggered).await;
+        if let Err(err) = self.roll_back(id, promotion, from).await {
+            error!(err = %self.state.redactor.debug(&err), "Unable to roll back release");
+            self.alert_verification_failed(id, promotion, &reason, None)
+                .await;
+            return Some(ProjectOutcome::Failed {
+                error: format!(
+                    "release failed verification ({reason}) and was not rolled back: {err:#}"
+                ),
+            });
+        }
+        self.alert_verification_failed(id, promotion, &reason, Some(from))
+            .await;
+        if let Some(incident) = &verification.incident {
+            actions.push(self.open_incident(incident, promotion, from, &reason).await);
+        }
+
+        // Pointed back at the previous release the same way
+        let rollback = Promotion {
+            project: promotion.project,
+            from: Some(promotion.to),
+            to: from,
+        };
+        actions.extend(
+            self.run_actions(&rollback)
+                .instrument(info_span!("rollback actions", %id))
+                .await,
+        );
+        Some(ProjectOutcome::RolledBack {
+            from: from.to_string(),
+            to: promotion.to.to_string(),
+            reason,
+        })
+    }
+
+    /// Moves the env tag from the release back to `from`, unless it was
+    /// moved elsewhere in the meantime.
+    async fn roll_back(
+        &self,
+        id: &ProjectId,
+        promotion: &Promotion<'_>,
+        from: &str,
+    ) -> anyhow::Result<()> {
+        let project = promotion.project;
+        let (owner, repo, env) = (&project.owner, &project.repo, &project.env);
+        let current = self
+            .observe_ref(id, owner, repo, &format!("tags/{env}"))
+            .await
+            .context("Unable to re-read env tag")?;
+        if current.as_deref() != Some(promotion.to) {
+            bail!("Env tag moved during verification, to {current:?}");
+        }
+        if self.state.shadow {
+            bail!("Shadow mode moves no env tag, so there is nothing to roll back");
+        }
+
+        let store = &self.state.store;
+        let held = events::hold(
+            store.as_ref(),
+            &self.state.sinks,
+            Event::RefMoved {
+                project: id.clone(),
+                owner: owner.clone(),
+                repo: repo.clone(),
+                env: env.clone(),
+                from: Some(promotion.to.to_string()),
+                to: from.to_string(),
+                at: self.state.clock.now(),
+                simulated: false,
+                // Moving back to what ran before isn't scored
+                risk: None,
+            },
+        )
+        .await
+        .context("Unable to queue ref moved event")?;
+        let moved = self
+            .state
+            .writer(env)
+            .update_ref(owner, repo, &format!("tags/{env}"), from)
+            .await
+            .context("Unable to move env tag back")
+            .and_then(|updated| updated.context("Env tag vanished during verification"));
+        if let Err(err) = moved {
+            if let Err(err) = events::discard(store.as_ref(), &held).await {
+                warn!(err = %self.state.redactor.debug(&err), "Unable to discard ref moved event");
             }
+            return Err(err);
         }
+        warn!(from = promotion.to, to = from, "Rolled back release");
+        events::release(store.as_ref(), &held, self.state.clock.now())
+            .await
+            .context("Unable to release ref moved event")?;
+        store
+            .record_deployment(
+                id,
+                env,
+                &Deployment {
+                    sha: from.to_string(),
+                    deployed_at: self.state.clock.now(),
+                },
+            )
+            .await
+            .context("Unable to record deployment")?;
         Ok(())
     }
 
-    async fn update_github(&self, project: &GithubProject) -> anyhow::Result<()> {
-        let _span = info_span!("update Github project", ?project).entered();
+    async fn alert_verification_failed(
+        &self,
+        id: &ProjectId,
+        promotion: &Promotion<'_>,
+        reason: &str,
+        rolled_back_to: Option<&str>,
+    ) {
+        let project = promotion.project;
+        let event = Event::VerificationFailed {
+            project: id.clone(),
+            owner: project.owner.clone(),
+            repo: project.repo.clone(),
+            env: project.env.clone(),
+            sha: promotion.to.to_string(),
+            reason: reason.to_string(),
+            rolled_back_to: rolled_back_to.map(str::to_string),
+            at: self.state.clock.now(),
+        };
+        if let Err(err) = events::enqueue(
+            self.state.store.as_ref(),
+            &self.state.sinks,
+            self.state.clock.now(),
+            event,
+        )
+        .await
+        {
+            error!(
+                err = %self.state.redactor.debug(&err),
+                "Unable to queue verification failed event"
+            );
+        }
+    }
+
+    /// Opens `incident` for the rollback of `promotion`, reported like an
+    /// action.
+    async fn open_incident(
+        &self,
+        incident: &RollbackIncident,
+        promotion: &Promotion<'_>,
+        from: &str,
+        reason: &str,
+    ) -> ActionReport {
+        let error = match self
+            .state
+            .integrations
+            .open_incident(incident, promotion, from, reason)
+            .await
+        {
+            Ok(opened) => {
+                info!(opened, "Opened rollback incident");
+                None
+            }
+            Err(err) => {
+                error!(err = %self.state.redactor.debug(&err), "Unable to open rollback incident");
+                Some(format!("{err:#}"))
+            }
+        };
+        ActionReport {
+            action: "rollback-incident".to_string(),
+            error,
+            simulated: false,
+        }
+    }
+
+    /// Runs every configured action, in order, regardless of earlier
+    /// failures; the ref has already moved either way.
+    async fn run_actions(&self, promotion: &Promotion<'_>) -> Vec<ActionReport> {
+        let mut reports = Vec::new();
+        for action in &promotion.project.actions {
+            if self.state.shadow {
+                info!(action = action.name(), "Shadow mode, not running action");
+                reports.push(ActionReport {
+                    action: action.name().to_string(),
+                    error: None,
+                    simulated: true,
+                });
+                continue;
+            }
+            let result = self
+                .state
+                .integrations
+                .run(action, promotion, self.state.clock.now())
+                .await;
+            if let Err(err) = &result {
+                error!(
+                    action = action.name(),
+                    err = %self.state.redactor.debug(&err),
+                    "Post-sync action failed"
+                );
+            }
+            reports.push(ActionReport {
+                action: action.name().to_string(),
+                error: result.err().map(|err| format!("{err:#}")),
+                simulated: false,
+            });
+        }
+        reports
+    }
+
+    async fn update_github_inner(
+        &self,
+        id: &ProjectId,
+        project: &GithubProject,
+        decisions: &mut Vec<PolicyDecision>,
+        inputs: &mut Option<ReleaseInputs>,
+        permit: &mut Permit,
+        priority: Priority,
+    ) -> anyhow::Result<ProjectOutcome> {
+        let planned = self.plan_github(id, project, decisions, inputs, permit, priority);
+        match planned.await? {
+            Ok(release) => self.release_github(id, project, release).await,
+            Err(outcome) => Ok(outcome),
+        }
+    }
+
+    /// Everything up to moving the env tag: where it should point and
+    /// whether it may. The outcome if the project doesn't move. What the
+    /// plan was based on ends up in `inputs`, for replays. `permit` is
+    /// released while waiting on checks.
+    async fn plan_github(
+        &self,
+        id: &ProjectId,
+        project: &GithubProject,
+        decisions: &mut Vec<PolicyDecision>,
+        inputs: &mut Option<ReleaseInputs>,
+        permit: &mut Permit,
+        priority: Priority,
+    ) -> anyhow::Result<Result<Release, ProjectOutcome>> {
+        let store = &self.state.store;
+        if let Some(deletion) = store.deletion(id).await? {
+            let mut detail = format!(
+                "deleted {}",
+                deletion.deleted_at.format("%Y-%m-%d %H:%M UTC")
+            );
+            if let Some(reason) = &deletion.reason {
+                detail.push_str(&format!(": {reason}"));
+            }
+            if deletion.purged_at.is_some() {
+                detail.push_str("; history purged");
+            }
+            debug!(%id, "Project is deleted");
+            return Ok(Err(ProjectOutcome::Skipped {
+                reason: SkipReason::Deleted,
+                detail,
+            }));
+        }
+        for scope in [FreezeScope::Global, FreezeScope::Project(id.clone())] {
+            if let Some(freeze) = store.freeze(&scope).await? {
+                let reason = freeze
+                    .reason
+                    .unwrap_or_else(|| "no reason given".to_string());
+                info!(?scope, reason, "Project is frozen");
+                return Ok(Err(ProjectOutcome::Skipped {
+                    reason: SkipReason::Frozen,
+                    detail: reason,
+                }));
+            }
+        }
+
+        if let Some(ttl_secs) = project.ttl_secs {
+            if let Some(detail) = self.expire(id, project, ttl_secs).await? {
+                return Ok(Err(ProjectOutcome::Skipped {
+                    reason: SkipReason::Expired,
+                    detail,
+                }));
+            }
+        }
+
         let owner = project.owner.as_str();
         let repo_path = project.repo.as_str();
         let env = project.env.as_str();
-        let repo_handler = self.state.octo.repos(owner, repo_path);
-        let repo = repo_handler.get().await?;
-        let tracked_branch_sha = Self::sha_for_ref(match repo.default_branch {
-            Some(main_branch) => {
-                repo_handler
-                    .get_ref(&Reference::Branch(main_branch))
-                    .await?
+        let repo = self
+            .state
+            .repos
+            .get(&self.state.read_octo, owner, repo_path)
+            .await?;
+        if repo.archived {
+            info!("Repository is archived");
+            return Ok(Err(ProjectOutcome::Skipped {
+                reason: SkipReason::Archived,
+                detail: format!("{owner}/{repo_path} is archived"),
+            }));
+        }
+        let empty = || {
+            info!("Repository is empty");
+            Ok(Err(ProjectOutcome::Skipped {
+                reason: SkipReason::EmptyRepo,
+                detail: format!("{owner}/{repo_path} has no commits"),
+            }))
+        };
+        // Forks and mirrors release what their upstream has on its branch
+        let (branch_owner, branch_repo, main_branch) = match &project.upstream {
+            Some(upstream) => {
+                let branch = match &upstream.branch {
+                    Some(branch) => branch.clone(),
+                    None => self
+                        .state
+                        .repos
+                        .get(&self.state.read_octo, &upstream.owner, &upstream.repo)
+                        .await?
+                        .default_branch
+                        .with_context(|| {
+                            format!(
+                                "upstream {}/{} has no default branch",
+                                upstream.owner, upstream.repo
+                            )
+                        })?,
+                };
+                (upstream.owner.as_str(), upstream.repo.as_str(), branch)
             }
-            None => bail!("project does not have main branch defined"),
-        })?;
-
-        let tag_sha = match repo_handler.get_ref(&Reference::Tag(env.to_string())).await {
-            Ok(tag) => match tag.object {
-                Object::Tag { sha, url: _ } => Some(sha),
-                _ => bail!("unexpected ref type"),
+            // GitHub reports no default branch, or one that doesn't exist
+            // yet, until the first push
+            None => match repo.default_branch {
+                Some(branch) => (owner, repo_path, branch),
+                None => return empty(),
             },
-            Err(err) => match &err {
-                octocrab::Error::GitHub {
-                    source: GitHubError { message, .. },
-                    ..
-                } => match message == "Not Found" {
-                    true => None,
-                    false => bail!(err),
+        };
+        let tracked_branch_sha = match self
+            .observe_ref(
+                id,
+                branch_owner,
+                branch_repo,
+                &format!("heads/{main_branch}"),
+            )
+            .await?
+        {
+            Some(sha) => sha,
+            None if repo.empty && project.upstream.is_none() => return empty(),
+            None => {
+                bail!("main branch {main_branch} of {branch_owner}/{branch_repo} does not exist")
+            }
+        };
+        let (target_sha, source) = match store.pin(id, &project.env).await? {
+            Some(pin) => {
+                info!(sha = pin.sha, "Environment is pinned");
+                (pin.sha, ReleaseSource::Pin)
+            }
+            None => match &project.promote_from {
+                Some(promotion) => match self.promotable(id, project, promotion).await? {
+                    Ok(sha) => (
+                        sha,
+                        ReleaseSource::Promotion {
+                            env: promotion.env.clone(),
+                        },
+                    ),
+                    Err(detail) => {
+                        info!(detail, "Promotion source not ready");
+                        return Ok(Err(ProjectOutcome::Blocked {
+                            reason: BlockReason::Baking,
+                            detail,
+                        }));
+                    }
                 },
-                _ => bail!(err),
+                None => {
+                    let sha = match &project.train {
+                        Some(train) => match self
+                            .board_train(train, branch_owner, branch_repo, &main_branch)
+                            .await?
+                        {
+                            Ok(sha) => sha,
+                            Err(detail) => {
+                                info!(detail, "Waiting for a release train");
+                                return Ok(Err(ProjectOutcome::Blocked {
+                                    reason: BlockReason::Train,
+                                    detail,
+                                }));
+                            }
+                        },
+                        None => tracked_branch_sha,
+                    };
+                    let source = ReleaseSource::Branch {
+                        name: main_branch,
+                        upstream: project
+                            .upstream
+                            .as_ref()
+                            .map(|upstream| format!("{}/{}", upstream.owner, upstream.repo)),
+                    };
+                    (sha, source)
+                }
             },
         };
 
-        fn full_ref(env: &str) -> String {
-            format!("refs/tags/{env}")
+        let tag_sha = self
+            .observe_ref(id, owner, repo_path, &format!("tags/{env}"))
+            .await?;
+        self.settle_pending(id, env, tag_sha.as_deref()).await?;
+        let inputs = inputs.insert(ReleaseInputs {
+            from: tag_sha.clone(),
+            to: target_sha.clone(),
+            source,
+            policy: project.policy.clone(),
+            candidate: None,
+        });
+        // Known right after a restart too, since both sides are persisted
+        if let Some(deployed) = store.last_deployment(id, env).await? {
+            if tag_sha.as_deref() != Some(deployed.sha.as_str()) {
+                warn!(
+                    deployed = deployed.sha,
+                    current = ?tag_sha,
+                    "Env tag moved since the last deployment"
+                );
+            }
+        }
+
+        if tag_sha.as_deref() == Some(target_sha.as_str()) {
+            info!("Deployment already in appropriate spot");
+            return Ok(Err(ProjectOutcome::Unchanged { sha: target_sha }));
         }
 
-        let _result = match tag_sha {
-            Some(tag_sha) => match tag_sha == tracked_branch_sha {
-                true => {
-                    info!("Deployment already in appropriate spot");
-                    return Ok(());
+        // Pins and promotions are asked for, whatever they release
+        if let (Some(from), ReleaseSource::Branch { .. }) = (&tag_sha, &inputs.source) {
+            if !project.ignore.is_empty() {
+                if let Some(detail) = self
+                    .unwarranted(
+                        &project.ignore,
+                        branch_owner,
+                        branch_repo,
+                        from,
+                        &target_sha,
+                    )
+                    .await?
+                {
+                    info!(detail, "Nothing to release");
+                    return Ok(Err(ProjectOutcome::Skipped {
+                        reason: SkipReason::Ignored,
+                        detail,
+                    }));
                 }
-                // Update ref
-                false => self
-                    .state
-                    .octo
-                    .update_ref(
-                        owner.to_string(),
-                        repo_path.to_string(),
-                        full_ref(env),
-                        tracked_branch_sha,
+            }
+        }
+
+        let mut risk = None;
+        if !project.policy.is_empty() {
+            let from = tag_sha.as_deref();
+            let candidate = policy::gather(
+                &self.state,
+                id,
+                project,
+                from,
+                &target_sha,
+                permit,
+                priority,
+            )
+            .await
+            .context("Unable to gather release candidate")?;
+            *decisions = policy::evaluate(&project.policy, &candidate);
+            risk = project
+                .policy
+                .risk
+                .as_ref()
+                .and_then(|scoring| policy::risk_score(scoring, &candidate).ok())
+                .map(|(score, _)| score);
+            inputs.candidate = Some(candidate);
+            if let Some((reason, detail)) = policy::violations(decisions) {
+                info!(?reason, detail, "Release blocked by policy");
+                return Ok(Err(ProjectOutcome::Blocked { reason, detail }));
+            }
+        }
+
+        // Pins are an operator's call, whatever the budget
+        if !matches!(inputs.source, ReleaseSource::Pin) {
+            if let Err(detail) = self.claim_budget(id, project).await? {
+                info!(detail, "Promotion deferred");
+                return Ok(Err(ProjectOutcome::Blocked {
+                    reason: BlockReason::Budget,
+                    detail,
+                }));
+            }
+        }
+
+        if let Some(blue_green) = &project.blue_green {
+            if let Some(outcome) = self
+                .stage_color(id, project, blue_green, tag_sha.as_deref(), &target_sha)
+                .await?
+            {
+                return Ok(Err(outcome));
+            }
+        }
+        Ok(Ok(Release {
+            tag_sha,
+            target_sha,
+            risk,
+        }))
+    }
+
+    /// Moves the env tag as planned, announcing and recording the move.
+    async fn release_github(
+        &self,
+        id: &ProjectId,
+        project: &GithubProject,
+        release: Release,
+    ) -> anyhow::Result<ProjectOutcome> {
+        let store = &self.state.store;
+        let (owner, repo_path, env) = (&project.owner, &project.repo, &project.env);
+        let Release {
+            tag_sha,
+            target_sha,
+            risk,
+        } = release;
+        let promotion = Promotion {
+            project,
+            from: tag_sha.as_deref(),
+            to: &target_sha,
+        };
+        // Opened before the ref moves and closed whatever the result, so the
+        // status page covers exactly the release
+        let maintenance = match &project.statuspage {
+            Some(maintenance) if !self.state.shadow => self
+                .state
+                .integrations
+                .open_maintenance(maintenance, &promotion, self.state.clock.now())
+                .await
+                .map_err(|err| {
+                    warn!(
+                        err = %self.state.redactor.debug(&err),
+                        "Unable to open Statuspage maintenance"
                     )
-                    .await
-                    .context("Unable to update existing ref"),
+                })
+                .ok(),
+            _ => None,
+        };
+        // Held before the ref moves and released once it has, so the
+        // announcement survives a crash in between
+        let held = events::hold(
+            store.as_ref(),
+            &self.state.sinks,
+            Event::RefMoved {
+                project: id.clone(),
+                owner: owner.to_string(),
+                repo: repo_path.to_string(),
+                env: env.to_string(),
+                from: tag_sha.clone(),
+                to: target_sha.clone(),
+                at: self.state.clock.now(),
+                simulated: self.state.shadow,
+                risk,
             },
-            // Create ref
-            None => self
+        )
+        .await
+        .context("Unable to queue ref moved event")?;
+        let moved = self
+            .move_ref(
+                id,
+                project,
+                &format!("tags/{env}"),
+                tag_sha.clone(),
+                &target_sha,
+            )
+            .await;
+        let succeeded = matches!(
+            moved,
+            Ok(ProjectOutcome::Created { .. }
+                | ProjectOutcome::Updated { .. }
+                | ProjectOutcome::Recreated { .. })
+        );
+        if succeeded {
+            events::release(store.as_ref(), &held, self.state.clock.now())
+                .await
+                .context("Unable to release ref moved event")?;
+        } else if let Err(err) = events::discard(store.as_ref(), &held).await {
+            // Settled by the next sync, which finds the ref where it was
+            warn!(err = %self.state.redactor.debug(&err), "Unable to discard ref moved event");
+        }
+        if let (Some(maintenance), Some(incident)) = (&project.statuspage, maintenance) {
+            if let Err(err) = self
                 .state
-                .octo
-                .post::<_, Ref>(
-                    format!("/repos/{}/{}/git/refs", owner, repo_path),
-                    Some(&json!({
-                        "ref": full_ref(env),
-                        "sha": tracked_branch_sha,
-                        "force": true
-                    })),
+                .integrations
+                .close_maintenance(maintenance, &incident, &promotion, succeeded)
+                .await
+            {
+                warn!(
+                    err = %self.state.redactor.debug(&err),
+                    "Unable to close Statuspage maintenance"
+                );
+            }
+        }
+        let outcome = moved?;
+        if let ProjectOutcome::Conflict { expected, actual } = &outcome {
+            warn!(
+                ?expected,
+                ?actual,
+                "Env tag moved concurrently, not touching it"
+            );
+            return Ok(outcome);
+        }
+
+        // Nothing was deployed, and the next sync should plan the same
+        if !self.state.shadow {
+            store
+                .record_deployment(
+                    id,
+                    env,
+                    &Deployment {
+                        sha: target_sha,
+                        deployed_at: self.state.clock.now(),
+                    },
                 )
                 .await
-                .context("Unable to create new ref"),
-        }?;
+                .context("Unable to record deployment")?;
+        }
+        Ok(outcome)
+    }
 
+    /// Settles the env tag moves of `id` a crash left pending, now that the
+    /// tag is known to be at `tag_sha`: the latest one that got where it
+    /// says is announced and recorded, the others are dropped, as is one
+    /// a later deployment of the same SHA already superseded. Only moves
+    /// older than [`PENDING_GRACE`], so one still in flight, e.g. on an
+    /// instance losing its lease, isn't settled under it.
+    async fn settle_pending(
+        &self,
+        id: &ProjectId,
+        env: &str,
+        tag_sha: Option<&str>,
+    ) -> anyhow::Result<()> {
+        let store = &self.state.store;
+        let now = self.state.clock.now();
+        let mut pending: Vec<_> = store
+            .pending_outbox()
+            .await
+            .context("Unable to read pending events")?
+            .into_iter()
+            .filter_map(|(outbox_id, entry)| match entry.event {
+                Event::RefMoved {
+                    project,
+                    env: moved_env,
+                    to,
+                    at,
+                    simulated,
+                    ..
+                } if &project == id && moved_env == env && now - at >= PENDING_GRACE => {
+                    Some((outbox_id, to, at, simulated))
+                }
+                _ => None,
+            })
+            .collect();
+        if pending.is_empty() {
+            return Ok(());
+        }
+        // Newest first; a move is held once per sink, all released together
+        pending.sort_by_key(|(_, _, at, _)| std::cmp::Reverse(*at));
+        let deployed = store.last_deployment(id, env).await?;
+        let mut settled = None;
+        for (outbox_id, to, at, simulated) in pending {
+            if settled.is_some_and(|settled| settled != at) {
+                warn!(
+                    to,
+                    "Dropping announcement of a move superseded by a later one"
+                );
+                store.complete_outbox(outbox_id).await?;
+                continue;
+            }
+            if tag_sha != Some(to.as_str()) {
+                warn!(to, "Dropping announcement of a move that didn't happen");
+                store.complete_outbox(outbox_id).await?;
+                continue;
+            }
+            if deployed
+                .as_ref()
+                .is_some_and(|deployed| deployed.sha == to && deployed.deployed_at >= at)
+            {
+                warn!(to, "Dropping announcement of a move deployed again since");
+                store.complete_outbox(outbox_id).await?;
+                continue;
+            }
+            // Released ahead of the deployment record, which would drop it
+            store.release_outbox(&[outbox_id], now).await?;
+            if settled.is_none() {
+                warn!(to, "Announcing a move interrupted before it was recorded");
+                if !simulated {
+                    let deployment = Deployment {
+                        sha: to.clone(),
+                        deployed_at: at,
+                    };
+                    store.record_deployment(id, env, &deployment).await?;
+                }
+            }
+            settled = Some(at);
+        }
         Ok(())
     }
 
-    fn sha_for_ref(git_ref: Ref) -> anyhow::Result<String> {
-        match git_ref.object {
-            Object::Commit { sha, url: _ } => Ok(sha),
-            Object::Tag { sha, url: _ } => Ok(sha),
-            _ => bail!("Unexpected ref object type"),
+    /// Fails if `git_ref` is protected. Checked right before mutations
+    /// rather than when loading projects, so no path to one gets around
+    /// it. Tags are matched by name, other refs in full.
+    fn ensure_unprotected(&self, git_ref: &str) -> anyhow::Result<()> {
+        let protected_name = git_ref.strip_prefix("tags/").unwrap_or(git_ref);
+        if let Some(pattern) = self
+            .state
+            .protected_refs
+            .iter()
+            .find(|pattern| pattern.matches(protected_name))
+        {
+            bail!("Refusing to move {git_ref}, protected by {pattern}");
         }
+        Ok(())
     }
+
+    /// Points `git_ref` (e.g. `tags/prod`) of `project`, its env tag, one
+    /// of its colors or an extra ref, at `target_sha`, creating it if
+    /// `tag_sha` is unknown. The ref is read again right before and must
+    /// still be at `tag_sha`, so a concurrent move is reported as a
+    /// conflict instead of being overwritten.
+    async fn move_ref(
+        &self,
+        id: &ProjectId,
+        project: &GithubProject,
+        git_ref: &str,
+        tag_sha: Option<String>,
+        target_sha: &str,
+    ) -> anyhow::Result<ProjectOutcome> {
+        let (owner, repo) = (project.owner.as_str(), project.repo.as_str());
+        self.ensure_unprotected(git_ref)?;
+
+        // Revalidated against the observation made moments ago, so this is
+        // almost always a free 304
+        let current = self
+            .observe_ref(id, owner, repo, git_ref)
+            .await
+            .context("Unable to re-read env tag")?;
+        if current != tag_sha {
+            return Ok(ProjectOutcome::Conflict {
+                expected: tag_sha,
+                actual: current,
+            });
+        }
+
+        if self.state.shadow {
+            info!(git_ref, target_sha, "Shadow mode, not moving ref");
+            return Ok(match tag_sha {
+                Some(from) => ProjectOutcome::Updated {
+                    from,
+                    to: target_sha.to_string(),
+                },
+                None => ProjectOutcome::Created {
+                    sha: target_sha.to_string(),
+                },
+            });
+        }
+
+        let Some(tag_sha) = tag_sha else {
+            return self
+                .create_tag(id, project, git_ref, None, target_sha)
+                .await;
+        };
+        let updated = match self
+            .state
+            .writer(&project.env)
+            .update_ref(owner, repo, git_ref, target_sha)
+            .await
+        {
+            Err(RefWriteError::Ruleset { rules }) => {
+                return Ok(self.blocked_by_ruleset(project, git_ref, rules).await)
+            }
+            result => result.context("Unable to update existing ref")?,
+        };
+        if updated.is_some() {
+            return Ok(ProjectOutcome::Updated {
+                from: tag_sha,
+                to: target_sha.to_string(),
+            });
+        }
+
+        // Refused, most likely because the tag was deleted since the re-read
+        let current = self
+            .observe_ref(id, owner, repo, git_ref)
+            .await
+            .context("Unable to re-read env tag")?;
+        match current {
+            None => {
+                warn!(
+                    git_ref,
+                    "Ref vanished before it could be moved, recreating it"
+                );
+                self.create_tag(id, project, git_ref, Some(tag_sha), target_sha)
+                    .await
+            }
+            Some(current) if current != tag_sha => Ok(ProjectOutcome::Conflict {
+                expected: Some(tag_sha),
+                actual: Some(current),
+            }),
+            Some(_) => bail!("GitHub refused to move {git_ref} to {target_sha}"),
+        }
+    }
+
+    /// Creates `git_ref` at `target_sha`. `from` is where it pointed
+    /// before it vanished, if it did.
+    async fn create_tag(
+        &self,
+        id: &ProjectId,
+        project: &GithubProject,
+        git_ref: &str,
+        from: Option<String>,
+        target_sha: &str,
+    ) -> anyhow::Result<ProjectOutcome> {
+        let (owner, repo) = (project.owner.as_str(), project.repo.as_str());
+        let created = match self
+            .state
+            .writer(&project.env)
+            .create_ref(owner, repo, &format!("refs/{git_ref}"), target_sha)
+            .await
+        {
+            Err(RefWriteError::Ruleset { rules }) => {
+                return Ok(self.blocked_by_ruleset(project, git_ref, rules).await)
+            }
+            result => result.context("Unable to create new ref")?,
+        };
+        let to = target_sha.to_string();
+        Ok(match (created, from) {
+            (Some(_), None) => ProjectOutcome::Created { sha: to },
+            (Some(_), Some(from)) => ProjectOutcome::Recreated { from, to },
+            // GitHub refuses if the tag appeared in the meantime
+            (None, _) => ProjectOutcome::Conflict {
+                expected: None,
+                actual: self
+                    .observe_ref(id, owner, repo, git_ref)
+                    .await
+                    .context("Unable to re-read env tag")?,
+            },
+        })
+    }
+
+    /// Why a ruleset kept `git_ref` from moving: the rules GitHub named,
+    /// and the rulesets covering tags, so it's clear which one to look at
+    /// or ask a bypass of.
+    async fn blocked_by_ruleset(
+        &self,
+        project: &GithubProject,
+        git_ref: &str,
+        rules: Vec<String>,
+    ) -> ProjectOutcome {
+        let (owner, repo) = (project.owner.as_str(), project.repo.as_str());
+        warn!(git_ref, ?rules, "Repository rules blocked the ref");
+        let mut detail = format!("repository rules block {git_ref}");
+        if !rules.is_empty() {
+            detail.push_str(&format!(": {}", rules.join("; ")));
+        }
+        // Only a hint, as listing rulesets may need more than the token has
+        match self.state.read_octo.tag_rulesets(owner, repo).await {
+            Ok(rulesets) if !rulesets.is_empty() => {
+                detail.push_str(&format!(" (tag rulesets: {})", rulesets.join(", ")));
+            }
+            Ok(_) => {}
+            Err(err) => debug!(err = %self.state.redactor.debug(&err), "Unable to list rulesets"),
+        }
+        ProjectOutcome::Blocked {
+            reason: BlockReason::Ruleset,
+            detail,
+        }
+    }
+
+    /// The commit `git_ref` (e.g. `heads/main`) points at, `None` if it
+    /// doesn't exist. Annotated tags are peeled. The last observation is
+    /// kept in the state store and revalidated, so an unchanged ref costs
+    /// no rate limit, even after a restart.
+    async fn observe_ref(
+        &self,
+        id: &ProjectId,
+        owner: &str,
+        repo: &str,
+        git_ref: &str,
+    ) -> anyhow::Result<Option<String>> {
+        let store = &self.state.store;
+        let last = store.ref_state(id, git_ref).await?;
+        let etag = last.as_ref().and_then(|last| last.etag.as_deref());
+        let (sha, etag) = match self
+            .state
+            .read_octo
+            .lookup_ref(owner, repo, git_ref, etag)
+            .await
+        {
+            Ok(RefLookup::Unchanged) => {
+                let last = last.context("Ref revalidated without a previous observation")?;
+                return Ok(Some(last.sha));
+            }
+            Ok(RefLookup::Found {
+                git_ref: found,
+                etag,
+            }) => (self.peel(owner, repo, found.object).await?, etag),
+            Ok(RefLookup::Missing) => return Ok(None),
+            Err(err) => bail!(err),
+        };
+
+        store
+            .record_ref_state(
+                id,
+                git_ref,
+                &RefState {
+                    sha: sha.clone(),
+                    etag,
+                    observed_at: self.state.clock.now(),
+                },
+            )
+            .await
+            .context("Unable to record ref state")?;
+        Ok(Some(sha))
+    }
+
+    /// The commit `object` ultimately points at. An annotated tag is a
+    /// separate object whose SHA never equals the commit's, so comparing it
+    /// with a branch head would move the ref on every sync.
+    async fn peel(&self, owner: &str, repo: &str, object: Object) -> anyhow::Result<String> {
+        let mut object = object;
+        // Tags of tags are legal, but anything deeper than this is a loop
+        for _ in 0..MAX_TAG_DEPTH {
+            object = match object {
+                Object::Commit { sha, url: _ } => return Ok(sha),
+                Object::Tag { sha, url: _ } => {
+                    self.state
+                        .read_octo
+                        .tag_object(owner, repo, &sha)
+                        .await
+                        .with_context(|| format!("Unable to read tag object {sha}"))?
+                        .object
+                }
+                _ => bail!("Unexpected ref object type"),
+            };
+        }
+        bail!("Tag nesting deeper than {MAX_TAG_DEPTH}")
+    }
+}
+
+/// Where a planned release moves the env tag.
+#[derive(Debug, Clone)]
+struct Release {
+    tag_sha: Option<String>,
+    target_sha: String,
+    /// Risk score of the release, if the policy scores them
+    risk: Option<u32>,
 }
 
 #[derive(Deserialize)]
 #[serde(rename_all = "kebab-case")]
 pub struct HorSystemConfiguration {
-    github_personal_token: String,
+    /// Token used for ref mutations, and for reads when no read token is
+    /// set. Not needed when running as a GitHub App
+    github_personal_token: Option<String>,
+    /// Authenticate as a GitHub App instead of with a personal token
+    github_app: Option<GithubAppConfig>,
+    /// Optional lower-privilege token used for all read-only calls
+    github_read_token: Option<String>,
+    /// API root of GitHub Enterprise Server or a stand-in, api.github.com
+    /// if unset
+    github_api_url: Option<String>,
+    /// Connection pooling of the GitHub clients
+    #[serde(default)]
+    github_client: GithubClientConfig,
+    /// Seconds between scheduled syncs once the system is running
+    sync_interval_secs: Option<u64>,
+    /// Seconds between scheduled full passes. When set, the scheduled syncs
+    /// in between only cover projects marked dirty
+    full_sync_interval_secs: Option<u64>,
+    /// Poll each project as often as it has been active lately, between
+    /// full passes
+    adaptive_polling: Option<AdaptivePolling>,
+    /// Days soft-deleted projects keep their history before it's purged
+    deleted_project_retention_days: Option<u32>,
+    /// Seconds startup waits for the projects to be validated against
+    /// GitHub before syncing anyway
+    startup_validation_timeout_secs: Option<u64>,
+    /// Seconds repository metadata is reused before being fetched again
+    repository_cache_ttl_secs: Option<u64>,
+    /// Where deployments, run history and operator controls are kept
+    #[serde(default)]
+    state_store: StateStoreConfig,
+    /// How replicas agree on which one mutates refs
+    #[serde(default)]
+    leader_election: LeaderElectionConfig,
+    /// Sinks every ref mutation is announced to
+    #[serde(default)]
+    notifications: Vec<SinkConfig>,
+    /// Send the sinks a daily digest of the projects drifted from where
+    /// their releases should have taken them
+    drift_reports: Option<DriftReports>,
+    /// Systems post-sync actions may call
+    #[serde(default)]
+    integrations: IntegrationsConfig,
+    /// Concurrency and rate-limit budget of project syncs
+    #[serde(default)]
+    scheduler: SchedulerConfig,
+    /// Globs of tag names never to move, e.g. `v*` or `release-*`, guarding
+    /// against a project misconfigured with a real release tag as its env.
+    /// Other refs are matched in full, e.g. `heads/main`
+    #[serde(default)]
+    protected_refs: Vec<String>,
+    /// Plan and gate every release, but move no tags and run no actions;
+    /// runs and events are marked as simulated. For trying the system out
+    /// next to whatever releases today
+    #[serde(default)]
+    shadow: bool,
+    /// Regexes of further credentials to scrub from reports, notifications
+    /// and logged errors, next to the configured tokens and well-known
+    /// token formats. A first group, if any, is kept before the redaction and a
+    /// second one after it
+    #[serde(default)]
+    redact: Vec<String>,
+    /// Runs as if it were this instant at startup, the clock moving on
+    /// from there; for simulating a sync at another time, best combined
+    /// with `shadow`
+    simulated_time: Option<DateTime<Utc>>,
+    /// How often each env may be promoted to across all of its projects,
+    /// by env, e.g. at most ten prod promotions a day
+    #[serde(default)]
+    promotion_budgets: HashMap<String, PromotionBudget>,
 }
 
-impl Mediate<HorSystemConfiguration> for HorSystem<UninitializedState> {
-    type Out = Result<HorSystem<InitializedState>, HorSystemInitializationError>;
+impl<R: Registry + ?Sized> Mediate<HorSystemConfiguration> for HorSystem<UninitializedState, R> {
+    type Out = Result<HorSystem<InitializedState, R>, HorSystemInitializationError>;
 
     fn mediate(self, config: HorSystemConfiguration) -> Self::Out {
+        let http = reqwest::Client::new();
+        let github = GithubClient::new(&config.github_client);
+        let api_url = config.github_api_url.as_deref();
+        let (write_octo, env_octos) = match (&config.github_app, &config.github_personal_token) {
+            (Some(app), _) => {
+                let app = github.app(app, api_url)?;
+                (app.default, app.environments)
+            }
+            (None, Some(token)) => (github.octocrab(token, api_url)?, HashMap::new()),
+            (None, None) => return Err(HorSystemInitializationError::GithubAuth),
+        };
+        let read_octo = match &config.github_read_token {
+            Some(token) => github.octocrab(token, api_url)?,
+            None => write_octo.clone(),
+        };
+        let secrets = [&config.github_personal_token, &config.github_read_token]
+            .into_iter()
+            .flatten()
+            .cloned()
+            .chain(events::sink_secrets(&config.notifications))
+            .chain(config.integrations.secrets());
+        let redactor = Redactor::new(secrets, &config.redact)
+            .map_err(HorSystemInitializationError::RedactPattern)?;
+        let integrations = Integrations::new(config.integrations, http.clone(), write_octo.clone());
+        let clock: ClockRef = match config.simulated_time {
+            Some(start) => Arc::new(ShiftedClock::starting_at(start)),
+            None => Arc::new(SystemClock),
+        };
+        let scheduler = Scheduler::new(
+            &config.scheduler,
+            read_octo.clone(),
+            clock.clone(),
+            redactor.clone(),
+        );
+        let protected_refs = config
+            .protected_refs
+            .iter()
+            .map(|glob| Pattern::new(glob))
+            .collect::<Result<_, _>>()
+            .map_err(HorSystemInitializationError::ProtectedRefs)?;
+        if let Some(reports) = &config.drift_reports {
+            if reports.hour_utc >= 24 {
+                return Err(HorSystemInitializationError::DriftReportHour(
+                    reports.hour_utc,
+                ));
+            }
+            let unknown = reports
+                .sinks
+                .iter()
+                .find(|name| !config.notifications.iter().any(|sink| &&sink.name == name));
+            if let Some(name) = unknown {
+                return Err(HorSystemInitializationError::DriftReportSink(name.clone()));
+            }
+        }
+
         Ok(HorSystem {
             registry: self.registry,
             state: InitializedState {
-                octo: OctocrabBuilder::default()
-                    .personal_token(config.github_personal_token)
+                read_octo,
+                write_octo,
+                env_octos,
+                store: config
+                    .state_store
+                    .build()
+                    .map_err(HorSystemInitializationError::StateStore)?,
+                lease: config
+                    .leader_election
                     .build()
-                    .map_err(|err| HorSystemInitializationError::Octo(err))?,
+                    .map_err(HorSystemInitializationError::StateStore)?,
+                sinks: events::build_sinks(&config.notifications, &http),
+                integrations,
+                github,
+                scheduler,
+                repos: RepoCache::new(
+                    config
+                        .repository_cache_ttl_secs
+                        .map(Duration::from_secs)
+                        .unwrap_or(DEFAULT_REPOSITORY_CACHE_TTL),
+                ),
+                project_locks: ProjectLocks::default(),
+                protected_refs,
+                shadow: config.shadow,
+                redactor,
+                clock,
+                promotion_budgets: config.promotion_budgets,
+                budget_claims: BudgetClaims::default(),
+                api_usage: Mutex::default(),
+                sync_interval: config
+                    .sync_interval_secs
+                    .map(Duration::from_secs)
+                    .unwrap_or(DEFAULT_SYNC_INTERVAL),
+                full_sync_interval: config
+                    .full_sync_interval_secs
+                    .or(config
+                        .adaptive_polling
+                        .as_ref()
+                        .map(|polling| polling.max_interval_secs))
+                    .map(Duration::from_secs),
+                adaptive_polling: config.adaptive_polling,
+                drift_reports: config.drift_reports,
+                deletion_retention: chrono::Duration::days(
+                    config
+                        .deleted_project_retention_days
+                        .map_or(DEFAULT_DELETED_PROJECT_RETENTION_DAYS, i64::from),
+                ),
+                startup_validation_timeout: config
+                    .startup_validation_timeout_secs
+                    .map(Duration::from_secs)
+                    .unwrap_or(DEFAULT_STARTUP_VALIDATION_TIMEOUT),
             },
         })
     }
@@ -185,35 +2075,24 @@ pub enum HorSystemInitializationError {
     ConfigParse(#[source] ConfigParseErr),
     #[error("an error occurred while initializing Octocrab")]
     Octo(#[source] octocrab::Error),
-}
-
-#[async_trait]
-trait HorOctocrabExtension {
-    async fn update_ref(
-        &self,
-        owner: String,
-        repo: String,
-        reference: String,
-        sha: String,
-    ) -> octocrab::Result<Ref>;
-}
-
-#[async_trait]
-impl HorOctocrabExtension for Octocrab {
-    async fn update_ref(
-        &self,
-        owner: String,
-        repo: String,
-        reference: String,
-        sha: String,
-    ) -> octocrab::Result<Ref> {
-        self.patch::<Ref, _, _>(
-            format!("/repos/{}/{}/git/refs/{}", owner, repo, reference),
-            Some(&json!({
-                "sha": sha,
-                "force": true
-            })),
-        )
-        .await
-    }
+    #[error("invalid GitHub API URL")]
+    GithubApiUrl(#[source] http::uri::InvalidUri),
+    #[error("GitHub token is not a valid header value")]
+    GithubToken(#[source] http::header::InvalidHeaderValue),
+    #[error("neither a GitHub token nor a GitHub App is configured")]
+    GithubAuth,
+    #[error("unable to read the GitHub App's private key")]
+    GithubAppKeyFile(#[source] std::io::Error),
+    #[error("invalid GitHub App private key")]
+    GithubAppKey(#[source] jsonwebtoken::errors::Error),
+    #[error("unable to set up the state store")]
+    StateStore(#[source] StateStoreError),
+    #[error("invalid protected ref pattern")]
+    ProtectedRefs(#[source] glob::PatternError),
+    #[error("invalid redaction pattern")]
+    RedactPattern(#[source] regex::Error),
+    #[error("drift reports are due at hour {0}, past the end of the day")]
+    DriftReportHour(u32),
+    #[error("drift reports go to sink {0}, which isn't configured")]
+    DriftReportSink(String),
 }
@@ -0,0 +1,59 @@
+//! Keeps a project from being synced twice at once within the process,
+//! e.g. by the scheduler and a webhook, whose reads and writes of the env
+//! ref would otherwise interleave.
+
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+};
+
+use hor_registry::ProjectId;
+use tokio::sync::OwnedMutexGuard;
+
+#[derive(Default)]
+pub(crate) struct ProjectLocks {
+    locks: Mutex<HashMap<ProjectId, Arc<tokio::sync::Mutex<()>>>>,
+}
+
+/// Held while a project syncs; dropping it lets the next sync of the
+/// project in.
+pub(crate) struct ProjectGuard<'a> {
+    locks: &'a ProjectLocks,
+    id: ProjectId,
+    guard: Option<OwnedMutexGuard<()>>,
+}
+
+impl ProjectLocks {
+    /// Waits for any other sync of `id` to finish.
+    pub async fn lock(&self, id: &ProjectId) -> ProjectGuard<'_> {
+        let lock = self.entries().entry(id.clone()).or_default().clone();
+        ProjectGuard {
+            locks: self,
+            id: id.clone(),
+            guard: Some(lock.lock_owned().await),
+        }
+    }
+
+    fn entries(
+        &self,
+    ) -> std::sync::MutexGuard<'_, HashMap<ProjectId, Arc<tokio::sync::Mutex<()>>>> {
+        self.locks
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+impl Drop for ProjectGuard<'_> {
+    fn drop(&mut self) {
+        drop(self.guard.take());
+        let mut entries = self.locks.entries();
+        // Nobody else holds or waits for the lock, so it can go; otherwise
+        // the last one out removes it
+        if entries
+            .get(&self.id)
+            .is_some_and(|lock| Arc::strong_count(lock) == 1)
+        {
+            entries.remove(&self.id);
+        }
+    }
+}
@@ -0,0 +1,157 @@
+//! Manifests of promotions: what was released where, and what the release
+//! passed on its way, kept with the run and published for audits.
+
+use anyhow::Context;
+use hor_registry::{ProjectId, Registry, ReleaseManifests};
+use hor_state::{ActionReport, ManifestCommit, PolicyDecision, ReleaseInputs, ReleaseManifest};
+use tracing::info;
+
+use crate::{actions::Promotion, github::HorOctocrabExtension, HorSystem, InitializedState};
+
+impl<R: Registry + ?Sized> HorSystem<InitializedState, R> {
+    /// The commits `promotion` released, newest first; only the released
+    /// commit itself for the env's first release.
+    pub(crate) async fn released_commits(
+        &self,
+        promotion: &Promotion<'_>,
+    ) -> anyhow::Result<Vec<ManifestCommit>> {
+        let project = promotion.project;
+        let (owner, repo) = (project.owner.as_str(), project.repo.as_str());
+        let octo = &self.state.read_octo;
+        let commits = match promotion.from {
+            Some(from) => {
+                octo.compare(owner, repo, from, promotion.to)
+                    .await
+                    .context("Unable to compare with the previous release")?
+                    .commits
+            }
+            None => vec![octo
+                .commit(owner, repo, promotion.to)
+                .await
+                .context("Unable to read the released commit")?],
+        };
+        Ok(commits
+            .into_iter()
+            // Compare lists oldest first
+            .rev()
+            .map(|commit| ManifestCommit {
+                summary: commit
+                    .commit
+                    .message
+                    .lines()
+                    .next()
+                    .unwrap_or_default()
+                    .to_string(),
+                sha: commit.sha,
+                author: commit.author.map(|account| account.login),
+            })
+            .collect())
+    }
+
+    /// The manifest of `promotion`, from what its plan looked at.
+    pub(crate) async fn manifest(
+        &self,
+        id: &ProjectId,
+        promotion: &Promotion<'_>,
+        decisions: &[PolicyDecision],
+        inputs: Option<&ReleaseInputs>,
+    ) -> anyhow::Result<ReleaseManifest> {
+        let project = promotion.project;
+        let approvals = self
+            .state
+            .store
+            .approvals(id, &project.env, promotion.to)
+            .await?;
+        Ok(ReleaseManifest {
+            project: id.clone(),
+            owner: project.owner.clone(),
+            repo: project.repo.clone(),
+            env: project.env.clone(),
+            from: promotion.from.map(str::to_string),
+            to: promotion.to.to_string(),
+            source: inputs.map(|inputs| inputs.source.clone()),
+            commits: self.released_commits(promotion).await?,
+            decisions: decisions.to_vec(),
+            approvals,
+            planned_at: inputs
+                .and_then(|inputs| inputs.candidate.as_ref())
+                .map(|candidate| candidate.evaluated_at),
+            released_at: self.state.clock.now(),
+            simulated: self.state.shadow,
+        })
+    }
+
+    /// Publishes `manifest` wherever `manifests` asks for, besides the run
+    /// report that always keeps it.
+    pub(crate) async fn publish_manifest(
+        &self,
+        manifests: &ReleaseManifests,
+        promotion: &Promotion<'_>,
+        manifest: &ReleaseManifest,
+    ) -> Option<ActionReport> {
+        let tag = promotion.render(manifests.github_release.as_deref()?);
+        let action = format!("manifest {tag}");
+        if self.state.shadow {
+            info!(tag, "Shadow mode, not publishing manifest");
+            return Some(ActionReport {
+                action,
+                error: None,
+                simulated: true,
+            });
+        }
+        let error = self
+            .attach_manifest(promotion, &tag, manifest)
+            .await
+            .err()
+            .map(|err| format!("{err:#}"));
+        Some(ActionReport {
+            action,
+            error,
+            simulated: false,
+        })
+    }
+
+    async fn attach_manifest(
+        &self,
+        promotion: &Promotion<'_>,
+        tag: &str,
+        manifest: &ReleaseManifest,
+    ) -> anyhow::Result<()> {
+        let project = promotion.project;
+        let (owner, repo) = (project.owner.as_str(), project.repo.as_str());
+        let octo = self.state.writer(&project.env);
+        let release = match octo
+            .release_by_tag(owner, repo, tag)
+            .await
+            .context("Unable to look up the release")?
+        {
+            Some(release) => release,
+            None => octo
+                .repos(owner, repo)
+                .releases()
+                .create(tag)
+                .target_commitish(promotion.to)
+                .name(tag)
+                .prerelease(true)
+                .send()
+                .await
+                .context("Unable to create the release")?,
+        };
+        let name = format!(
+            "manifest-{}-{}.json",
+            project.env,
+            promotion.to.get(..7).unwrap_or(promotion.to)
+        );
+        let asset = serde_json::to_value(manifest)?;
+        let uploaded = octo
+            .upload_json_asset(&release, &name, &asset)
+            .await
+            .context("Unable to upload the manifest")?;
+        if uploaded {
+            info!(tag, name, "Published manifest");
+        } else {
+            info!(tag, name, "Manifest was already published");
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,124 @@
+//! Env tags kept in step across mirrors of a project's repository, e.g.
+//! read replicas that services deploy from.
+
+use anyhow::{bail, Context};
+use hor_registry::{GithubProject, ProjectId, Registry, TagMirror};
+use hor_state::{ActionReport, ProjectOutcome};
+use tracing::{info, info_span, warn, Instrument};
+
+use crate::{github::HorOctocrabExtension, HorSystem, InitializedState};
+
+impl<R: Registry + ?Sized> HorSystem<InitializedState, R> {
+    /// Points the env tag of every mirror of `project` at its counterpart
+    /// of `sha`. Reports the mirrors that moved or failed to.
+    pub(crate) async fn sync_mirrors(
+        &self,
+        project: &GithubProject,
+        sha: &str,
+    ) -> Vec<ActionReport> {
+        let mut reports = Vec::new();
+        for mirror in &project.mirrors {
+            let (owner, repo) = (&mirror.owner, &mirror.repo);
+            let error = match self
+                .sync_mirror(project, mirror, sha)
+                .instrument(info_span!("mirror", owner, repo))
+                .await
+            {
+                Ok(false) => continue,
+                Ok(true) => None,
+                Err(err) => {
+                    warn!(
+                        owner,
+                        repo,
+                        err = %self.state.redactor.debug(&err),
+                        "Unable to mirror env tag"
+                    );
+                    Some(format!("{err:#}"))
+                }
+            };
+            reports.push(ActionReport {
+                action: format!("mirror {owner}/{repo}"),
+                error,
+                simulated: self.state.shadow,
+            });
+        }
+        reports
+    }
+
+    /// Whether the mirror's env tag moved.
+    async fn sync_mirror(
+        &self,
+        project: &GithubProject,
+        mirror: &TagMirror,
+        sha: &str,
+    ) -> anyhow::Result<bool> {
+        let (owner, repo, env) = (&mirror.owner, &mirror.repo, &project.env);
+        // Observations are kept per project, so the mirror's are kept under
+        // the id it would have as a project of its own
+        let id = ProjectId::derived(&["github", owner, repo, env]);
+        let target = match &mirror.origin_trailer {
+            Some(trailer) => self.mirrored_commit(owner, repo, trailer, sha).await?,
+            None => sha.to_string(),
+        };
+        let git_ref = format!("tags/{env}");
+        let tag_sha = self.observe_ref(&id, owner, repo, &git_ref).await?;
+        if tag_sha.as_deref() == Some(target.as_str()) {
+            return Ok(false);
+        }
+
+        let mirrored = GithubProject {
+            owner: owner.clone(),
+            repo: repo.clone(),
+            ..project.clone()
+        };
+        match self
+            .move_ref(&id, &mirrored, &git_ref, tag_sha, &target)
+            .await?
+        {
+            ProjectOutcome::Conflict { expected, actual } => {
+                bail!("env tag moved concurrently, expected at {expected:?}, found at {actual:?}")
+            }
+            _ => {
+                info!(target, "Mirrored env tag");
+                Ok(true)
+            }
+        }
+    }
+
+    /// The recent commit of the mirror's default branch whose message has
+    /// the trailer `{trailer}: {sha}`.
+    async fn mirrored_commit(
+        &self,
+        owner: &str,
+        repo: &str,
+        trailer: &str,
+        sha: &str,
+    ) -> anyhow::Result<String> {
+        let branch = self
+            .state
+            .repos
+            .get(&self.state.read_octo, owner, repo)
+            .await?
+            .default_branch
+            .with_context(|| format!("mirror {owner}/{repo} has no default branch"))?;
+        let line = format!("{trailer}: {sha}");
+        let commits = self
+            .state
+            .read_octo
+            .recent_commits(owner, repo, &branch)
+            .await
+            .with_context(|| format!("Unable to list commits of {owner}/{repo}"))?;
+        commits
+            .into_iter()
+            .find(|commit| {
+                commit
+                    .commit
+                    .message
+                    .lines()
+                    .any(|text| text.trim() == line)
+            })
+            .map(|commit| commit.sha)
+            // Most likely not mirrored yet; the next sync tries again
+            .with_context(|| format!("no recent commit of {owner}/{repo} has {line}"))
+    }
+}
@@ -0,0 +1,87 @@
+//! Just enough of the NATS client protocol to publish a message: connect,
+//! publish, and wait for the server to acknowledge with a PONG.
+
+use std::time::Duration;
+
+use anyhow::{bail, Context};
+use serde::Deserialize;
+use serde_json::json;
+use tokio::{
+    io::{AsyncBufReadExt, AsyncWriteExt, BufReader},
+    net::TcpStream,
+    time::timeout,
+};
+
+const TIMEOUT: Duration = Duration::from_secs(10);
+
+/// A NATS server without TLS, e.g. `nats://nats.internal:4222`.
+#[derive(Deserialize, Debug, Clone)]
+#[serde(rename_all = "kebab-case")]
+pub struct NatsServer {
+    url: String,
+    #[serde(default)]
+    token: Option<String>,
+    #[serde(default)]
+    user: Option<String>,
+    #[serde(default)]
+    password: Option<String>,
+}
+
+impl NatsServer {
+    /// The token and password, which errors may echo.
+    pub(crate) fn secrets(&self) -> impl Iterator<Item = &String> {
+        self.token.iter().chain(&self.password)
+    }
+}
+
+/// Publishes `payload` to `subject` on a connection of its own.
+pub(crate) async fn publish(
+    server: &NatsServer,
+    subject: &str,
+    payload: &[u8],
+) -> anyhow::Result<()> {
+    timeout(TIMEOUT, publish_inner(server, subject, payload))
+        .await
+        .context("NATS server didn't answer in time")?
+}
+
+async fn publish_inner(server: &NatsServer, subject: &str, payload: &[u8]) -> anyhow::Result<()> {
+    let Some(address) = server.url.strip_prefix("nats://") else {
+        bail!("Only nats:// URLs are supported, not {}", server.url);
+    };
+    let stream = TcpStream::connect(address)
+        .await
+        .with_context(|| format!("Unable to connect to {address}"))?;
+    let (reader, mut writer) = stream.into_split();
+    let mut lines = BufReader::new(reader).lines();
+
+    // The server speaks first, with its INFO
+    let info = lines.next_line().await?.unwrap_or_default();
+    if !info.starts_with("INFO") {
+        bail!("Unexpected greeting from NATS server: {info}");
+    }
+    let connect = json!({
+        "verbose": false,
+        "pedantic": false,
+        "name": "hands-off-release",
+        "auth_token": server.token,
+        "user": server.user,
+        "pass": server.password,
+    });
+    let mut message =
+        format!("CONNECT {connect}\r\nPUB {subject} {}\r\n", payload.len()).into_bytes();
+    message.extend_from_slice(payload);
+    message.extend_from_slice(b"\r\nPING\r\n");
+    writer.write_all(&message).await?;
+
+    // Errors, e.g. of authorization, come before the PONG
+    while let Some(line) = lines.next_line().await? {
+        if line.starts_with("PONG") {
+            return Ok(());
+        }
+        if let Some(error) = line.strip_prefix("-ERR") {
+            bail!("NATS server refused: {}", error.trim());
+        }
+    }
+    bail!("NATS server closed the connection")
+}
@@ -0,0 +1,145 @@
+//! Just enough of the OCI distribution API to tell whether an image tag
+//! has been pushed, and as what digest.
+
+use anyhow::{bail, Context};
+use regex::Regex;
+use reqwest::{header, StatusCode};
+use serde::Deserialize;
+
+const MANIFEST_TYPES: &str = "application/vnd.oci.image.index.v1+json, \
+    application/vnd.oci.image.manifest.v1+json, \
+    application/vnd.docker.distribution.manifest.list.v2+json, \
+    application/vnd.docker.distribution.manifest.v2+json";
+
+/// Basic credentials for a registry, e.g. a GHCR personal token or the
+/// `AWS` user with an ECR password.
+#[derive(Deserialize, Clone)]
+#[serde(rename_all = "kebab-case")]
+pub struct RegistryCredentials {
+    username: String,
+    password: String,
+}
+
+impl RegistryCredentials {
+    /// The password, which errors may echo.
+    pub(crate) fn secrets(&self) -> impl Iterator<Item = &str> {
+        std::iter::once(self.password.as_str())
+    }
+}
+
+#[derive(Deserialize)]
+struct TokenResponse {
+    #[serde(alias = "access_token")]
+    token: String,
+}
+
+/// Whether `registry/repository:tag` resolves to a manifest.
+pub(crate) async fn manifest_exists(
+    http: &reqwest::Client,
+    credentials: Option<&RegistryCredentials>,
+    registry: &str,
+    repository: &str,
+    tag: &str,
+) -> anyhow::Result<bool> {
+    Ok(head_manifest(http, credentials, registry, repository, tag)
+        .await?
+        .is_some())
+}
+
+/// The digest `registry/repository:tag` resolves to, `None` if it doesn't.
+pub(crate) async fn manifest_digest(
+    http: &reqwest::Client,
+    credentials: Option<&RegistryCredentials>,
+    registry: &str,
+    repository: &str,
+    tag: &str,
+) -> anyhow::Result<Option<String>> {
+    let Some(response) = head_manifest(http, credentials, registry, repository, tag).await? else {
+        return Ok(None);
+    };
+    let digest = response
+        .headers()
+        .get("docker-content-digest")
+        .and_then(|value| value.to_str().ok())
+        .with_context(|| format!("{registry} sent no digest for {repository}:{tag}"))?;
+    Ok(Some(digest.to_string()))
+}
+
+/// The answer to a `HEAD` of the manifest, `None` if there is none.
+///
+/// Anonymous access is tried first; on a challenge the registry's token
+/// service (or basic auth) is used with `credentials`, if any.
+async fn head_manifest(
+    http: &reqwest::Client,
+    credentials: Option<&RegistryCredentials>,
+    registry: &str,
+    repository: &str,
+    tag: &str,
+) -> anyhow::Result<Option<reqwest::Response>> {
+    let url = format!("https://{registry}/v2/{repository}/manifests/{tag}");
+    let head = || http.head(&url).header(header::ACCEPT, MANIFEST_TYPES);
+
+    let response = head().send().await?;
+    let response = match response.status() {
+        StatusCode::UNAUTHORIZED => {
+            let challenge = response
+                .headers()
+                .get(header::WWW_AUTHENTICATE)
+                .and_then(|value| value.to_str().ok())
+                .unwrap_or_default()
+                .to_string();
+            match challenge.split_once(' ') {
+                Some((scheme, params)) if scheme.eq_ignore_ascii_case("bearer") => {
+                    let token = bearer_token(http, credentials, params).await?;
+                    head().bearer_auth(token).send().await?
+                }
+                Some((scheme, _)) if scheme.eq_ignore_ascii_case("basic") => {
+                    let credentials =
+                        credentials.with_context(|| format!("{registry} requires credentials"))?;
+                    head()
+                        .basic_auth(&credentials.username, Some(&credentials.password))
+                        .send()
+                        .await?
+                }
+                _ => bail!("{registry} sent an unsupported authentication challenge"),
+            }
+        }
+        _ => response,
+    };
+
+    match response.status() {
+        status if status.is_success() => Ok(Some(response)),
+        StatusCode::NOT_FOUND => Ok(None),
+        status => bail!("{registry} answered {status} for {repository}:{tag}"),
+    }
+}
+
+/// Exchanges the challenge's `realm`, `service` and `scope` for a token.
+async fn bearer_token(
+    http: &reqwest::Client,
+    credentials: Option<&RegistryCredentials>,
+    params: &str,
+) -> anyhow::Result<String> {
+    // Values are quoted and may themselves contain commas
+    let param = Regex::new(r#"(\w+)="([^"]*)""#).expect("valid regex");
+    let mut realm = None;
+    let mut query = Vec::new();
+    for captures in param.captures_iter(params) {
+        match (&captures[1], &captures[2]) {
+            ("realm", value) => realm = Some(value.to_string()),
+            (key @ ("service" | "scope"), value) => {
+                query.push((key.to_string(), value.to_string()))
+            }
+            _ => {}
+        }
+    }
+
+    let mut request = http
+        .get(realm.context("Authentication challenge has no realm")?)
+        .query(&query);
+    if let Some(credentials) = credentials {
+        request = request.basic_auth(&credentials.username, Some(&credentials.password));
+    }
+    let response: TokenResponse = request.send().await?.error_for_status()?.json().await?;
+    Ok(response.token)
+}
@@ -0,0 +1,1007 @@
+use std::{collections::BTreeSet, time::Duration};
+
+use anyhow::{bail, Context};
+use chrono::Datelike;
+use glob::Pattern;
+use hor_registry::{
+    AttestationSource, CommitConvention, GithubProject, ImageGate, MergeCommits, Policy, ProjectId,
+    RegoPolicy, RiskScoring,
+};
+use hor_state::{
+    BlockReason, CandidateCheck, CandidateCommit, CandidateFlag, CandidateImage, CodeOwner,
+    PolicyDecision, ReleaseCandidate,
+};
+use octocrab::Octocrab;
+use regex::Regex;
+use tracing::info;
+
+use crate::{
+    actions::{Integrations, Promotion},
+    codeowners::{CodeOwners, LOCATIONS},
+    github::{ChangedFile, GitCommit, HorOctocrabExtension},
+    scheduler::Permit,
+    InitializedState, Priority,
+};
+
+/// Conclusions that satisfy a required check.
+const PASSING_CONCLUSIONS: &[&str] = &["success", "neutral", "skipped"];
+
+const IMAGE_RULE: &str = "image-ready";
+
+/// Collects the release candidate for moving `project` from `from` to
+/// `to`, asking GitHub only for what `project.policy` needs. `permit` is
+/// released while waiting on checks.
+pub(crate) async fn gather(
+    state: &InitializedState,
+    id: &ProjectId,
+    project: &GithubProject,
+    from: Option<&str>,
+    to: &str,
+    permit: &mut Permit,
+    priority: Priority,
+) -> anyhow::Result<ReleaseCandidate> {
+    let (octo, store, integrations) = (&state.read_octo, state.store.as_ref(), &state.integrations);
+    let policy = &project.policy;
+    let (owner, repo) = (project.owner.as_str(), project.repo.as_str());
+    // A Rego module may look at any part of the candidate
+    let everything = policy.rego.is_some();
+
+    let wants_range = everything
+        || policy.codeowners_approvals
+        || policy.max_commits.is_some()
+        || !policy.allowed_authors.is_empty()
+        || !policy.denied_authors.is_empty()
+        || !policy.denied_messages.is_empty()
+        || policy.merge_commits.is_some()
+        || policy.commit_convention.is_some()
+        || policy.risk.is_some();
+    let (commits, total_commits, listed) = match (wants_range, from) {
+        (false, _) => (Vec::new(), 0, Vec::new()),
+        (true, Some(from)) => {
+            let comparison = octo.compare(owner, repo, from, to).await?;
+            (
+                comparison
+                    .commits
+                    .into_iter()
+                    .map(candidate_commit)
+                    .collect(),
+                comparison.total_commits,
+                comparison.files,
+            )
+        }
+        (true, None) => {
+            let mut commit = octo.commit(owner, repo, to).await?;
+            let files = std::mem::take(&mut commit.files);
+            (vec![candidate_commit(commit)], 1, files)
+        }
+    };
+    let files_truncated = listed.len() >= ChangedFile::LISTED_AT_MOST;
+    let files: Vec<_> = listed
+        .iter()
+        .flat_map(ChangedFile::paths)
+        .map(str::to_string)
+        .collect();
+
+    let code_owners = match everything || policy.codeowners_approvals {
+        true => code_owners(octo, owner, repo, to, &files)
+            .await
+            .context("Unable to resolve code owners")?,
+        false => Vec::new(),
+    };
+
+    let checks = if !everything && policy.required_checks.is_empty() {
+        Vec::new()
+    } else {
+        let deadline = policy
+            .check_wait
+            .as_ref()
+            .map(|wait| state.clock.now() + chrono::Duration::seconds(wait.timeout_secs as i64));
+        loop {
+            let checks: Vec<_> = octo
+                .check_runs(owner, repo, to)
+                .await?
+                .into_iter()
+                .map(|run| CandidateCheck {
+                    name: run.name,
+                    status: run.status,
+                    conclusion: run.conclusion,
+                })
+                .collect();
+            let (Some(wait), Some(deadline)) = (&policy.check_wait, deadline) else {
+                break checks;
+            };
+            let unsettled: Vec<_> = policy
+                .required_checks
+                .iter()
+                .filter(|name| {
+                    let matching = matching_checks(name, &checks);
+                    matching.is_empty() || matching.iter().any(|check| check.conclusion.is_none())
+                })
+                .collect();
+            let interval = Duration::from_secs(wait.interval_secs);
+            let next = state.clock.now() + chrono::Duration::seconds(wait.interval_secs as i64);
+            if unsettled.is_empty() || next > deadline {
+                break checks;
+            }
+            info!(?unsettled, "Waiting for required checks");
+            // Only waiting, so other projects get the slot meanwhile
+            state
+                .scheduler
+                .released_while(permit, priority, state.clock.sleep(interval))
+                .await;
+        }
+    };
+
+    let gated_by_risk = policy
+        .risk
+        .as_ref()
+        .is_some_and(|risk| risk.approval_threshold.is_some());
+    let approvers = match everything
+        || policy.codeowners_approvals
+        || policy.min_approvals.is_some()
+        || gated_by_risk
+    {
+        true => store
+            .approvals(id, &project.env, to)
+            .await?
+            .into_iter()
+            .map(|approval| approval.approver)
+            .collect(),
+        false => Vec::new(),
+    };
+
+    let image = match &policy.image {
+        Some(gate) => {
+            let promotion = Promotion { project, from, to };
+            let tag = promotion.render(&gate.tag);
+            let reference = format!("{}/{}:{tag}", gate.registry, gate.repository);
+            Some(match gate.attestation {
+                None => CandidateImage {
+                    reference,
+                    exists: integrations
+                        .image_exists(&gate.registry, &gate.repository, &tag)
+                        .await
+                        .context("Unable to look up container image")?,
+                    digest: None,
+                    attested: None,
+                },
+                Some(source) => {
+                    let digest = integrations
+                        .image_digest(&gate.registry, &gate.repository, &tag)
+                        .await
+                        .context("Unable to look up container image")?;
+                    let attested = match &digest {
+                        Some(digest) => Some(
+                            attested(octo, integrations, project, gate, source, digest)
+                                .await
+                                .context("Unable to look up attestations")?,
+                        ),
+                        None => None,
+                    };
+                    CandidateImage {
+                        reference,
+                        exists: digest.is_some(),
+                        digest,
+                        attested,
+                    }
+                }
+            })
+        }
+        None => None,
+    };
+
+    let mut flags = Vec::new();
+    for expected in &policy.feature_flags {
+        let on = integrations
+            .flag_on(&expected.project, &expected.flag, &expected.environment)
+            .await
+            .with_context(|| format!("Unable to look up feature flag {}", expected.flag))?;
+        flags.push(CandidateFlag {
+            project: expected.project.clone(),
+            flag: expected.flag.clone(),
+            environment: expected.environment.clone(),
+            on,
+        });
+    }
+
+    Ok(ReleaseCandidate {
+        project: id.clone(),
+        env: project.env.clone(),
+        from: from.map(str::to_string),
+        to: to.to_string(),
+        commits,
+        total_commits,
+        files,
+        files_truncated,
+        code_owners,
+        checks,
+        approvers,
+        image,
+        flags,
+        evaluated_at: state.clock.now(),
+    })
+}
+
+/// Owners of `files` per the CODEOWNERS file at `reference`, each with
+/// the logins that may approve for it.
+async fn code_owners(
+    octo: &Octocrab,
+    owner: &str,
+    repo: &str,
+    reference: &str,
+    files: &[String],
+) -> anyhow::Result<Vec<CodeOwner>> {
+    let mut content = None;
+    for location in LOCATIONS {
+        content = octo.file_content(owner, repo, location, reference).await?;
+        if content.is_some() {
+            break;
+        }
+    }
+    let Some(content) = content else {
+        return Ok(Vec::new());
+    };
+
+    let codeowners = CodeOwners::parse(&content);
+    let affected: BTreeSet<_> = files
+        .iter()
+        .flat_map(|file| codeowners.owners_of(file))
+        .collect();
+
+    let mut resolved = Vec::new();
+    for code_owner in affected {
+        let members = match code_owner.strip_prefix('@') {
+            Some(handle) => match handle.split_once('/') {
+                Some((org, team)) => octo.team_members(org, team).await?,
+                None => vec![handle.to_string()],
+            },
+            // Email owners can't be mapped to approvers
+            None => Vec::new(),
+        };
+        resolved.push(CodeOwner {
+            owner: code_owner.clone(),
+            members,
+        });
+    }
+    Ok(resolved)
+}
+
+fn candidate_commit(commit: GitCommit) -> CandidateCommit {
+    CandidateCommit {
+        sha: commit.sha,
+        message: commit.commit.message,
+        author: commit.author.map(|account| account.login),
+        parents: commit.parents.len(),
+    }
+}
+
+/// Whether `source` has an attestation of the image `digest`.
+async fn attested(
+    octo: &Octocrab,
+    integrations: &Integrations,
+    project: &GithubProject,
+    gate: &ImageGate,
+    source: AttestationSource,
+    digest: &str,
+) -> anyhow::Result<bool> {
+    match source {
+        AttestationSource::Cosign => {
+            // cosign names the tag after the digest, `sha256:` and all
+            let tag = format!("{}.att", digest.replace(':', "-"));
+            integrations
+                .image_exists(&gate.registry, &gate.repository, &tag)
+                .await
+        }
+        AttestationSource::Github => Ok(octo
+            .has_attestations(&project.owner, &project.repo, digest)
+            .await?),
+    }
+}
+
+/// The checks named `name`, or matching it if it is a glob.
+fn matching_checks<'a>(name: &str, checks: &'a [CandidateCheck]) -> Vec<&'a CandidateCheck> {
+    let pattern = Pattern::new(name).ok();
+    checks
+        .iter()
+        .filter(|check| {
+            check.name == name
+                || pattern
+                    .as_ref()
+                    .is_some_and(|pattern| pattern.matches(&check.name))
+        })
+        .collect()
+}
+
+/// One decision per rule set in `policy`; every required check is its own
+/// decision.
+pub fn evaluate(policy: &Policy, candidate: &ReleaseCandidate) -> Vec<PolicyDecision> {
+    let mut decisions = Vec::new();
+    // Rules over commits can't vouch for the ones GitHub didn't return
+    let truncated = candidate.commits.len() < candidate.total_commits;
+    const TRUNCATED: &str = "too many commits to verify every one";
+    const FILES_TRUNCATED: &str = "too many changed files to verify every one";
+
+    for name in &policy.required_checks {
+        let matching = matching_checks(name, &candidate.checks);
+        // The first run that doesn't pass tells why, if any
+        let blocking = matching.iter().find(|check| {
+            !check
+                .conclusion
+                .as_deref()
+                .is_some_and(|conclusion| PASSING_CONCLUSIONS.contains(&conclusion))
+        });
+        let (passed, detail) = match (matching.as_slice(), blocking) {
+            ([], _) => (false, format!("check {name} has not reported")),
+            (_, Some(check)) => match check.conclusion.as_deref() {
+                Some(conclusion) => (
+                    false,
+                    format!("check {} concluded {conclusion}", check.name),
+                ),
+                None => (false, format!("check {} is {}", check.name, check.status)),
+            },
+            ([check], None) => (
+                true,
+                format!(
+                    "check {} concluded {}",
+                    check.name,
+                    check.conclusion.as_deref().unwrap_or_default()
+                ),
+            ),
+            (matching, None) => (
+                true,
+                format!("all {} checks matching {name} passed", matching.len()),
+            ),
+        };
+        decisions.push(decision("required-check", passed, detail));
+    }
+
+    if !policy.windows.is_empty() {
+        let at = candidate.evaluated_at;
+        let passed = policy
+            .windows
+            .iter()
+            .any(|window| window.contains(at.weekday(), at.time()));
+        let detail = format!(
+            "{} is {} a release window",
+            at.format("%a %H:%M UTC"),
+            if passed { "inside" } else { "outside" }
+        );
+        decisions.push(decision("window", passed, detail));
+    }
+
+    if let Some(min) = policy.min_approvals {
+        let count = candidate.approvers.len();
+        decisions.push(decision(
+            "min-approvals",
+            count >= min,
+            format!("{count} of {min} required approvals"),
+        ));
+    }
+
+    if policy.codeowners_approvals {
+        if candidate.files_truncated {
+            decisions.push(decision("codeowners", false, FILES_TRUNCATED.to_string()));
+        } else if candidate.code_owners.is_empty() {
+            decisions.push(decision(
+                "codeowners",
+                true,
+                "no owned paths changed".to_string(),
+            ));
+        }
+        for code_owner in &candidate.code_owners {
+            let approver = candidate.approvers.iter().find(|approver| {
+                code_owner
+                    .members
+                    .iter()
+                    .any(|member| member.eq_ignore_ascii_case(approver))
+            });
+            let detail = match approver {
+                Some(approver) => format!("{} approved by {approver}", code_owner.owner),
+                None => format!("no approval from {}", code_owner.owner),
+            };
+            decisions.push(decision("codeowners", approver.is_some(), detail));
+        }
+    }
+
+    if let Some(max) = policy.max_commits {
+        let count = candidate.total_commits;
+        decisions.push(decision(
+            "max-commits",
+            count <= max,
+            format!("{count} commits, at most {max} allowed"),
+        ));
+    }
+
+    if !policy.allowed_authors.is_empty() {
+        let disallowed: Vec<_> = candidate
+            .commits
+            .iter()
+            .filter(|commit| {
+                !commit
+                    .author
+                    .as_ref()
+                    .is_some_and(|author| policy.allowed_authors.contains(author))
+            })
+            .map(|commit| {
+                let author = commit.author.as_deref().unwrap_or("unknown author");
+                format!("{} by {author}", short_sha(&commit.sha))
+            })
+            .collect();
+        let detail = match (&disallowed[..], truncated) {
+            ([], false) => "all commits by allowed authors".to_string(),
+            ([], true) => TRUNCATED.to_string(),
+            (disallowed, _) => format!("disallowed authors: {}", disallowed.join(", ")),
+        };
+        decisions.push(decision(
+            "allowed-authors",
+            disallowed.is_empty() && !truncated,
+            detail,
+        ));
+    }
+
+    if !policy.denied_authors.is_empty() {
+        let denied: Vec<_> = candidate
+            .commits
+            .iter()
+            .filter_map(|commit| {
+                let author = commit.author.as_ref()?;
+                policy
+                    .denied_authors
+                    .iter()
+                    .any(|denied| denied.eq_ignore_ascii_case(author))
+                    .then(|| format!("{} by {author}", short_sha(&commit.sha)))
+            })
+            .collect();
+        decisions.push(match &denied[..] {
+            [] if truncated => decision("denied-authors", false, TRUNCATED.to_string()),
+            [] => decision(
+                "denied-authors",
+                true,
+                "no commits by denied authors".to_string(),
+            ),
+            denied => decision(
+                "denied-authors",
+                false,
+                format!("commits by denied authors: {}", denied.join(", ")),
+            ),
+        });
+    }
+
+    for pattern in &policy.denied_messages {
+        let (passed, detail) = match Regex::new(pattern) {
+            Ok(regex) => {
+                let matching: Vec<_> = candidate
+                    .commits
+                    .iter()
+                    .filter(|commit| regex.is_match(&commit.message))
+                    .map(|commit| short_sha(&commit.sha))
+                    .collect();
+                match &matching[..] {
+                    [] if truncated => (false, TRUNCATED.to_string()),
+                    [] => (true, format!("no commit message matches {pattern}")),
+                    matching => (false, format!("{} match {pattern}", matching.join(", "))),
+                }
+            }
+            Err(err) => (false, format!("invalid pattern {pattern}: {err}")),
+        };
+        decisions.push(decision("denied-messages", passed, detail));
+    }
+
+    if let Some(mode) = policy.merge_commits {
+        let merges: Vec<_> = candidate
+            .commits
+            .iter()
+            .filter(|commit| commit.parents > 1)
+            .map(|commit| short_sha(&commit.sha))
+            .collect();
+        let (passed, detail) = match (mode, &merges[..]) {
+            (MergeCommits::Forbid, []) if truncated => (false, TRUNCATED.to_string()),
+            (MergeCommits::Forbid, []) => (true, "history is linear".to_string()),
+            (MergeCommits::Forbid, merges) => {
+                (false, format!("merge commits: {}", merges.join(", ")))
+            }
+            (MergeCommits::Require, []) => (false, "no merge commit in range".to_string()),
+            (MergeCommits::Require, merges) => {
+                (true, format!("merge commits: {}", merges.join(", ")))
+            }
+        };
+        decisions.push(decision("merge-commits", passed, detail));
+    }
+
+    if let Some(convention) = &policy.commit_convention {
+        let (passed, detail) = match convention_regex(convention) {
+            Ok(regex) => {
+                let violations: Vec<_> = candidate
+                    .commits
+                    .iter()
+                    .filter(|commit| commit.parents <= 1)
+                    .filter(|commit| {
+                        let summary = commit.message.lines().next().unwrap_or_default();
+                        !regex.is_match(summary)
+                    })
+                    .map(|commit| short_sha(&commit.sha))
+                    .collect();
+                match &violations[..] {
+                    [] if truncated => (false, TRUNCATED.to_string()),
+                    [] => (true, "every commit follows the convention".to_string()),
+                    violations => (
+                        false,
+                        format!("not following the convention: {}", violations.join(", ")),
+                    ),
+                }
+            }
+            Err(err) => (false, format!("invalid convention: {err}")),
+        };
+        decisions.push(decision("commit-convention", passed, detail));
+    }
+
+    if let Some(gate) = &policy.image {
+        let (passed, detail) = match &candidate.image {
+            Some(image) if image.exists => (true, format!("{} is published", image.reference)),
+            Some(image) => (false, format!("{} is not published yet", image.reference)),
+            None => (false, "image was not looked up".to_string()),
+        };
+        decisions.push(decision(IMAGE_RULE, passed, detail));
+
+        // Only told apart once the image is there to be attested
+        let image = candidate.image.as_ref().filter(|image| image.exists);
+        if let (Some(_), Some(image)) = (gate.attestation, image) {
+            let digest = image.digest.as_deref().unwrap_or("unknown digest");
+            let (passed, detail) = match image.attested {
+                Some(true) => (true, format!("{digest} is attested")),
+                Some(false) => (false, format!("{digest} has no attestation")),
+                None => (false, "attestations were not looked up".to_string()),
+            };
+            decisions.push(decision("attestation", passed, detail));
+        }
+    }
+
+    for expected in &policy.feature_flags {
+        let state = |on: bool| if on { "on" } else { "off" };
+        let actual = candidate.flags.iter().find(|flag| {
+            flag.project == expected.project
+                && flag.flag == expected.flag
+                && flag.environment == expected.environment
+        });
+        let (passed, detail) = match actual {
+            Some(flag) => (
+                flag.on == expected.on,
+                format!(
+                    "flag {} is {} in {}, expected {}",
+                    flag.flag,
+                    state(flag.on),
+                    flag.environment,
+                    state(expected.on)
+                ),
+            ),
+            None => (false, format!("flag {} was not looked up", expected.flag)),
+        };
+        decisions.push(decision("feature-flag", passed, detail));
+    }
+
+    if let Some(scoring) = &policy.risk {
+        let (passed, detail) = match risk_score(scoring, candidate) {
+            Ok(_) if candidate.files_truncated => (false, FILES_TRUNCATED.to_string()),
+            Ok((score, breakdown)) => match scoring.approval_threshold {
+                Some(threshold) if score > threshold => {
+                    let approvals = candidate.approvers.len();
+                    (
+                        approvals >= scoring.approvals,
+                        format!(
+                            "risk {score} ({breakdown}) is over {threshold}, \
+                             {approvals} of {} approvals",
+                            scoring.approvals
+                        ),
+                    )
+                }
+                _ => (true, format!("risk {score} ({breakdown})")),
+            },
+            Err(err) => (false, format!("invalid critical path: {err}")),
+        };
+        decisions.push(decision("risk", passed, detail));
+    }
+
+    if let Some(rego) = &policy.rego {
+        let (passed, detail) = match evaluate_rego(rego, candidate) {
+            Ok(denials) if denials.is_empty() => (true, format!("{} is empty", rego.rule)),
+            Ok(denials) => (false, denials.join(", ")),
+            Err(err) => (false, format!("unable to evaluate {}: {err:#}", rego.file)),
+        };
+        decisions.push(decision("rego", passed, detail));
+    }
+
+    decisions
+}
+
+/// How risky `candidate` is by `scoring`, and what the score is made of.
+/// Only a lower bound if GitHub left some of the files out.
+pub(crate) fn risk_score(
+    scoring: &RiskScoring,
+    candidate: &ReleaseCandidate,
+) -> Result<(u32, String), glob::PatternError> {
+    let critical_paths = scoring
+        .critical_paths
+        .iter()
+        .map(|path| Pattern::new(path))
+        .collect::<Result<Vec<_>, _>>()?;
+    let critical = candidate
+        .files
+        .iter()
+        .filter(|file| critical_paths.iter().any(|path| path.matches(file)))
+        .count();
+    let authors: BTreeSet<_> = candidate
+        .commits
+        .iter()
+        .filter_map(|commit| commit.author.as_deref())
+        .collect();
+    let count = |count: usize| u32::try_from(count).unwrap_or(u32::MAX);
+    let score = [
+        (candidate.total_commits, scoring.per_commit),
+        (candidate.files.len(), scoring.per_file),
+        (critical, scoring.per_critical_file),
+        (authors.len(), scoring.per_author),
+    ]
+    .into_iter()
+    .fold(0u32, |score, (count_of, points)| {
+        score.saturating_add(count(count_of).saturating_mul(points))
+    });
+    let breakdown = format!(
+        "{} commits, {} files, {critical} critical, {} authors",
+        candidate.total_commits,
+        candidate.files.len(),
+        authors.len()
+    );
+    Ok((score, breakdown))
+}
+
+/// Matches summary lines following `convention`.
+fn convention_regex(convention: &CommitConvention) -> Result<Regex, regex::Error> {
+    if let Some(pattern) = &convention.pattern {
+        return Regex::new(pattern);
+    }
+    let types = match &convention.types[..] {
+        [] => r"[A-Za-z]+".to_string(),
+        types => types
+            .iter()
+            .map(|kind| regex::escape(kind))
+            .collect::<Vec<_>>()
+            .join("|"),
+    };
+    Regex::new(&format!(r"^(?:{types})(?:\([^()]+\))?!?: \S"))
+}
+
+/// Denial messages produced by the Rego rule for `candidate`.
+#[cfg(feature = "rego")]
+fn evaluate_rego(rego: &RegoPolicy, candidate: &ReleaseCandidate) -> anyhow::Result<Vec<String>> {
+    use regorus::{Engine, Value};
+
+    let mut engine = Engine::new();
+    engine.add_policy_from_file(&rego.file)?;
+    engine.set_input(Value::from_json_str(&serde_json::to_string(candidate)?)?);
+    let denials = match engine.eval_rule(rego.rule.clone())? {
+        Value::Undefined => return Ok(Vec::new()),
+        Value::Set(denials) => denials.iter().cloned().collect(),
+        Value::Array(denials) => denials.as_ref().clone(),
+        other => bail!("{} must be a set or an array, got {other}", rego.rule),
+    };
+    denials
+        .into_iter()
+        .map(|denial| match denial {
+            Value::String(message) => Ok(message.to_string()),
+            other => other.to_json_str(),
+        })
+        .collect()
+}
+
+#[cfg(not(feature = "rego"))]
+fn evaluate_rego(_: &RegoPolicy, _: &ReleaseCandidate) -> anyhow::Result<Vec<String>> {
+    bail!("built without the rego feature")
+}
+
+/// Why the release is blocked and a summary of the failed decisions,
+/// `None` if every rule passed.
+pub fn violations(decisions: &[PolicyDecision]) -> Option<(BlockReason, String)> {
+    let failed: Vec<_> = decisions
+        .iter()
+        .filter(|decision| !decision.passed)
+        .collect();
+    // A missing image resolves itself once CI publishes it, unlike the
+    // other rules, so it gets its own reason when it's all that's wrong
+    let reason = match failed.iter().all(|decision| decision.rule == IMAGE_RULE) {
+        true => BlockReason::ImageNotReady,
+        false => BlockReason::PolicyViolation,
+    };
+    let summary: Vec<_> = failed
+        .iter()
+        .map(|decision| format!("{}: {}", decision.rule, decision.detail))
+        .collect();
+    (!failed.is_empty()).then(|| (reason, summary.join("; ")))
+}
+
+fn decision(rule: &str, passed: bool, detail: String) -> PolicyDecision {
+    PolicyDecision {
+        rule: rule.to_string(),
+        passed,
+        detail,
+    }
+}
+
+fn short_sha(sha: &str) -> &str {
+    sha.get(..7).unwrap_or(sha)
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{TimeZone, Utc};
+    use serde_json::json;
+
+    use super::*;
+
+    fn policy(policy: serde_json::Value) -> Policy {
+        serde_json::from_value(policy).unwrap()
+    }
+
+    fn commit(sha: &str, message: &str, author: Option<&str>, parents: usize) -> CandidateCommit {
+        CandidateCommit {
+            sha: sha.to_string(),
+            message: message.to_string(),
+            author: author.map(str::to_string),
+            parents,
+        }
+    }
+
+    fn check(name: &str, status: &str, conclusion: Option<&str>) -> CandidateCheck {
+        CandidateCheck {
+            name: name.to_string(),
+            status: status.to_string(),
+            conclusion: conclusion.map(str::to_string),
+        }
+    }
+
+    fn candidate() -> ReleaseCandidate {
+        ReleaseCandidate {
+            project: ProjectId::new("github/acme/api"),
+            env: "prod".to_string(),
+            from: Some("0000000aaa".to_string()),
+            to: "2222222ccc".to_string(),
+            commits: vec![
+                commit("1111111bbb", "feat: add endpoint", Some("octocat"), 1),
+                commit("2222222ccc", "fix: typo", Some("hubot"), 1),
+            ],
+            total_commits: 2,
+            files: vec!["src/main.rs".to_string(), "migrations/001.sql".to_string()],
+            files_truncated: false,
+            code_owners: Vec::new(),
+            checks: Vec::new(),
+            approvers: Vec::new(),
+            image: None,
+            flags: Vec::new(),
+            // A Monday
+            evaluated_at: Utc.with_ymd_and_hms(2024, 1, 15, 10, 0, 0).unwrap(),
+        }
+    }
+
+    /// Rule and outcome of every decision.
+    fn outcomes(decisions: &[PolicyDecision]) -> Vec<(&str, bool)> {
+        decisions
+            .iter()
+            .map(|decision| (decision.rule.as_str(), decision.passed))
+            .collect()
+    }
+
+    #[test]
+    fn empty_policy_decides_nothing() {
+        assert!(evaluate(&Policy::default(), &candidate()).is_empty());
+    }
+
+    #[test]
+    fn required_checks_must_all_pass() {
+        let mut candidate = candidate();
+        candidate.checks = vec![
+            check("lint", "completed", Some("success")),
+            check("test (linux)", "completed", Some("success")),
+            check("test (macos)", "in_progress", None),
+            check("docs", "completed", Some("skipped")),
+        ];
+        let policy = policy(json!({ "required-checks": ["lint", "test (*)", "docs", "e2e"] }));
+        let decisions = evaluate(&policy, &candidate);
+        assert_eq!(
+            outcomes(&decisions),
+            [
+                ("required-check", true),
+                ("required-check", false),
+                ("required-check", true),
+                ("required-check", false),
+            ]
+        );
+        assert_eq!(decisions[1].detail, "check test (macos) is in_progress");
+        assert_eq!(decisions[3].detail, "check e2e has not reported");
+    }
+
+    #[test]
+    fn windows_are_evaluated_at_evaluation_time() {
+        let inside =
+            policy(json!({ "windows": [{ "days": ["Mon"], "start": "09:00", "end": "16:00" }] }));
+        let outside =
+            policy(json!({ "windows": [{ "days": ["Tue"], "start": "09:00", "end": "16:00" }] }));
+        assert_eq!(
+            outcomes(&evaluate(&inside, &candidate())),
+            [("window", true)]
+        );
+        assert_eq!(
+            outcomes(&evaluate(&outside, &candidate())),
+            [("window", false)]
+        );
+    }
+
+    #[test]
+    fn codeowners_need_an_approval_each() {
+        let mut candidate = candidate();
+        candidate.approvers = vec!["Octocat".to_string()];
+        candidate.code_owners = vec![
+            CodeOwner {
+                owner: "@acme/api".to_string(),
+                members: vec!["octocat".to_string()],
+            },
+            CodeOwner {
+                owner: "@acme/dba".to_string(),
+                members: vec!["hubot".to_string()],
+            },
+        ];
+        let policy = policy(json!({ "codeowners-approvals": true, "min-approvals": 1 }));
+        let decisions = evaluate(&policy, &candidate);
+        assert_eq!(
+            outcomes(&decisions),
+            [
+                ("min-approvals", true),
+                ("codeowners", true),
+                ("codeowners", false),
+            ]
+        );
+        assert_eq!(decisions[2].detail, "no approval from @acme/dba");
+    }
+
+    #[test]
+    fn codeowners_fail_when_files_were_left_out() {
+        let mut candidate = candidate();
+        candidate.files_truncated = true;
+        let policy = policy(json!({ "codeowners-approvals": true }));
+        let decisions = evaluate(&policy, &candidate);
+        assert_eq!(outcomes(&decisions), [("codeowners", false)]);
+        assert_eq!(
+            decisions[0].detail,
+            "too many changed files to verify every one"
+        );
+    }
+
+    #[test]
+    fn author_rules_check_every_commit() {
+        let allowed = policy(json!({ "allowed-authors": ["octocat"] }));
+        let decisions = evaluate(&allowed, &candidate());
+        assert_eq!(outcomes(&decisions), [("allowed-authors", false)]);
+        assert_eq!(decisions[0].detail, "disallowed authors: 2222222 by hubot");
+
+        let denied = policy(json!({ "denied-authors": ["HUBOT"] }));
+        assert_eq!(
+            outcomes(&evaluate(&denied, &candidate())),
+            [("denied-authors", false)]
+        );
+        let denied = policy(json!({ "denied-authors": ["dependabot"] }));
+        assert_eq!(
+            outcomes(&evaluate(&denied, &candidate())),
+            [("denied-authors", true)]
+        );
+    }
+
+    #[test]
+    fn commit_rules_fail_when_commits_were_left_out() {
+        let mut candidate = candidate();
+        candidate.total_commits = 300;
+        let policy = policy(json!({
+            "allowed-authors": ["octocat", "hubot"],
+            "denied-authors": ["dependabot"],
+            "denied-messages": ["^WIP"],
+        }));
+        let decisions = evaluate(&policy, &candidate);
+        assert_eq!(
+            outcomes(&decisions),
+            [
+                ("allowed-authors", false),
+                ("denied-authors", false),
+                ("denied-messages", false),
+            ]
+        );
+        assert!(decisions
+            .iter()
+            .all(|decision| decision.detail == "too many commits to verify every one"));
+    }
+
+    #[test]
+    fn merge_commit_rules() {
+        let mut candidate = candidate();
+        let forbid = policy(json!({ "merge-commits": "forbid" }));
+        let require = policy(json!({ "merge-commits": "require" }));
+        assert_eq!(
+            outcomes(&evaluate(&forbid, &candidate)),
+            [("merge-commits", true)]
+        );
+        assert_eq!(
+            outcomes(&evaluate(&require, &candidate)),
+            [("merge-commits", false)]
+        );
+
+        candidate.total_commits = 300;
+        let decisions = evaluate(&forbid, &candidate);
+        assert_eq!(outcomes(&decisions), [("merge-commits", false)]);
+        assert_eq!(decisions[0].detail, "too many commits to verify every one");
+
+        candidate
+            .commits
+            .push(commit("3333333ddd", "Merge branch 'main'", None, 2));
+        let decisions = evaluate(&forbid, &candidate);
+        assert_eq!(outcomes(&decisions), [("merge-commits", false)]);
+        assert_eq!(decisions[0].detail, "merge commits: 3333333");
+        assert_eq!(
+            outcomes(&evaluate(&require, &candidate)),
+            [("merge-commits", true)]
+        );
+    }
+
+    #[test]
+    fn commit_convention_skips_merge_commits() {
+        let mut candidate = candidate();
+        candidate
+            .commits
+            .push(commit("3333333ddd", "Merge branch 'main'", None, 2));
+        let conventional = policy(json!({ "commit-convention": { "types": ["feat", "fix"] } }));
+        assert_eq!(
+            outcomes(&evaluate(&conventional, &candidate)),
+            [("commit-convention", true)]
+        );
+        let features = policy(json!({ "commit-convention": { "types": ["feat"] } }));
+        let decisions = evaluate(&features, &candidate);
+        assert_eq!(outcomes(&decisions), [("commit-convention", false)]);
+        assert_eq!(decisions[0].detail, "not following the convention: 2222222");
+    }
+
+    #[test]
+    fn scores_commits_files_critical_paths_and_authors() {
+        let scoring: RiskScoring =
+            serde_json::from_value(json!({ "critical-paths": ["migrations/**"] })).unwrap();
+        let (score, breakdown) = risk_score(&scoring, &candidate()).unwrap();
+        // 2 commits, 2 files, 1 critical at 10 and 2 authors at 2
+        assert_eq!(score, 2 + 2 + 10 + 4);
+        assert_eq!(breakdown, "2 commits, 2 files, 1 critical, 2 authors");
+    }
+
+    #[test]
+    fn refuses_invalid_critical_paths() {
+        let scoring: RiskScoring =
+            serde_json::from_value(json!({ "critical-paths": ["[migrations"] })).unwrap();
+        assert!(risk_score(&scoring, &candidate()).is_err());
+    }
+
+    #[test]
+    fn risk_over_threshold_needs_approvals() {
+        let risky = policy(json!({ "risk": { "approval-threshold": 5, "approvals": 1 } }));
+        assert_eq!(outcomes(&evaluate(&risky, &candidate())), [("risk", false)]);
+
+        let mut approved = candidate();
+        approved.approvers = vec!["octocat".to_string()];
+        assert_eq!(outcomes(&evaluate(&risky, &approved)), [("risk", true)]);
+
+        let lenient = policy(json!({ "risk": { "approval-threshold": 100 } }));
+        assert_eq!(
+            outcomes(&evaluate(&lenient, &candidate())),
+            [("risk", true)]
+        );
+    }
+
+    #[test]
+    fn risk_fails_when_files_were_left_out() {
+        let mut candidate = candidate();
+        candidate.files_truncated = true;
+        let policy = policy(json!({ "risk": {} }));
+        assert_eq!(outcomes(&evaluate(&policy, &candidate)), [("risk", false)]);
+    }
+}
@@ -0,0 +1,103 @@
+//! Adaptive polling: between full passes, each project is polled as
+//! often as it has lately been active, so busy repos are picked up within
+//! a tick while dormant ones spend hardly any rate limit.
+
+use std::collections::{HashMap, HashSet};
+
+use chrono::{DateTime, Duration, Utc};
+use hor_registry::ProjectId;
+use hor_state::{ProjectOutcome, StateStore, SyncReport};
+use serde::Deserialize;
+use tracing::warn;
+
+use crate::Redactor;
+
+/// A project is polled every tenth of the time since it was last active,
+/// e.g. every 6 minutes once it's been quiet for an hour.
+const IDLE_DIVISOR: i32 = 10;
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(rename_all = "kebab-case")]
+pub(crate) struct AdaptivePolling {
+    /// Longest a dormant project goes without being polled; also how often
+    /// full passes run, unless `full-sync-interval-secs` says otherwise
+    #[serde(default = "AdaptivePolling::default_max_interval_secs")]
+    pub max_interval_secs: u64,
+}
+
+impl AdaptivePolling {
+    fn default_max_interval_secs() -> u64 {
+        60 * 60
+    }
+
+    fn max_interval(&self) -> Duration {
+        Duration::seconds(self.max_interval_secs as i64)
+    }
+}
+
+struct Polled {
+    at: DateTime<Utc>,
+    /// Last time a sync found something to do
+    active_at: Option<DateTime<Utc>>,
+}
+
+/// When each project seen in a sync was last polled and last active.
+#[derive(Default)]
+pub(crate) struct PollSchedule {
+    projects: HashMap<ProjectId, Polled>,
+}
+
+impl PollSchedule {
+    /// Learns from `report`, synced at `now`. A project seen for the first
+    /// time counts as active when it was last released, so history carries
+    /// over a restart.
+    pub async fn observe(
+        &mut self,
+        store: &dyn StateStore,
+        redactor: &Redactor,
+        report: &SyncReport,
+        now: DateTime<Utc>,
+    ) {
+        for project in &report.projects {
+            let active = !matches!(
+                project.outcome,
+                ProjectOutcome::Unchanged { .. }
+                    | ProjectOutcome::Skipped { .. }
+                    | ProjectOutcome::Failed { .. }
+            );
+            let active_at = match (active, self.projects.get(&project.id)) {
+                (true, _) => Some(now),
+                (false, Some(polled)) => polled.active_at,
+                (false, None) => match store.last_deployment(&project.id, &project.env).await {
+                    Ok(deployment) => deployment.map(|deployment| deployment.deployed_at),
+                    Err(err) => {
+                        warn!(
+                            id = %project.id,
+                            err = %redactor.debug(&err),
+                            "Unable to read last deployment"
+                        );
+                        None
+                    }
+                },
+            };
+            self.projects
+                .insert(project.id.clone(), Polled { at: now, active_at });
+        }
+    }
+
+    /// The projects due to be polled at `now`.
+    pub fn due(&self, polling: &AdaptivePolling, now: DateTime<Utc>) -> HashSet<ProjectId> {
+        self.projects
+            .iter()
+            .filter(|(_, polled)| {
+                let interval = match polled.active_at {
+                    Some(at) => ((now - at) / IDLE_DIVISOR).min(polling.max_interval()),
+                    // Never active, as far as history goes
+                    None => polling.max_interval(),
+                };
+                now - polled.at >= interval
+            })
+            .map(|(id, _)| id.clone())
+            .collect()
+    }
+}
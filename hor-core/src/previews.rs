@@ -0,0 +1,177 @@
+//! Preview envs of pull requests: one project per open pull request,
+//! releasing its head, and its env tag deleted once it's merged or closed.
+
+use std::collections::HashSet;
+
+use anyhow::Context;
+use hor_registry::{GithubProject, PreviewEnvs, Registry, Upstream};
+use hor_state::{ApiUsage, ProjectOutcome, ProjectReport, SkipReason};
+use octocrab::params;
+use tracing::{error, info};
+
+use crate::{github::HorOctocrabExtension, HorSystem, InitializedState};
+
+impl<R: Registry + ?Sized> HorSystem<InitializedState, R> {
+    /// The preview project of every open pull request of `project` that
+    /// `previews` selects, and reports on the previews torn down as their
+    /// pull request went away.
+    pub(crate) async fn expand_previews(
+        &self,
+        project: &GithubProject,
+        previews: &PreviewEnvs,
+    ) -> anyhow::Result<(Vec<GithubProject>, Vec<ProjectReport>)> {
+        let (owner, repo) = (project.owner.as_str(), project.repo.as_str());
+        let octo = &self.state.read_octo;
+        let first_page = octo
+            .pulls(owner, repo)
+            .list()
+            .state(params::State::Open)
+            .per_page(100)
+            .send()
+            .await
+            .with_context(|| format!("Unable to list pull requests of {owner}/{repo}"))?;
+        let pulls = octo.all_pages(first_page).await?;
+
+        let mut expanded = Vec::new();
+        for pull in pulls {
+            if pull.draft == Some(true) && !previews.drafts {
+                continue;
+            }
+            let labelled = previews.labels.is_empty()
+                || pull.labels.iter().flatten().any(|label| {
+                    previews
+                        .labels
+                        .iter()
+                        .any(|wanted| wanted.eq_ignore_ascii_case(&label.name))
+                });
+            if !labelled {
+                continue;
+            }
+            // The head repository is gone if the fork was deleted
+            let Some((head_owner, head_repo)) = pull
+                .head
+                .repo
+                .as_ref()
+                .and_then(|head| Some((head.owner.as_ref()?.login.clone(), head.name.clone())))
+            else {
+                continue;
+            };
+            let fork = !head_owner.eq_ignore_ascii_case(owner) || head_repo != repo;
+            if fork && !previews.forks {
+                continue;
+            }
+            expanded.push((
+                pull.number,
+                Upstream {
+                    owner: head_owner,
+                    repo: head_repo,
+                    branch: Some(pull.head.ref_field.clone()),
+                },
+            ));
+        }
+
+        let open: HashSet<_> = expanded.iter().map(|(number, _)| *number).collect();
+        let torn_down = self.tear_down_previews(project, previews, &open).await?;
+        let projects: Vec<_> = expanded
+            .into_iter()
+            .map(|(number, head)| GithubProject {
+                id: None,
+                env: previews.env_for(number),
+                upstream: Some(head),
+                // A preview releases its pull request, nothing else
+                promote_from: None,
+                blue_green: None,
+                group: None,
+                mirrors: Vec::new(),
+                release_tags: None,
+                extra_refs: Vec::new(),
+                regions: Vec::new(),
+                rollout: None,
+                train: None,
+                budget: None,
+                ttl_secs: None,
+                previews: None,
+                ..project.clone()
+            })
+            .collect();
+        info!(
+            owner,
+            repo,
+            count = projects.len(),
+            removed = torn_down.len(),
+            "Expanded previews"
+        );
+        Ok((projects, torn_down))
+    }
+
+    /// Deletes the env tags of previews whose pull request isn't in `open`.
+    async fn tear_down_previews(
+        &self,
+        project: &GithubProject,
+        previews: &PreviewEnvs,
+        open: &HashSet<u64>,
+    ) -> anyhow::Result<Vec<ProjectReport>> {
+        let (owner, repo) = (project.owner.as_str(), project.repo.as_str());
+        let refs = self
+            .state
+            .read_octo
+            .matching_refs(owner, repo, "tags/")
+            .await
+            .context("Unable to list tags")?;
+        let mut reports = Vec::new();
+        for git_ref in refs {
+            let Some(env) = git_ref.ref_field.strip_prefix("refs/tags/") else {
+                continue;
+            };
+            let Some(number) = previews.pull_request_of(env) else {
+                continue;
+            };
+            if open.contains(&number) {
+                continue;
+            }
+            let outcome = match self.delete_preview(project, env).await {
+                Ok(()) => ProjectOutcome::Skipped {
+                    reason: SkipReason::Expired,
+                    detail: format!("pull request #{number} is no longer previewed"),
+                },
+                Err(err) => {
+                    error!(
+                        env,
+                        err = %self.state.redactor.debug(&err),
+                        "Unable to tear down preview"
+                    );
+                    ProjectOutcome::Failed {
+                        error: format!("{err:#}"),
+                    }
+                }
+            };
+            reports.push(ProjectReport {
+                id: project.id_for_env(env),
+                env: env.to_string(),
+                outcome,
+                decisions: Vec::new(),
+                actions: Vec::new(),
+                inputs: None,
+                manifest: None,
+                api_usage: ApiUsage::default(),
+            });
+        }
+        Ok(reports)
+    }
+
+    async fn delete_preview(&self, project: &GithubProject, env: &str) -> anyhow::Result<()> {
+        let git_ref = format!("tags/{env}");
+        self.ensure_unprotected(&git_ref)?;
+        if self.state.shadow {
+            info!(git_ref, "Shadow mode, not deleting preview");
+            return Ok(());
+        }
+        self.state
+            .writer(env)
+            .delete_ref(&project.owner, &project.repo, &git_ref)
+            .await
+            .context("Unable to delete preview env tag")?;
+        info!(git_ref, "Deleted preview");
+        Ok(())
+    }
+}
@@ -0,0 +1,167 @@
+//! Immutable version tags created at each release, giving consumers a
+//! version history the env tag, which moves, can't.
+
+use anyhow::Context;
+use chrono::{DateTime, Datelike, Utc};
+use hor_registry::{GithubProject, Registry, ReleaseTags, VersionScheme};
+use hor_state::ActionReport;
+use tracing::{info, warn};
+
+use crate::{
+    github::HorOctocrabExtension,
+    versions::{conventional_bump, Bump, Version},
+    HorSystem, InitializedState,
+};
+
+impl<R: Registry + ?Sized> HorSystem<InitializedState, R> {
+    /// Tags `sha` with the next version, unless the latest release tag is
+    /// already there. Reports the tag created or the failure to.
+    pub(crate) async fn tag_release(
+        &self,
+        project: &GithubProject,
+        sha: &str,
+    ) -> Option<ActionReport> {
+        let tags = project.release_tags.as_ref()?;
+        let (action, error) = match self.create_release_tag(project, tags, sha).await {
+            Ok(None) => return None,
+            Ok(Some(name)) => (format!("release tag {name}"), None),
+            Err(err) => {
+                warn!(err = %self.state.redactor.debug(&err), "Unable to create release tag");
+                ("release tag".to_string(), Some(format!("{err:#}")))
+            }
+        };
+        Some(ActionReport {
+            action,
+            error,
+            simulated: self.state.shadow,
+        })
+    }
+
+    /// The name of the tag created, `None` if there was nothing to tag.
+    async fn create_release_tag(
+        &self,
+        project: &GithubProject,
+        tags: &ReleaseTags,
+        sha: &str,
+    ) -> anyhow::Result<Option<String>> {
+        let (owner, repo) = (project.owner.as_str(), project.repo.as_str());
+        let octo = &self.state.read_octo;
+        let prefix = format!("tags/{}", tags.prefix);
+        let refs = octo
+            .matching_refs(owner, repo, &prefix)
+            .await
+            .context("Unable to list release tags")?;
+        // Tags that aren't plain versions, e.g. release candidates, are
+        // left out
+        let latest = refs
+            .into_iter()
+            .filter_map(|git_ref| {
+                let name = git_ref
+                    .ref_field
+                    .strip_prefix("refs/")?
+                    .strip_prefix(&prefix)?;
+                Some((Version::parse(name).ok()?, git_ref.object))
+            })
+            .max_by_key(|(version, _)| *version);
+
+        let latest = match latest {
+            None => None,
+            Some((version, object)) => {
+                let tagged = self.peel(owner, repo, object).await?;
+                if tagged == sha {
+                    return Ok(None);
+                }
+                let comparison = octo
+                    .compare(owner, repo, &tagged, sha)
+                    .await
+                    .context("Unable to compare with the latest release tag")?;
+                // A rollback or pin to an older release doesn't get a newer
+                // version
+                if comparison.status == "behind" {
+                    info!(%version, "Release predates the latest release tag");
+                    return Ok(None);
+                }
+                Some((version, comparison))
+            }
+        };
+
+        let next = match (tags.scheme, latest) {
+            (VersionScheme::Calver, latest) => {
+                calver(latest.map(|(version, _)| version), self.state.clock.now())
+            }
+            (_, None) => Version::parse(&tags.initial)
+                .context("Invalid initial version")?
+                .to_string(),
+            (VersionScheme::Conventional, Some((version, comparison))) => {
+                let messages: Vec<_> = comparison
+                    .commits
+                    .into_iter()
+                    .map(|commit| commit.commit.message)
+                    .collect();
+                let bump = conventional_bump(&messages).unwrap_or(Bump::Patch);
+                version.bump(bump).to_string()
+            }
+            (VersionScheme::Counter, Some((version, _))) => version.bump(Bump::Patch).to_string(),
+        };
+
+        let name = format!("{}{next}", tags.prefix);
+        if self.state.shadow {
+            info!(name, "Shadow mode, not creating release tag");
+            return Ok(Some(name));
+        }
+        // Only ever created, never moved, so protected refs don't apply
+        self.state
+            .writer(&project.env)
+            .create_ref(owner, repo, &format!("refs/tags/{name}"), sha)
+            .await
+            .context("Unable to create release tag")?
+            .with_context(|| format!("release tag {name} already exists"))?;
+        info!(name, sha, "Created release tag");
+        Ok(Some(name))
+    }
+}
+
+/// The calendar version released `today` after `latest`: the month's next
+/// release, or its first once the month has rolled over.
+fn calver(latest: Option<Version>, today: DateTime<Utc>) -> String {
+    let (year, month) = (today.year() as u64, today.month() as u64);
+    // Calendar versions parse as versions, the month as minor
+    let release = match latest {
+        Some(version) if version.major == year && version.minor == month => version.patch + 1,
+        _ => 1,
+    };
+    format!("{year}.{month:02}.{release}")
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::TimeZone;
+
+    use super::*;
+
+    fn day(year: i32, month: u32, day: u32) -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(year, month, day, 12, 0, 0).unwrap()
+    }
+
+    fn version(version: &str) -> Option<Version> {
+        Some(Version::parse(version).unwrap())
+    }
+
+    #[test]
+    fn first_calver_release_is_one() {
+        assert_eq!(calver(None, day(2024, 3, 5)), "2024.03.1");
+    }
+
+    #[test]
+    fn counts_the_releases_of_the_month() {
+        assert_eq!(calver(version("2024.03.4"), day(2024, 3, 31)), "2024.03.5");
+    }
+
+    #[test]
+    fn rolls_over_with_the_month_and_year() {
+        assert_eq!(calver(version("2024.03.4"), day(2024, 4, 1)), "2024.04.1");
+        assert_eq!(calver(version("2024.12.9"), day(2025, 1, 1)), "2025.01.1");
+        // Same month of another year
+        assert_eq!(calver(version("2023.03.2"), day(2024, 3, 1)), "2024.03.1");
+    }
+}
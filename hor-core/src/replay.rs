@@ -0,0 +1,69 @@
+//! Replays of recorded runs: every project's policy evaluated again from
+//! the inputs the run recorded, without asking GitHub, to debug why a gate
+//! blocked or why a tag moved.
+
+use anyhow::Context;
+use hor_registry::{ProjectId, Registry};
+use hor_state::{PolicyDecision, ProjectOutcome, ReleaseInputs, RunId};
+
+use crate::{policy, HorSystem, InitializedState};
+
+/// One project of a replayed run.
+#[derive(Debug, Clone)]
+pub struct ReplayedProject {
+    pub id: ProjectId,
+    pub env: String,
+    /// What the run did
+    pub outcome: ProjectOutcome,
+    /// What the run's plan was based on; `None` if it didn't get as far as
+    /// reading the env tag, or the run predates recording them
+    pub inputs: Option<ReleaseInputs>,
+    /// Decisions as recorded by the run
+    pub recorded: Vec<PolicyDecision>,
+    /// Decisions of the recorded policy over the recorded candidate now;
+    /// empty if there was no candidate
+    pub replayed: Vec<PolicyDecision>,
+}
+
+impl ReplayedProject {
+    /// Whether the replay decided differently than the run, e.g. as the
+    /// rules have changed since.
+    pub fn diverged(&self) -> bool {
+        self.replayed != self.recorded
+    }
+}
+
+impl<R: Registry + ?Sized> HorSystem<InitializedState, R> {
+    /// Replays `run` from its recorded inputs. Only the state store is
+    /// read; nothing is moved, announced or recorded.
+    pub async fn replay(&self, run: RunId) -> anyhow::Result<Vec<ReplayedProject>> {
+        let report = self
+            .state
+            .store
+            .run(run)
+            .await?
+            .with_context(|| format!("No run {run}"))?;
+        Ok(report
+            .projects
+            .into_iter()
+            .map(|project| {
+                let replayed = match &project.inputs {
+                    Some(ReleaseInputs {
+                        policy,
+                        candidate: Some(candidate),
+                        ..
+                    }) => policy::evaluate(policy, candidate),
+                    _ => Vec::new(),
+                };
+                ReplayedProject {
+                    id: project.id,
+                    env: project.env,
+                    outcome: project.outcome,
+                    inputs: project.inputs,
+                    recorded: project.decisions,
+                    replayed,
+                }
+            })
+            .collect())
+    }
+}
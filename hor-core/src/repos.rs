@@ -0,0 +1,99 @@
+//! Repository metadata cached across syncs; it almost never changes, so
+//! it isn't worth a request per project and cycle.
+
+use std::{
+    collections::HashMap,
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+use anyhow::Context;
+use octocrab::Octocrab;
+
+use crate::github::HorOctocrabExtension;
+
+#[derive(Debug, Clone)]
+pub(crate) struct RepoMetadata {
+    /// Current owner and name, which differ from the ones asked for once
+    /// the repository was renamed or transferred
+    pub owner: String,
+    pub name: String,
+    pub default_branch: Option<String>,
+    pub node_id: Option<String>,
+    pub archived: bool,
+    /// No commits, as far as the reported size tells
+    pub empty: bool,
+}
+
+pub(crate) struct RepoCache {
+    ttl: Duration,
+    entries: Mutex<HashMap<(String, String), (Instant, RepoMetadata)>>,
+}
+
+impl RepoCache {
+    pub fn new(ttl: Duration) -> Self {
+        Self {
+            ttl,
+            entries: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// The cached metadata of `owner/repo`, fetched if missing or stale.
+    pub async fn get(
+        &self,
+        octo: &Octocrab,
+        owner: &str,
+        repo: &str,
+    ) -> anyhow::Result<RepoMetadata> {
+        let key = (owner.to_string(), repo.to_string());
+        if let Some((fetched_at, metadata)) = self.lock().get(&key) {
+            if fetched_at.elapsed() < self.ttl {
+                return Ok(metadata.clone());
+            }
+        }
+
+        let repository = octo
+            .repository(owner, repo)
+            .await?
+            .with_context(|| format!("repository {owner}/{repo} does not exist"))?;
+        let metadata = RepoMetadata {
+            owner: repository
+                .owner
+                .map(|owner| owner.login)
+                .unwrap_or_else(|| owner.to_string()),
+            name: repository.name,
+            default_branch: repository.default_branch,
+            node_id: repository.node_id,
+            archived: repository.archived.unwrap_or(false),
+            empty: repository.size == Some(0),
+        };
+        self.lock().insert(key, (Instant::now(), metadata.clone()));
+        Ok(metadata)
+    }
+
+    /// Forgets `owner/repo`, or every repository if `repo` is `None`.
+    pub fn invalidate(&self, owner: &str, repo: Option<&str>) {
+        self.lock().retain(|(cached_owner, cached_repo), _| {
+            cached_owner != owner || repo.is_some_and(|repo| cached_repo != repo)
+        });
+    }
+
+    /// Forgets the repository with GraphQL node id `node_id`, which
+    /// survives renames and transfers unlike the name.
+    pub fn invalidate_node(&self, node_id: &str) {
+        self.lock()
+            .retain(|_, (_, metadata)| metadata.node_id.as_deref() != Some(node_id));
+    }
+
+    pub fn clear(&self) {
+        self.lock().clear();
+    }
+
+    fn lock(
+        &self,
+    ) -> std::sync::MutexGuard<'_, HashMap<(String, String), (Instant, RepoMetadata)>> {
+        self.entries
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
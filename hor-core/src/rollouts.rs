@@ -0,0 +1,229 @@
+//! Region-by-region releases of projects templated over regions, each
+//! region baking before the next one gets the release.
+
+use std::collections::HashSet;
+
+use hor_registry::{GithubProject, ProjectId, RegionRollout, Registry};
+use hor_state::{ApiUsage, BlockReason, ProjectOutcome, ProjectReport};
+use tracing::{error, info, info_span, warn, Instrument};
+
+use crate::{actions::Promotion, HorSystem, InitializedState, Priority};
+
+/// Runs searched for what a region ran before a release it has baked,
+/// enough to cover bakes of a day at a sync a minute.
+const ROLLBACK_RUNS: usize = 2000;
+
+impl<R: Registry + ?Sized> HorSystem<InitializedState, R> {
+    /// Syncs the regions of `project` in order, all of them if any is in
+    /// `ids`, so that a region is never released ahead of the earlier
+    /// ones. A region only releases once the one before it has run its
+    /// release for the bake time, so bakes span syncs rather than hold one
+    /// up. A region that doesn't release halts the rollout: the regions
+    /// after it are held back and, if configured, the ones at the release
+    /// it failed are moved back.
+    pub(crate) async fn update_rollout(
+        &self,
+        project: &GithubProject,
+        rollout: &RegionRollout,
+        ids: Option<&HashSet<ProjectId>>,
+        priority: Priority,
+    ) -> Vec<ProjectReport> {
+        let regions: Vec<_> = project
+            .regions
+            .iter()
+            .zip(project.expand_regions())
+            .collect();
+        if let Some(ids) = ids {
+            if !regions.iter().any(|(_, region)| ids.contains(&region.id())) {
+                return Vec::new();
+            }
+        }
+        // Regions at their release, as report indices
+        let mut released = Vec::new();
+        let mut reports = Vec::new();
+        let mut halted: Option<String> = None;
+        // What the halting region was to release, if it got that far
+        let mut target = None;
+        let mut baking: Option<String> = None;
+        for (index, (region, project)) in regions.iter().enumerate() {
+            let id = project.id();
+            let blocked = match (&halted, &baking) {
+                (Some(detail), _) => Some((BlockReason::Rollout, detail.clone())),
+                (None, Some(detail)) => Some((BlockReason::Baking, detail.clone())),
+                (None, None) => None,
+            };
+            if let Some((reason, detail)) = blocked {
+                reports.push(ProjectReport {
+                    id,
+                    env: project.env.clone(),
+                    outcome: ProjectOutcome::Blocked { reason, detail },
+                    decisions: Vec::new(),
+                    actions: Vec::new(),
+                    inputs: None,
+                    manifest: None,
+                    api_usage: ApiUsage::default(),
+                });
+                continue;
+            }
+
+            let report = async {
+                let _guard = self.state.project_locks.lock(&id).await;
+                let permit = self.state.scheduler.acquire(priority).await;
+                self.update_github(project, permit, priority).await
+            }
+            .instrument(info_span!("rollout", region))
+            .await;
+            let last = index + 1 == regions.len();
+            match &report.outcome {
+                ProjectOutcome::Created { .. }
+                | ProjectOutcome::Updated { .. }
+                | ProjectOutcome::Recreated { .. }
+                | ProjectOutcome::Unchanged { .. } => {
+                    released.push((reports.len(), project));
+                    if !last {
+                        baking = self.region_baking(region, project, rollout).await;
+                    }
+                }
+                outcome => {
+                    warn!(region, ?outcome, "Region did not release, halting rollout");
+                    halted = Some(format!("region {region} of the rollout did not release"));
+                    target = match outcome {
+                        ProjectOutcome::RolledBack { to, .. } => Some(to.clone()),
+                        _ => report.inputs.as_ref().map(|inputs| inputs.to.clone()),
+                    };
+                }
+            }
+            reports.push(report);
+        }
+
+        if let (Some(reason), true) = (&halted, rollout.roll_back) {
+            for (index, project) in released {
+                let report = &mut reports[index];
+                // Regions already at another release before the rollout
+                // are left alone
+                if let ProjectOutcome::Unchanged { sha } = &report.outcome {
+                    if target.as_ref() != Some(sha) {
+                        continue;
+                    }
+                }
+                report.outcome = self
+                    .roll_back_region(project, &report.outcome, reason)
+                    .await;
+            }
+        }
+        reports
+    }
+
+    /// Why the next region has to wait for `project`, the region just at
+    /// the release, to bake; `None` once it has. Shadow releases deploy
+    /// nothing to bake, and a release that predates the history counts as
+    /// baked.
+    async fn region_baking(
+        &self,
+        region: &str,
+        project: &GithubProject,
+        rollout: &RegionRollout,
+    ) -> Option<String> {
+        let bake = rollout
+            .region_bake_secs
+            .get(region)
+            .copied()
+            .unwrap_or(rollout.bake_secs);
+        if bake == 0 || self.state.shadow {
+            return None;
+        }
+        let id = project.id();
+        let deployed = match self.state.store.last_deployment(&id, &project.env).await {
+            Ok(deployed) => deployed?,
+            Err(err) => {
+                error!(%id, err = %self.state.redactor.debug(&err), "Unable to read region release");
+                return Some(format!("unable to tell whether region {region} has baked"));
+            }
+        };
+        let baked = self.state.clock.now() - deployed.deployed_at;
+        let bake = chrono::Duration::seconds(bake as i64);
+        if baked >= bake {
+            return None;
+        }
+        info!(region, sha = deployed.sha, "Region is baking");
+        Some(format!(
+            "region {region} has run {} for {}m of {}m",
+            deployed.sha,
+            baked.num_minutes(),
+            bake.num_minutes()
+        ))
+    }
+
+    /// Moves a region at the release back to what it ran before, told by
+    /// `outcome` if it released in this sync and by the run history if it
+    /// released in an earlier one.
+    async fn roll_back_region(
+        &self,
+        project: &GithubProject,
+        outcome: &ProjectOutcome,
+        reason: &str,
+    ) -> ProjectOutcome {
+        let id = project.id();
+        let (from, to) = match outcome {
+            ProjectOutcome::Updated { from, to } | ProjectOutcome::Recreated { from, to } => {
+                (from.clone(), to.clone())
+            }
+            ProjectOutcome::Unchanged { sha } => match self.released_from(&id, sha).await {
+                Ok(Some(from)) => (from, sha.clone()),
+                Ok(None) => {
+                    return ProjectOutcome::Failed {
+                        error: format!("{reason}; what the region ran before is unknown"),
+                    }
+                }
+                Err(err) => {
+                    return ProjectOutcome::Failed {
+                        error: format!("{reason}; reading the run history failed: {err:#}"),
+                    }
+                }
+            },
+            _ => {
+                return ProjectOutcome::Failed {
+                    error: format!("{reason}; the created env tag was left in place"),
+                }
+            }
+        };
+        let _guard = self.state.project_locks.lock(&id).await;
+        let promotion = Promotion {
+            project,
+            from: Some(from.as_str()),
+            to: &to,
+        };
+        match self.roll_back(&id, &promotion, &from).await {
+            Ok(()) => ProjectOutcome::RolledBack {
+                from,
+                to,
+                reason: reason.to_string(),
+            },
+            Err(err) => {
+                error!(%id, err = %self.state.redactor.debug(&err), "Unable to roll back region");
+                ProjectOutcome::Failed {
+                    error: format!("{reason}; moving it back failed: {err:#}"),
+                }
+            }
+        }
+    }
+
+    /// What `id` ran before the latest release of `sha` by a recorded
+    /// sync, `None` if none on record moved it there.
+    async fn released_from(&self, id: &ProjectId, sha: &str) -> anyhow::Result<Option<String>> {
+        let runs = self.state.store.recent_runs(ROLLBACK_RUNS).await?;
+        let from = runs
+            .iter()
+            .flat_map(|(_, report)| &report.projects)
+            .filter(|report| &report.id == id)
+            .find_map(|report| match &report.outcome {
+                ProjectOutcome::Updated { from, to } | ProjectOutcome::Recreated { from, to }
+                    if to == sha =>
+                {
+                    Some(from.clone())
+                }
+                _ => None,
+            });
+        Ok(from)
+    }
+}
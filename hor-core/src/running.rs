@@ -0,0 +1,421 @@
+use std::{
+    collections::{BTreeMap, HashSet},
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use hor_registry::{LabelSelector, ProjectId, Registry};
+use hor_state::{
+    ApiUsage, Deletion, DriftReport, JobId, StateStoreRef, SyncJob, SyncReport, SyncTarget,
+};
+use tokio::sync::watch;
+use tracing::{debug, error};
+
+use crate::{
+    drift::DriftReports,
+    polling::PollSchedule,
+    supervisor::{Supervisor, TaskHealth, TaskState},
+    ConnectionStats, DynRegistry, HorSystem, InitializedState, Priority,
+};
+
+const OUTBOX_INTERVAL: Duration = Duration::from_secs(10);
+const JOBS_INTERVAL: Duration = Duration::from_secs(5);
+const PURGE_INTERVAL: Duration = Duration::from_secs(60 * 60);
+
+type DirtySet = Mutex<HashSet<ProjectId>>;
+
+/// A system whose background tasks are live.
+///
+/// The wrapped initialized system is shared with every task. Tasks are
+/// supervised: a task that panics is restarted with backoff, a task that
+/// returns is considered stopped.
+pub struct RunningState<R: ?Sized = DynRegistry> {
+    system: Arc<HorSystem<InitializedState, R>>,
+    supervisor: Supervisor,
+    /// Projects the next scheduled sync covers between full passes
+    dirty: Arc<DirtySet>,
+}
+
+impl<R: Registry + Send + Sync + ?Sized + 'static> HorSystem<InitializedState, R> {
+    /// Spawns the background tasks (sync scheduler, job and outbox workers,
+    /// purges of deleted projects, drift reports if configured) and
+    /// transitions into the running state.
+    pub fn start(self) -> HorSystem<RunningState<R>, R> {
+        let registry = self.registry.clone();
+        let system = Arc::new(self);
+        let dirty: Arc<DirtySet> = Arc::default();
+        let mut supervisor = Supervisor::new(system.clock().clone(), system.redactor().clone());
+
+        {
+            let system = system.clone();
+            let dirty = Arc::clone(&dirty);
+            supervisor.spawn("scheduler", move |shutdown| {
+                run_scheduler(system.clone(), dirty.clone(), shutdown)
+            });
+        }
+        {
+            let system = system.clone();
+            let dirty = Arc::clone(&dirty);
+            supervisor.spawn("jobs", move |shutdown| {
+                run_jobs(system.clone(), dirty.clone(), shutdown)
+            });
+        }
+        {
+            let system = system.clone();
+            supervisor.spawn("outbox", move |shutdown| {
+                run_outbox(system.clone(), shutdown)
+            });
+        }
+        {
+            let system = system.clone();
+            supervisor.spawn("purge", move |shutdown| run_purge(system.clone(), shutdown));
+        }
+        if let Some(reports) = system.state.drift_reports.clone() {
+            let system = system.clone();
+            supervisor.spawn("drift-reports", move |shutdown| {
+                run_drift_reports(system.clone(), reports.clone(), shutdown)
+            });
+        }
+
+        HorSystem {
+            registry,
+            state: RunningState {
+                system,
+                supervisor,
+                dirty,
+            },
+        }
+    }
+}
+
+impl<R: Registry + ?Sized> HorSystem<RunningState<R>, R> {
+    /// Triggers an immediate sync outside of the schedule.
+    pub async fn sync(&self) -> anyhow::Result<SyncReport> {
+        self.sync_filtered(&LabelSelector::default()).await
+    }
+
+    /// Triggers an immediate sync of the projects matching `selector`.
+    /// Projects that hit a conflict or wait for a blue/green flip are
+    /// reconciled again on the next tick.
+    pub async fn sync_filtered(&self, selector: &LabelSelector) -> anyhow::Result<SyncReport> {
+        let report = self.state.system.sync_filtered(selector).await?;
+        lock(&self.state.dirty).extend(report.follow_ups().map(|project| project.id.clone()));
+        Ok(report)
+    }
+
+    /// See [`HorSystem::queue_sync`].
+    pub async fn queue_sync(
+        &self,
+        target: &SyncTarget,
+        idempotency_key: Option<&str>,
+    ) -> anyhow::Result<JobId> {
+        self.state.system.queue_sync(target, idempotency_key).await
+    }
+
+    /// See [`HorSystem::recent_jobs`].
+    pub async fn recent_jobs(&self, limit: usize) -> anyhow::Result<Vec<(JobId, SyncJob)>> {
+        self.state.system.recent_jobs(limit).await
+    }
+
+    pub fn state_store(&self) -> &StateStoreRef {
+        self.state.system.state_store()
+    }
+
+    /// See [`HorSystem::github_connections`].
+    pub fn github_connections(&self) -> ConnectionStats {
+        self.state.system.github_connections()
+    }
+
+    /// Includes the project in the next scheduled sync, e.g. after a
+    /// webhook or a configuration change. Only matters when full passes
+    /// are spaced out with `full-sync-interval-secs`.
+    pub fn mark_dirty(&self, id: ProjectId) {
+        lock(&self.state.dirty).insert(id);
+    }
+
+    /// See [`HorSystem::delete_project`].
+    pub async fn delete_project(
+        &self,
+        id: &ProjectId,
+        reason: Option<&str>,
+    ) -> anyhow::Result<bool> {
+        self.state.system.delete_project(id, reason).await
+    }
+
+    /// See [`HorSystem::restore_project`]; the project is included in the
+    /// next scheduled sync.
+    pub async fn restore_project(&self, id: &ProjectId) -> anyhow::Result<bool> {
+        let restored = self.state.system.restore_project(id).await?;
+        if restored {
+            self.mark_dirty(id.clone());
+        }
+        Ok(restored)
+    }
+
+    /// See [`HorSystem::deleted_projects`].
+    pub async fn deleted_projects(&self) -> anyhow::Result<Vec<(ProjectId, Deletion)>> {
+        self.state.system.deleted_projects().await
+    }
+
+    /// See [`HorSystem::drift_report`].
+    pub async fn drift_report(&self) -> anyhow::Result<DriftReport> {
+        self.state.system.drift_report().await
+    }
+
+    /// See [`HorSystem::api_usage`].
+    pub fn api_usage(&self) -> BTreeMap<ProjectId, ApiUsage> {
+        self.state.system.api_usage()
+    }
+
+    /// See [`HorSystem::invalidate_repository`].
+    pub fn invalidate_repository(&self, owner: &str, repo: Option<&str>) {
+        self.state.system.invalidate_repository(owner, repo);
+    }
+
+    /// See [`HorSystem::invalidate_repository_node`].
+    pub fn invalidate_repository_node(&self, node_id: &str) {
+        self.state.system.invalidate_repository_node(node_id);
+    }
+
+    /// See [`HorSystem::refresh_repositories`].
+    pub fn refresh_repositories(&self) {
+        self.state.system.refresh_repositories();
+    }
+
+    /// Every background task by name, e.g. for a status page.
+    pub fn task_health(&self) -> BTreeMap<&'static str, TaskHealth> {
+        self.state.supervisor.health()
+    }
+
+    /// Whether every background task is running, e.g. for a readiness
+    /// probe; not while one waits to be restarted after a panic.
+    pub fn ready(&self) -> bool {
+        self.task_health()
+            .values()
+            .all(|task| task.state == TaskState::Running)
+    }
+
+    /// Signals every background task to stop, waits for them and hands the
+    /// initialized system back.
+    pub async fn shutdown(self) -> HorSystem<InitializedState, R> {
+        self.state.supervisor.shutdown().await;
+
+        match Arc::try_unwrap(self.state.system) {
+            Ok(system) => system,
+            Err(_) => unreachable!("all background tasks have been joined"),
+        }
+    }
+}
+
+async fn run_scheduler<R: Registry + ?Sized>(
+    system: Arc<HorSystem<InitializedState, R>>,
+    dirty: Arc<DirtySet>,
+    mut shutdown: watch::Receiver<bool>,
+) {
+    let mut interval = tokio::time::interval(system.state.sync_interval);
+    let mut last_full_pass: Option<Instant> = None;
+    let mut schedule = PollSchedule::default();
+    loop {
+        tokio::select! {
+            _ = interval.tick() => {
+                match system.is_leader().await {
+                    Ok(true) => {}
+                    Ok(false) => {
+                        debug!("another instance is the leader, skipping scheduled sync");
+                        continue;
+                    }
+                    Err(err) => {
+                        error!(
+                            err = %system.state.redactor.debug(&err),
+                            "unable to determine leadership"
+                        );
+                        continue;
+                    }
+                }
+
+                let full_pass = match (system.state.full_sync_interval, last_full_pass) {
+                    (Some(every), Some(at)) => at.elapsed() >= every,
+                    _ => true,
+                };
+                // Taken up front so projects marked during the sync aren't lost
+                let mut ids = std::mem::take(&mut *lock(&dirty));
+                if let (false, Some(polling)) = (full_pass, &system.state.adaptive_polling) {
+                    ids.extend(schedule.due(polling, system.state.clock.now()));
+                }
+                let result = if full_pass {
+                    system.sync_with(&LabelSelector::default(), Priority::Poll).await
+                } else if ids.is_empty() {
+                    debug!("no dirty or due projects, skipping scheduled sync");
+                    continue;
+                } else {
+                    system.sync_ids(&ids, Priority::Poll).await
+                };
+
+                match result {
+                    Ok(report) => {
+                        if full_pass {
+                            last_full_pass = Some(Instant::now());
+                        }
+                        if system.state.adaptive_polling.is_some() {
+                            let now = system.state.clock.now();
+                            let state = &system.state;
+                            let store = state.store.as_ref();
+                            schedule.observe(store, &state.redactor, &report, now).await;
+                        }
+                        let mut dirty = lock(&dirty);
+                        for failure in report.failures() {
+                            let outcome = &failure.outcome;
+                            error!(id = %failure.id, ?outcome, "project failed to sync");
+                            // Retried on the next tick rather than the next full pass
+                            dirty.insert(failure.id.clone());
+                        }
+                        dirty.extend(report.follow_ups().map(|project| project.id.clone()));
+                    }
+                    Err(err) => {
+                        error!(err = %system.state.redactor.debug(&err), "scheduled sync failed");
+                        lock(&dirty).extend(ids);
+                    }
+                }
+            }
+            _ = shutdown.changed() => return,
+        }
+    }
+}
+
+fn lock(dirty: &DirtySet) -> std::sync::MutexGuard<'_, HashSet<ProjectId>> {
+    // Inserts and takes can't leave the set half-updated
+    dirty
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+}
+
+async fn run_outbox<R: Registry + ?Sized>(
+    system: Arc<HorSystem<InitializedState, R>>,
+    mut shutdown: watch::Receiver<bool>,
+) {
+    let mut interval = tokio::time::interval(OUTBOX_INTERVAL);
+    loop {
+        tokio::select! {
+            _ = interval.tick() => {
+                if let Err(err) = system.deliver_outbox().await {
+                    error!(err = %system.state.redactor.debug(&err), "outbox delivery failed");
+                }
+            }
+            _ = shutdown.changed() => return,
+        }
+    }
+}
+
+async fn run_drift_reports<R: Registry + ?Sized>(
+    system: Arc<HorSystem<InitializedState, R>>,
+    reports: DriftReports,
+    mut shutdown: watch::Receiver<bool>,
+) {
+    loop {
+        // Counted from now, so a restart doesn't send the day's report again
+        let now = system.state.clock.now();
+        let wait = (reports.next_after(now) - now).to_std().unwrap_or_default();
+        tokio::select! {
+            _ = tokio::time::sleep(wait) => {
+                match system.is_leader().await {
+                    Ok(true) => {}
+                    Ok(false) => {
+                        debug!("another instance is the leader, skipping drift report");
+                        continue;
+                    }
+                    Err(err) => {
+                        error!(
+                            err = %system.state.redactor.debug(&err),
+                            "unable to determine leadership"
+                        );
+                        continue;
+                    }
+                }
+                if let Err(err) = system.send_drift_report(&reports).await {
+                    error!(err = %system.state.redactor.debug(&err), "drift report failed");
+                }
+            }
+            _ = shutdown.changed() => return,
+        }
+    }
+}
+
+async fn run_purge<R: Registry + ?Sized>(
+    system: Arc<HorSystem<InitializedState, R>>,
+    mut shutdown: watch::Receiver<bool>,
+) {
+    let mut interval = tokio::time::interval(PURGE_INTERVAL);
+    loop {
+        tokio::select! {
+            _ = interval.tick() => {
+                match system.is_leader().await {
+                    Ok(true) => {}
+                    Ok(false) => continue,
+                    Err(err) => {
+                        error!(
+                            err = %system.state.redactor.debug(&err),
+                            "unable to determine leadership"
+                        );
+                        continue;
+                    }
+                }
+                if let Err(err) = system.purge_deleted().await {
+                    error!(
+                        err = %system.state.redactor.debug(&err),
+                        "unable to purge deleted projects"
+                    );
+                }
+            }
+            _ = shutdown.changed() => return,
+        }
+    }
+}
+
+async fn run_jobs<R: Registry + ?Sized>(
+    system: Arc<HorSystem<InitializedState, R>>,
+    dirty: Arc<DirtySet>,
+    mut shutdown: watch::Receiver<bool>,
+) {
+    let mut interval = tokio::time::interval(JOBS_INTERVAL);
+    loop {
+        tokio::select! {
+            _ = interval.tick() => {
+                // Only the leader may sync, so only the leader claims jobs
+                match system.is_leader().await {
+                    Ok(true) => {}
+                    Ok(false) => continue,
+                    Err(err) => {
+                        error!(
+                            err = %system.state.redactor.debug(&err),
+                            "unable to determine leadership"
+                        );
+                        continue;
+                    }
+                }
+                // Drained one job at a time, so shutdown is seen in between
+                loop {
+                    match system.run_next_job().await {
+                        Ok(Some((_, Some(report)))) => {
+                            let mut dirty = lock(&dirty);
+                            dirty.extend(report.failures().map(|project| project.id.clone()));
+                            dirty.extend(report.follow_ups().map(|project| project.id.clone()));
+                        }
+                        Ok(Some((_, None))) => {}
+                        Ok(None) => break,
+                        Err(err) => {
+                            error!(
+                                err = %system.state.redactor.debug(&err),
+                                "unable to run queued sync"
+                            );
+                            break;
+                        }
+                    }
+                    if *shutdown.borrow() {
+                        return;
+                    }
+                }
+            }
+            _ = shutdown.changed() => return,
+        }
+    }
+}
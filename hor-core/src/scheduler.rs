@@ -0,0 +1,263 @@
+//! Sequences project syncs across every caller so GitHub sees a bounded
+//! number of concurrent projects and the rate-limit budget is never
+//! exhausted.
+
+use std::{
+    collections::VecDeque,
+    future::Future,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use chrono::{TimeZone, Utc};
+use octocrab::Octocrab;
+use serde::Deserialize;
+use tokio::sync::oneshot;
+use tracing::{debug, warn};
+
+use crate::{ClockRef, Redactor};
+
+/// What a project costs in the bucket. A sync reads the env ref, the
+/// target branch and maybe a handful of policy inputs; policy-heavy
+/// projects go over, which the next refresh corrects.
+const PROJECT_COST: usize = 5;
+
+/// How long to wait for a budget when GitHub can't be asked for one.
+const RETRY_DELAY: Duration = Duration::from_secs(60);
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(rename_all = "kebab-case")]
+pub struct SchedulerConfig {
+    /// Projects synced at the same time
+    #[serde(default = "SchedulerConfig::default_concurrency")]
+    concurrency: usize,
+    /// Requests of the hourly budget left for everything else using the
+    /// token
+    #[serde(default = "SchedulerConfig::default_reserve")]
+    reserve: usize,
+}
+
+impl SchedulerConfig {
+    fn default_concurrency() -> usize {
+        4
+    }
+
+    fn default_reserve() -> usize {
+        500
+    }
+}
+
+impl Default for SchedulerConfig {
+    fn default() -> Self {
+        Self {
+            concurrency: SchedulerConfig::default_concurrency(),
+            reserve: SchedulerConfig::default_reserve(),
+        }
+    }
+}
+
+/// Why a project is being synced. Triggered projects are let through
+/// before any waiting poll.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Priority {
+    /// Requested explicitly, e.g. by a webhook or an operator
+    Triggered,
+    /// Part of the periodic sync
+    Poll,
+}
+
+pub(crate) struct Scheduler {
+    slots: Arc<Slots>,
+    budget: tokio::sync::Mutex<Budget>,
+    github: Octocrab,
+    reserve: usize,
+    /// Times waits for the budget
+    clock: ClockRef,
+    redactor: Redactor,
+}
+
+/// A running project's slot, handed to the next waiter once dropped.
+pub(crate) struct Permit {
+    /// Only `None` while a handoff is being undone, or while the slot is
+    /// released
+    slots: Option<Arc<Slots>>,
+}
+
+struct Slots {
+    state: Mutex<SlotState>,
+}
+
+struct SlotState {
+    available: usize,
+    triggered: VecDeque<oneshot::Sender<Permit>>,
+    polls: VecDeque<oneshot::Sender<Permit>>,
+}
+
+/// Requests believed left until `reset`, a Unix timestamp.
+struct Budget {
+    tokens: usize,
+    reset: i64,
+}
+
+impl Scheduler {
+    pub fn new(
+        config: &SchedulerConfig,
+        github: Octocrab,
+        clock: ClockRef,
+        redactor: Redactor,
+    ) -> Self {
+        Self {
+            slots: Arc::new(Slots {
+                state: Mutex::new(SlotState {
+                    available: config.concurrency.max(1),
+                    triggered: VecDeque::new(),
+                    polls: VecDeque::new(),
+                }),
+            }),
+            // Empty, so the first project looks up the real budget
+            budget: tokio::sync::Mutex::new(Budget {
+                tokens: 0,
+                reset: 0,
+            }),
+            github,
+            reserve: config.reserve,
+            clock,
+            redactor,
+        }
+    }
+
+    /// Follows the system's clock when a test or simulation replaces it.
+    pub fn set_clock(&mut self, clock: ClockRef) {
+        self.clock = clock;
+    }
+
+    /// Waits for a free slot, then for enough rate-limit budget to sync
+    /// one project.
+    pub async fn acquire(&self, priority: Priority) -> Permit {
+        let permit = self.slot(priority).await;
+        self.take_budget().await;
+        permit
+    }
+
+    /// Hands `permit`'s slot to the next waiter while `wait` runs, e.g. a
+    /// sleep, then waits for a slot again. The rate-limit budget taken
+    /// with the permit is kept.
+    pub async fn released_while<T>(
+        &self,
+        permit: &mut Permit,
+        priority: Priority,
+        wait: impl Future<Output = T>,
+    ) -> T {
+        drop(std::mem::replace(permit, Permit { slots: None }));
+        let output = wait.await;
+        *permit = self.slot(priority).await;
+        output
+    }
+
+    async fn slot(&self, priority: Priority) -> Permit {
+        let waiting = {
+            let mut state = self
+                .slots
+                .state
+                .lock()
+                .unwrap_or_else(|poisoned| poisoned.into_inner());
+            // Polls also queue behind triggered projects that are waiting
+            let ahead = match priority {
+                Priority::Triggered => state.triggered.len(),
+                Priority::Poll => state.triggered.len() + state.polls.len(),
+            };
+            if state.available > 0 && ahead == 0 {
+                state.available -= 1;
+                None
+            } else {
+                let (tx, rx) = oneshot::channel();
+                match priority {
+                    Priority::Triggered => state.triggered.push_back(tx),
+                    Priority::Poll => state.polls.push_back(tx),
+                }
+                Some(rx)
+            }
+        };
+        match waiting {
+            None => Permit {
+                slots: Some(self.slots.clone()),
+            },
+            Some(rx) => rx.await.expect("slots outlive their waiters"),
+        }
+    }
+
+    async fn take_budget(&self) {
+        let mut budget = self.budget.lock().await;
+        while budget.tokens < PROJECT_COST {
+            match self.github.ratelimit().get().await {
+                Ok(limit) => {
+                    let core = limit.resources.core;
+                    budget.tokens = core.remaining.saturating_sub(self.reserve);
+                    budget.reset = core.reset as i64;
+                    debug!(
+                        remaining = core.remaining,
+                        "Refreshed GitHub rate-limit budget"
+                    );
+                }
+                Err(err) => {
+                    warn!(
+                        err = %self.redactor.debug(&err),
+                        "Unable to read the GitHub rate limit"
+                    );
+                    self.clock.sleep(RETRY_DELAY).await;
+                    continue;
+                }
+            }
+            if budget.tokens < PROJECT_COST {
+                let reset = Utc
+                    .timestamp_opt(budget.reset, 0)
+                    .single()
+                    .unwrap_or_else(|| self.clock.now());
+                let wait = (reset - self.clock.now())
+                    .to_std()
+                    .unwrap_or(Duration::ZERO);
+                warn!(%reset, "GitHub rate-limit budget exhausted, waiting for the reset");
+                // Still holding the lock, so every other project waits too
+                self.clock.sleep(wait.max(Duration::from_secs(1))).await;
+            }
+        }
+        budget.tokens -= PROJECT_COST;
+    }
+}
+
+impl Drop for Permit {
+    fn drop(&mut self) {
+        let Some(slots) = self.slots.take() else {
+            return;
+        };
+        loop {
+            let next = {
+                let mut state = slots
+                    .state
+                    .lock()
+                    .unwrap_or_else(|poisoned| poisoned.into_inner());
+                match state
+                    .triggered
+                    .pop_front()
+                    .or_else(|| state.polls.pop_front())
+                {
+                    Some(next) => next,
+                    None => {
+                        state.available += 1;
+                        return;
+                    }
+                }
+            };
+            let handoff = Permit {
+                slots: Some(slots.clone()),
+            };
+            match next.send(handoff) {
+                Ok(()) => return,
+                // The waiter gave up; its slot goes to the one after it
+                Err(mut handoff) => {
+                    handoff.slots = None;
+                }
+            }
+        }
+    }
+}
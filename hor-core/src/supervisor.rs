@@ -0,0 +1,161 @@
+//! Owner of every background task: restarts tasks that panic, with
+//! backoff, stops them all on shutdown, and tells how each is doing.
+
+use std::{
+    any::Any,
+    collections::BTreeMap,
+    future::Future,
+    sync::{Arc, Mutex, MutexGuard},
+    time::Duration,
+};
+
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use tokio::{sync::watch, task::JoinHandle};
+use tracing::{error, info};
+
+use crate::{ClockRef, Redactor};
+
+const INITIAL_BACKOFF: Duration = Duration::from_secs(1);
+const MAX_BACKOFF: Duration = Duration::from_secs(5 * 60);
+/// Running this long without a panic resets the backoff
+const STABLE_AFTER: Duration = Duration::from_secs(10 * 60);
+
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum TaskState {
+    Running,
+    /// Panicked, and waiting out its backoff before it's restarted
+    Restarting,
+    /// Returned, or stopped for shutdown
+    Stopped,
+}
+
+/// How a background task is doing.
+#[derive(Serialize, Debug, Clone)]
+#[serde(rename_all = "kebab-case")]
+pub struct TaskHealth {
+    pub state: TaskState,
+    /// Restarts since startup
+    pub restarts: u32,
+    /// Message of the last panic, if it had one
+    pub last_panic: Option<String>,
+    pub last_panic_at: Option<DateTime<Utc>>,
+}
+
+type HealthMap = Mutex<BTreeMap<&'static str, TaskHealth>>;
+
+pub(crate) struct Supervisor {
+    shutdown: watch::Sender<bool>,
+    tasks: Vec<JoinHandle<()>>,
+    health: Arc<HealthMap>,
+    /// Dates panics and times backoffs
+    clock: ClockRef,
+    redactor: Redactor,
+}
+
+impl Supervisor {
+    pub fn new(clock: ClockRef, redactor: Redactor) -> Self {
+        Self {
+            shutdown: watch::channel(false).0,
+            tasks: Vec::new(),
+            health: Arc::default(),
+            clock,
+            redactor,
+        }
+    }
+
+    /// Runs `task` until it returns or shutdown is signalled, starting it
+    /// again whenever it panics. Tasks get the shutdown signal to watch.
+    pub fn spawn<F, Fut>(&mut self, name: &'static str, task: F)
+    where
+        F: Fn(watch::Receiver<bool>) -> Fut + Send + 'static,
+        Fut: Future<Output = ()> + Send + 'static,
+    {
+        let mut shutdown = self.shutdown.subscribe();
+        let health = Arc::clone(&self.health);
+        let clock = self.clock.clone();
+        update(&health, name, |_| {});
+        self.tasks.push(tokio::spawn(async move {
+            let mut backoff = INITIAL_BACKOFF;
+            loop {
+                let started = tokio::time::Instant::now();
+                let err = match tokio::spawn(task(shutdown.clone())).await {
+                    Err(err) if err.is_panic() && !*shutdown.borrow() => err,
+                    _ => break,
+                };
+                if started.elapsed() >= STABLE_AFTER {
+                    backoff = INITIAL_BACKOFF;
+                }
+                let message = panic_message(err.into_panic());
+                error!(
+                    task = name,
+                    panic = message.as_deref(),
+                    ?backoff,
+                    "background task panicked, restarting"
+                );
+                update(&health, name, |task| {
+                    task.state = TaskState::Restarting;
+                    task.restarts += 1;
+                    task.last_panic = message;
+                    task.last_panic_at = Some(clock.now());
+                });
+                tokio::select! {
+                    _ = clock.sleep(backoff) => {}
+                    _ = shutdown.changed() => break,
+                }
+                backoff = (backoff * 2).min(MAX_BACKOFF);
+                update(&health, name, |task| task.state = TaskState::Running);
+            }
+            update(&health, name, |task| task.state = TaskState::Stopped);
+            info!(task = name, "background task stopped");
+        }));
+    }
+
+    /// Every task by name.
+    pub fn health(&self) -> BTreeMap<&'static str, TaskHealth> {
+        lock(&self.health).clone()
+    }
+
+    /// Signals every task to stop and waits for them.
+    pub async fn shutdown(self) {
+        // Receivers only go away once every task has already stopped
+        let _ = self.shutdown.send(true);
+        for task in self.tasks {
+            if let Err(err) = task.await {
+                error!(
+                    err = %self.redactor.debug(&err),
+                    "supervisor task failed to stop cleanly"
+                );
+            }
+        }
+    }
+}
+
+fn update(health: &HealthMap, name: &'static str, change: impl FnOnce(&mut TaskHealth)) {
+    let mut health = lock(health);
+    let task = health.entry(name).or_insert(TaskHealth {
+        state: TaskState::Running,
+        restarts: 0,
+        last_panic: None,
+        last_panic_at: None,
+    });
+    change(task);
+}
+
+fn lock(health: &HealthMap) -> MutexGuard<'_, BTreeMap<&'static str, TaskHealth>> {
+    // Every update leaves a complete entry behind
+    health
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+}
+
+/// What a task panicked with, if it was a message.
+fn panic_message(panic: Box<dyn Any + Send>) -> Option<String> {
+    match panic.downcast::<String>() {
+        Ok(message) => Some(*message),
+        Err(panic) => panic
+            .downcast_ref::<&str>()
+            .map(|message| message.to_string()),
+    }
+}
@@ -0,0 +1,98 @@
+//! Release trains: the branch is released only as it was at fixed
+//! departure times, and each departure is announced with its manifest.
+
+use anyhow::Context;
+use chrono::{DateTime, Datelike, Duration, Utc};
+use hor_registry::{ProjectId, Registry, ReleaseTrain};
+use hor_state::Event;
+
+use crate::{
+    actions::Promotion, events, github::HorOctocrabExtension, HorSystem, InitializedState,
+};
+
+impl<R: Registry + ?Sized> HorSystem<InitializedState, R> {
+    /// The newest commit of `branch` that predates the latest departure,
+    /// or why none boards. The same commit is picked until the next
+    /// departure, so commits landing in between wait for it.
+    pub(crate) async fn board_train(
+        &self,
+        train: &ReleaseTrain,
+        owner: &str,
+        repo: &str,
+        branch: &str,
+    ) -> anyhow::Result<Result<String, String>> {
+        let Some(departure) = last_departure(train, self.state.clock.now()) else {
+            return Ok(Err("no release train has departed yet".to_string()));
+        };
+        let commits = self
+            .state
+            .read_octo
+            .recent_commits(owner, repo, branch)
+            .await
+            .with_context(|| format!("Unable to list commits of {branch}"))?;
+        // Merges and squashes are committed when they land, so the
+        // committer date tells what was on the branch at the departure
+        let boarded = commits.into_iter().find(|commit| {
+            commit
+                .commit
+                .committer
+                .as_ref()
+                .is_some_and(|committer| committer.date <= departure)
+        });
+        Ok(match boarded {
+            Some(commit) => Ok(commit.sha),
+            None => Err(format!(
+                "no recent commit of {branch} predates the train of {}",
+                departure.format("%a %H:%M UTC")
+            )),
+        })
+    }
+
+    /// Queues the manifest of the train that released `promotion`.
+    pub(crate) async fn announce_train(
+        &self,
+        id: &ProjectId,
+        train: &ReleaseTrain,
+        promotion: &Promotion<'_>,
+    ) -> anyhow::Result<()> {
+        let project = promotion.project;
+        let (owner, repo) = (project.owner.as_str(), project.repo.as_str());
+        let commits = self.released_commits(promotion).await?;
+        let now = self.state.clock.now();
+        events::enqueue(
+            self.state.store.as_ref(),
+            &self.state.sinks,
+            now,
+            Event::TrainDeparted {
+                project: id.clone(),
+                owner: owner.to_string(),
+                repo: repo.to_string(),
+                env: project.env.clone(),
+                departure: last_departure(train, now).unwrap_or(now),
+                from: promotion.from.map(str::to_string),
+                to: promotion.to.to_string(),
+                commits,
+                at: now,
+                simulated: self.state.shadow,
+            },
+        )
+        .await
+        .context("Unable to queue train departed event")
+    }
+}
+
+/// The latest departure at or before `now`, looking back a week.
+fn last_departure(train: &ReleaseTrain, now: DateTime<Utc>) -> Option<DateTime<Utc>> {
+    (0..=7).find_map(|days_back| {
+        let date = now.date_naive() - Duration::days(days_back);
+        if !train.days.is_empty() && !train.days.contains(&date.weekday()) {
+            return None;
+        }
+        train
+            .departures
+            .iter()
+            .map(|time| date.and_time(*time).and_utc())
+            .filter(|departure| *departure <= now)
+            .max()
+    })
+}
@@ -0,0 +1,48 @@
+//! GitHub API usage by project: every request sent while a project is
+//! synced is counted against it, however deep in the stack it's sent.
+
+use std::{
+    future::Future,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+};
+
+use hor_state::ApiUsage;
+use http::StatusCode;
+
+tokio::task_local! {
+    static METER: Arc<Meter>;
+}
+
+#[derive(Default)]
+struct Meter {
+    requests: AtomicU64,
+    points: AtomicU64,
+}
+
+/// Runs `future`, counting the GitHub requests it sends.
+pub(crate) async fn metered<T>(future: impl Future<Output = T>) -> (T, ApiUsage) {
+    let meter = Arc::new(Meter::default());
+    let output = METER.scope(meter.clone(), future).await;
+    let usage = ApiUsage {
+        requests: meter.requests.load(Ordering::Relaxed),
+        points: meter.points.load(Ordering::Relaxed),
+    };
+    (output, usage)
+}
+
+/// Counts a request against whatever is being metered, given its status
+/// if it got a response. Conditional requests answered 304 don't count
+/// against the rate limit.
+pub(crate) fn record(status: Option<StatusCode>) {
+    // Requests outside of a project, e.g. listing an owner's repos, aren't
+    // counted against any
+    let _ = METER.try_with(|meter| {
+        meter.requests.fetch_add(1, Ordering::Relaxed);
+        if status.is_some_and(|status| status != StatusCode::NOT_MODIFIED) {
+            meter.points.fetch_add(1, Ordering::Relaxed);
+        }
+    });
+}
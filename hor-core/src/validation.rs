@@ -0,0 +1,229 @@
+//! Checks of the registered projects against GitHub, run when they are
+//! loaded so a typo or a missing grant shows up before the first sync
+//! fails on it.
+
+use std::{
+    future::Future,
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
+use futures::future::join_all;
+use hor_registry::{GithubOwnerProject, GithubProject, Registry, SourceProject};
+use tracing::info;
+
+use crate::{
+    github::{HorOctocrabExtension, RefLookup},
+    HorSystem, InitializedState, Priority,
+};
+
+/// Registered projects between progress updates while validating.
+const PROGRESS_EVERY: usize = 50;
+
+#[derive(thiserror::Error, Debug)]
+#[non_exhaustive]
+pub enum ProjectValidationError {
+    #[error("{owner}/{repo} does not exist or the read token can't see it")]
+    RepositoryNotFound { owner: String, repo: String },
+    #[error("the write token can't push to {owner}/{repo}; grant it write access to contents")]
+    CannotWriteRefs { owner: String, repo: String },
+    #[error("{owner}/{repo} has no default branch; push a first commit")]
+    NoDefaultBranch { owner: String, repo: String },
+    #[error("default branch {branch} of {owner}/{repo} does not exist")]
+    BranchMissing {
+        owner: String,
+        repo: String,
+        branch: String,
+    },
+    #[error("no repository of {owner} matches {pattern}")]
+    NoMatchingRepositories { owner: String, pattern: String },
+    #[error("unable to list repositories of {owner}")]
+    Owner {
+        owner: String,
+        #[source]
+        source: anyhow::Error,
+    },
+    #[error("unable to validate {owner}/{repo} against GitHub")]
+    Github {
+        owner: String,
+        repo: String,
+        #[source]
+        source: octocrab::Error,
+    },
+    #[error("project type currently not supported")]
+    Unsupported,
+}
+
+impl<R: Registry + ?Sized> HorSystem<InitializedState, R> {
+    /// Checks that every registered project's repository exists, that the
+    /// write token can move its refs and that its default branch exists.
+    /// Owner entries are checked to match at least one repository, not
+    /// repository by repository, which would cost a sync's worth of
+    /// requests. Checks take scheduler slots like syncs do, so a large
+    /// registry is checked within the configured concurrency and budget.
+    pub async fn validate_projects(&self) -> Vec<ProjectValidationError> {
+        let projects = self.registry.get_projects();
+        let total = projects.len();
+        let checked = &AtomicUsize::new(0);
+        let checks = projects.iter().map(|project| async move {
+            let errors = match project {
+                // Regions may be written to by different installations
+                SourceProject::Github(project) => join_all(
+                    project
+                        .expand_regions()
+                        .iter()
+                        .map(|project| self.in_slot(self.validate_github(project))),
+                )
+                .await
+                .into_iter()
+                .filter_map(Result::err)
+                .collect(),
+                SourceProject::GithubOwner(owner) => self
+                    .in_slot(self.validate_github_owner(owner))
+                    .await
+                    .err()
+                    .into_iter()
+                    .collect(),
+                _ => vec![ProjectValidationError::Unsupported],
+            };
+            let checked = checked.fetch_add(1, Ordering::Relaxed) + 1;
+            if checked.is_multiple_of(PROGRESS_EVERY) {
+                info!(checked, total, "Validating projects");
+            }
+            errors
+        });
+        join_all(checks).await.into_iter().flatten().collect()
+    }
+
+    async fn in_slot<T>(&self, check: impl Future<Output = T>) -> T {
+        let _permit = self.state.scheduler.acquire(Priority::Poll).await;
+        check.await
+    }
+
+    async fn validate_github(&self, project: &GithubProject) -> Result<(), ProjectValidationError> {
+        let owner = project.owner.as_str();
+        let repo = project.repo.as_str();
+        let github = |source| ProjectValidationError::Github {
+            owner: owner.to_string(),
+            repo: repo.to_string(),
+            source,
+        };
+
+        let Some(repository) = self
+            .state
+            .read_octo
+            .repository(owner, repo)
+            .await
+            .map_err(github)?
+        else {
+            return Err(ProjectValidationError::RepositoryNotFound {
+                owner: owner.to_string(),
+                repo: repo.to_string(),
+            });
+        };
+        // Forks and mirrors track their upstream's branch, not their own
+        match &project.upstream {
+            Some(upstream) => {
+                self.validate_branch(&upstream.owner, &upstream.repo, upstream.branch.clone())
+                    .await?
+            }
+            None => {
+                let Some(branch) = repository.default_branch else {
+                    return Err(ProjectValidationError::NoDefaultBranch {
+                        owner: owner.to_string(),
+                        repo: repo.to_string(),
+                    });
+                };
+                self.validate_branch(owner, repo, Some(branch)).await?
+            }
+        }
+
+        // Permissions are those of the token asking, hence the write client
+        let written = self
+            .state
+            .writer(&project.env)
+            .repository(owner, repo)
+            .await
+            .map_err(github)?;
+        match written.map(|repository| repository.permissions) {
+            // App installation tokens get no permissions here; the first
+            // sync will tell
+            Some(None) => Ok(()),
+            Some(Some(permissions)) if permissions.push => Ok(()),
+            _ => Err(ProjectValidationError::CannotWriteRefs {
+                owner: owner.to_string(),
+                repo: repo.to_string(),
+            }),
+        }
+    }
+
+    /// Checks that `branch` of `owner/repo` exists, its default branch if
+    /// `None`.
+    async fn validate_branch(
+        &self,
+        owner: &str,
+        repo: &str,
+        branch: Option<String>,
+    ) -> Result<(), ProjectValidationError> {
+        let github = |source| ProjectValidationError::Github {
+            owner: owner.to_string(),
+            repo: repo.to_string(),
+            source,
+        };
+        let branch = match branch {
+            Some(branch) => branch,
+            None => {
+                let Some(repository) = self
+                    .state
+                    .read_octo
+                    .repository(owner, repo)
+                    .await
+                    .map_err(github)?
+                else {
+                    return Err(ProjectValidationError::RepositoryNotFound {
+                        owner: owner.to_string(),
+                        repo: repo.to_string(),
+                    });
+                };
+                repository.default_branch.ok_or_else(|| {
+                    ProjectValidationError::NoDefaultBranch {
+                        owner: owner.to_string(),
+                        repo: repo.to_string(),
+                    }
+                })?
+            }
+        };
+        match self
+            .state
+            .read_octo
+            .lookup_ref(owner, repo, &format!("heads/{branch}"), None)
+            .await
+            .map_err(github)?
+        {
+            RefLookup::Missing => Err(ProjectValidationError::BranchMissing {
+                owner: owner.to_string(),
+                repo: repo.to_string(),
+                branch,
+            }),
+            RefLookup::Found { .. } | RefLookup::Unchanged => Ok(()),
+        }
+    }
+
+    async fn validate_github_owner(
+        &self,
+        owner: &GithubOwnerProject,
+    ) -> Result<(), ProjectValidationError> {
+        let projects = self.expand_github_owner(owner).await.map_err(|source| {
+            ProjectValidationError::Owner {
+                owner: owner.owner.clone(),
+                source,
+            }
+        })?;
+        if projects.is_empty() {
+            return Err(ProjectValidationError::NoMatchingRepositories {
+                owner: owner.owner.clone(),
+                pattern: owner.repos.clone(),
+            });
+        }
+        Ok(())
+    }
+}
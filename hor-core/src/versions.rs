@@ -0,0 +1,157 @@
+//! Semantic versions and the conventional commits that move them, shared
+//! by version bump pull requests and release tags.
+
+use std::fmt;
+
+use anyhow::bail;
+use regex::Regex;
+
+/// How far a release moves the version, least first.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub(crate) enum Bump {
+    Patch,
+    Minor,
+    Major,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub(crate) struct Version {
+    pub major: u64,
+    pub minor: u64,
+    pub patch: u64,
+}
+
+/// The largest bump the conventional commits among `messages` call for,
+/// `None` if none calls for a release.
+pub(crate) fn conventional_bump(messages: &[String]) -> Option<Bump> {
+    messages.iter().filter_map(|message| bump(message)).max()
+}
+
+/// What a conventional commit calls for, `None` for types that don't
+/// release anything, e.g. `chore` or `docs`, and for other commits.
+fn bump(message: &str) -> Option<Bump> {
+    let header = Regex::new(r"^(\w+)(\([^)]*\))?(!)?:").expect("valid regex");
+    let captures = header.captures(message.lines().next()?)?;
+    let breaking = captures.get(3).is_some()
+        || message.lines().any(|line| {
+            line.starts_with("BREAKING CHANGE:") || line.starts_with("BREAKING-CHANGE:")
+        });
+    match &captures[1] {
+        _ if breaking => Some(Bump::Major),
+        "feat" => Some(Bump::Minor),
+        "fix" | "perf" => Some(Bump::Patch),
+        _ => None,
+    }
+}
+
+impl Version {
+    pub fn parse(version: &str) -> anyhow::Result<Version> {
+        let parts: Vec<_> = version.split('.').map(str::parse::<u64>).collect();
+        match parts.as_slice() {
+            [Ok(major), Ok(minor), Ok(patch)] => Ok(Version {
+                major: *major,
+                minor: *minor,
+                patch: *patch,
+            }),
+            _ => bail!("{version} is not a MAJOR.MINOR.PATCH version"),
+        }
+    }
+
+    pub fn bump(self, bump: Bump) -> Version {
+        // Before 1.0, breaking changes bump the minor version, as Cargo and
+        // npm read it
+        let bump = match (bump, self.major) {
+            (Bump::Major, 0) => Bump::Minor,
+            (bump, _) => bump,
+        };
+        match bump {
+            Bump::Major => Version {
+                major: self.major + 1,
+                minor: 0,
+                patch: 0,
+            },
+            Bump::Minor => Version {
+                minor: self.minor + 1,
+                patch: 0,
+                ..self
+            },
+            Bump::Patch => Version {
+                patch: self.patch + 1,
+                ..self
+            },
+        }
+    }
+}
+
+impl fmt::Display for Version {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}.{}.{}", self.major, self.minor, self.patch)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn version(version: &str) -> Version {
+        Version::parse(version).unwrap()
+    }
+
+    fn messages(messages: &[&str]) -> Vec<String> {
+        messages.iter().map(|message| message.to_string()).collect()
+    }
+
+    #[test]
+    fn parses_and_displays_versions() {
+        assert_eq!(version("1.12.3").to_string(), "1.12.3");
+        assert!(Version::parse("1.2").is_err());
+        assert!(Version::parse("v1.2.3").is_err());
+        assert!(Version::parse("1.2.3-rc.1").is_err());
+    }
+
+    #[test]
+    fn bumps_reset_lower_parts() {
+        assert_eq!(version("1.2.3").bump(Bump::Patch), version("1.2.4"));
+        assert_eq!(version("1.2.3").bump(Bump::Minor), version("1.3.0"));
+        assert_eq!(version("1.2.3").bump(Bump::Major), version("2.0.0"));
+    }
+
+    #[test]
+    fn breaking_changes_bump_the_minor_version_before_1_0() {
+        assert_eq!(version("0.4.1").bump(Bump::Major), version("0.5.0"));
+        assert_eq!(version("0.4.1").bump(Bump::Patch), version("0.4.2"));
+    }
+
+    #[test]
+    fn takes_the_largest_conventional_bump() {
+        assert_eq!(
+            conventional_bump(&messages(&["fix: typo", "feat(api): add endpoint"])),
+            Some(Bump::Minor)
+        );
+        assert_eq!(
+            conventional_bump(&messages(&["perf: faster", "chore: deps"])),
+            Some(Bump::Patch)
+        );
+        assert_eq!(
+            conventional_bump(&messages(&["chore: deps", "docs: readme", "Merge branch"])),
+            None
+        );
+        assert_eq!(conventional_bump(&[]), None);
+    }
+
+    #[test]
+    fn breaking_changes_are_major_whatever_their_type() {
+        assert_eq!(
+            conventional_bump(&messages(&["refactor(core)!: drop v1"])),
+            Some(Bump::Major)
+        );
+        assert_eq!(
+            conventional_bump(&messages(&["fix: config\n\nBREAKING CHANGE: renamed"])),
+            Some(Bump::Major)
+        );
+        assert_eq!(
+            conventional_bump(&messages(&["feat: x\n\nBREAKING-CHANGE: y"])),
+            Some(Bump::Major)
+        );
+    }
+}
@@ -0,0 +1,558 @@
+use std::collections::BTreeMap;
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+/// Something to do once a project's env ref has moved. Credentials live
+/// in the system configuration; actions only say what to touch.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(tag = "kind", rename_all = "kebab-case")]
+#[non_exhaustive]
+pub enum PostSyncAction {
+    /// Points an ArgoCD Application at the released revision
+    Argocd(ArgoCdAction),
+    /// Points a Flux GitRepository at the released revision and requests
+    /// an immediate reconcile
+    Flux(FluxAction),
+    /// Opens a pull request bumping the released revision in a file of
+    /// another (GitOps) repository
+    GitopsPullRequest(GitopsPullRequestAction),
+    /// Opens a pull request bumping the project's version file to the next
+    /// version, told by the conventional commits released
+    VersionBump(VersionBumpAction),
+    /// Points a submodule of a parent (meta) repository at the released
+    /// commit, through a pull request or a commit to its branch
+    SubmoduleBump(SubmoduleBumpAction),
+    /// Queues a Terraform Cloud run with the released revision as a
+    /// variable
+    TerraformCloud(TerraformCloudAction),
+    /// Transitions the Jira issues referenced by the released commits
+    Jira(JiraAction),
+    /// Labels or comments on the Linear issues referenced by the released
+    /// commits
+    Linear(LinearAction),
+    /// Sends a Datadog event tagged with service, env and version
+    Datadog(DatadogAction),
+    /// Publishes the released commits as a Confluence or Notion page
+    ReleaseNotes(ReleaseNotesAction),
+    /// Creates a Kubernetes Job from a manifest template
+    KubernetesJob(KubernetesJobAction),
+    /// Dispatches a GitHub Actions workflow, e.g. of a deploy repository
+    WorkflowDispatch(WorkflowDispatchAction),
+    /// POSTs a payload to a webhook of the integrations config
+    Webhook(WebhookAction),
+    /// Puts an event on an AWS EventBridge bus
+    Eventbridge(EventBridgeAction),
+    /// Publishes a message to a GCP Pub/Sub topic
+    PubSub(PubSubAction),
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+#[jsm::public]
+struct ArgoCdAction {
+    application: String,
+    /// Namespace of the Application, if not the ArgoCD control plane's
+    #[serde(default)]
+    app_namespace: Option<String>,
+    #[serde(default)]
+    revision: Revision,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+#[jsm::public]
+struct FluxAction {
+    namespace: String,
+    git_repository: String,
+    #[serde(default)]
+    revision: Revision,
+}
+
+/// `path`, `commit-message` and `branch` are templates over `{owner}`,
+/// `{repo}`, `{env}`, `{sha}` and `{short-sha}` of the released project.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+#[jsm::public]
+struct GitopsPullRequestAction {
+    owner: String,
+    repo: String,
+    /// Branch the pull request targets, the default branch if unset
+    #[serde(default)]
+    base: Option<String>,
+    path: String,
+    /// Regex whose first capture group is replaced by the revision, e.g.
+    /// `tag: "?([0-9a-f]{40})"?`
+    pattern: String,
+    #[serde(default = "GitopsPullRequestAction::default_commit_message")]
+    commit_message: String,
+    #[serde(default = "GitopsPullRequestAction::default_branch")]
+    branch: String,
+    #[serde(default)]
+    revision: Revision,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+#[jsm::public]
+struct VersionBumpAction {
+    /// Version file in the project's repository, e.g. `Cargo.toml`
+    path: String,
+    /// Told by the file name if unset
+    #[serde(default)]
+    format: Option<VersionFormat>,
+    /// Branch the pull request targets, the default branch if unset
+    #[serde(default)]
+    base: Option<String>,
+    /// Also takes `{version}`
+    #[serde(default = "VersionBumpAction::default_commit_message")]
+    commit_message: String,
+    /// Also takes `{version}`
+    #[serde(default = "VersionBumpAction::default_branch")]
+    branch: String,
+}
+
+/// `commit-message` and `branch` are templates over `{owner}`, `{repo}`,
+/// `{env}`, `{sha}` and `{short-sha}` of the released project.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+#[jsm::public]
+struct SubmoduleBumpAction {
+    /// The parent repository
+    owner: String,
+    repo: String,
+    /// Where the submodule is checked out in the parent, e.g. `vendor/app`
+    path: String,
+    /// Branch of the parent to bump, the default branch if unset
+    #[serde(default)]
+    base: Option<String>,
+    /// Commit straight to `base` rather than opening a pull request; only
+    /// fast-forwards, so a concurrent push fails the action
+    #[serde(default)]
+    push: bool,
+    #[serde(default = "SubmoduleBumpAction::default_commit_message")]
+    commit_message: String,
+    #[serde(default = "SubmoduleBumpAction::default_branch")]
+    branch: String,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum VersionFormat {
+    /// The `version` of the `[package]` table
+    Cargo,
+    /// The top-level `version`
+    PackageJson,
+    /// Nothing but the version, e.g. a `VERSION` file
+    Plain,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+#[jsm::public]
+struct TerraformCloudAction {
+    /// Workspace id, e.g. `ws-abc123`
+    workspace_id: String,
+    /// Terraform variable the revision is passed in
+    #[serde(default = "TerraformCloudAction::default_variable")]
+    variable: String,
+    #[serde(default)]
+    revision: Revision,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+#[jsm::public]
+struct JiraAction {
+    /// Name of the site under the Jira integration
+    site: String,
+    /// Transition to apply, by its name or its target status, e.g. `Done`
+    transition: String,
+    /// Jira project keys to consider, every key if empty
+    #[serde(default)]
+    projects: Vec<String>,
+}
+
+/// `title` is a template over `{owner}`, `{repo}`, `{env}`, `{sha}` and
+/// `{short-sha}`.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+#[jsm::public]
+struct ReleaseNotesAction {
+    #[serde(flatten)]
+    destination: ReleaseNotesDestination,
+    #[serde(default = "ReleaseNotesAction::default_title")]
+    title: String,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(tag = "to", rename_all = "kebab-case")]
+pub enum ReleaseNotesDestination {
+    /// A page in a space of a site under the Confluence integration
+    Confluence {
+        site: String,
+        /// Space key, e.g. `REL`
+        space: String,
+        /// Id of the page to create the notes under
+        #[serde(default)]
+        parent: Option<String>,
+    },
+    /// A page in a Notion database shared with the integration
+    Notion {
+        database: String,
+        /// The database's title property
+        #[serde(default = "ReleaseNotesDestination::default_title_property")]
+        title_property: String,
+    },
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+#[jsm::public]
+struct LinearAction {
+    /// Label added to each issue, e.g. `shipped`
+    #[serde(default)]
+    label: Option<String>,
+    /// Comment template over `{owner}`, `{repo}`, `{env}`, `{sha}` and
+    /// `{short-sha}`
+    #[serde(default)]
+    comment: Option<String>,
+    /// Team keys to consider, every key if empty
+    #[serde(default)]
+    teams: Vec<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+#[jsm::public]
+struct DatadogAction {
+    /// Name of the account under the Datadog integration
+    #[serde(default = "DatadogAction::default_account")]
+    account: String,
+    /// Datadog service the project deploys as
+    service: String,
+    /// Extra `key:value` tags
+    #[serde(default)]
+    tags: Vec<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+#[jsm::public]
+struct KubernetesJobAction {
+    namespace: String,
+    /// Path of a Job manifest, templated over `{owner}`, `{repo}`,
+    /// `{env}`, `{sha}` and `{short-sha}`
+    template: String,
+}
+
+/// Input values are templates over `{owner}`, `{repo}`, `{env}`, `{sha}`
+/// and `{short-sha}` of the released project.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+#[jsm::public]
+struct WorkflowDispatchAction {
+    owner: String,
+    repo: String,
+    /// File name or id of the workflow, e.g. `deploy.yml`; it needs a
+    /// `workflow_dispatch` trigger
+    workflow: String,
+    /// Branch or tag to run the workflow at, the default branch if unset
+    #[serde(default, rename = "ref")]
+    git_ref: Option<String>,
+    #[serde(default)]
+    inputs: BTreeMap<String, String>,
+}
+
+/// Strings anywhere in `payload` are templates over `{owner}`, `{repo}`,
+/// `{env}`, `{sha}` and `{short-sha}`; so are those of the payloads of
+/// the EventBridge and Pub/Sub actions.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+#[jsm::public]
+struct WebhookAction {
+    /// Name of the webhook under the integrations config, which holds its
+    /// URL and secrets
+    webhook: String,
+    /// The released project's owner, repo, env and SHA if unset
+    #[serde(default)]
+    payload: Option<Value>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+#[jsm::public]
+struct EventBridgeAction {
+    /// Name of the account under the AWS integration
+    #[serde(default = "EventBridgeAction::default_account")]
+    account: String,
+    /// Name or ARN of the event bus
+    #[serde(default = "EventBridgeAction::default_bus")]
+    event_bus: String,
+    #[serde(default = "EventBridgeAction::default_source")]
+    source: String,
+    #[serde(default = "EventBridgeAction::default_detail_type")]
+    detail_type: String,
+    /// The released project's owner, repo, env and SHA if unset
+    #[serde(default)]
+    detail: Option<Value>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+#[jsm::public]
+struct PubSubAction {
+    /// GCP project of the topic
+    project: String,
+    topic: String,
+    /// Templated like the payload
+    #[serde(default)]
+    attributes: BTreeMap<String, String>,
+    /// Published as JSON; the released project's owner, repo, env and SHA
+    /// if unset
+    #[serde(default)]
+    data: Option<Value>,
+}
+
+/// A Statuspage maintenance opened while the env ref moves, covering
+/// the given components.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+#[jsm::public]
+struct StatuspageMaintenance {
+    page_id: String,
+    #[serde(default)]
+    component_ids: Vec<String>,
+    /// Template over `{owner}`, `{repo}`, `{env}`, `{sha}` and `{short-sha}`
+    #[serde(default = "StatuspageMaintenance::default_name")]
+    name: String,
+}
+
+/// A health check polled once the env ref moved and the actions ran. If
+/// it fails too often within the window, the ref is moved back.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+#[jsm::public]
+struct Verification {
+    /// Fetched with GET, any 2xx being healthy; templated over `{owner}`,
+    /// `{repo}`, `{env}`, `{sha}` and `{short-sha}`
+    url: String,
+    /// How long the release is watched for
+    #[serde(default = "Verification::default_window_secs")]
+    window_secs: u64,
+    #[serde(default = "Verification::default_interval_secs")]
+    interval_secs: u64,
+    /// Unhealthy polls in a row that fail the release
+    #[serde(default = "Verification::default_failures")]
+    failures: u32,
+    /// Opened once a failed release was rolled back; not used by
+    /// blue/green confirmations, which never roll back
+    #[serde(default)]
+    incident: Option<RollbackIncident>,
+}
+
+/// A record of a rollback for people to follow up on, listing the rolled
+/// back commits and their authors.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(tag = "kind", rename_all = "kebab-case")]
+#[non_exhaustive]
+pub enum RollbackIncident {
+    /// Issue in the project's repository, mentioning the authors
+    GithubIssue(GithubIssueIncident),
+    /// Incident triggered through the PagerDuty Events API
+    PagerDuty(PagerDutyIncident),
+}
+
+/// `title` and `body` are templates over `{owner}`, `{repo}`, `{env}`,
+/// `{sha}` and `{short-sha}` of the rolled back release, `{from}` it was
+/// rolled back to, `{reason}` and `{commits}`, a list of the rolled back
+/// commits with their authors mentioned.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+#[jsm::public]
+struct GithubIssueIncident {
+    #[serde(default = "GithubIssueIncident::default_title")]
+    title: String,
+    #[serde(default = "GithubIssueIncident::default_body")]
+    body: String,
+    #[serde(default)]
+    labels: Vec<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+#[jsm::public]
+struct PagerDutyIncident {
+    /// Name of the service under the integrations config, which holds its
+    /// routing key
+    service: String,
+    /// `critical`, `error`, `warning` or `info`
+    #[serde(default = "PagerDutyIncident::default_severity")]
+    severity: String,
+}
+
+/// Releases through a `{env}-blue` and a `{env}-green` tag: the idle color
+/// is moved to the target first, and the env tag follows once that color
+/// is confirmed, at the earliest on the next sync.
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+#[jsm::public]
+struct BlueGreen {
+    /// Health check of the idle color that must pass before the flip; its
+    /// URL may also use `{color}`
+    #[serde(default)]
+    confirm: Option<Verification>,
+}
+
+/// How a released commit is referred to downstream.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum Revision {
+    /// The commit SHA
+    #[default]
+    Sha,
+    /// The env tag, which now points at the commit
+    Tag,
+}
+
+impl PostSyncAction {
+    /// Short name used in logs and reports.
+    pub fn name(&self) -> &'static str {
+        match self {
+            PostSyncAction::Argocd(_) => "argocd",
+            PostSyncAction::Flux(_) => "flux",
+            PostSyncAction::GitopsPullRequest(_) => "gitops-pull-request",
+            PostSyncAction::VersionBump(_) => "version-bump",
+            PostSyncAction::SubmoduleBump(_) => "submodule-bump",
+            PostSyncAction::TerraformCloud(_) => "terraform-cloud",
+            PostSyncAction::Jira(_) => "jira",
+            PostSyncAction::Linear(_) => "linear",
+            PostSyncAction::Datadog(_) => "datadog",
+            PostSyncAction::ReleaseNotes(_) => "release-notes",
+            PostSyncAction::KubernetesJob(_) => "kubernetes-job",
+            PostSyncAction::WorkflowDispatch(_) => "workflow-dispatch",
+            PostSyncAction::Webhook(_) => "webhook",
+            PostSyncAction::Eventbridge(_) => "eventbridge",
+            PostSyncAction::PubSub(_) => "pub-sub",
+        }
+    }
+}
+
+impl GitopsPullRequestAction {
+    fn default_commit_message() -> String {
+        "Release {owner}/{repo} {short-sha} to {env}".to_string()
+    }
+
+    fn default_branch() -> String {
+        "hor/{env}/{owner}-{repo}-{short-sha}".to_string()
+    }
+}
+
+impl VersionBumpAction {
+    fn default_commit_message() -> String {
+        "Release {version}".to_string()
+    }
+
+    fn default_branch() -> String {
+        "hor/version-{version}".to_string()
+    }
+}
+
+impl SubmoduleBumpAction {
+    fn default_commit_message() -> String {
+        "Bump {repo} to {short-sha}".to_string()
+    }
+
+    fn default_branch() -> String {
+        "hor/submodule/{owner}-{repo}-{short-sha}".to_string()
+    }
+}
+
+impl ReleaseNotesAction {
+    fn default_title() -> String {
+        "{repo} {short-sha} released to {env}".to_string()
+    }
+}
+
+impl ReleaseNotesDestination {
+    fn default_title_property() -> String {
+        "Name".to_string()
+    }
+}
+
+impl TerraformCloudAction {
+    fn default_variable() -> String {
+        "release_sha".to_string()
+    }
+}
+
+impl DatadogAction {
+    fn default_account() -> String {
+        "default".to_string()
+    }
+}
+
+impl EventBridgeAction {
+    fn default_account() -> String {
+        "default".to_string()
+    }
+
+    fn default_bus() -> String {
+        "default".to_string()
+    }
+
+    fn default_source() -> String {
+        "hands-off-release".to_string()
+    }
+
+    fn default_detail_type() -> String {
+        "Release".to_string()
+    }
+}
+
+impl StatuspageMaintenance {
+    fn default_name() -> String {
+        "Releasing {repo} to {env}".to_string()
+    }
+}
+
+impl Verification {
+    fn default_window_secs() -> u64 {
+        300
+    }
+
+    fn default_interval_secs() -> u64 {
+        15
+    }
+
+    fn default_failures() -> u32 {
+        3
+    }
+}
+
+impl GithubIssueIncident {
+    fn default_title() -> String {
+        "Rolled back {env} of {owner}/{repo} from {short-sha}".to_string()
+    }
+
+    fn default_body() -> String {
+        "{env} was rolled back from {sha} to {from}: {reason}\n\n\
+         Rolled back commits:\n\n{commits}"
+            .to_string()
+    }
+}
+
+impl PagerDutyIncident {
+    fn default_severity() -> String {
+        "error".to_string()
+    }
+}
+
+impl Revision {
+    pub fn resolve(self, env: &str, sha: &str) -> String {
+        match self {
+            Revision::Sha => sha.to_string(),
+            Revision::Tag => env.to_string(),
+        }
+    }
+}
@@ -0,0 +1,117 @@
+use std::{
+    collections::BTreeMap,
+    fmt::{self, Display, Formatter},
+};
+
+use serde_json::Value;
+
+use crate::{ProjectId, SourceProject, SourceProjects};
+
+/// What a registry change adds, removes and modifies, for reviewing it
+/// before it takes effect.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct RegistryDiff {
+    pub added: Vec<RegistryEntry>,
+    pub removed: Vec<RegistryEntry>,
+    /// Entries whose configuration changed, as they are after the change
+    pub modified: Vec<RegistryEntry>,
+}
+
+/// One project of a registry, or one owner whose repositories are only
+/// known once expanded at sync time.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RegistryEntry {
+    Project {
+        id: ProjectId,
+        owner: String,
+        repo: String,
+        env: String,
+    },
+    Owner {
+        owner: String,
+        repos: String,
+        env: String,
+    },
+}
+
+impl Display for RegistryEntry {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            RegistryEntry::Project {
+                id,
+                owner,
+                repo,
+                env,
+            } => write!(f, "{owner}/{repo} to {env} ({id})"),
+            RegistryEntry::Owner { owner, repos, env } => write!(f, "{owner}/{repos} to {env}"),
+        }
+    }
+}
+
+/// Projects are matched by id, so a repository moved under an explicit id
+/// is modified rather than removed and added.
+#[derive(PartialEq, Eq, PartialOrd, Ord)]
+enum Key {
+    Project(ProjectId),
+    Owner(String, String, String),
+}
+
+impl RegistryDiff {
+    pub fn between(old: &SourceProjects, new: &SourceProjects) -> Self {
+        let (old, new) = (entries(old), entries(new));
+        let mut diff = RegistryDiff::default();
+        for (key, (entry, config)) in &new {
+            match old.get(key) {
+                None => diff.added.push(entry.clone()),
+                Some((_, old_config)) if old_config != config => diff.modified.push(entry.clone()),
+                Some(_) => {}
+            }
+        }
+        diff.removed = old
+            .into_iter()
+            .filter(|(key, _)| !new.contains_key(key))
+            .map(|(_, (entry, _))| entry)
+            .collect();
+        diff
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty() && self.modified.is_empty()
+    }
+}
+
+/// Every entry of `projects` with its configuration, regions expanded.
+fn entries(projects: &SourceProjects) -> BTreeMap<Key, (RegistryEntry, Value)> {
+    let mut entries = BTreeMap::new();
+    for project in projects {
+        match project {
+            SourceProject::Github(project) => {
+                for project in project.expand_regions() {
+                    let id = project.id();
+                    let entry = RegistryEntry::Project {
+                        id: id.clone(),
+                        owner: project.owner.clone(),
+                        repo: project.repo.clone(),
+                        env: project.env.clone(),
+                    };
+                    entries.insert(Key::Project(id), (entry, config(&project)));
+                }
+            }
+            SourceProject::GithubOwner(owner) => {
+                let key = Key::Owner(owner.owner.clone(), owner.repos.clone(), owner.env.clone());
+                let entry = RegistryEntry::Owner {
+                    owner: owner.owner.clone(),
+                    repos: owner.repos.clone(),
+                    env: owner.env.clone(),
+                };
+                entries.insert(key, (entry, config(owner)));
+            }
+        }
+    }
+    entries
+}
+
+/// Configuration as compared, independent of the order of maps.
+fn config(project: &impl serde::Serialize) -> Value {
+    serde_json::to_value(project).unwrap_or(Value::Null)
+}
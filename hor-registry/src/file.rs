@@ -1,3 +1,5 @@
+use std::collections::HashMap;
+
 use config::{Config, ConfigError, File};
 use derive_more::From;
 use serde::Deserialize;
@@ -10,11 +12,14 @@ pub struct FileBasedRegistry {
 }
 
 impl FileBasedRegistry {
-    pub fn from_file(path: &'static str) -> Result<FileBasedRegistry, ConfigRsError> {
-        let config: SourceProjectsWrapper = Config::builder()
+    pub fn from_file(path: &str) -> Result<FileBasedRegistry, ConfigRsError> {
+        let mut config: SourceProjectsWrapper = Config::builder()
             .add_source(File::with_name(path))
             .build()?
             .try_deserialize()?;
+        for project in &mut config.projects {
+            project.resolve_aliases(&config.env_aliases);
+        }
 
         Ok(FileBasedRegistry {
             source_projects: Box::new(config.projects),
@@ -23,8 +28,13 @@ impl FileBasedRegistry {
 }
 
 #[derive(Deserialize)]
+#[serde(rename_all = "kebab-case")]
 struct SourceProjectsWrapper {
     projects: SourceProjects,
+    /// Other names of envs, e.g. `production: prod`, usable wherever an
+    /// env is named
+    #[serde(default)]
+    env_aliases: HashMap<String, String>,
 }
 
 #[derive(Error, Debug, From)]
@@ -0,0 +1,43 @@
+use std::fmt::{self, Display, Formatter};
+
+use serde::{Deserialize, Serialize};
+
+/// Stable identifier of a project, used wherever a project is referred to
+/// independently of its current owner/repo slug.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[serde(transparent)]
+pub struct ProjectId(String);
+
+impl ProjectId {
+    pub fn new(id: impl Into<String>) -> Self {
+        ProjectId(id.into())
+    }
+
+    /// Derives an id by hashing `parts` with 64-bit FNV-1a, which is stable
+    /// across builds and platforms.
+    pub fn derived(parts: &[&str]) -> Self {
+        const OFFSET_BASIS: u64 = 0xcbf29ce484222325;
+        const PRIME: u64 = 0x100000001b3;
+
+        let mut hash = OFFSET_BASIS;
+        for (index, part) in parts.iter().enumerate() {
+            // Separator keeps ["ab", "c"] and ["a", "bc"] apart
+            let separator = (index > 0).then_some(0u8);
+            for byte in separator.into_iter().chain(part.bytes()) {
+                hash ^= u64::from(byte);
+                hash = hash.wrapping_mul(PRIME);
+            }
+        }
+        ProjectId(format!("{hash:016x}"))
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+
+impl Display for ProjectId {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
@@ -0,0 +1,91 @@
+use std::{collections::BTreeMap, str::FromStr};
+
+use thiserror::Error;
+
+/// Free-form `key: value` labels attached to a project.
+pub type Labels = BTreeMap<String, String>;
+
+/// Selects projects by their labels, e.g. `team=payments,tier!=batch`.
+///
+/// Every requirement must hold for a project to match; the empty selector
+/// matches everything.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct LabelSelector {
+    requirements: Vec<Requirement>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Requirement {
+    Equals(String, String),
+    NotEquals(String, String),
+}
+
+impl LabelSelector {
+    pub fn matches(&self, labels: &Labels) -> bool {
+        self.requirements
+            .iter()
+            .all(|requirement| match requirement {
+                Requirement::Equals(key, value) => labels.get(key) == Some(value),
+                Requirement::NotEquals(key, value) => labels.get(key) != Some(value),
+            })
+    }
+}
+
+impl FromStr for LabelSelector {
+    type Err = LabelSelectorParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut requirements = Vec::new();
+        for term in s.split(',').map(str::trim).filter(|term| !term.is_empty()) {
+            let requirement = if let Some((key, value)) = term.split_once("!=") {
+                Requirement::NotEquals(key.trim().to_string(), value.trim().to_string())
+            } else if let Some((key, value)) = term.split_once('=') {
+                Requirement::Equals(key.trim().to_string(), value.trim().to_string())
+            } else {
+                return Err(LabelSelectorParseError(term.to_string()));
+            };
+            requirements.push(requirement);
+        }
+        Ok(LabelSelector { requirements })
+    }
+}
+
+#[derive(Error, Debug)]
+#[error("invalid label requirement `{0}`, expected `key=value` or `key!=value`")]
+pub struct LabelSelectorParseError(String);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn labels(pairs: &[(&str, &str)]) -> Labels {
+        pairs
+            .iter()
+            .map(|(key, value)| (key.to_string(), value.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn empty_selector_matches_everything() {
+        let selector: LabelSelector = " , ".parse().unwrap();
+        assert_eq!(selector, LabelSelector::default());
+        assert!(selector.matches(&Labels::new()));
+        assert!(selector.matches(&labels(&[("team", "payments")])));
+    }
+
+    #[test]
+    fn every_requirement_must_hold() {
+        let selector: LabelSelector = "team = payments, tier!=batch".parse().unwrap();
+        assert!(selector.matches(&labels(&[("team", "payments"), ("tier", "web")])));
+        assert!(selector.matches(&labels(&[("team", "payments")])));
+        assert!(!selector.matches(&labels(&[("team", "payments"), ("tier", "batch")])));
+        assert!(!selector.matches(&labels(&[("team", "search")])));
+        assert!(!selector.matches(&Labels::new()));
+    }
+
+    #[test]
+    fn refuses_terms_without_operator() {
+        let err = "team=payments,tier".parse::<LabelSelector>().unwrap_err();
+        assert_eq!(err.0, "tier");
+    }
+}
@@ -1,9 +1,42 @@
+pub mod actions;
+mod diff;
 pub mod file;
+pub mod id;
+pub mod labels;
+pub mod policy;
 
+use std::collections::HashMap;
+
+use glob::{Pattern, PatternError};
 use serde::{Deserialize, Serialize};
 
+pub use actions::{
+    ArgoCdAction, BlueGreen, DatadogAction, EventBridgeAction, FluxAction, GithubIssueIncident,
+    GitopsPullRequestAction, JiraAction, KubernetesJobAction, LinearAction, PagerDutyIncident,
+    PostSyncAction, PubSubAction, ReleaseNotesAction, ReleaseNotesDestination, Revision,
+    RollbackIncident, StatuspageMaintenance, SubmoduleBumpAction, TerraformCloudAction,
+    Verification, VersionBumpAction, VersionFormat, WebhookAction, WorkflowDispatchAction,
+};
+pub use diff::{RegistryDiff, RegistryEntry};
+pub use id::ProjectId;
+pub use labels::{LabelSelector, Labels};
+pub use policy::{
+    AttestationSource, AutoPromotion, CheckWait, CommitConvention, FlagExpectation, IgnoredChanges,
+    ImageGate, MergeCommits, Policy, PromotionBudget, RegoPolicy, ReleaseTrain, ReleaseWindow,
+    RiskScoring,
+};
+
 pub trait Registry {
     fn get_projects(&self) -> &SourceProjects;
+
+    /// Told when the repository registered as `owner/repo` turned out to
+    /// have moved to `new_owner/new_repo`. Registries that can persist the
+    /// change should; by default the move is followed anew every sync.
+    fn repository_moved(&self, _owner: &str, _repo: &str, _new_owner: &str, _new_repo: &str) {}
+
+    /// Told when project `id` expired at the end of its TTL. Registries
+    /// that can should deregister it; otherwise it stays, frozen.
+    fn project_expired(&self, _id: &ProjectId) {}
 }
 
 #[derive(Serialize, Deserialize, Debug)]
@@ -11,15 +44,422 @@ pub trait Registry {
 #[non_exhaustive]
 pub enum SourceProject {
     Github(GithubProject),
+    /// Every repository of a GitHub organization matching the given globs,
+    /// expanded at sync time
+    GithubOwner(GithubOwnerProject),
 }
 
 pub type SourceProjects = Vec<SourceProject>;
 
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, Clone)]
 #[serde(rename_all = "kebab-case")]
 #[jsm::public]
 struct GithubProject {
+    /// Explicit identifier; set this to keep history across renames
+    #[serde(default)]
+    id: Option<ProjectId>,
+    owner: String,
+    repo: String,
+    env: String,
+    #[serde(default)]
+    labels: Labels,
+    #[serde(default)]
+    policy: Policy,
+    /// Run in order after the env ref moves
+    #[serde(default)]
+    actions: Vec<PostSyncAction>,
+    /// Maintenance announced while the env ref moves
+    #[serde(default)]
+    statuspage: Option<StatuspageMaintenance>,
+    /// Health watched after the release, rolling it back on failure
+    #[serde(default)]
+    verification: Option<Verification>,
+    /// Follow another environment rather than the main branch
+    #[serde(default)]
+    promote_from: Option<AutoPromotion>,
+    /// Release through a pair of color tags
+    #[serde(default)]
+    blue_green: Option<BlueGreen>,
+    /// Projects of the same group are released all or nothing
+    #[serde(default)]
+    group: Option<String>,
+    /// Track this repository's branch instead, `owner`/`repo` being a fork
+    /// or mirror that only gets the env tag
+    #[serde(default)]
+    upstream: Option<Upstream>,
+    /// Repositories whose env tag follows this project's
+    #[serde(default)]
+    mirrors: Vec<TagMirror>,
+    /// Immutable version tags created alongside the env tag
+    #[serde(default)]
+    release_tags: Option<ReleaseTags>,
+    /// Further refs moved in order to wherever the env tag is, e.g.
+    /// `heads/deploy/prod` for systems watching branches
+    #[serde(default)]
+    extra_refs: Vec<String>,
+    /// Regions the env is released to, one project each, named after
+    /// `region-env`
+    #[serde(default)]
+    regions: Vec<String>,
+    /// Template over `{env}` and `{region}` naming the env of each region
+    #[serde(default = "GithubProject::default_region_env")]
+    region_env: String,
+    /// Release the regions one after the other, in order, rather than at
+    /// once. Such projects are never part of a group
+    #[serde(default)]
+    rollout: Option<RegionRollout>,
+    /// Release the branch only at fixed departure times
+    #[serde(default)]
+    train: Option<ReleaseTrain>,
+    /// Commits of the branch that don't warrant a release on their own
+    #[serde(default)]
+    ignore: IgnoredChanges,
+    /// How often the project may be promoted to its env; pins are exempt
+    #[serde(default)]
+    budget: Option<PromotionBudget>,
+    /// Seconds after its last release that the env expires, e.g. the
+    /// preview of a pull request: the env tag is deleted and the project
+    /// frozen
+    #[serde(default)]
+    ttl_secs: Option<u64>,
+    /// Also release a preview env of every open pull request
+    #[serde(default)]
+    previews: Option<PreviewEnvs>,
+    /// Keep a manifest of each promotion with the run, and publish it
+    #[serde(default)]
+    manifest: Option<ReleaseManifests>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+#[jsm::public]
+struct RegionRollout {
+    /// How long a released region runs, after its verification, before
+    /// the next region is released
+    #[serde(default)]
+    bake_secs: u64,
+    /// Bake times of particular regions, overriding `bake-secs`
+    #[serde(default)]
+    region_bake_secs: HashMap<String, u64>,
+    /// Move the regions released earlier in the rollout back when one
+    /// fails, rather than only halting
+    #[serde(default)]
+    roll_back: bool,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+#[jsm::public]
+struct Upstream {
     owner: String,
     repo: String,
+    /// The upstream's default branch if unset
+    #[serde(default)]
+    branch: Option<String>,
+}
+
+/// A preview env per open pull request, releasing the pull request's head
+/// and torn down once it's merged or closed.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+#[jsm::public]
+struct PreviewEnvs {
+    /// Template over `{pr}`, the pull request's number
+    #[serde(default = "PreviewEnvs::default_env")]
+    env: String,
+    /// Only pull requests with one of these labels; every one if empty
+    #[serde(default)]
+    labels: Vec<String>,
+    /// Draft pull requests too
+    #[serde(default)]
+    drafts: bool,
+    /// Pull requests from forks too, releasing outside contributors' code
+    #[serde(default)]
+    forks: bool,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+#[jsm::public]
+struct TagMirror {
+    owner: String,
+    repo: String,
+    /// Commit message trailer naming the original commit, e.g. copybara's
+    /// `GitOrigin-RevId`, for mirrors that rewrite history. The mirror's
+    /// env tag then points at its commit carrying the trailer rather than
+    /// at the same sha
+    #[serde(default)]
+    origin_trailer: Option<String>,
+}
+
+/// Version tags of a repository's releases, e.g. `v1.4.2` or
+/// `2024.06.2`. Set on one env per repository, usually the one customers
+/// get.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+#[jsm::public]
+struct ReleaseTags {
+    #[serde(default)]
+    scheme: VersionScheme,
+    /// Put in front of the version
+    #[serde(default = "ReleaseTags::default_prefix")]
+    prefix: String,
+    /// Version of the first release tag; calendar versions start at the
+    /// current month
+    #[serde(default = "ReleaseTags::default_initial")]
+    initial: String,
+}
+
+/// How the next version is picked.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum VersionScheme {
+    /// Told by the conventional commits since the last release tag, a
+    /// patch release if none calls for more
+    #[default]
+    Conventional,
+    /// Every release bumps the patch version
+    Counter,
+    /// `YYYY.MM.N`, N counting the releases of the month from 1
+    Calver,
+}
+
+/// Where promotion manifests are published besides the run report.
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+#[jsm::public]
+struct ReleaseManifests {
+    /// Template over `{owner}`, `{repo}`, `{env}`, `{sha}` and
+    /// `{short-sha}` naming the tag whose GitHub release gets the manifest
+    /// as an asset. A missing release is created, as a prerelease, and
+    /// with it the tag
+    #[serde(default)]
+    github_release: Option<String>,
+}
+
+impl ReleaseTags {
+    fn default_prefix() -> String {
+        "v".to_string()
+    }
+
+    fn default_initial() -> String {
+        "0.1.0".to_string()
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(rename_all = "kebab-case")]
+#[jsm::public]
+struct GithubOwnerProject {
+    owner: String,
+    /// Glob of repository names to include, e.g. `*` or `svc-*`
+    repos: String,
+    /// Globs of repository names to leave out
+    #[serde(default)]
+    exclude: Vec<String>,
     env: String,
+    /// Labels inherited by every expanded project
+    #[serde(default)]
+    labels: Labels,
+    /// Policy inherited by every expanded project
+    #[serde(default)]
+    policy: Policy,
+    /// Actions inherited by every expanded project
+    #[serde(default)]
+    actions: Vec<PostSyncAction>,
+    #[serde(default)]
+    statuspage: Option<StatuspageMaintenance>,
+    #[serde(default)]
+    verification: Option<Verification>,
+    #[serde(default)]
+    promote_from: Option<AutoPromotion>,
+    #[serde(default)]
+    blue_green: Option<BlueGreen>,
+    #[serde(default)]
+    group: Option<String>,
+    #[serde(default)]
+    release_tags: Option<ReleaseTags>,
+    #[serde(default)]
+    extra_refs: Vec<String>,
+    #[serde(default)]
+    regions: Vec<String>,
+    #[serde(default = "GithubProject::default_region_env")]
+    region_env: String,
+    #[serde(default)]
+    train: Option<ReleaseTrain>,
+    #[serde(default)]
+    ignore: IgnoredChanges,
+    /// Budget of each expanded project, not shared between them
+    #[serde(default)]
+    budget: Option<PromotionBudget>,
+    #[serde(default)]
+    previews: Option<PreviewEnvs>,
+    #[serde(default)]
+    manifest: Option<ReleaseManifests>,
+}
+
+impl SourceProject {
+    pub fn labels(&self) -> &Labels {
+        match self {
+            SourceProject::Github(project) => &project.labels,
+            SourceProject::GithubOwner(owner) => &owner.labels,
+        }
+    }
+
+    /// Id of the entry as a whole, e.g. to report on an owner whose
+    /// repositories couldn't be listed.
+    pub fn id(&self) -> ProjectId {
+        match self {
+            SourceProject::Github(project) => project.id(),
+            SourceProject::GithubOwner(owner) => owner.id(),
+        }
+    }
+
+    pub fn env(&self) -> &str {
+        match self {
+            SourceProject::Github(project) => &project.env,
+            SourceProject::GithubOwner(owner) => &owner.env,
+        }
+    }
+
+    /// Replaces the envs named by an alias, e.g. `production` for `prod`,
+    /// with the env the alias stands for.
+    pub fn resolve_aliases(&mut self, aliases: &HashMap<String, String>) {
+        let resolve = |env: &mut String| {
+            if let Some(resolved) = aliases.get(env.as_str()) {
+                *env = resolved.clone();
+            }
+        };
+        let (env, promote_from) = match self {
+            SourceProject::Github(project) => (&mut project.env, &mut project.promote_from),
+            SourceProject::GithubOwner(owner) => (&mut owner.env, &mut owner.promote_from),
+        };
+        resolve(env);
+        if let Some(promote_from) = promote_from {
+            resolve(&mut promote_from.env);
+        }
+    }
+}
+
+impl GithubProject {
+    /// The explicit id if configured, otherwise one derived from the
+    /// project's coordinates.
+    pub fn id(&self) -> ProjectId {
+        self.id
+            .clone()
+            .unwrap_or_else(|| self.id_for_env(&self.env))
+    }
+
+    /// The derived id of the project releasing the same repository to
+    /// `env`.
+    pub fn id_for_env(&self, env: &str) -> ProjectId {
+        ProjectId::derived(&["github", &self.owner, &self.repo, env])
+    }
+
+    /// The project of each region, or the project itself if it has none.
+    /// `{region}` is also filled in wherever another env is named, so
+    /// `staging-{region}` promotes region by region. Explicit ids get the
+    /// region appended.
+    pub fn expand_regions(&self) -> Vec<GithubProject> {
+        if self.regions.is_empty() {
+            return vec![self.clone()];
+        }
+        self.regions
+            .iter()
+            .map(|region| {
+                let fill = |template: &str| {
+                    template
+                        .replace("{env}", &self.env)
+                        .replace("{region}", region)
+                };
+                GithubProject {
+                    id: self
+                        .id
+                        .as_ref()
+                        .map(|id| ProjectId::new(format!("{id}-{region}"))),
+                    env: fill(&self.region_env),
+                    promote_from: self.promote_from.clone().map(|promote_from| AutoPromotion {
+                        env: promote_from.env.replace("{region}", region),
+                        ..promote_from
+                    }),
+                    regions: Vec::new(),
+                    ..self.clone()
+                }
+            })
+            .collect()
+    }
+
+    fn default_region_env() -> String {
+        "{env}-{region}".to_string()
+    }
+}
+
+impl GithubOwnerProject {
+    /// Derived id of the entry itself, apart from those of the projects it
+    /// expands to.
+    pub fn id(&self) -> ProjectId {
+        ProjectId::derived(&["github-owner", &self.owner, &self.repos, &self.env])
+    }
+
+    /// Whether `repo` is selected by the include glob and none of the
+    /// exclude globs.
+    pub fn matches(&self, repo: &str) -> Result<bool, PatternError> {
+        if !Pattern::new(&self.repos)?.matches(repo) {
+            return Ok(false);
+        }
+        for exclude in &self.exclude {
+            if Pattern::new(exclude)?.matches(repo) {
+                return Ok(false);
+            }
+        }
+        Ok(true)
+    }
+
+    /// The concrete project tracking `repo` under this owner.
+    pub fn project_for(&self, repo: impl Into<String>) -> GithubProject {
+        GithubProject {
+            id: None,
+            owner: self.owner.clone(),
+            repo: repo.into(),
+            env: self.env.clone(),
+            labels: self.labels.clone(),
+            policy: self.policy.clone(),
+            actions: self.actions.clone(),
+            statuspage: self.statuspage.clone(),
+            verification: self.verification.clone(),
+            promote_from: self.promote_from.clone(),
+            blue_green: self.blue_green.clone(),
+            group: self.group.clone(),
+            upstream: None,
+            mirrors: Vec::new(),
+            release_tags: self.release_tags.clone(),
+            extra_refs: self.extra_refs.clone(),
+            regions: self.regions.clone(),
+            region_env: self.region_env.clone(),
+            rollout: None,
+            train: self.train.clone(),
+            ignore: self.ignore.clone(),
+            budget: self.budget.clone(),
+            ttl_secs: None,
+            previews: self.previews.clone(),
+            manifest: self.manifest.clone(),
+        }
+    }
+}
+
+impl PreviewEnvs {
+    fn default_env() -> String {
+        "preview-{pr}".to_string()
+    }
+
+    /// The env of pull request `number`.
+    pub fn env_for(&self, number: u64) -> String {
+        self.env.replace("{pr}", &number.to_string())
+    }
+
+    /// The number of the pull request whose env is `env`, if it is one.
+    pub fn pull_request_of(&self, env: &str) -> Option<u64> {
+        let (prefix, suffix) = self.env.split_once("{pr}")?;
+        env.strip_prefix(prefix)?.strip_suffix(suffix)?.parse().ok()
+    }
 }
@@ -0,0 +1,425 @@
+use chrono::{NaiveTime, Weekday};
+use serde::{Deserialize, Serialize};
+
+use crate::ProjectId;
+
+/// Release gates for a project. Every rule that is set has to pass before
+/// the env ref is moved; an empty policy allows everything.
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+#[jsm::public]
+struct Policy {
+    /// Check runs that must have concluded successfully on the target, by
+    /// name or glob, e.g. `test (*)` for every run of a matrix; a glob
+    /// needs at least one run and every matching one to pass
+    #[serde(default)]
+    required_checks: Vec<String>,
+    /// Wait for required checks that haven't concluded yet, e.g. ones
+    /// reporting from an external system on a delay, instead of blocking
+    /// right away
+    #[serde(default)]
+    check_wait: Option<CheckWait>,
+    /// When releases may happen; any matching window allows the release
+    #[serde(default)]
+    windows: Vec<ReleaseWindow>,
+    /// Approvals recorded for the target commit
+    #[serde(default)]
+    min_approvals: Option<usize>,
+    /// Upper bound on commits between the deployed and the target commit
+    #[serde(default)]
+    max_commits: Option<usize>,
+    /// GitHub logins allowed to author released commits
+    #[serde(default)]
+    allowed_authors: Vec<String>,
+    /// GitHub logins, bots included, whose commits block a release
+    #[serde(default)]
+    denied_authors: Vec<String>,
+    /// Regexes; a commit message matching any of them blocks a release
+    #[serde(default)]
+    denied_messages: Vec<String>,
+    /// Whether the range may, or must, contain merge commits
+    #[serde(default)]
+    merge_commits: Option<MergeCommits>,
+    /// Form the summary line of every released commit must take
+    #[serde(default)]
+    commit_convention: Option<CommitConvention>,
+    /// Require an approval from an owner of every CODEOWNERS entry the
+    /// release touches
+    #[serde(default)]
+    codeowners_approvals: bool,
+    /// Container image that must have been published for the target
+    #[serde(default)]
+    image: Option<ImageGate>,
+    /// LaunchDarkly flags that must be in the given state
+    #[serde(default)]
+    feature_flags: Vec<FlagExpectation>,
+    /// Rego module evaluated against the release candidate
+    #[serde(default)]
+    rego: Option<RegoPolicy>,
+    /// Score how risky each release is, and require approvals above a
+    /// threshold
+    #[serde(default)]
+    risk: Option<RiskScoring>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum MergeCommits {
+    /// Linear history only
+    Forbid,
+    /// At least one merge commit, e.g. releases only through merged PRs
+    Require,
+}
+
+/// Conventional Commits, `type(scope)!: description`, unless a pattern is
+/// given. Merge commits are exempt, as their messages are generated.
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+#[jsm::public]
+struct CommitConvention {
+    /// Types allowed, e.g. `feat` and `fix`; any type if empty
+    #[serde(default)]
+    types: Vec<String>,
+    /// Regex the summary line must match instead
+    #[serde(default)]
+    pattern: Option<String>,
+}
+
+/// An image reference, e.g. `ghcr.io`, `org/app` and tag `sha-{sha}`.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+#[jsm::public]
+struct ImageGate {
+    registry: String,
+    repository: String,
+    /// Template over `{env}`, `{sha}` and `{short-sha}`
+    #[serde(default = "ImageGate::default_tag")]
+    tag: String,
+    /// Also require a provenance or SBOM attestation of the image's digest
+    #[serde(default)]
+    attestation: Option<AttestationSource>,
+}
+
+/// Where attestations of an image are looked up. Only their presence is
+/// checked; signatures are left to admission control at deploy time.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum AttestationSource {
+    /// `cosign attest`'s `sha256-<digest>.att` tag next to the image
+    Cosign,
+    /// GitHub's artifact attestations of the project's repository, e.g.
+    /// from `actions/attest-build-provenance`
+    Github,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+#[jsm::public]
+struct FlagExpectation {
+    /// LaunchDarkly project key
+    project: String,
+    flag: String,
+    /// LaunchDarkly environment key
+    environment: String,
+    /// Whether targeting must be on, e.g. `false` for a kill switch
+    on: bool,
+}
+
+/// A Rego gate in the OPA `deny` style: every message the rule produces
+/// blocks the release.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+#[jsm::public]
+struct RegoPolicy {
+    /// Path of the `.rego` file
+    file: String,
+    /// Rule yielding the set of denial messages
+    #[serde(default = "RegoPolicy::default_rule")]
+    rule: String,
+}
+
+/// Points a release scores for what it contains; the sum is its risk.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+#[jsm::public]
+struct RiskScoring {
+    #[serde(default = "RiskScoring::default_per_commit")]
+    per_commit: u32,
+    #[serde(default = "RiskScoring::default_per_file")]
+    per_file: u32,
+    /// Globs of paths that make a release riskier, e.g. `migrations/**`
+    #[serde(default)]
+    critical_paths: Vec<String>,
+    /// For every changed file matching a critical path
+    #[serde(default = "RiskScoring::default_per_critical_file")]
+    per_critical_file: u32,
+    /// For every distinct commit author
+    #[serde(default = "RiskScoring::default_per_author")]
+    per_author: u32,
+    /// Score above which the release needs `approvals`; only scored if
+    /// unset
+    #[serde(default)]
+    approval_threshold: Option<u32>,
+    #[serde(default = "RiskScoring::default_approvals")]
+    approvals: usize,
+}
+
+/// Releases what another environment of the same repository has been
+/// running for a while, e.g. prod following staging after four hours,
+/// instead of the main branch.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+#[jsm::public]
+struct AutoPromotion {
+    /// Environment promoted from
+    env: String,
+    /// How long the source environment must have run a commit without
+    /// reporting a failure
+    after_secs: u64,
+    /// Id of the source project, if it sets an explicit one
+    #[serde(default)]
+    project: Option<ProjectId>,
+}
+
+/// How long, and how often, to poll for required checks to conclude.
+/// The sync of the project holds its slot meanwhile.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+#[jsm::public]
+struct CheckWait {
+    timeout_secs: u64,
+    #[serde(default = "CheckWait::default_interval_secs")]
+    interval_secs: u64,
+}
+
+/// A recurring UTC time range, e.g. weekdays 09:00 to 16:00. One ending
+/// before it starts runs past midnight, e.g. Fridays 22:00 to 02:00 takes
+/// the first two hours of Saturday.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+#[jsm::public]
+struct ReleaseWindow {
+    /// Days the window applies to; every day if empty
+    #[serde(default)]
+    days: Vec<Weekday>,
+    #[serde(with = "hh_mm")]
+    start: NaiveTime,
+    /// Exclusive
+    #[serde(with = "hh_mm")]
+    end: NaiveTime,
+}
+
+/// Fixed departure times, e.g. 10:00 and 16:00 UTC. What the branch had
+/// at a departure is released once; later commits wait for the next one.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+#[jsm::public]
+struct ReleaseTrain {
+    /// Days trains run; every day if empty
+    #[serde(default)]
+    days: Vec<Weekday>,
+    #[serde(with = "hh_mm_list")]
+    departures: Vec<NaiveTime>,
+}
+
+/// How often an environment may be promoted to, e.g. at most ten prod
+/// promotions a day. A promotion over budget is held back and released by
+/// the first sync after the budget has room again.
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+#[jsm::public]
+struct PromotionBudget {
+    /// Promotions in any 24 hours
+    #[serde(default)]
+    max_per_day: Option<usize>,
+    /// Seconds to wait after a promotion before the next one
+    #[serde(default)]
+    min_interval_secs: Option<u64>,
+}
+
+/// Commits that alone don't warrant a release of the branch, e.g.
+/// Dependabot's or docs-only ones. They're still released along with any
+/// other commit.
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+#[jsm::public]
+struct IgnoredChanges {
+    /// GitHub logins, e.g. `dependabot[bot]`
+    #[serde(default)]
+    authors: Vec<String>,
+    /// Globs, e.g. `docs/**`; commits touching only matching paths are
+    /// ignored
+    #[serde(default)]
+    paths: Vec<String>,
+}
+
+impl Policy {
+    pub fn is_empty(&self) -> bool {
+        self == &Policy::default()
+    }
+}
+
+impl IgnoredChanges {
+    pub fn is_empty(&self) -> bool {
+        self.authors.is_empty() && self.paths.is_empty()
+    }
+}
+
+impl CheckWait {
+    fn default_interval_secs() -> u64 {
+        30
+    }
+}
+
+impl ImageGate {
+    fn default_tag() -> String {
+        "{sha}".to_string()
+    }
+}
+
+impl RiskScoring {
+    fn default_per_commit() -> u32 {
+        1
+    }
+
+    fn default_per_file() -> u32 {
+        1
+    }
+
+    fn default_per_critical_file() -> u32 {
+        10
+    }
+
+    fn default_per_author() -> u32 {
+        2
+    }
+
+    fn default_approvals() -> usize {
+        1
+    }
+}
+
+impl RegoPolicy {
+    fn default_rule() -> String {
+        "data.hor.deny".to_string()
+    }
+}
+
+impl ReleaseWindow {
+    pub fn contains(&self, day: Weekday, time: NaiveTime) -> bool {
+        let on = |day| self.days.is_empty() || self.days.contains(&day);
+        match self.start <= self.end {
+            true => on(day) && self.start <= time && time < self.end,
+            // Past midnight, the window is the one opened the day before
+            false => (on(day) && self.start <= time) || (on(day.pred()) && time < self.end),
+        }
+    }
+}
+
+mod hh_mm_list {
+    use chrono::NaiveTime;
+    use serde::{de::Error, ser::SerializeSeq, Deserialize, Deserializer, Serializer};
+
+    use super::hh_mm::FORMAT;
+
+    pub fn serialize<S: Serializer>(times: &[NaiveTime], serializer: S) -> Result<S::Ok, S::Error> {
+        let mut seq = serializer.serialize_seq(Some(times.len()))?;
+        for time in times {
+            seq.serialize_element(&time.format(FORMAT).to_string())?;
+        }
+        seq.end()
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<Vec<NaiveTime>, D::Error> {
+        Vec::<String>::deserialize(deserializer)?
+            .iter()
+            .map(|raw| NaiveTime::parse_from_str(raw, FORMAT).map_err(D::Error::custom))
+            .collect()
+    }
+}
+
+mod hh_mm {
+    use chrono::NaiveTime;
+    use serde::{de::Error, Deserialize, Deserializer, Serializer};
+
+    pub(super) const FORMAT: &str = "%H:%M";
+
+    pub fn serialize<S: Serializer>(time: &NaiveTime, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(&time.format(FORMAT))
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<NaiveTime, D::Error> {
+        let raw = String::deserialize(deserializer)?;
+        NaiveTime::parse_from_str(&raw, FORMAT).map_err(D::Error::custom)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::Weekday::{Fri, Mon, Sat, Sun, Thu};
+    use serde_json::json;
+
+    use super::*;
+
+    fn window(days: &[&str], start: &str, end: &str) -> ReleaseWindow {
+        serde_json::from_value(json!({ "days": days, "start": start, "end": end })).unwrap()
+    }
+
+    fn at(time: &str) -> NaiveTime {
+        NaiveTime::parse_from_str(time, "%H:%M").unwrap()
+    }
+
+    #[test]
+    fn contains_its_start_but_not_its_end() {
+        let window = window(&["Mon", "Fri"], "09:00", "16:00");
+        assert!(window.contains(Mon, at("09:00")));
+        assert!(window.contains(Fri, at("15:59")));
+        assert!(!window.contains(Mon, at("16:00")));
+        assert!(!window.contains(Mon, at("08:59")));
+        assert!(!window.contains(Thu, at("12:00")));
+    }
+
+    #[test]
+    fn applies_every_day_without_days() {
+        let window = window(&[], "09:00", "16:00");
+        assert!(window.contains(Sun, at("12:00")));
+        assert!(window.contains(Thu, at("12:00")));
+    }
+
+    #[test]
+    fn runs_past_midnight_when_ending_before_it_starts() {
+        let window = window(&["Fri"], "22:00", "02:00");
+        assert!(window.contains(Fri, at("22:00")));
+        assert!(window.contains(Fri, at("23:59")));
+        assert!(window.contains(Sat, at("00:00")));
+        assert!(window.contains(Sat, at("01:59")));
+        assert!(!window.contains(Sat, at("02:00")));
+        assert!(!window.contains(Sat, at("22:00")));
+        // Friday's early hours belong to Thursday's window, which there isn't
+        assert!(!window.contains(Fri, at("01:00")));
+        assert!(!window.contains(Fri, at("21:59")));
+    }
+
+    #[test]
+    fn every_day_window_past_midnight_covers_both_ends() {
+        let window = window(&[], "22:00", "02:00");
+        assert!(window.contains(Mon, at("01:00")));
+        assert!(window.contains(Mon, at("23:00")));
+        assert!(!window.contains(Mon, at("12:00")));
+    }
+
+    #[test]
+    fn parses_and_serializes_hh_mm() {
+        let window = window(&["Mon"], "09:30", "16:00");
+        assert_eq!(
+            serde_json::to_value(&window).unwrap(),
+            json!({ "days": ["Mon"], "start": "09:30", "end": "16:00" })
+        );
+        assert!(
+            serde_json::from_value::<ReleaseWindow>(json!({ "start": "9am", "end": "16:00" }))
+                .is_err()
+        );
+    }
+}
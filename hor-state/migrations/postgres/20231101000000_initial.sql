@@ -0,0 +1,42 @@
+-- IF NOT EXISTS adopts databases created before migrations were introduced
+CREATE TABLE IF NOT EXISTS deployments (
+    project_id TEXT NOT NULL,
+    env TEXT NOT NULL,
+    sha TEXT NOT NULL,
+    deployed_at TIMESTAMPTZ NOT NULL,
+    PRIMARY KEY (project_id, env)
+);
+CREATE TABLE IF NOT EXISTS runs (
+    id BIGSERIAL PRIMARY KEY,
+    started_at TIMESTAMPTZ NOT NULL,
+    report JSONB NOT NULL
+);
+CREATE TABLE IF NOT EXISTS approvals (
+    project_id TEXT NOT NULL,
+    env TEXT NOT NULL,
+    sha TEXT NOT NULL,
+    approver TEXT NOT NULL,
+    approved_at TIMESTAMPTZ NOT NULL,
+    PRIMARY KEY (project_id, env, sha, approver)
+);
+CREATE TABLE IF NOT EXISTS pins (
+    project_id TEXT NOT NULL,
+    env TEXT NOT NULL,
+    sha TEXT NOT NULL,
+    reason TEXT,
+    pinned_at TIMESTAMPTZ NOT NULL,
+    PRIMARY KEY (project_id, env)
+);
+CREATE TABLE IF NOT EXISTS freezes (
+    scope TEXT PRIMARY KEY,
+    reason TEXT,
+    frozen_at TIMESTAMPTZ NOT NULL
+);
+CREATE TABLE IF NOT EXISTS outbox (
+    id BIGSERIAL PRIMARY KEY,
+    sink TEXT NOT NULL,
+    event JSONB NOT NULL,
+    attempts BIGINT NOT NULL,
+    next_attempt_at TIMESTAMPTZ,
+    last_error TEXT
+);
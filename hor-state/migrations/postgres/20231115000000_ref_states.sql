@@ -0,0 +1,8 @@
+CREATE TABLE ref_states (
+    project_id TEXT NOT NULL,
+    git_ref TEXT NOT NULL,
+    sha TEXT NOT NULL,
+    etag TEXT,
+    observed_at TIMESTAMPTZ NOT NULL,
+    PRIMARY KEY (project_id, git_ref)
+);
@@ -0,0 +1,11 @@
+CREATE TABLE jobs (
+    id BIGSERIAL PRIMARY KEY,
+    target JSONB NOT NULL,
+    state TEXT NOT NULL,
+    queued_at TIMESTAMPTZ NOT NULL,
+    started_at TIMESTAMPTZ,
+    finished_at TIMESTAMPTZ,
+    run_id BIGINT,
+    error TEXT
+);
+CREATE INDEX jobs_by_state ON jobs (state, id);
@@ -0,0 +1,6 @@
+CREATE TABLE deleted_projects (
+    project_id TEXT PRIMARY KEY,
+    reason TEXT,
+    deleted_at TIMESTAMPTZ NOT NULL,
+    purged_at TIMESTAMPTZ
+);
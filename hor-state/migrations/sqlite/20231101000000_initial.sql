@@ -0,0 +1,42 @@
+-- IF NOT EXISTS adopts databases created before migrations were introduced
+CREATE TABLE IF NOT EXISTS deployments (
+    project_id TEXT NOT NULL,
+    env TEXT NOT NULL,
+    sha TEXT NOT NULL,
+    deployed_at TEXT NOT NULL,
+    PRIMARY KEY (project_id, env)
+);
+CREATE TABLE IF NOT EXISTS runs (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    started_at TEXT NOT NULL,
+    report TEXT NOT NULL
+);
+CREATE TABLE IF NOT EXISTS approvals (
+    project_id TEXT NOT NULL,
+    env TEXT NOT NULL,
+    sha TEXT NOT NULL,
+    approver TEXT NOT NULL,
+    approved_at TEXT NOT NULL,
+    PRIMARY KEY (project_id, env, sha, approver)
+);
+CREATE TABLE IF NOT EXISTS pins (
+    project_id TEXT NOT NULL,
+    env TEXT NOT NULL,
+    sha TEXT NOT NULL,
+    reason TEXT,
+    pinned_at TEXT NOT NULL,
+    PRIMARY KEY (project_id, env)
+);
+CREATE TABLE IF NOT EXISTS freezes (
+    scope TEXT PRIMARY KEY NOT NULL,
+    reason TEXT,
+    frozen_at TEXT NOT NULL
+);
+CREATE TABLE IF NOT EXISTS outbox (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    sink TEXT NOT NULL,
+    event TEXT NOT NULL,
+    attempts INTEGER NOT NULL,
+    next_attempt_at TEXT,
+    last_error TEXT
+);
@@ -0,0 +1,7 @@
+CREATE TABLE promotions (
+    project_id TEXT NOT NULL,
+    env TEXT NOT NULL,
+    promoted_at TEXT NOT NULL,
+    PRIMARY KEY (project_id, env, promoted_at)
+);
+CREATE INDEX promotions_by_env ON promotions (env, promoted_at);
@@ -0,0 +1,11 @@
+CREATE TABLE jobs (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    target TEXT NOT NULL,
+    state TEXT NOT NULL,
+    queued_at TEXT NOT NULL,
+    started_at TEXT,
+    finished_at TEXT,
+    run_id INTEGER,
+    error TEXT
+);
+CREATE INDEX jobs_by_state ON jobs (state, id);
@@ -0,0 +1,6 @@
+CREATE TABLE deleted_projects (
+    project_id TEXT PRIMARY KEY NOT NULL,
+    reason TEXT,
+    deleted_at TEXT NOT NULL,
+    purged_at TEXT
+);
@@ -0,0 +1 @@
+ALTER TABLE outbox ADD COLUMN pending BOOLEAN NOT NULL DEFAULT FALSE;
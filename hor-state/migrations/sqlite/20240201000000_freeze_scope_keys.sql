@@ -0,0 +1,2 @@
+-- Namespace scopes, so a project id can't collide with the global one
+UPDATE freezes SET scope = CASE WHEN scope = '*' THEN 'global' ELSE 'project:' || scope END;
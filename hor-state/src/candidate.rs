@@ -0,0 +1,101 @@
+use chrono::{DateTime, Utc};
+use hor_registry::ProjectId;
+use serde::{Deserialize, Serialize};
+
+/// Everything release gates look at, gathered once per project before a
+/// ref mutation. Decisions are a pure function of this document and the
+/// project's policy.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(rename_all = "kebab-case")]
+#[jsm::public]
+struct ReleaseCandidate {
+    project: ProjectId,
+    env: String,
+    /// Currently deployed commit, `None` if the env ref doesn't exist yet
+    from: Option<String>,
+    to: String,
+    /// Commits being released, newest last; only the target when `from`
+    /// is unknown
+    commits: Vec<CandidateCommit>,
+    /// May exceed `commits.len()` as GitHub truncates long comparisons
+    total_commits: usize,
+    /// Paths changed by the release, the old paths of renamed files
+    /// included
+    files: Vec<String>,
+    /// Whether GitHub left some of the changed paths out of `files`
+    #[serde(default)]
+    files_truncated: bool,
+    /// CODEOWNERS entries owning any of `files`
+    code_owners: Vec<CodeOwner>,
+    checks: Vec<CandidateCheck>,
+    approvers: Vec<String>,
+    /// Whether the gated container image has been published
+    image: Option<CandidateImage>,
+    /// Current state of the gated feature flags
+    flags: Vec<CandidateFlag>,
+    evaluated_at: DateTime<Utc>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(rename_all = "kebab-case")]
+#[jsm::public]
+struct CandidateCommit {
+    sha: String,
+    message: String,
+    /// GitHub login, if the commit email is linked to an account
+    author: Option<String>,
+    parents: usize,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(rename_all = "kebab-case")]
+#[jsm::public]
+struct CandidateCheck {
+    name: String,
+    status: String,
+    conclusion: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(rename_all = "kebab-case")]
+#[jsm::public]
+struct CandidateImage {
+    reference: String,
+    exists: bool,
+    /// Manifest digest, looked up if an attestation is required
+    #[serde(default)]
+    digest: Option<String>,
+    /// Whether an attestation of the digest was found, if one is required
+    #[serde(default)]
+    attested: Option<bool>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(rename_all = "kebab-case")]
+#[jsm::public]
+struct CandidateFlag {
+    project: String,
+    flag: String,
+    environment: String,
+    on: bool,
+}
+
+/// A user or team from CODEOWNERS, with whoever may approve on its behalf.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(rename_all = "kebab-case")]
+#[jsm::public]
+struct CodeOwner {
+    /// As written in CODEOWNERS, e.g. `@org/team`
+    owner: String,
+    members: Vec<String>,
+}
+
+/// The verdict of one policy rule.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+#[jsm::public]
+struct PolicyDecision {
+    rule: String,
+    passed: bool,
+    detail: String,
+}
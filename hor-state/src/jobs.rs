@@ -0,0 +1,175 @@
+use std::fmt::{self, Display, Formatter};
+
+use chrono::{DateTime, Utc};
+use hor_registry::ProjectId;
+use serde::{Deserialize, Serialize};
+
+use crate::RunId;
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[serde(transparent)]
+pub struct JobId(pub i64);
+
+impl Display for JobId {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+/// A requested sync, run once by whichever instance claims it first.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+#[jsm::public]
+struct SyncJob {
+    target: SyncTarget,
+    state: JobState,
+    queued_at: DateTime<Utc>,
+    /// When it was last claimed
+    started_at: Option<DateTime<Utc>>,
+    /// When whoever claimed it last showed it's still running
+    #[serde(default)]
+    heartbeat_at: Option<DateTime<Utc>>,
+    finished_at: Option<DateTime<Utc>>,
+    /// The run the sync was recorded as, once completed
+    run: Option<RunId>,
+    error: Option<String>,
+    /// Key the client queued it under, so a retried request gets this job
+    /// back rather than queuing another
+    #[serde(default)]
+    idempotency_key: Option<String>,
+}
+
+/// What a queued sync covers.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(tag = "kind", rename_all = "kebab-case")]
+#[non_exhaustive]
+pub enum SyncTarget {
+    /// Projects whose labels match a selector such as `team=payments`;
+    /// every project if it's empty
+    Selector {
+        selector: String,
+    },
+    Projects {
+        ids: Vec<ProjectId>,
+    },
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum JobState {
+    Queued,
+    Running,
+    Completed,
+    Failed,
+}
+
+impl JobState {
+    pub fn key(self) -> &'static str {
+        match self {
+            JobState::Queued => "queued",
+            JobState::Running => "running",
+            JobState::Completed => "completed",
+            JobState::Failed => "failed",
+        }
+    }
+
+    pub fn from_key(key: &str) -> Self {
+        match key {
+            "queued" => JobState::Queued,
+            "running" => JobState::Running,
+            "completed" => JobState::Completed,
+            _ => JobState::Failed,
+        }
+    }
+}
+
+impl SyncJob {
+    pub fn new(
+        target: SyncTarget,
+        idempotency_key: Option<&str>,
+        queued_at: DateTime<Utc>,
+    ) -> Self {
+        SyncJob {
+            target,
+            state: JobState::Queued,
+            queued_at,
+            started_at: None,
+            heartbeat_at: None,
+            finished_at: None,
+            run: None,
+            error: None,
+            idempotency_key: idempotency_key.map(str::to_string),
+        }
+    }
+}
+
+/// `id, target, state, queued_at, started_at, finished_at, run_id, error,
+/// idempotency_key, heartbeat_at` of the `jobs` table.
+#[cfg(any(feature = "sqlite", feature = "postgres"))]
+pub(crate) type JobRow = (
+    i64,
+    sqlx::types::Json<SyncTarget>,
+    String,
+    DateTime<Utc>,
+    Option<DateTime<Utc>>,
+    Option<DateTime<Utc>>,
+    Option<i64>,
+    Option<String>,
+    Option<String>,
+    Option<DateTime<Utc>>,
+);
+
+#[cfg(any(feature = "sqlite", feature = "postgres"))]
+pub(crate) const JOB_COLUMNS: &str = "id, target, state, queued_at, started_at, finished_at, \
+     run_id, error, idempotency_key, heartbeat_at";
+
+#[cfg(any(feature = "sqlite", feature = "postgres"))]
+pub(crate) fn from_row(
+    (
+        id,
+        target,
+        state,
+        queued_at,
+        started_at,
+        finished_at,
+        run,
+        error,
+        idempotency_key,
+        heartbeat_at,
+    ): JobRow,
+) -> (JobId, SyncJob) {
+    let job = SyncJob {
+        target: target.0,
+        state: JobState::from_key(&state),
+        queued_at,
+        started_at,
+        heartbeat_at,
+        finished_at,
+        run: run.map(RunId),
+        error,
+        idempotency_key,
+    };
+    (JobId(id), job)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn states_round_trip_through_their_keys() {
+        for state in [
+            JobState::Queued,
+            JobState::Running,
+            JobState::Completed,
+            JobState::Failed,
+        ] {
+            assert_eq!(JobState::from_key(state.key()), state);
+        }
+    }
+
+    #[test]
+    fn unknown_keys_are_failed() {
+        assert_eq!(JobState::from_key("cancelled"), JobState::Failed);
+    }
+}
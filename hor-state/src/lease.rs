@@ -0,0 +1,108 @@
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use serde::Deserialize;
+
+use crate::StateStoreError;
+
+/// Decides which of several replicas is allowed to mutate refs.
+#[async_trait]
+pub trait LeaderLease: Send + Sync {
+    /// Whether this instance holds (or just acquired) the lease.
+    async fn is_leader(&self) -> Result<bool, StateStoreError>;
+}
+
+pub type LeaderLeaseRef = Arc<dyn LeaderLease>;
+
+/// A single replica is always the leader.
+pub struct StandaloneLease;
+
+#[async_trait]
+impl LeaderLease for StandaloneLease {
+    async fn is_leader(&self) -> Result<bool, StateStoreError> {
+        Ok(true)
+    }
+}
+
+#[derive(Deserialize, Debug, Clone, Default)]
+#[serde(tag = "kind", rename_all = "kebab-case")]
+pub enum LeaderElectionConfig {
+    #[default]
+    Standalone,
+    /// Session-level advisory lock; held for as long as the connection lives
+    #[cfg(feature = "postgres")]
+    Postgres {
+        url: String,
+        #[serde(default = "postgres::default_lock_key")]
+        lock_key: i64,
+    },
+}
+
+impl LeaderElectionConfig {
+    pub fn build(&self) -> Result<LeaderLeaseRef, StateStoreError> {
+        Ok(match self {
+            LeaderElectionConfig::Standalone => Arc::new(StandaloneLease),
+            #[cfg(feature = "postgres")]
+            LeaderElectionConfig::Postgres { url, lock_key } => {
+                Arc::new(postgres::PostgresLease::connect_lazy(url, *lock_key)?)
+            }
+        })
+    }
+}
+
+#[cfg(feature = "postgres")]
+pub mod postgres {
+    use async_trait::async_trait;
+    use sqlx::{Connection, PgConnection, PgPool};
+    use tokio::sync::Mutex;
+
+    use super::LeaderLease;
+    use crate::StateStoreError;
+
+    pub(super) fn default_lock_key() -> i64 {
+        // "hor" in ASCII
+        0x686f72
+    }
+
+    pub struct PostgresLease {
+        pool: PgPool,
+        lock_key: i64,
+        /// Connection whose session owns the advisory lock
+        held: Mutex<Option<PgConnection>>,
+    }
+
+    impl PostgresLease {
+        pub fn connect_lazy(url: &str, lock_key: i64) -> Result<Self, StateStoreError> {
+            Ok(PostgresLease {
+                pool: PgPool::connect_lazy(url)?,
+                lock_key,
+                held: Mutex::new(None),
+            })
+        }
+    }
+
+    #[async_trait]
+    impl LeaderLease for PostgresLease {
+        async fn is_leader(&self) -> Result<bool, StateStoreError> {
+            let mut held = self.held.lock().await;
+            if let Some(connection) = held.as_mut() {
+                if connection.ping().await.is_ok() {
+                    return Ok(true);
+                }
+                // The session, and the lock with it, is gone
+                *held = None;
+            }
+
+            // Detached so the locked session never goes back to the pool
+            let mut connection = self.pool.acquire().await?.detach();
+            let (acquired,): (bool,) = sqlx::query_as("SELECT pg_try_advisory_lock($1)")
+                .bind(self.lock_key)
+                .fetch_one(&mut connection)
+                .await?;
+            if acquired {
+                *held = Some(connection);
+            }
+            Ok(acquired)
+        }
+    }
+}
@@ -0,0 +1,438 @@
+pub mod candidate;
+pub mod jobs;
+pub mod lease;
+pub mod memory;
+pub mod outbox;
+#[cfg(feature = "postgres")]
+pub mod postgres;
+pub mod report;
+pub mod snapshot;
+#[cfg(feature = "sqlite")]
+pub mod sqlite;
+
+use std::{
+    fmt::{self, Display, Formatter},
+    sync::Arc,
+};
+
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use hor_registry::ProjectId;
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+pub use candidate::{
+    CandidateCheck, CandidateCommit, CandidateFlag, CandidateImage, CodeOwner, PolicyDecision,
+    ReleaseCandidate,
+};
+pub use jobs::{JobId, JobState, SyncJob, SyncTarget};
+pub use lease::{LeaderElectionConfig, LeaderLease, LeaderLeaseRef};
+pub use outbox::{
+    DriftReport, DriftedProject, Event, ManifestCommit, OutboxEntry, OutboxId, SyncedProject,
+};
+pub use report::{
+    ActionReport, ApiUsage, BlockReason, ProjectOutcome, ProjectReport, ReleaseInputs,
+    ReleaseManifest, ReleaseSource, SkipReason, SyncReport,
+};
+pub use snapshot::StateSnapshot;
+
+/// Persistence shared by every stateful feature: what is deployed where,
+/// what each run did, and the operator controls (approvals, pins,
+/// freezes, deletions) that influence the next run.
+#[async_trait]
+pub trait StateStore: Send + Sync {
+    /// Whether the state outlives the process.
+    fn persistent(&self) -> bool {
+        true
+    }
+
+    /// Connects and brings the schema in line with the configured
+    /// [`MigrationMode`], failing if the store isn't usable.
+    async fn prepare(&self) -> Result<(), StateStoreError> {
+        Ok(())
+    }
+
+    /// Versions of embedded migrations not yet applied to the backend.
+    async fn pending_migrations(&self) -> Result<Vec<i64>, StateStoreError> {
+        Ok(Vec::new())
+    }
+
+    async fn apply_migrations(&self) -> Result<(), StateStoreError> {
+        Ok(())
+    }
+
+    async fn last_deployment(
+        &self,
+        project: &ProjectId,
+        env: &str,
+    ) -> Result<Option<Deployment>, StateStoreError>;
+
+    async fn record_deployment(
+        &self,
+        project: &ProjectId,
+        env: &str,
+        deployment: &Deployment,
+    ) -> Result<(), StateStoreError>;
+
+    /// What `git_ref` (e.g. `tags/prod`) of the project's repository last
+    /// pointed at.
+    async fn ref_state(
+        &self,
+        project: &ProjectId,
+        git_ref: &str,
+    ) -> Result<Option<RefState>, StateStoreError>;
+
+    async fn record_ref_state(
+        &self,
+        project: &ProjectId,
+        git_ref: &str,
+        state: &RefState,
+    ) -> Result<(), StateStoreError>;
+
+    /// Records that `project` took a slot of the promotion budget of
+    /// `env`. Promotions older than [`PROMOTION_RETENTION_DAYS`] are
+    /// forgotten.
+    async fn record_promotion(
+        &self,
+        project: &ProjectId,
+        env: &str,
+        at: DateTime<Utc>,
+    ) -> Result<(), StateStoreError>;
+
+    /// Promotions to `env` at or after `since`, of every project, oldest
+    /// first.
+    async fn promotions_since(
+        &self,
+        env: &str,
+        since: DateTime<Utc>,
+    ) -> Result<Vec<(ProjectId, DateTime<Utc>)>, StateStoreError>;
+
+    async fn record_run(&self, report: &SyncReport) -> Result<RunId, StateStoreError>;
+
+    async fn run(&self, id: RunId) -> Result<Option<SyncReport>, StateStoreError>;
+
+    /// The most recent runs, newest first.
+    async fn recent_runs(&self, limit: usize) -> Result<Vec<(RunId, SyncReport)>, StateStoreError>;
+
+    async fn add_approval(&self, approval: &Approval) -> Result<(), StateStoreError>;
+
+    async fn approvals(
+        &self,
+        project: &ProjectId,
+        env: &str,
+        sha: &str,
+    ) -> Result<Vec<Approval>, StateStoreError>;
+
+    /// Pins `env` to a specific SHA, or removes the pin with `None`.
+    async fn set_pin(
+        &self,
+        project: &ProjectId,
+        env: &str,
+        pin: Option<&Pin>,
+    ) -> Result<(), StateStoreError>;
+
+    async fn pin(&self, project: &ProjectId, env: &str) -> Result<Option<Pin>, StateStoreError>;
+
+    /// Freezes the scope, or lifts the freeze with `None`.
+    async fn set_freeze(
+        &self,
+        scope: &FreezeScope,
+        freeze: Option<&Freeze>,
+    ) -> Result<(), StateStoreError>;
+
+    async fn freeze(&self, scope: &FreezeScope) -> Result<Option<Freeze>, StateStoreError>;
+
+    /// Soft-deletes the project unless it already is, in one step so
+    /// concurrent deletions keep the first one. False if it already was.
+    async fn insert_deletion(
+        &self,
+        project: &ProjectId,
+        deletion: &Deletion,
+    ) -> Result<bool, StateStoreError>;
+
+    /// Restores a soft-deleted project. False if it wasn't deleted.
+    async fn remove_deletion(&self, project: &ProjectId) -> Result<bool, StateStoreError>;
+
+    async fn deletion(&self, project: &ProjectId) -> Result<Option<Deletion>, StateStoreError>;
+
+    /// Every soft-deleted project, purged or not.
+    async fn deletions(&self) -> Result<Vec<(ProjectId, Deletion)>, StateStoreError>;
+
+    /// Forgets the deployments, ref states, promotions, approvals, pins and
+    /// freeze of a soft-deleted project, and marks it purged at `at`. Runs,
+    /// which cover many projects at once, are kept.
+    async fn purge_project(
+        &self,
+        project: &ProjectId,
+        at: DateTime<Utc>,
+    ) -> Result<(), StateStoreError>;
+
+    /// Queues `entries`, handing back their ids in the same order.
+    async fn enqueue_outbox(
+        &self,
+        entries: &[OutboxEntry],
+    ) -> Result<Vec<OutboxId>, StateStoreError>;
+
+    /// Entries held back until the mutation they announce is confirmed,
+    /// oldest first.
+    async fn pending_outbox(&self) -> Result<Vec<(OutboxId, OutboxEntry)>, StateStoreError>;
+
+    /// Makes pending entries due at `at`; entries no longer pending, or
+    /// gone, are left alone.
+    async fn release_outbox(
+        &self,
+        ids: &[OutboxId],
+        at: DateTime<Utc>,
+    ) -> Result<(), StateStoreError>;
+
+    /// Entries whose next attempt is due at `now`, oldest first.
+    async fn due_outbox(
+        &self,
+        now: DateTime<Utc>,
+        limit: usize,
+    ) -> Result<Vec<(OutboxId, OutboxEntry)>, StateStoreError>;
+
+    /// Removes a delivered entry, or a pending one whose mutation didn't
+    /// happen.
+    async fn complete_outbox(&self, id: OutboxId) -> Result<(), StateStoreError>;
+
+    /// Records a failed attempt; `next_attempt_at: None` gives up on the
+    /// entry but keeps it for inspection.
+    async fn retry_outbox(
+        &self,
+        id: OutboxId,
+        next_attempt_at: Option<DateTime<Utc>>,
+        error: &str,
+    ) -> Result<(), StateStoreError>;
+
+    /// Queues a sync of `target`. A job already queued under
+    /// `idempotency_key` is handed back instead of queuing another.
+    async fn enqueue_job(
+        &self,
+        target: &SyncTarget,
+        idempotency_key: Option<&str>,
+        at: DateTime<Utc>,
+    ) -> Result<JobId, StateStoreError>;
+
+    /// Marks the oldest queued job running at `now` and hands it out.
+    /// Running jobs without a heartbeat since before `stale_before` count
+    /// as queued, as whoever claimed them went away.
+    async fn claim_job(
+        &self,
+        now: DateTime<Utc>,
+        stale_before: DateTime<Utc>,
+    ) -> Result<Option<(JobId, SyncJob)>, StateStoreError>;
+
+    /// Records that the claimed job `id` is still running at `at`, so it
+    /// isn't claimed again.
+    async fn heartbeat_job(&self, id: JobId, at: DateTime<Utc>) -> Result<(), StateStoreError>;
+
+    /// Records how a claimed job ended: completed as `run`, or failed
+    /// with `error`.
+    async fn finish_job(
+        &self,
+        id: JobId,
+        run: Option<RunId>,
+        error: Option<&str>,
+        at: DateTime<Utc>,
+    ) -> Result<(), StateStoreError>;
+
+    /// The most recent jobs, newest first.
+    async fn recent_jobs(&self, limit: usize) -> Result<Vec<(JobId, SyncJob)>, StateStoreError>;
+
+    /// Copies out everything in the store.
+    async fn export_state(&self) -> Result<StateSnapshot, StateStoreError>;
+
+    /// Writes every entry of `snapshot` into the store, keeping run and
+    /// outbox ids and replacing entries that already exist.
+    async fn import_state(&self, snapshot: &StateSnapshot) -> Result<(), StateStoreError>;
+}
+
+pub type StateStoreRef = Arc<dyn StateStore>;
+
+/// How many days promotions are kept, bounding the window of promotion
+/// budgets.
+pub const PROMOTION_RETENTION_DAYS: i64 = 7;
+
+/// Which backend to persist state in.
+#[derive(Deserialize, Debug, Clone, Default)]
+#[serde(tag = "kind", rename_all = "kebab-case")]
+pub enum StateStoreConfig {
+    #[default]
+    Memory,
+    #[cfg(feature = "sqlite")]
+    Sqlite {
+        url: String,
+        #[serde(default)]
+        migrations: MigrationMode,
+    },
+    #[cfg(feature = "postgres")]
+    Postgres {
+        url: String,
+        #[serde(default)]
+        migrations: MigrationMode,
+    },
+}
+
+impl StateStoreConfig {
+    /// Builds the configured store. SQL backends connect lazily, so this
+    /// doesn't block on the database being reachable.
+    pub fn build(&self) -> Result<StateStoreRef, StateStoreError> {
+        Ok(match self {
+            StateStoreConfig::Memory => Arc::new(memory::MemoryStateStore::default()),
+            #[cfg(feature = "sqlite")]
+            StateStoreConfig::Sqlite { url, migrations } => {
+                Arc::new(sqlite::SqliteStateStore::connect_lazy(url, *migrations)?)
+            }
+            #[cfg(feature = "postgres")]
+            StateStoreConfig::Postgres { url, migrations } => Arc::new(
+                postgres::PostgresStateStore::connect_lazy(url, *migrations)?,
+            ),
+        })
+    }
+}
+
+/// What SQL backends do with outstanding schema migrations on first use.
+#[derive(Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum MigrationMode {
+    /// Apply them
+    #[default]
+    Apply,
+    /// Refuse to run, for environments where schema changes are rolled out
+    /// separately
+    Check,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[serde(transparent)]
+pub struct RunId(pub i64);
+
+impl Display for RunId {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+#[jsm::public]
+struct Deployment {
+    sha: String,
+    deployed_at: DateTime<Utc>,
+}
+
+/// Last observation of a git ref, kept so a restarted instance can
+/// revalidate it instead of refetching, and notice it moved meanwhile.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+#[jsm::public]
+struct RefState {
+    sha: String,
+    /// Validator of the response the SHA was read from
+    etag: Option<String>,
+    observed_at: DateTime<Utc>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+#[jsm::public]
+struct Approval {
+    project: ProjectId,
+    env: String,
+    sha: String,
+    approver: String,
+    approved_at: DateTime<Utc>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+#[jsm::public]
+struct Pin {
+    sha: String,
+    reason: Option<String>,
+    pinned_at: DateTime<Utc>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+#[jsm::public]
+struct Freeze {
+    reason: Option<String>,
+    frozen_at: DateTime<Utc>,
+}
+
+/// A soft-deleted project: not synced, but its history is kept until it's
+/// purged.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+#[jsm::public]
+struct Deletion {
+    reason: Option<String>,
+    deleted_at: DateTime<Utc>,
+    /// When the project's history was forgotten, `None` while it's kept
+    purged_at: Option<DateTime<Utc>>,
+}
+
+/// What a freeze applies to.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, Hash)]
+#[serde(rename_all = "kebab-case")]
+pub enum FreezeScope {
+    Global,
+    Project(ProjectId),
+}
+
+impl FreezeScope {
+    /// Key used by the SQL backends, namespaced so that no project id can
+    /// be taken for the global scope.
+    pub fn key(&self) -> String {
+        match self {
+            FreezeScope::Global => "global".to_string(),
+            FreezeScope::Project(id) => format!("project:{id}"),
+        }
+    }
+
+    pub fn from_key(key: &str) -> Self {
+        match key.strip_prefix("project:") {
+            Some(id) => FreezeScope::Project(ProjectId::new(id)),
+            None => FreezeScope::Global,
+        }
+    }
+}
+
+#[derive(Error, Debug)]
+#[non_exhaustive]
+pub enum StateStoreError {
+    #[cfg(any(feature = "sqlite", feature = "postgres"))]
+    #[error("state store database error")]
+    Sqlx(#[from] sqlx::Error),
+    #[cfg(any(feature = "sqlite", feature = "postgres"))]
+    #[error("unable to migrate the state store schema")]
+    Migrate(#[from] sqlx::migrate::MigrateError),
+    #[error("state store has pending migrations {0:?}")]
+    PendingMigrations(Vec<i64>),
+    #[error("unable to (de)serialize stored state")]
+    Serde(#[from] serde_json::Error),
+    #[error("unsupported state snapshot version {0}")]
+    SnapshotVersion(u32),
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn freeze_scopes_round_trip_through_their_keys() {
+        let scopes = [
+            FreezeScope::Global,
+            FreezeScope::Project(ProjectId::new("github/acme/api/prod")),
+            FreezeScope::Project(ProjectId::new("*")),
+            FreezeScope::Project(ProjectId::new("global")),
+        ];
+        for scope in &scopes {
+            assert_eq!(&FreezeScope::from_key(&scope.key()), scope);
+        }
+        let keys: std::collections::HashSet<_> = scopes.iter().map(FreezeScope::key).collect();
+        assert_eq!(keys.len(), scopes.len());
+    }
+}
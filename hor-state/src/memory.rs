@@ -0,0 +1,573 @@
+use std::{
+    collections::{hash_map::Entry, BTreeMap, HashMap},
+    sync::Mutex,
+};
+
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use hor_registry::ProjectId;
+
+use crate::{
+    snapshot::{
+        DeletionEntry, DeploymentEntry, FreezeEntry, JobEntry, OutboxSnapshotEntry, PinEntry,
+        PromotionEntry, RefStateEntry, RunEntry, SNAPSHOT_VERSION,
+    },
+    Approval, Deletion, Deployment, Freeze, FreezeScope, JobId, JobState, OutboxEntry, OutboxId,
+    Pin, RefState, RunId, StateSnapshot, StateStore, StateStoreError, SyncJob, SyncReport,
+    SyncTarget, PROMOTION_RETENTION_DAYS,
+};
+
+type EnvKey = (ProjectId, String);
+
+/// Process-local store; everything is lost on restart.
+#[derive(Default)]
+pub struct MemoryStateStore {
+    state: Mutex<MemoryState>,
+}
+
+#[derive(Default)]
+struct MemoryState {
+    deployments: HashMap<EnvKey, Deployment>,
+    /// Keyed by project and ref rather than env
+    refs: HashMap<EnvKey, RefState>,
+    runs: BTreeMap<RunId, SyncReport>,
+    approvals: Vec<Approval>,
+    pins: HashMap<EnvKey, Pin>,
+    freezes: HashMap<FreezeScope, Freeze>,
+    outbox: BTreeMap<OutboxId, OutboxEntry>,
+    /// Oldest first
+    promotions: Vec<PromotionEntry>,
+    jobs: BTreeMap<JobId, SyncJob>,
+    deletions: HashMap<ProjectId, Deletion>,
+}
+
+impl MemoryStateStore {
+    fn state(&self) -> std::sync::MutexGuard<'_, MemoryState> {
+        // Every mutation is a single insert or remove, so a poisoned lock is still consistent
+        self.state
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+fn key(project: &ProjectId, env: &str) -> EnvKey {
+    (project.clone(), env.to_string())
+}
+
+#[async_trait]
+impl StateStore for MemoryStateStore {
+    fn persistent(&self) -> bool {
+        false
+    }
+
+    async fn last_deployment(
+        &self,
+        project: &ProjectId,
+        env: &str,
+    ) -> Result<Option<Deployment>, StateStoreError> {
+        Ok(self.state().deployments.get(&key(project, env)).cloned())
+    }
+
+    async fn record_deployment(
+        &self,
+        project: &ProjectId,
+        env: &str,
+        deployment: &Deployment,
+    ) -> Result<(), StateStoreError> {
+        self.state()
+            .deployments
+            .insert(key(project, env), deployment.clone());
+        Ok(())
+    }
+
+    async fn ref_state(
+        &self,
+        project: &ProjectId,
+        git_ref: &str,
+    ) -> Result<Option<RefState>, StateStoreError> {
+        Ok(self.state().refs.get(&key(project, git_ref)).cloned())
+    }
+
+    async fn record_ref_state(
+        &self,
+        project: &ProjectId,
+        git_ref: &str,
+        state: &RefState,
+    ) -> Result<(), StateStoreError> {
+        self.state()
+            .refs
+            .insert(key(project, git_ref), state.clone());
+        Ok(())
+    }
+
+    async fn record_promotion(
+        &self,
+        project: &ProjectId,
+        env: &str,
+        at: DateTime<Utc>,
+    ) -> Result<(), StateStoreError> {
+        let mut state = self.state();
+        state.promotions.retain(|promotion| {
+            promotion.promoted_at >= at - chrono::Duration::days(PROMOTION_RETENTION_DAYS)
+        });
+        state.promotions.push(PromotionEntry {
+            project: project.clone(),
+            env: env.to_string(),
+            promoted_at: at,
+        });
+        state
+            .promotions
+            .sort_by_key(|promotion| promotion.promoted_at);
+        Ok(())
+    }
+
+    async fn promotions_since(
+        &self,
+        env: &str,
+        since: DateTime<Utc>,
+    ) -> Result<Vec<(ProjectId, DateTime<Utc>)>, StateStoreError> {
+        Ok(self
+            .state()
+            .promotions
+            .iter()
+            .filter(|promotion| promotion.env == env && promotion.promoted_at >= since)
+            .map(|promotion| (promotion.project.clone(), promotion.promoted_at))
+            .collect())
+    }
+
+    async fn record_run(&self, report: &SyncReport) -> Result<RunId, StateStoreError> {
+        let mut state = self.state();
+        let id = RunId(state.runs.keys().next_back().map_or(1, |last| last.0 + 1));
+        state.runs.insert(id, report.clone());
+        Ok(id)
+    }
+
+    async fn run(&self, id: RunId) -> Result<Option<SyncReport>, StateStoreError> {
+        Ok(self.state().runs.get(&id).cloned())
+    }
+
+    async fn recent_runs(&self, limit: usize) -> Result<Vec<(RunId, SyncReport)>, StateStoreError> {
+        let state = self.state();
+        Ok(state
+            .runs
+            .iter()
+            .rev()
+            .take(limit)
+            .map(|(id, report)| (*id, report.clone()))
+            .collect())
+    }
+
+    async fn add_approval(&self, approval: &Approval) -> Result<(), StateStoreError> {
+        let mut state = self.state();
+        let duplicate = state.approvals.iter().any(|existing| {
+            existing.project == approval.project
+                && existing.env == approval.env
+                && existing.sha == approval.sha
+                && existing.approver == approval.approver
+        });
+        if !duplicate {
+            state.approvals.push(approval.clone());
+        }
+        Ok(())
+    }
+
+    async fn approvals(
+        &self,
+        project: &ProjectId,
+        env: &str,
+        sha: &str,
+    ) -> Result<Vec<Approval>, StateStoreError> {
+        Ok(self
+            .state()
+            .approvals
+            .iter()
+            .filter(|approval| {
+                &approval.project == project && approval.env == env && approval.sha == sha
+            })
+            .cloned()
+            .collect())
+    }
+
+    async fn set_pin(
+        &self,
+        project: &ProjectId,
+        env: &str,
+        pin: Option<&Pin>,
+    ) -> Result<(), StateStoreError> {
+        let mut state = self.state();
+        match pin {
+            Some(pin) => state.pins.insert(key(project, env), pin.clone()),
+            None => state.pins.remove(&key(project, env)),
+        };
+        Ok(())
+    }
+
+    async fn pin(&self, project: &ProjectId, env: &str) -> Result<Option<Pin>, StateStoreError> {
+        Ok(self.state().pins.get(&key(project, env)).cloned())
+    }
+
+    async fn set_freeze(
+        &self,
+        scope: &FreezeScope,
+        freeze: Option<&Freeze>,
+    ) -> Result<(), StateStoreError> {
+        let mut state = self.state();
+        match freeze {
+            Some(freeze) => state.freezes.insert(scope.clone(), freeze.clone()),
+            None => state.freezes.remove(scope),
+        };
+        Ok(())
+    }
+
+    async fn freeze(&self, scope: &FreezeScope) -> Result<Option<Freeze>, StateStoreError> {
+        Ok(self.state().freezes.get(scope).cloned())
+    }
+
+    async fn insert_deletion(
+        &self,
+        project: &ProjectId,
+        deletion: &Deletion,
+    ) -> Result<bool, StateStoreError> {
+        match self.state().deletions.entry(project.clone()) {
+            Entry::Occupied(_) => Ok(false),
+            Entry::Vacant(entry) => {
+                entry.insert(deletion.clone());
+                Ok(true)
+            }
+        }
+    }
+
+    async fn remove_deletion(&self, project: &ProjectId) -> Result<bool, StateStoreError> {
+        Ok(self.state().deletions.remove(project).is_some())
+    }
+
+    async fn deletion(&self, project: &ProjectId) -> Result<Option<Deletion>, StateStoreError> {
+        Ok(self.state().deletions.get(project).cloned())
+    }
+
+    async fn deletions(&self) -> Result<Vec<(ProjectId, Deletion)>, StateStoreError> {
+        Ok(self
+            .state()
+            .deletions
+            .iter()
+            .map(|(project, deletion)| (project.clone(), deletion.clone()))
+            .collect())
+    }
+
+    async fn purge_project(
+        &self,
+        project: &ProjectId,
+        at: DateTime<Utc>,
+    ) -> Result<(), StateStoreError> {
+        let mut state = self.state();
+        state.deployments.retain(|(id, _), _| id != project);
+        state.refs.retain(|(id, _), _| id != project);
+        state.promotions.retain(|entry| &entry.project != project);
+        state
+            .approvals
+            .retain(|approval| &approval.project != project);
+        state.pins.retain(|(id, _), _| id != project);
+        state.freezes.remove(&FreezeScope::Project(project.clone()));
+        if let Some(deletion) = state.deletions.get_mut(project) {
+            deletion.purged_at = Some(at);
+        }
+        Ok(())
+    }
+
+    async fn enqueue_outbox(
+        &self,
+        entries: &[OutboxEntry],
+    ) -> Result<Vec<OutboxId>, StateStoreError> {
+        let mut state = self.state();
+        let mut ids = Vec::with_capacity(entries.len());
+        for entry in entries {
+            let id = OutboxId(state.outbox.keys().next_back().map_or(1, |last| last.0 + 1));
+            state.outbox.insert(id, entry.clone());
+            ids.push(id);
+        }
+        Ok(ids)
+    }
+
+    async fn pending_outbox(&self) -> Result<Vec<(OutboxId, OutboxEntry)>, StateStoreError> {
+        Ok(self
+            .state()
+            .outbox
+            .iter()
+            .filter(|(_, entry)| entry.pending)
+            .map(|(id, entry)| (*id, entry.clone()))
+            .collect())
+    }
+
+    async fn release_outbox(
+        &self,
+        ids: &[OutboxId],
+        at: DateTime<Utc>,
+    ) -> Result<(), StateStoreError> {
+        let mut state = self.state();
+        for id in ids {
+            if let Some(entry) = state.outbox.get_mut(id).filter(|entry| entry.pending) {
+                entry.pending = false;
+                entry.next_attempt_at = Some(at);
+            }
+        }
+        Ok(())
+    }
+
+    async fn due_outbox(
+        &self,
+        now: DateTime<Utc>,
+        limit: usize,
+    ) -> Result<Vec<(OutboxId, OutboxEntry)>, StateStoreError> {
+        Ok(self
+            .state()
+            .outbox
+            .iter()
+            .filter(|(_, entry)| entry.next_attempt_at.is_some_and(|at| at <= now))
+            .take(limit)
+            .map(|(id, entry)| (*id, entry.clone()))
+            .collect())
+    }
+
+    async fn complete_outbox(&self, id: OutboxId) -> Result<(), StateStoreError> {
+        self.state().outbox.remove(&id);
+        Ok(())
+    }
+
+    async fn retry_outbox(
+        &self,
+        id: OutboxId,
+        next_attempt_at: Option<DateTime<Utc>>,
+        error: &str,
+    ) -> Result<(), StateStoreError> {
+        if let Some(entry) = self.state().outbox.get_mut(&id) {
+            entry.attempts += 1;
+            entry.next_attempt_at = next_attempt_at;
+            entry.last_error = Some(error.to_string());
+        }
+        Ok(())
+    }
+
+    async fn enqueue_job(
+        &self,
+        target: &SyncTarget,
+        idempotency_key: Option<&str>,
+        at: DateTime<Utc>,
+    ) -> Result<JobId, StateStoreError> {
+        let mut state = self.state();
+        if let Some(key) = idempotency_key {
+            let queued = state
+                .jobs
+                .iter()
+                .find(|(_, job)| job.idempotency_key.as_deref() == Some(key));
+            if let Some((id, _)) = queued {
+                return Ok(*id);
+            }
+        }
+        let id = JobId(state.jobs.keys().next_back().map_or(1, |last| last.0 + 1));
+        state
+            .jobs
+            .insert(id, SyncJob::new(target.clone(), idempotency_key, at));
+        Ok(id)
+    }
+
+    async fn claim_job(
+        &self,
+        now: DateTime<Utc>,
+        stale_before: DateTime<Utc>,
+    ) -> Result<Option<(JobId, SyncJob)>, StateStoreError> {
+        let mut state = self.state();
+        let claimable = state.jobs.iter_mut().find(|(_, job)| match job.state {
+            JobState::Queued => true,
+            JobState::Running => job
+                .heartbeat_at
+                .or(job.started_at)
+                .is_some_and(|at| at < stale_before),
+            _ => false,
+        });
+        Ok(claimable.map(|(id, job)| {
+            job.state = JobState::Running;
+            job.started_at = Some(now);
+            job.heartbeat_at = Some(now);
+            (*id, job.clone())
+        }))
+    }
+
+    async fn heartbeat_job(&self, id: JobId, at: DateTime<Utc>) -> Result<(), StateStoreError> {
+        if let Some(job) = self.state().jobs.get_mut(&id) {
+            if job.state == JobState::Running {
+                job.heartbeat_at = Some(at);
+            }
+        }
+        Ok(())
+    }
+
+    async fn finish_job(
+        &self,
+        id: JobId,
+        run: Option<RunId>,
+        error: Option<&str>,
+        at: DateTime<Utc>,
+    ) -> Result<(), StateStoreError> {
+        if let Some(job) = self.state().jobs.get_mut(&id) {
+            job.state = match error {
+                Some(_) => JobState::Failed,
+                None => JobState::Completed,
+            };
+            job.finished_at = Some(at);
+            job.run = run;
+            job.error = error.map(str::to_string);
+        }
+        Ok(())
+    }
+
+    async fn recent_jobs(&self, limit: usize) -> Result<Vec<(JobId, SyncJob)>, StateStoreError> {
+        Ok(self
+            .state()
+            .jobs
+            .iter()
+            .rev()
+            .take(limit)
+            .map(|(id, job)| (*id, job.clone()))
+            .collect())
+    }
+
+    async fn export_state(&self) -> Result<StateSnapshot, StateStoreError> {
+        let state = self.state();
+        Ok(StateSnapshot {
+            version: SNAPSHOT_VERSION,
+            deployments: state
+                .deployments
+                .iter()
+                .map(|((project, env), deployment)| DeploymentEntry {
+                    project: project.clone(),
+                    env: env.clone(),
+                    deployment: deployment.clone(),
+                })
+                .collect(),
+            refs: state
+                .refs
+                .iter()
+                .map(|((project, git_ref), ref_state)| RefStateEntry {
+                    project: project.clone(),
+                    git_ref: git_ref.clone(),
+                    state: ref_state.clone(),
+                })
+                .collect(),
+            runs: state
+                .runs
+                .iter()
+                .map(|(id, report)| RunEntry {
+                    id: *id,
+                    report: report.clone(),
+                })
+                .collect(),
+            approvals: state.approvals.clone(),
+            pins: state
+                .pins
+                .iter()
+                .map(|((project, env), pin)| PinEntry {
+                    project: project.clone(),
+                    env: env.clone(),
+                    pin: pin.clone(),
+                })
+                .collect(),
+            freezes: state
+                .freezes
+                .iter()
+                .map(|(scope, freeze)| FreezeEntry {
+                    scope: scope.clone(),
+                    freeze: freeze.clone(),
+                })
+                .collect(),
+            outbox: state
+                .outbox
+                .iter()
+                .map(|(id, entry)| OutboxSnapshotEntry {
+                    id: *id,
+                    entry: entry.clone(),
+                })
+                .collect(),
+            promotions: state.promotions.clone(),
+            jobs: state
+                .jobs
+                .iter()
+                .map(|(id, job)| JobEntry {
+                    id: *id,
+                    job: job.clone(),
+                })
+                .collect(),
+            deletions: state
+                .deletions
+                .iter()
+                .map(|(project, deletion)| DeletionEntry {
+                    project: project.clone(),
+                    deletion: deletion.clone(),
+                })
+                .collect(),
+        }
+        .sorted())
+    }
+
+    async fn import_state(&self, snapshot: &StateSnapshot) -> Result<(), StateStoreError> {
+        snapshot.check_version()?;
+
+        let mut state = self.state();
+        for entry in &snapshot.deployments {
+            state
+                .deployments
+                .insert(key(&entry.project, &entry.env), entry.deployment.clone());
+        }
+        for entry in &snapshot.refs {
+            state
+                .refs
+                .insert(key(&entry.project, &entry.git_ref), entry.state.clone());
+        }
+        for entry in &snapshot.runs {
+            state.runs.insert(entry.id, entry.report.clone());
+        }
+        for approval in &snapshot.approvals {
+            state.approvals.retain(|existing| {
+                existing.project != approval.project
+                    || existing.env != approval.env
+                    || existing.sha != approval.sha
+                    || existing.approver != approval.approver
+            });
+            state.approvals.push(approval.clone());
+        }
+        for entry in &snapshot.pins {
+            state
+                .pins
+                .insert(key(&entry.project, &entry.env), entry.pin.clone());
+        }
+        for entry in &snapshot.freezes {
+            state
+                .freezes
+                .insert(entry.scope.clone(), entry.freeze.clone());
+        }
+        for entry in &snapshot.outbox {
+            state.outbox.insert(entry.id, entry.entry.clone());
+        }
+        for entry in &snapshot.jobs {
+            state.jobs.insert(entry.id, entry.job.clone());
+        }
+        for entry in &snapshot.deletions {
+            state
+                .deletions
+                .insert(entry.project.clone(), entry.deletion.clone());
+        }
+        for entry in &snapshot.promotions {
+            let duplicate = state.promotions.iter().any(|existing| {
+                existing.project == entry.project
+                    && existing.env == entry.env
+                    && existing.promoted_at == entry.promoted_at
+            });
+            if !duplicate {
+                state.promotions.push(entry.clone());
+            }
+        }
+        state
+            .promotions
+            .sort_by_key(|promotion| promotion.promoted_at);
+        Ok(())
+    }
+}
@@ -0,0 +1,200 @@
+use chrono::{DateTime, Utc};
+use hor_registry::ProjectId;
+use serde::{Deserialize, Serialize};
+
+use crate::{ProjectOutcome, RunId};
+
+/// Something worth announcing to the outside world.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(tag = "kind", rename_all = "kebab-case")]
+#[non_exhaustive]
+pub enum Event {
+    /// An env ref was created (`from` is empty) or moved
+    RefMoved {
+        project: ProjectId,
+        owner: String,
+        repo: String,
+        env: String,
+        from: Option<String>,
+        to: String,
+        at: DateTime<Utc>,
+        /// Announced by a sync in shadow mode; the ref didn't move
+        #[serde(default)]
+        simulated: bool,
+        /// Risk score of the release, if its policy scores them
+        #[serde(default)]
+        risk: Option<u32>,
+    },
+    /// A release failed its verification; the env ref was moved back to
+    /// `rolled_back_to` if it could be
+    VerificationFailed {
+        project: ProjectId,
+        owner: String,
+        repo: String,
+        env: String,
+        sha: String,
+        reason: String,
+        rolled_back_to: Option<String>,
+        at: DateTime<Utc>,
+    },
+    /// A release train departed, releasing `commits` (newest first, as
+    /// far as GitHub lists them)
+    TrainDeparted {
+        project: ProjectId,
+        owner: String,
+        repo: String,
+        env: String,
+        departure: DateTime<Utc>,
+        from: Option<String>,
+        to: String,
+        commits: Vec<ManifestCommit>,
+        at: DateTime<Utc>,
+        #[serde(default)]
+        simulated: bool,
+    },
+    /// A sync finished, with how each project came out; only queued for
+    /// sinks that stream every sync, e.g. for analytics
+    SyncFinished {
+        run: RunId,
+        started_at: DateTime<Utc>,
+        finished_at: DateTime<Utc>,
+        projects: Vec<SyncedProject>,
+        #[serde(default)]
+        simulated: bool,
+    },
+    /// The daily digest of the projects whose env ref isn't where their
+    /// releases should have taken it
+    DriftReport {
+        #[serde(flatten)]
+        report: DriftReport,
+    },
+}
+
+/// How one project came out of a sync.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+#[jsm::public]
+struct SyncedProject {
+    id: ProjectId,
+    env: String,
+    outcome: ProjectOutcome,
+}
+
+/// Which projects drifted from where their releases should have taken
+/// them, going by the runs of a day.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+#[jsm::public]
+struct DriftReport {
+    /// Runs from here on were looked at
+    since: DateTime<Utc>,
+    at: DateTime<Utc>,
+    /// Projects whose env ref was where it should be when last synced
+    converged: usize,
+    /// Every other project synced since `since`, longest drifted first
+    drifted: Vec<DriftedProject>,
+    /// Compiled by an instance in shadow mode, from simulated runs
+    #[serde(default)]
+    simulated: bool,
+}
+
+/// A project whose env ref wasn't where it should be when last synced.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+#[jsm::public]
+struct DriftedProject {
+    id: ProjectId,
+    env: String,
+    /// How the last sync of the project came out
+    outcome: ProjectOutcome,
+    /// Start of the first of the runs since which the project has been
+    /// drifted, as far back as the report looks
+    drifted_since: DateTime<Utc>,
+}
+
+/// One released commit, e.g. aboard a release train.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+#[jsm::public]
+struct ManifestCommit {
+    sha: String,
+    /// First line of the message
+    summary: String,
+    /// GitHub login, if the author has an account
+    author: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[serde(transparent)]
+pub struct OutboxId(pub i64);
+
+/// One pending delivery of an event to one sink.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(rename_all = "kebab-case")]
+#[jsm::public]
+struct OutboxEntry {
+    sink: String,
+    event: Event,
+    attempts: u32,
+    /// `None` once delivery has been given up on, or while pending
+    next_attempt_at: Option<DateTime<Utc>>,
+    last_error: Option<String>,
+    /// Written ahead of the mutation it announces, and only delivered once
+    /// that is known to have happened
+    #[serde(default)]
+    pending: bool,
+}
+
+impl OutboxEntry {
+    pub fn new(sink: impl Into<String>, event: Event, now: DateTime<Utc>) -> Self {
+        OutboxEntry {
+            sink: sink.into(),
+            event,
+            attempts: 0,
+            next_attempt_at: Some(now),
+            last_error: None,
+            pending: false,
+        }
+    }
+
+    /// An entry held back until it's released.
+    pub fn pending(sink: impl Into<String>, event: Event) -> Self {
+        OutboxEntry {
+            next_attempt_at: None,
+            pending: true,
+            ..OutboxEntry::new(sink, event, DateTime::<Utc>::MIN_UTC)
+        }
+    }
+}
+
+/// `id, sink, event, attempts, next_attempt_at, last_error, pending` of the
+/// `outbox` table.
+#[cfg(any(feature = "sqlite", feature = "postgres"))]
+pub(crate) type OutboxRow = (
+    i64,
+    String,
+    sqlx::types::Json<Event>,
+    i64,
+    Option<DateTime<Utc>>,
+    Option<String>,
+    bool,
+);
+
+#[cfg(any(feature = "sqlite", feature = "postgres"))]
+pub(crate) const OUTBOX_COLUMNS: &str =
+    "id, sink, event, attempts, next_attempt_at, last_error, pending";
+
+#[cfg(any(feature = "sqlite", feature = "postgres"))]
+pub(crate) fn from_row(
+    (id, sink, event, attempts, next_attempt_at, last_error, pending): OutboxRow,
+) -> (OutboxId, OutboxEntry) {
+    let entry = OutboxEntry {
+        sink,
+        event: event.0,
+        attempts: u32::try_from(attempts).unwrap_or(u32::MAX),
+        next_attempt_at,
+        last_error,
+        pending,
+    };
+    (OutboxId(id), entry)
+}
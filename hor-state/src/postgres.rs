@@ -0,0 +1,851 @@
+use std::collections::HashSet;
+
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use hor_registry::ProjectId;
+use sqlx::{
+    migrate::{Migrate, Migrator},
+    types::Json,
+    PgPool,
+};
+use tokio::sync::OnceCell;
+
+use crate::{
+    jobs::{self, JobRow, JOB_COLUMNS},
+    outbox::{self, OutboxRow, OUTBOX_COLUMNS},
+    snapshot::{
+        deletion_from_row, DeletionEntry, DeletionRow, JobEntry, OutboxSnapshotEntry, SnapshotRows,
+    },
+    Approval, Deletion, Deployment, Freeze, FreezeScope, JobId, JobState, MigrationMode,
+    OutboxEntry, OutboxId, Pin, RefState, RunId, StateSnapshot, StateStore, StateStoreError,
+    SyncJob, SyncReport, SyncTarget, PROMOTION_RETENTION_DAYS,
+};
+
+static MIGRATOR: Migrator = sqlx::migrate!("./migrations/postgres");
+
+const UPSERT_REF_STATE: &str =
+    "INSERT INTO ref_states (project_id, git_ref, sha, etag, observed_at) \
+     VALUES ($1, $2, $3, $4, $5) \
+     ON CONFLICT (project_id, git_ref) DO UPDATE \
+     SET sha = excluded.sha, etag = excluded.etag, observed_at = excluded.observed_at";
+
+const UPSERT_DELETION: &str =
+    "INSERT INTO deleted_projects (project_id, reason, deleted_at, purged_at) \
+     VALUES ($1, $2, $3, $4) \
+     ON CONFLICT (project_id) DO UPDATE \
+     SET reason = excluded.reason, deleted_at = excluded.deleted_at, \
+     purged_at = excluded.purged_at";
+
+pub struct PostgresStateStore {
+    pool: PgPool,
+    migrations: MigrationMode,
+    schema: OnceCell<()>,
+}
+
+impl PostgresStateStore {
+    /// Creates the pool without connecting; migrations are handled on
+    /// first use according to `migrations`.
+    pub fn connect_lazy(url: &str, migrations: MigrationMode) -> Result<Self, StateStoreError> {
+        Ok(PostgresStateStore {
+            pool: PgPool::connect_lazy(url)?,
+            migrations,
+            schema: OnceCell::new(),
+        })
+    }
+
+    async fn pool(&self) -> Result<&PgPool, StateStoreError> {
+        self.schema
+            .get_or_try_init(|| async {
+                match self.migrations {
+                    MigrationMode::Apply => MIGRATOR.run(&self.pool).await?,
+                    MigrationMode::Check => {
+                        let pending = pending_migrations(&self.pool).await?;
+                        if !pending.is_empty() {
+                            return Err(StateStoreError::PendingMigrations(pending));
+                        }
+                    }
+                }
+                Ok::<_, StateStoreError>(())
+            })
+            .await?;
+        Ok(&self.pool)
+    }
+}
+
+async fn pending_migrations(pool: &PgPool) -> Result<Vec<i64>, StateStoreError> {
+    let mut connection = pool.acquire().await?;
+    connection.ensure_migrations_table().await?;
+    let applied: HashSet<i64> = connection
+        .list_applied_migrations()
+        .await?
+        .into_iter()
+        .map(|migration| migration.version)
+        .collect();
+    Ok(MIGRATOR
+        .iter()
+        .filter(|migration| !migration.migration_type.is_down_migration())
+        .map(|migration| migration.version)
+        .filter(|version| !applied.contains(version))
+        .collect())
+}
+
+#[async_trait]
+impl StateStore for PostgresStateStore {
+    async fn prepare(&self) -> Result<(), StateStoreError> {
+        self.pool().await.map(|_| ())
+    }
+
+    async fn pending_migrations(&self) -> Result<Vec<i64>, StateStoreError> {
+        pending_migrations(&self.pool).await
+    }
+
+    async fn apply_migrations(&self) -> Result<(), StateStoreError> {
+        Ok(MIGRATOR.run(&self.pool).await?)
+    }
+
+    async fn last_deployment(
+        &self,
+        project: &ProjectId,
+        env: &str,
+    ) -> Result<Option<Deployment>, StateStoreError> {
+        let row: Option<(String, DateTime<Utc>)> = sqlx::query_as(
+            "SELECT sha, deployed_at FROM deployments WHERE project_id = $1 AND env = $2",
+        )
+        .bind(project.as_str())
+        .bind(env)
+        .fetch_optional(self.pool().await?)
+        .await?;
+        Ok(row.map(|(sha, deployed_at)| Deployment { sha, deployed_at }))
+    }
+
+    async fn record_deployment(
+        &self,
+        project: &ProjectId,
+        env: &str,
+        deployment: &Deployment,
+    ) -> Result<(), StateStoreError> {
+        sqlx::query(
+            "INSERT INTO deployments (project_id, env, sha, deployed_at) VALUES ($1, $2, $3, $4) \
+             ON CONFLICT (project_id, env) DO UPDATE \
+             SET sha = excluded.sha, deployed_at = excluded.deployed_at",
+        )
+        .bind(project.as_str())
+        .bind(env)
+        .bind(&deployment.sha)
+        .bind(deployment.deployed_at)
+        .execute(self.pool().await?)
+        .await?;
+        Ok(())
+    }
+
+    async fn ref_state(
+        &self,
+        project: &ProjectId,
+        git_ref: &str,
+    ) -> Result<Option<RefState>, StateStoreError> {
+        let row: Option<(String, Option<String>, DateTime<Utc>)> = sqlx::query_as(
+            "SELECT sha, etag, observed_at FROM ref_states WHERE project_id = $1 AND git_ref = $2",
+        )
+        .bind(project.as_str())
+        .bind(git_ref)
+        .fetch_optional(self.pool().await?)
+        .await?;
+        Ok(row.map(|(sha, etag, observed_at)| RefState {
+            sha,
+            etag,
+            observed_at,
+        }))
+    }
+
+    async fn record_ref_state(
+        &self,
+        project: &ProjectId,
+        git_ref: &str,
+        state: &RefState,
+    ) -> Result<(), StateStoreError> {
+        sqlx::query(UPSERT_REF_STATE)
+            .bind(project.as_str())
+            .bind(git_ref)
+            .bind(&state.sha)
+            .bind(&state.etag)
+            .bind(state.observed_at)
+            .execute(self.pool().await?)
+            .await?;
+        Ok(())
+    }
+
+    async fn record_promotion(
+        &self,
+        project: &ProjectId,
+        env: &str,
+        at: DateTime<Utc>,
+    ) -> Result<(), StateStoreError> {
+        let pool = self.pool().await?;
+        sqlx::query("DELETE FROM promotions WHERE promoted_at < $1")
+            .bind(at - chrono::Duration::days(PROMOTION_RETENTION_DAYS))
+            .execute(pool)
+            .await?;
+        sqlx::query(
+            "INSERT INTO promotions (project_id, env, promoted_at) VALUES ($1, $2, $3) \
+             ON CONFLICT DO NOTHING",
+        )
+        .bind(project.as_str())
+        .bind(env)
+        .bind(at)
+        .execute(pool)
+        .await?;
+        Ok(())
+    }
+
+    async fn promotions_since(
+        &self,
+        env: &str,
+        since: DateTime<Utc>,
+    ) -> Result<Vec<(ProjectId, DateTime<Utc>)>, StateStoreError> {
+        let rows: Vec<(String, DateTime<Utc>)> = sqlx::query_as(
+            "SELECT project_id, promoted_at FROM promotions \
+             WHERE env = $1 AND promoted_at >= $2 ORDER BY promoted_at",
+        )
+        .bind(env)
+        .bind(since)
+        .fetch_all(self.pool().await?)
+        .await?;
+        Ok(rows
+            .into_iter()
+            .map(|(project, at)| (ProjectId::new(project), at))
+            .collect())
+    }
+
+    async fn record_run(&self, report: &SyncReport) -> Result<RunId, StateStoreError> {
+        let (id,): (i64,) =
+            sqlx::query_as("INSERT INTO runs (started_at, report) VALUES ($1, $2) RETURNING id")
+                .bind(report.started_at)
+                .bind(Json(report))
+                .fetch_one(self.pool().await?)
+                .await?;
+        Ok(RunId(id))
+    }
+
+    async fn run(&self, id: RunId) -> Result<Option<SyncReport>, StateStoreError> {
+        let row: Option<(Json<SyncReport>,)> =
+            sqlx::query_as("SELECT report FROM runs WHERE id = $1")
+                .bind(id.0)
+                .fetch_optional(self.pool().await?)
+                .await?;
+        Ok(row.map(|(Json(report),)| report))
+    }
+
+    async fn recent_runs(&self, limit: usize) -> Result<Vec<(RunId, SyncReport)>, StateStoreError> {
+        let rows: Vec<(i64, Json<SyncReport>)> =
+            sqlx::query_as("SELECT id, report FROM runs ORDER BY id DESC LIMIT $1")
+                .bind(i64::try_from(limit).unwrap_or(i64::MAX))
+                .fetch_all(self.pool().await?)
+                .await?;
+        Ok(rows
+            .into_iter()
+            .map(|(id, Json(report))| (RunId(id), report))
+            .collect())
+    }
+
+    async fn add_approval(&self, approval: &Approval) -> Result<(), StateStoreError> {
+        sqlx::query(
+            "INSERT INTO approvals (project_id, env, sha, approver, approved_at) \
+             VALUES ($1, $2, $3, $4, $5) ON CONFLICT DO NOTHING",
+        )
+        .bind(approval.project.as_str())
+        .bind(&approval.env)
+        .bind(&approval.sha)
+        .bind(&approval.approver)
+        .bind(approval.approved_at)
+        .execute(self.pool().await?)
+        .await?;
+        Ok(())
+    }
+
+    async fn approvals(
+        &self,
+        project: &ProjectId,
+        env: &str,
+        sha: &str,
+    ) -> Result<Vec<Approval>, StateStoreError> {
+        let rows: Vec<(String, DateTime<Utc>)> = sqlx::query_as(
+            "SELECT approver, approved_at FROM approvals \
+             WHERE project_id = $1 AND env = $2 AND sha = $3 ORDER BY approved_at",
+        )
+        .bind(project.as_str())
+        .bind(env)
+        .bind(sha)
+        .fetch_all(self.pool().await?)
+        .await?;
+        Ok(rows
+            .into_iter()
+            .map(|(approver, approved_at)| Approval {
+                project: project.clone(),
+                env: env.to_string(),
+                sha: sha.to_string(),
+                approver,
+                approved_at,
+            })
+            .collect())
+    }
+
+    async fn set_pin(
+        &self,
+        project: &ProjectId,
+        env: &str,
+        pin: Option<&Pin>,
+    ) -> Result<(), StateStoreError> {
+        let pool = self.pool().await?;
+        match pin {
+            Some(pin) => {
+                sqlx::query(
+                    "INSERT INTO pins (project_id, env, sha, reason, pinned_at) \
+                 VALUES ($1, $2, $3, $4, $5) \
+                 ON CONFLICT (project_id, env) DO UPDATE \
+                 SET sha = excluded.sha, reason = excluded.reason, pinned_at = excluded.pinned_at",
+                )
+                .bind(project.as_str())
+                .bind(env)
+                .bind(&pin.sha)
+                .bind(&pin.reason)
+                .bind(pin.pinned_at)
+                .execute(pool)
+                .await?
+            }
+            None => {
+                sqlx::query("DELETE FROM pins WHERE project_id = $1 AND env = $2")
+                    .bind(project.as_str())
+                    .bind(env)
+                    .execute(pool)
+                    .await?
+            }
+        };
+        Ok(())
+    }
+
+    async fn pin(&self, project: &ProjectId, env: &str) -> Result<Option<Pin>, StateStoreError> {
+        let row: Option<(String, Option<String>, DateTime<Utc>)> = sqlx::query_as(
+            "SELECT sha, reason, pinned_at FROM pins WHERE project_id = $1 AND env = $2",
+        )
+        .bind(project.as_str())
+        .bind(env)
+        .fetch_optional(self.pool().await?)
+        .await?;
+        Ok(row.map(|(sha, reason, pinned_at)| Pin {
+            sha,
+            reason,
+            pinned_at,
+        }))
+    }
+
+    async fn set_freeze(
+        &self,
+        scope: &FreezeScope,
+        freeze: Option<&Freeze>,
+    ) -> Result<(), StateStoreError> {
+        let pool = self.pool().await?;
+        match freeze {
+            Some(freeze) => {
+                sqlx::query(
+                    "INSERT INTO freezes (scope, reason, frozen_at) VALUES ($1, $2, $3) \
+                 ON CONFLICT (scope) DO UPDATE \
+                 SET reason = excluded.reason, frozen_at = excluded.frozen_at",
+                )
+                .bind(scope.key())
+                .bind(&freeze.reason)
+                .bind(freeze.frozen_at)
+                .execute(pool)
+                .await?
+            }
+            None => {
+                sqlx::query("DELETE FROM freezes WHERE scope = $1")
+                    .bind(scope.key())
+                    .execute(pool)
+                    .await?
+            }
+        };
+        Ok(())
+    }
+
+    async fn freeze(&self, scope: &FreezeScope) -> Result<Option<Freeze>, StateStoreError> {
+        let row: Option<(Option<String>, DateTime<Utc>)> =
+            sqlx::query_as("SELECT reason, frozen_at FROM freezes WHERE scope = $1")
+                .bind(scope.key())
+                .fetch_optional(self.pool().await?)
+                .await?;
+        Ok(row.map(|(reason, frozen_at)| Freeze { reason, frozen_at }))
+    }
+
+    async fn insert_deletion(
+        &self,
+        project: &ProjectId,
+        deletion: &Deletion,
+    ) -> Result<bool, StateStoreError> {
+        let inserted = sqlx::query(
+            "INSERT INTO deleted_projects (project_id, reason, deleted_at, purged_at) \
+             VALUES ($1, $2, $3, $4) \
+             ON CONFLICT (project_id) DO NOTHING",
+        )
+        .bind(project.as_str())
+        .bind(&deletion.reason)
+        .bind(deletion.deleted_at)
+        .bind(deletion.purged_at)
+        .execute(self.pool().await?)
+        .await?;
+        Ok(inserted.rows_affected() > 0)
+    }
+
+    async fn remove_deletion(&self, project: &ProjectId) -> Result<bool, StateStoreError> {
+        let removed = sqlx::query("DELETE FROM deleted_projects WHERE project_id = $1")
+            .bind(project.as_str())
+            .execute(self.pool().await?)
+            .await?;
+        Ok(removed.rows_affected() > 0)
+    }
+
+    async fn deletion(&self, project: &ProjectId) -> Result<Option<Deletion>, StateStoreError> {
+        let row: Option<DeletionRow> = sqlx::query_as(
+            "SELECT project_id, reason, deleted_at, purged_at FROM deleted_projects \
+             WHERE project_id = $1",
+        )
+        .bind(project.as_str())
+        .fetch_optional(self.pool().await?)
+        .await?;
+        Ok(row.map(|row| deletion_from_row(row).1))
+    }
+
+    async fn deletions(&self) -> Result<Vec<(ProjectId, Deletion)>, StateStoreError> {
+        let rows: Vec<DeletionRow> = sqlx::query_as(
+            "SELECT project_id, reason, deleted_at, purged_at FROM deleted_projects \
+             ORDER BY deleted_at",
+        )
+        .fetch_all(self.pool().await?)
+        .await?;
+        Ok(rows.into_iter().map(deletion_from_row).collect())
+    }
+
+    async fn purge_project(
+        &self,
+        project: &ProjectId,
+        at: DateTime<Utc>,
+    ) -> Result<(), StateStoreError> {
+        let mut transaction = self.pool().await?.begin().await?;
+        for table in [
+            "deployments",
+            "ref_states",
+            "promotions",
+            "approvals",
+            "pins",
+        ] {
+            sqlx::query(&format!("DELETE FROM {table} WHERE project_id = $1"))
+                .bind(project.as_str())
+                .execute(&mut *transaction)
+                .await?;
+        }
+        sqlx::query("DELETE FROM freezes WHERE scope = $1")
+            .bind(FreezeScope::Project(project.clone()).key())
+            .execute(&mut *transaction)
+            .await?;
+        sqlx::query("UPDATE deleted_projects SET purged_at = $1 WHERE project_id = $2")
+            .bind(at)
+            .bind(project.as_str())
+            .execute(&mut *transaction)
+            .await?;
+        transaction.commit().await?;
+        Ok(())
+    }
+
+    async fn enqueue_outbox(
+        &self,
+        entries: &[OutboxEntry],
+    ) -> Result<Vec<OutboxId>, StateStoreError> {
+        let mut transaction = self.pool().await?.begin().await?;
+        let mut ids = Vec::with_capacity(entries.len());
+        for entry in entries {
+            let (id,): (i64,) = sqlx::query_as(
+                "INSERT INTO outbox (sink, event, attempts, next_attempt_at, last_error, pending) \
+                 VALUES ($1, $2, $3, $4, $5, $6) RETURNING id",
+            )
+            .bind(&entry.sink)
+            .bind(Json(&entry.event))
+            .bind(i64::from(entry.attempts))
+            .bind(entry.next_attempt_at)
+            .bind(&entry.last_error)
+            .bind(entry.pending)
+            .fetch_one(&mut *transaction)
+            .await?;
+            ids.push(OutboxId(id));
+        }
+        transaction.commit().await?;
+        Ok(ids)
+    }
+
+    async fn pending_outbox(&self) -> Result<Vec<(OutboxId, OutboxEntry)>, StateStoreError> {
+        let rows: Vec<OutboxRow> = sqlx::query_as(&format!(
+            "SELECT {OUTBOX_COLUMNS} FROM outbox WHERE pending ORDER BY id"
+        ))
+        .fetch_all(self.pool().await?)
+        .await?;
+        Ok(rows.into_iter().map(outbox::from_row).collect())
+    }
+
+    async fn release_outbox(
+        &self,
+        ids: &[OutboxId],
+        at: DateTime<Utc>,
+    ) -> Result<(), StateStoreError> {
+        let mut transaction = self.pool().await?.begin().await?;
+        for id in ids {
+            sqlx::query(
+                "UPDATE outbox SET pending = FALSE, next_attempt_at = $1 \
+                 WHERE id = $2 AND pending",
+            )
+            .bind(at)
+            .bind(id.0)
+            .execute(&mut *transaction)
+            .await?;
+        }
+        transaction.commit().await?;
+        Ok(())
+    }
+
+    async fn due_outbox(
+        &self,
+        now: DateTime<Utc>,
+        limit: usize,
+    ) -> Result<Vec<(OutboxId, OutboxEntry)>, StateStoreError> {
+        let rows: Vec<OutboxRow> = sqlx::query_as(&format!(
+            "SELECT {OUTBOX_COLUMNS} FROM outbox \
+             WHERE next_attempt_at <= $1 \
+             ORDER BY id LIMIT $2"
+        ))
+        .bind(now)
+        .bind(i64::try_from(limit).unwrap_or(i64::MAX))
+        .fetch_all(self.pool().await?)
+        .await?;
+        Ok(rows.into_iter().map(outbox::from_row).collect())
+    }
+
+    async fn complete_outbox(&self, id: OutboxId) -> Result<(), StateStoreError> {
+        sqlx::query("DELETE FROM outbox WHERE id = $1")
+            .bind(id.0)
+            .execute(self.pool().await?)
+            .await?;
+        Ok(())
+    }
+
+    async fn retry_outbox(
+        &self,
+        id: OutboxId,
+        next_attempt_at: Option<DateTime<Utc>>,
+        error: &str,
+    ) -> Result<(), StateStoreError> {
+        sqlx::query(
+            "UPDATE outbox SET attempts = attempts + 1, next_attempt_at = $1, last_error = $2 \
+             WHERE id = $3",
+        )
+        .bind(next_attempt_at)
+        .bind(error)
+        .bind(id.0)
+        .execute(self.pool().await?)
+        .await?;
+        Ok(())
+    }
+
+    async fn enqueue_job(
+        &self,
+        target: &SyncTarget,
+        idempotency_key: Option<&str>,
+        at: DateTime<Utc>,
+    ) -> Result<JobId, StateStoreError> {
+        let pool = self.pool().await?;
+        let inserted: Option<(i64,)> = sqlx::query_as(
+            "INSERT INTO jobs (target, state, queued_at, idempotency_key) \
+             VALUES ($1, $2, $3, $4) ON CONFLICT DO NOTHING RETURNING id",
+        )
+        .bind(Json(target))
+        .bind(JobState::Queued.key())
+        .bind(at)
+        .bind(idempotency_key)
+        .fetch_optional(pool)
+        .await?;
+        // Only a taken idempotency key keeps the row from being inserted
+        let (id,) = match inserted {
+            Some(id) => id,
+            None => {
+                sqlx::query_as("SELECT id FROM jobs WHERE idempotency_key = $1")
+                    .bind(idempotency_key)
+                    .fetch_one(pool)
+                    .await?
+            }
+        };
+        Ok(JobId(id))
+    }
+
+    async fn claim_job(
+        &self,
+        now: DateTime<Utc>,
+        stale_before: DateTime<Utc>,
+    ) -> Result<Option<(JobId, SyncJob)>, StateStoreError> {
+        // Concurrent claims skip the row rather than wait for it
+        let row: Option<JobRow> = sqlx::query_as(&format!(
+            "UPDATE jobs SET state = $1, started_at = $2, heartbeat_at = $2 \
+             WHERE id = (SELECT id FROM jobs \
+             WHERE state = $3 \
+             OR (state = $1 AND COALESCE(heartbeat_at, started_at) < $4) \
+             ORDER BY id LIMIT 1 FOR UPDATE SKIP LOCKED) \
+             RETURNING {JOB_COLUMNS}"
+        ))
+        .bind(JobState::Running.key())
+        .bind(now)
+        .bind(JobState::Queued.key())
+        .bind(stale_before)
+        .fetch_optional(self.pool().await?)
+        .await?;
+        Ok(row.map(jobs::from_row))
+    }
+
+    async fn heartbeat_job(&self, id: JobId, at: DateTime<Utc>) -> Result<(), StateStoreError> {
+        sqlx::query("UPDATE jobs SET heartbeat_at = $1 WHERE id = $2 AND state = $3")
+            .bind(at)
+            .bind(id.0)
+            .bind(JobState::Running.key())
+            .execute(self.pool().await?)
+            .await?;
+        Ok(())
+    }
+
+    async fn finish_job(
+        &self,
+        id: JobId,
+        run: Option<RunId>,
+        error: Option<&str>,
+        at: DateTime<Utc>,
+    ) -> Result<(), StateStoreError> {
+        let state = match error {
+            Some(_) => JobState::Failed,
+            None => JobState::Completed,
+        };
+        sqlx::query(
+            "UPDATE jobs SET state = $1, finished_at = $2, run_id = $3, error = $4 WHERE id = $5",
+        )
+        .bind(state.key())
+        .bind(at)
+        .bind(run.map(|run| run.0))
+        .bind(error)
+        .bind(id.0)
+        .execute(self.pool().await?)
+        .await?;
+        Ok(())
+    }
+
+    async fn recent_jobs(&self, limit: usize) -> Result<Vec<(JobId, SyncJob)>, StateStoreError> {
+        let rows: Vec<JobRow> = sqlx::query_as(&format!(
+            "SELECT {JOB_COLUMNS} FROM jobs ORDER BY id DESC LIMIT $1"
+        ))
+        .bind(i64::try_from(limit).unwrap_or(i64::MAX))
+        .fetch_all(self.pool().await?)
+        .await?;
+        Ok(rows.into_iter().map(jobs::from_row).collect())
+    }
+
+    async fn export_state(&self) -> Result<StateSnapshot, StateStoreError> {
+        let pool = self.pool().await?;
+        let rows = SnapshotRows {
+            deployments: sqlx::query_as(
+                "SELECT project_id, env, sha, deployed_at FROM deployments",
+            )
+            .fetch_all(pool)
+            .await?,
+            refs: sqlx::query_as(
+                "SELECT project_id, git_ref, sha, etag, observed_at FROM ref_states",
+            )
+            .fetch_all(pool)
+            .await?,
+            runs: sqlx::query_as("SELECT id, report FROM runs ORDER BY id")
+                .fetch_all(pool)
+                .await?,
+            approvals: sqlx::query_as(
+                "SELECT project_id, env, sha, approver, approved_at FROM approvals",
+            )
+            .fetch_all(pool)
+            .await?,
+            pins: sqlx::query_as("SELECT project_id, env, sha, reason, pinned_at FROM pins")
+                .fetch_all(pool)
+                .await?,
+            freezes: sqlx::query_as("SELECT scope, reason, frozen_at FROM freezes")
+                .fetch_all(pool)
+                .await?,
+            promotions: sqlx::query_as("SELECT project_id, env, promoted_at FROM promotions")
+                .fetch_all(pool)
+                .await?,
+            jobs: sqlx::query_as(&format!("SELECT {JOB_COLUMNS} FROM jobs ORDER BY id"))
+                .fetch_all(pool)
+                .await?,
+            deletions: sqlx::query_as(
+                "SELECT project_id, reason, deleted_at, purged_at FROM deleted_projects",
+            )
+            .fetch_all(pool)
+            .await?,
+            outbox: sqlx::query_as(&format!("SELECT {OUTBOX_COLUMNS} FROM outbox ORDER BY id"))
+                .fetch_all(pool)
+                .await?,
+        };
+        Ok(rows.into())
+    }
+
+    async fn import_state(&self, snapshot: &StateSnapshot) -> Result<(), StateStoreError> {
+        snapshot.check_version()?;
+
+        let mut transaction = self.pool().await?.begin().await?;
+        for entry in &snapshot.deployments {
+            sqlx::query(
+                "INSERT INTO deployments (project_id, env, sha, deployed_at) VALUES ($1, $2, $3, $4) \
+                 ON CONFLICT (project_id, env) DO UPDATE \
+                 SET sha = excluded.sha, deployed_at = excluded.deployed_at",
+            )
+            .bind(entry.project.as_str())
+            .bind(&entry.env)
+            .bind(&entry.deployment.sha)
+            .bind(entry.deployment.deployed_at)
+            .execute(&mut *transaction)
+            .await?;
+        }
+        for entry in &snapshot.refs {
+            sqlx::query(UPSERT_REF_STATE)
+                .bind(entry.project.as_str())
+                .bind(&entry.git_ref)
+                .bind(&entry.state.sha)
+                .bind(&entry.state.etag)
+                .bind(entry.state.observed_at)
+                .execute(&mut *transaction)
+                .await?;
+        }
+        for entry in &snapshot.runs {
+            sqlx::query(
+                "INSERT INTO runs (id, started_at, report) VALUES ($1, $2, $3) \
+                 ON CONFLICT (id) DO UPDATE \
+                 SET started_at = excluded.started_at, report = excluded.report",
+            )
+            .bind(entry.id.0)
+            .bind(entry.report.started_at)
+            .bind(Json(&entry.report))
+            .execute(&mut *transaction)
+            .await?;
+        }
+        for approval in &snapshot.approvals {
+            sqlx::query(
+                "INSERT INTO approvals (project_id, env, sha, approver, approved_at) \
+                 VALUES ($1, $2, $3, $4, $5) \
+                 ON CONFLICT (project_id, env, sha, approver) DO UPDATE \
+                 SET approved_at = excluded.approved_at",
+            )
+            .bind(approval.project.as_str())
+            .bind(&approval.env)
+            .bind(&approval.sha)
+            .bind(&approval.approver)
+            .bind(approval.approved_at)
+            .execute(&mut *transaction)
+            .await?;
+        }
+        for entry in &snapshot.pins {
+            sqlx::query(
+                "INSERT INTO pins (project_id, env, sha, reason, pinned_at) \
+                 VALUES ($1, $2, $3, $4, $5) \
+                 ON CONFLICT (project_id, env) DO UPDATE \
+                 SET sha = excluded.sha, reason = excluded.reason, pinned_at = excluded.pinned_at",
+            )
+            .bind(entry.project.as_str())
+            .bind(&entry.env)
+            .bind(&entry.pin.sha)
+            .bind(&entry.pin.reason)
+            .bind(entry.pin.pinned_at)
+            .execute(&mut *transaction)
+            .await?;
+        }
+        for entry in &snapshot.freezes {
+            sqlx::query(
+                "INSERT INTO freezes (scope, reason, frozen_at) VALUES ($1, $2, $3) \
+                 ON CONFLICT (scope) DO UPDATE \
+                 SET reason = excluded.reason, frozen_at = excluded.frozen_at",
+            )
+            .bind(entry.scope.key())
+            .bind(&entry.freeze.reason)
+            .bind(entry.freeze.frozen_at)
+            .execute(&mut *transaction)
+            .await?;
+        }
+        for OutboxSnapshotEntry { id, entry } in &snapshot.outbox {
+            sqlx::query(&format!(
+                "INSERT INTO outbox ({OUTBOX_COLUMNS}) VALUES ($1, $2, $3, $4, $5, $6, $7) \
+                 ON CONFLICT (id) DO UPDATE \
+                 SET sink = excluded.sink, event = excluded.event, attempts = excluded.attempts, \
+                 next_attempt_at = excluded.next_attempt_at, last_error = excluded.last_error, \
+                 pending = excluded.pending"
+            ))
+            .bind(id.0)
+            .bind(&entry.sink)
+            .bind(Json(&entry.event))
+            .bind(i64::from(entry.attempts))
+            .bind(entry.next_attempt_at)
+            .bind(&entry.last_error)
+            .bind(entry.pending)
+            .execute(&mut *transaction)
+            .await?;
+        }
+        for entry in &snapshot.promotions {
+            sqlx::query(
+                "INSERT INTO promotions (project_id, env, promoted_at) VALUES ($1, $2, $3) \
+                 ON CONFLICT DO NOTHING",
+            )
+            .bind(entry.project.as_str())
+            .bind(&entry.env)
+            .bind(entry.promoted_at)
+            .execute(&mut *transaction)
+            .await?;
+        }
+        for JobEntry { id, job } in &snapshot.jobs {
+            sqlx::query(&format!(
+                "INSERT INTO jobs ({JOB_COLUMNS}) VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10) \
+                 ON CONFLICT (id) DO UPDATE \
+                 SET target = excluded.target, state = excluded.state, \
+                 queued_at = excluded.queued_at, started_at = excluded.started_at, \
+                 finished_at = excluded.finished_at, run_id = excluded.run_id, \
+                 error = excluded.error, idempotency_key = excluded.idempotency_key, \
+                 heartbeat_at = excluded.heartbeat_at"
+            ))
+            .bind(id.0)
+            .bind(Json(&job.target))
+            .bind(job.state.key())
+            .bind(job.queued_at)
+            .bind(job.started_at)
+            .bind(job.finished_at)
+            .bind(job.run.map(|run| run.0))
+            .bind(&job.error)
+            .bind(&job.idempotency_key)
+            .bind(job.heartbeat_at)
+            .execute(&mut *transaction)
+            .await?;
+        }
+        for DeletionEntry { project, deletion } in &snapshot.deletions {
+            sqlx::query(UPSERT_DELETION)
+                .bind(project.as_str())
+                .bind(&deletion.reason)
+                .bind(deletion.deleted_at)
+                .bind(deletion.purged_at)
+                .execute(&mut *transaction)
+                .await?;
+        }
+        // Explicit ids bypass the sequences; move them past the imported rows
+        for table in ["runs", "outbox", "jobs"] {
+            sqlx::query(&format!(
+                "SELECT setval(pg_get_serial_sequence('{table}', 'id'), \
+                 COALESCE((SELECT MAX(id) FROM {table}), 0) + 1, false)"
+            ))
+            .execute(&mut *transaction)
+            .await?;
+        }
+        transaction.commit().await?;
+        Ok(())
+    }
+}
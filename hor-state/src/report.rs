@@ -0,0 +1,284 @@
+use std::ops::AddAssign;
+
+use chrono::{DateTime, Utc};
+use hor_registry::{Policy, ProjectId};
+use serde::{Deserialize, Serialize};
+
+use crate::{Approval, ManifestCommit, PolicyDecision, ReleaseCandidate};
+
+/// Everything a single sync did, project by project.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(rename_all = "kebab-case")]
+#[jsm::public]
+struct SyncReport {
+    /// Run in shadow mode: the outcomes say what would have happened
+    #[serde(default)]
+    simulated: bool,
+    started_at: DateTime<Utc>,
+    finished_at: DateTime<Utc>,
+    projects: Vec<ProjectReport>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(rename_all = "kebab-case")]
+#[jsm::public]
+struct ProjectReport {
+    id: ProjectId,
+    env: String,
+    outcome: ProjectOutcome,
+    /// Verdicts of the project's policy rules, empty if none were evaluated
+    #[serde(default)]
+    decisions: Vec<PolicyDecision>,
+    /// Post-sync actions run after the env ref moved
+    #[serde(default)]
+    actions: Vec<ActionReport>,
+    /// What the plan was based on, `None` if it failed or was skipped
+    /// before the env tag was read
+    #[serde(default)]
+    inputs: Option<ReleaseInputs>,
+    /// Record of the promotion, if the project keeps them
+    #[serde(default)]
+    manifest: Option<ReleaseManifest>,
+    /// GitHub API calls the project made during the run
+    #[serde(default)]
+    api_usage: ApiUsage,
+}
+
+impl ProjectReport {
+    /// A report with nothing but `outcome`, for an entry that never got
+    /// as far as being planned.
+    pub fn bare(id: ProjectId, env: String, outcome: ProjectOutcome) -> Self {
+        ProjectReport {
+            id,
+            env,
+            outcome,
+            decisions: Vec::new(),
+            actions: Vec::new(),
+            inputs: None,
+            manifest: None,
+            api_usage: ApiUsage::default(),
+        }
+    }
+}
+
+/// GitHub API calls, e.g. of one project during one run.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+#[jsm::public]
+struct ApiUsage {
+    /// Requests sent, retries included
+    requests: u64,
+    /// Rate limit points spent; conditional requests answered from the
+    /// cache are free
+    points: u64,
+}
+
+impl AddAssign for ApiUsage {
+    fn add_assign(&mut self, other: Self) {
+        self.requests += other.requests;
+        self.points += other.points;
+    }
+}
+
+/// Machine-readable record of one promotion, for audits and to tell
+/// exactly what an env ran when.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(rename_all = "kebab-case")]
+#[jsm::public]
+struct ReleaseManifest {
+    project: ProjectId,
+    owner: String,
+    repo: String,
+    env: String,
+    /// The previously released commit, `None` for the env's first release
+    from: Option<String>,
+    to: String,
+    /// `None` if the plan's inputs weren't recorded
+    source: Option<ReleaseSource>,
+    /// Commits released, newest first, as far as GitHub lists them
+    commits: Vec<ManifestCommit>,
+    /// Verdicts of the policy rules the release passed
+    decisions: Vec<PolicyDecision>,
+    approvals: Vec<Approval>,
+    /// When the policy was evaluated, `None` if it wasn't
+    planned_at: Option<DateTime<Utc>>,
+    released_at: DateTime<Utc>,
+    /// Written by a sync in shadow mode; nothing was released
+    #[serde(default)]
+    simulated: bool,
+}
+
+/// Everything a project's plan looked at, recorded so a run can be
+/// replayed without GitHub.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(rename_all = "kebab-case")]
+#[jsm::public]
+struct ReleaseInputs {
+    /// The env tag when planned, `None` if it didn't exist
+    from: Option<String>,
+    /// Where the plan wanted the env tag
+    to: String,
+    source: ReleaseSource,
+    /// The project's policy at the time
+    policy: Policy,
+    /// What the policy was evaluated against, `None` if it wasn't, e.g.
+    /// as the env tag was already in place
+    #[serde(default)]
+    candidate: Option<ReleaseCandidate>,
+}
+
+/// Why the plan picked its target.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(tag = "kind", rename_all = "kebab-case")]
+#[non_exhaustive]
+pub enum ReleaseSource {
+    /// The head of the tracked branch
+    Branch {
+        name: String,
+        /// `owner/repo` of the upstream whose branch it is, `None` for the
+        /// project's own
+        #[serde(default)]
+        upstream: Option<String>,
+    },
+    /// The environment is pinned
+    Pin,
+    /// Promoted from another environment that ran it long enough
+    Promotion { env: String },
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+#[jsm::public]
+struct ActionReport {
+    action: String,
+    /// `None` if the action succeeded
+    error: Option<String>,
+    /// Not run, as the sync was in shadow mode
+    #[serde(default)]
+    simulated: bool,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(tag = "kind", rename_all = "kebab-case")]
+#[non_exhaustive]
+pub enum ProjectOutcome {
+    /// The env ref already pointed at the target
+    Unchanged { sha: String },
+    /// The env ref did not exist and was created
+    Created { sha: String },
+    /// The env ref was moved
+    Updated { from: String, to: String },
+    /// The env ref was deleted between being read and being moved, and
+    /// was created again
+    Recreated { from: String, to: String },
+    /// The env ref was moved to `to`, which failed verification, and was
+    /// moved back to `from`
+    RolledBack {
+        from: String,
+        to: String,
+        reason: String,
+    },
+    /// The project was deliberately not synced
+    Skipped { reason: SkipReason, detail: String },
+    /// The idle color of a blue/green project was moved to `sha`; the env
+    /// ref follows once it is confirmed
+    Staged { color: String, sha: String },
+    /// A release gate held the env ref back
+    Blocked { reason: BlockReason, detail: String },
+    /// The env ref moved between being read and being written, e.g. by
+    /// another instance or a push; left alone and reconciled again
+    Conflict {
+        expected: Option<String>,
+        actual: Option<String>,
+    },
+    /// Syncing the project failed
+    Failed { error: String },
+}
+
+impl ProjectOutcome {
+    /// Whether the env ref was left somewhere other than where it should
+    /// be: held back, rolled back, frozen, staged or failed. Projects
+    /// skipped for having nothing to release haven't drifted.
+    pub fn drifted(&self) -> bool {
+        match self {
+            Self::Unchanged { .. }
+            | Self::Created { .. }
+            | Self::Updated { .. }
+            | Self::Recreated { .. } => false,
+            Self::Skipped { reason, .. } => *reason == SkipReason::Frozen,
+            Self::RolledBack { .. }
+            | Self::Staged { .. }
+            | Self::Blocked { .. }
+            | Self::Conflict { .. }
+            | Self::Failed { .. } => true,
+        }
+    }
+}
+
+impl SyncReport {
+    pub fn failures(&self) -> impl Iterator<Item = &ProjectReport> {
+        self.projects
+            .iter()
+            .filter(|project| matches!(project.outcome, ProjectOutcome::Failed { .. }))
+    }
+
+    /// Projects to reconcile again soon rather than on the next full pass:
+    /// conflicts, and blue/green releases waiting to flip.
+    pub fn follow_ups(&self) -> impl Iterator<Item = &ProjectReport> {
+        self.projects.iter().filter(|project| {
+            matches!(
+                project.outcome,
+                ProjectOutcome::Conflict { .. } | ProjectOutcome::Staged { .. }
+            )
+        })
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+#[non_exhaustive]
+pub enum SkipReason {
+    /// A freeze covers the project
+    Frozen,
+    /// The repository has no commits yet
+    EmptyRepo,
+    /// The repository is archived, so read-only
+    Archived,
+    /// Every commit since the release is one the project ignores
+    Ignored,
+    /// The env outlived its TTL and was torn down
+    Expired,
+    /// The project was soft-deleted, and is kept until it's restored or
+    /// purged
+    Deleted,
+    /// The registry entry is of a kind this version can't sync
+    Unsupported,
+    /// Recorded before reasons were told apart; the detail has the text
+    #[serde(other)]
+    Other,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+#[non_exhaustive]
+pub enum BlockReason {
+    /// At least one policy rule failed
+    PolicyViolation,
+    /// The container image for the target hasn't been published yet
+    ImageNotReady,
+    /// The environment promoted from hasn't run its commit long enough,
+    /// or reported a failure
+    Baking,
+    /// The idle color of a blue/green project failed its confirmation
+    Unconfirmed,
+    /// Another project of the group is held back
+    Group,
+    /// An earlier region of the rollout didn't release
+    Rollout,
+    /// The branch's commits wait for the next release train
+    Train,
+    /// The promotion budget of the project or its env is spent for now
+    Budget,
+    /// A repository ruleset forbids moving the ref
+    Ruleset,
+}
@@ -0,0 +1,484 @@
+use chrono::{DateTime, Utc};
+use hor_registry::ProjectId;
+use serde::{Deserialize, Serialize};
+
+#[cfg(any(feature = "sqlite", feature = "postgres"))]
+use crate::{
+    jobs::{self, JobRow},
+    outbox::{self, OutboxRow},
+};
+use crate::{
+    Approval, Deletion, Deployment, Freeze, FreezeScope, JobId, OutboxEntry, OutboxId, Pin,
+    RefState, RunId, StateStoreError, SyncJob, SyncReport,
+};
+
+pub const SNAPSHOT_VERSION: u32 = 1;
+
+/// Backend-independent copy of everything in a state store, used to move
+/// between backends or restore after rebuilding the service.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(rename_all = "kebab-case")]
+#[jsm::public]
+struct StateSnapshot {
+    version: u32,
+    deployments: Vec<DeploymentEntry>,
+    /// Absent from snapshots taken before ref states were kept
+    #[serde(default)]
+    refs: Vec<RefStateEntry>,
+    runs: Vec<RunEntry>,
+    approvals: Vec<Approval>,
+    pins: Vec<PinEntry>,
+    freezes: Vec<FreezeEntry>,
+    outbox: Vec<OutboxSnapshotEntry>,
+    /// Absent from snapshots taken before promotions were kept
+    #[serde(default)]
+    promotions: Vec<PromotionEntry>,
+    /// Absent from snapshots taken before syncs were queued
+    #[serde(default)]
+    jobs: Vec<JobEntry>,
+    /// Absent from snapshots taken before projects were soft-deleted
+    #[serde(default)]
+    deletions: Vec<DeletionEntry>,
+}
+
+impl Default for StateSnapshot {
+    fn default() -> Self {
+        StateSnapshot {
+            version: SNAPSHOT_VERSION,
+            deployments: Vec::new(),
+            refs: Vec::new(),
+            runs: Vec::new(),
+            approvals: Vec::new(),
+            pins: Vec::new(),
+            freezes: Vec::new(),
+            outbox: Vec::new(),
+            promotions: Vec::new(),
+            jobs: Vec::new(),
+            deletions: Vec::new(),
+        }
+    }
+}
+
+impl StateSnapshot {
+    /// Refuses snapshots of another version, before any of it is imported.
+    pub(crate) fn check_version(&self) -> Result<(), StateStoreError> {
+        if self.version != SNAPSHOT_VERSION {
+            return Err(StateStoreError::SnapshotVersion(self.version));
+        }
+        Ok(())
+    }
+
+    /// Orders every list by its key, so that exports of the same state are
+    /// the same whichever backend they come from.
+    pub(crate) fn sorted(mut self) -> Self {
+        self.deployments
+            .sort_by(|a, b| (&a.project, &a.env).cmp(&(&b.project, &b.env)));
+        self.refs
+            .sort_by(|a, b| (&a.project, &a.git_ref).cmp(&(&b.project, &b.git_ref)));
+        self.runs.sort_by_key(|entry| entry.id);
+        self.approvals.sort_by(|a, b| {
+            (&a.project, &a.env, &a.sha, &a.approver).cmp(&(
+                &b.project,
+                &b.env,
+                &b.sha,
+                &b.approver,
+            ))
+        });
+        self.pins
+            .sort_by(|a, b| (&a.project, &a.env).cmp(&(&b.project, &b.env)));
+        self.freezes.sort_by_key(|entry| entry.scope.key());
+        self.outbox.sort_by_key(|entry| entry.id);
+        self.promotions.sort_by(|a, b| {
+            (&a.project, &a.env, a.promoted_at).cmp(&(&b.project, &b.env, b.promoted_at))
+        });
+        self.jobs.sort_by_key(|entry| entry.id);
+        self.deletions.sort_by(|a, b| a.project.cmp(&b.project));
+        self
+    }
+}
+
+/// Project, git ref or env, sha, etag or reason, and when it was recorded.
+#[cfg(any(feature = "sqlite", feature = "postgres"))]
+type ShaRow = (String, String, String, Option<String>, DateTime<Utc>);
+
+/// Every table's rows, as the SQL backends export them.
+#[cfg(any(feature = "sqlite", feature = "postgres"))]
+pub(crate) struct SnapshotRows {
+    pub deployments: Vec<(String, String, String, DateTime<Utc>)>,
+    pub refs: Vec<ShaRow>,
+    pub runs: Vec<(i64, sqlx::types::Json<SyncReport>)>,
+    pub approvals: Vec<(String, String, String, String, DateTime<Utc>)>,
+    pub pins: Vec<ShaRow>,
+    pub freezes: Vec<(String, Option<String>, DateTime<Utc>)>,
+    pub promotions: Vec<(String, String, DateTime<Utc>)>,
+    pub jobs: Vec<JobRow>,
+    pub deletions: Vec<DeletionRow>,
+    pub outbox: Vec<OutboxRow>,
+}
+
+#[cfg(any(feature = "sqlite", feature = "postgres"))]
+pub(crate) type DeletionRow = (String, Option<String>, DateTime<Utc>, Option<DateTime<Utc>>);
+
+#[cfg(any(feature = "sqlite", feature = "postgres"))]
+pub(crate) fn deletion_from_row(
+    (project, reason, deleted_at, purged_at): DeletionRow,
+) -> (ProjectId, Deletion) {
+    (
+        ProjectId::new(project),
+        Deletion {
+            reason,
+            deleted_at,
+            purged_at,
+        },
+    )
+}
+
+#[cfg(any(feature = "sqlite", feature = "postgres"))]
+impl From<SnapshotRows> for StateSnapshot {
+    fn from(rows: SnapshotRows) -> Self {
+        StateSnapshot {
+            version: SNAPSHOT_VERSION,
+            deployments: rows
+                .deployments
+                .into_iter()
+                .map(|(project, env, sha, deployed_at)| DeploymentEntry {
+                    project: ProjectId::new(project),
+                    env,
+                    deployment: Deployment { sha, deployed_at },
+                })
+                .collect(),
+            refs: rows
+                .refs
+                .into_iter()
+                .map(|(project, git_ref, sha, etag, observed_at)| RefStateEntry {
+                    project: ProjectId::new(project),
+                    git_ref,
+                    state: RefState {
+                        sha,
+                        etag,
+                        observed_at,
+                    },
+                })
+                .collect(),
+            runs: rows
+                .runs
+                .into_iter()
+                .map(|(id, sqlx::types::Json(report))| RunEntry {
+                    id: RunId(id),
+                    report,
+                })
+                .collect(),
+            approvals: rows
+                .approvals
+                .into_iter()
+                .map(|(project, env, sha, approver, approved_at)| Approval {
+                    project: ProjectId::new(project),
+                    env,
+                    sha,
+                    approver,
+                    approved_at,
+                })
+                .collect(),
+            pins: rows
+                .pins
+                .into_iter()
+                .map(|(project, env, sha, reason, pinned_at)| PinEntry {
+                    project: ProjectId::new(project),
+                    env,
+                    pin: Pin {
+                        sha,
+                        reason,
+                        pinned_at,
+                    },
+                })
+                .collect(),
+            freezes: rows
+                .freezes
+                .into_iter()
+                .map(|(scope, reason, frozen_at)| FreezeEntry {
+                    scope: FreezeScope::from_key(&scope),
+                    freeze: Freeze { reason, frozen_at },
+                })
+                .collect(),
+            outbox: rows
+                .outbox
+                .into_iter()
+                .map(outbox::from_row)
+                .map(|(id, entry)| OutboxSnapshotEntry { id, entry })
+                .collect(),
+            promotions: rows
+                .promotions
+                .into_iter()
+                .map(|(project, env, promoted_at)| PromotionEntry {
+                    project: ProjectId::new(project),
+                    env,
+                    promoted_at,
+                })
+                .collect(),
+            jobs: rows
+                .jobs
+                .into_iter()
+                .map(jobs::from_row)
+                .map(|(id, job)| JobEntry { id, job })
+                .collect(),
+            deletions: rows
+                .deletions
+                .into_iter()
+                .map(deletion_from_row)
+                .map(|(project, deletion)| DeletionEntry { project, deletion })
+                .collect(),
+        }
+        .sorted()
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(rename_all = "kebab-case")]
+#[jsm::public]
+struct DeploymentEntry {
+    project: ProjectId,
+    env: String,
+    #[serde(flatten)]
+    deployment: Deployment,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(rename_all = "kebab-case")]
+#[jsm::public]
+struct PromotionEntry {
+    project: ProjectId,
+    env: String,
+    promoted_at: DateTime<Utc>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(rename_all = "kebab-case")]
+#[jsm::public]
+struct RefStateEntry {
+    project: ProjectId,
+    git_ref: String,
+    #[serde(flatten)]
+    state: RefState,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(rename_all = "kebab-case")]
+#[jsm::public]
+struct RunEntry {
+    id: RunId,
+    report: SyncReport,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(rename_all = "kebab-case")]
+#[jsm::public]
+struct PinEntry {
+    project: ProjectId,
+    env: String,
+    #[serde(flatten)]
+    pin: Pin,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(rename_all = "kebab-case")]
+#[jsm::public]
+struct FreezeEntry {
+    scope: FreezeScope,
+    #[serde(flatten)]
+    freeze: Freeze,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(rename_all = "kebab-case")]
+#[jsm::public]
+struct DeletionEntry {
+    project: ProjectId,
+    #[serde(flatten)]
+    deletion: Deletion,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(rename_all = "kebab-case")]
+#[jsm::public]
+struct OutboxSnapshotEntry {
+    id: OutboxId,
+    #[serde(flatten)]
+    entry: OutboxEntry,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(rename_all = "kebab-case")]
+#[jsm::public]
+struct JobEntry {
+    id: JobId,
+    #[serde(flatten)]
+    job: SyncJob,
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::TimeZone;
+
+    use super::*;
+    use crate::{memory::MemoryStateStore, Event, JobState, StateStore, SyncTarget};
+
+    fn snapshot() -> StateSnapshot {
+        let at = Utc.with_ymd_and_hms(2024, 1, 15, 9, 30, 0).unwrap();
+        let project = ProjectId::new("github/acme/api");
+        StateSnapshot {
+            version: SNAPSHOT_VERSION,
+            deployments: vec![DeploymentEntry {
+                project: project.clone(),
+                env: "prod".to_string(),
+                deployment: Deployment {
+                    sha: "abc".to_string(),
+                    deployed_at: at,
+                },
+            }],
+            refs: vec![RefStateEntry {
+                project: project.clone(),
+                git_ref: "heads/main".to_string(),
+                state: RefState {
+                    sha: "def".to_string(),
+                    etag: Some("\"etag\"".to_string()),
+                    observed_at: at,
+                },
+            }],
+            runs: vec![RunEntry {
+                id: RunId(1),
+                report: SyncReport {
+                    simulated: false,
+                    started_at: at,
+                    finished_at: at,
+                    projects: Vec::new(),
+                },
+            }],
+            approvals: vec![Approval {
+                project: project.clone(),
+                env: "prod".to_string(),
+                sha: "def".to_string(),
+                approver: "octocat".to_string(),
+                approved_at: at,
+            }],
+            pins: vec![PinEntry {
+                project: project.clone(),
+                env: "staging".to_string(),
+                pin: Pin {
+                    sha: "abc".to_string(),
+                    reason: Some("bisecting".to_string()),
+                    pinned_at: at,
+                },
+            }],
+            freezes: vec![
+                FreezeEntry {
+                    scope: FreezeScope::Global,
+                    freeze: Freeze {
+                        reason: None,
+                        frozen_at: at,
+                    },
+                },
+                FreezeEntry {
+                    scope: FreezeScope::Project(project.clone()),
+                    freeze: Freeze {
+                        reason: Some("incident".to_string()),
+                        frozen_at: at,
+                    },
+                },
+            ],
+            outbox: vec![OutboxSnapshotEntry {
+                id: OutboxId(3),
+                entry: OutboxEntry::new(
+                    "slack",
+                    Event::RefMoved {
+                        project: project.clone(),
+                        owner: "acme".to_string(),
+                        repo: "api".to_string(),
+                        env: "prod".to_string(),
+                        from: Some("abc".to_string()),
+                        to: "def".to_string(),
+                        at,
+                        simulated: false,
+                        risk: Some(12),
+                    },
+                    at,
+                ),
+            }],
+            promotions: vec![PromotionEntry {
+                project: project.clone(),
+                env: "prod".to_string(),
+                promoted_at: at,
+            }],
+            jobs: vec![JobEntry {
+                id: JobId(2),
+                job: SyncJob {
+                    target: SyncTarget::Projects {
+                        ids: vec![project.clone()],
+                    },
+                    state: JobState::Completed,
+                    queued_at: at,
+                    started_at: Some(at),
+                    heartbeat_at: Some(at),
+                    finished_at: Some(at),
+                    run: Some(RunId(1)),
+                    error: None,
+                    idempotency_key: Some("retry-me".to_string()),
+                },
+            }],
+            deletions: vec![DeletionEntry {
+                project: ProjectId::new("github/acme/legacy"),
+                deletion: Deletion {
+                    reason: Some("archived".to_string()),
+                    deleted_at: at,
+                    purged_at: None,
+                },
+            }],
+        }
+        .sorted()
+    }
+
+    /// Imports the snapshot into `from`, then its export into the empty
+    /// `into`, and checks both exports are the snapshot.
+    async fn round_trip(from: &dyn StateStore, into: &dyn StateStore) {
+        let snapshot = snapshot();
+        from.import_state(&snapshot).await.unwrap();
+        let exported = from.export_state().await.unwrap();
+        into.import_state(&exported).await.unwrap();
+        let reimported = into.export_state().await.unwrap();
+
+        let expected = serde_json::to_value(&snapshot).unwrap();
+        assert_eq!(serde_json::to_value(&exported).unwrap(), expected);
+        assert_eq!(serde_json::to_value(&reimported).unwrap(), expected);
+    }
+
+    #[tokio::test]
+    async fn memory_round_trip() {
+        round_trip(&MemoryStateStore::default(), &MemoryStateStore::default()).await;
+    }
+
+    #[cfg(feature = "sqlite")]
+    #[tokio::test]
+    async fn sqlite_round_trip() {
+        use crate::{sqlite::SqliteStateStore, MigrationMode};
+
+        let dir = std::env::temp_dir();
+        let store = |name: &str| {
+            let path = dir.join(format!("hor-snapshot-{}-{name}.db", std::process::id()));
+            let _ = std::fs::remove_file(&path);
+            let url = format!("sqlite://{}?mode=rwc", path.display());
+            SqliteStateStore::connect_lazy(&url, MigrationMode::Apply).unwrap()
+        };
+        round_trip(&store("from"), &store("into")).await;
+    }
+
+    #[test]
+    fn refuses_other_versions() {
+        let snapshot = StateSnapshot {
+            version: SNAPSHOT_VERSION + 1,
+            ..StateSnapshot::default()
+        };
+        assert!(matches!(
+            snapshot.check_version(),
+            Err(StateStoreError::SnapshotVersion(version)) if version == SNAPSHOT_VERSION + 1
+        ));
+    }
+}
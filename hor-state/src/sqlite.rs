@@ -0,0 +1,960 @@
+use std::collections::HashSet;
+
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use hor_registry::ProjectId;
+use sqlx::{
+    migrate::{Migrate, Migrator},
+    types::Json,
+    SqlitePool,
+};
+use tokio::sync::OnceCell;
+
+use crate::{
+    jobs::{self, JobRow, JOB_COLUMNS},
+    outbox::{self, OutboxRow, OUTBOX_COLUMNS},
+    snapshot::{
+        deletion_from_row, DeletionEntry, DeletionRow, JobEntry, OutboxSnapshotEntry, SnapshotRows,
+    },
+    Approval, Deletion, Deployment, Freeze, FreezeScope, JobId, JobState, MigrationMode,
+    OutboxEntry, OutboxId, Pin, RefState, RunId, StateSnapshot, StateStore, StateStoreError,
+    SyncJob, SyncReport, SyncTarget, PROMOTION_RETENTION_DAYS,
+};
+
+static MIGRATOR: Migrator = sqlx::migrate!("./migrations/sqlite");
+
+const UPSERT_REF_STATE: &str =
+    "INSERT INTO ref_states (project_id, git_ref, sha, etag, observed_at) \
+     VALUES (?, ?, ?, ?, ?) \
+     ON CONFLICT (project_id, git_ref) DO UPDATE \
+     SET sha = excluded.sha, etag = excluded.etag, observed_at = excluded.observed_at";
+
+const UPSERT_DELETION: &str =
+    "INSERT INTO deleted_projects (project_id, reason, deleted_at, purged_at) \
+     VALUES (?, ?, ?, ?) \
+     ON CONFLICT (project_id) DO UPDATE \
+     SET reason = excluded.reason, deleted_at = excluded.deleted_at, \
+     purged_at = excluded.purged_at";
+
+pub struct SqliteStateStore {
+    pool: SqlitePool,
+    migrations: MigrationMode,
+    schema: OnceCell<()>,
+}
+
+impl SqliteStateStore {
+    /// Creates the pool without connecting; migrations are handled on
+    /// first use according to `migrations`.
+    pub fn connect_lazy(url: &str, migrations: MigrationMode) -> Result<Self, StateStoreError> {
+        Ok(SqliteStateStore {
+            pool: SqlitePool::connect_lazy(url)?,
+            migrations,
+            schema: OnceCell::new(),
+        })
+    }
+
+    async fn pool(&self) -> Result<&SqlitePool, StateStoreError> {
+        self.schema
+            .get_or_try_init(|| async {
+                match self.migrations {
+                    MigrationMode::Apply => MIGRATOR.run(&self.pool).await?,
+                    MigrationMode::Check => {
+                        let pending = pending_migrations(&self.pool).await?;
+                        if !pending.is_empty() {
+                            return Err(StateStoreError::PendingMigrations(pending));
+                        }
+                    }
+                }
+                Ok::<_, StateStoreError>(())
+            })
+            .await?;
+        Ok(&self.pool)
+    }
+}
+
+async fn pending_migrations(pool: &SqlitePool) -> Result<Vec<i64>, StateStoreError> {
+    let mut connection = pool.acquire().await?;
+    connection.ensure_migrations_table().await?;
+    let applied: HashSet<i64> = connection
+        .list_applied_migrations()
+        .await?
+        .into_iter()
+        .map(|migration| migration.version)
+        .collect();
+    Ok(MIGRATOR
+        .iter()
+        .filter(|migration| !migration.migration_type.is_down_migration())
+        .map(|migration| migration.version)
+        .filter(|version| !applied.contains(version))
+        .collect())
+}
+
+#[async_trait]
+impl StateStore for SqliteStateStore {
+    async fn prepare(&self) -> Result<(), StateStoreError> {
+        self.pool().await.map(|_| ())
+    }
+
+    async fn pending_migrations(&self) -> Result<Vec<i64>, StateStoreError> {
+        pending_migrations(&self.pool).await
+    }
+
+    async fn apply_migrations(&self) -> Result<(), StateStoreError> {
+        Ok(MIGRATOR.run(&self.pool).await?)
+    }
+
+    async fn last_deployment(
+        &self,
+        project: &ProjectId,
+        env: &str,
+    ) -> Result<Option<Deployment>, StateStoreError> {
+        let row: Option<(String, DateTime<Utc>)> = sqlx::query_as(
+            "SELECT sha, deployed_at FROM deployments WHERE project_id = ? AND env = ?",
+        )
+        .bind(project.as_str())
+        .bind(env)
+        .fetch_optional(self.pool().await?)
+        .await?;
+        Ok(row.map(|(sha, deployed_at)| Deployment { sha, deployed_at }))
+    }
+
+    async fn record_deployment(
+        &self,
+        project: &ProjectId,
+        env: &str,
+        deployment: &Deployment,
+    ) -> Result<(), StateStoreError> {
+        sqlx::query(
+            "INSERT INTO deployments (project_id, env, sha, deployed_at) VALUES (?, ?, ?, ?) \
+             ON CONFLICT (project_id, env) DO UPDATE \
+             SET sha = excluded.sha, deployed_at = excluded.deployed_at",
+        )
+        .bind(project.as_str())
+        .bind(env)
+        .bind(&deployment.sha)
+        .bind(deployment.deployed_at)
+        .execute(self.pool().await?)
+        .await?;
+        Ok(())
+    }
+
+    async fn ref_state(
+        &self,
+        project: &ProjectId,
+        git_ref: &str,
+    ) -> Result<Option<RefState>, StateStoreError> {
+        let row: Option<(String, Option<String>, DateTime<Utc>)> = sqlx::query_as(
+            "SELECT sha, etag, observed_at FROM ref_states WHERE project_id = ? AND git_ref = ?",
+        )
+        .bind(project.as_str())
+        .bind(git_ref)
+        .fetch_optional(self.pool().await?)
+        .await?;
+        Ok(row.map(|(sha, etag, observed_at)| RefState {
+            sha,
+            etag,
+            observed_at,
+        }))
+    }
+
+    async fn record_ref_state(
+        &self,
+        project: &ProjectId,
+        git_ref: &str,
+        state: &RefState,
+    ) -> Result<(), StateStoreError> {
+        sqlx::query(UPSERT_REF_STATE)
+            .bind(project.as_str())
+            .bind(git_ref)
+            .bind(&state.sha)
+            .bind(&state.etag)
+            .bind(state.observed_at)
+            .execute(self.pool().await?)
+            .await?;
+        Ok(())
+    }
+
+    async fn record_promotion(
+        &self,
+        project: &ProjectId,
+        env: &str,
+        at: DateTime<Utc>,
+    ) -> Result<(), StateStoreError> {
+        let pool = self.pool().await?;
+        sqlx::query("DELETE FROM promotions WHERE promoted_at < ?")
+            .bind(at - chrono::Duration::days(PROMOTION_RETENTION_DAYS))
+            .execute(pool)
+            .await?;
+        sqlx::query(
+            "INSERT INTO promotions (project_id, env, promoted_at) VALUES (?, ?, ?) \
+             ON CONFLICT DO NOTHING",
+        )
+        .bind(project.as_str())
+        .bind(env)
+        .bind(at)
+        .execute(pool)
+        .await?;
+        Ok(())
+    }
+
+    async fn promotions_since(
+        &self,
+        env: &str,
+        since: DateTime<Utc>,
+    ) -> Result<Vec<(ProjectId, DateTime<Utc>)>, StateStoreError> {
+        let rows: Vec<(String, DateTime<Utc>)> = sqlx::query_as(
+            "SELECT project_id, promoted_at FROM promotions \
+             WHERE env = ? AND promoted_at >= ? ORDER BY promoted_at",
+        )
+        .bind(env)
+        .bind(since)
+        .fetch_all(self.pool().await?)
+        .await?;
+        Ok(rows
+            .into_iter()
+            .map(|(project, at)| (ProjectId::new(project), at))
+            .collect())
+    }
+
+    async fn record_run(&self, report: &SyncReport) -> Result<RunId, StateStoreError> {
+        let result = sqlx::query("INSERT INTO runs (started_at, report) VALUES (?, ?)")
+            .bind(report.started_at)
+            .bind(Json(report))
+            .execute(self.pool().await?)
+            .await?;
+        Ok(RunId(result.last_insert_rowid()))
+    }
+
+    async fn run(&self, id: RunId) -> Result<Option<SyncReport>, StateStoreError> {
+        let row: Option<(Json<SyncReport>,)> =
+            sqlx::query_as("SELECT report FROM runs WHERE id = ?")
+                .bind(id.0)
+                .fetch_optional(self.pool().await?)
+                .await?;
+        Ok(row.map(|(Json(report),)| report))
+    }
+
+    async fn recent_runs(&self, limit: usize) -> Result<Vec<(RunId, SyncReport)>, StateStoreError> {
+        let rows: Vec<(i64, Json<SyncReport>)> =
+            sqlx::query_as("SELECT id, report FROM runs ORDER BY id DESC LIMIT ?")
+                .bind(i64::try_from(limit).unwrap_or(i64::MAX))
+                .fetch_all(self.pool().await?)
+                .await?;
+        Ok(rows
+            .into_iter()
+            .map(|(id, Json(report))| (RunId(id), report))
+            .collect())
+    }
+
+    async fn add_approval(&self, approval: &Approval) -> Result<(), StateStoreError> {
+        sqlx::query(
+            "INSERT INTO approvals (project_id, env, sha, approver, approved_at) \
+             VALUES (?, ?, ?, ?, ?) ON CONFLICT DO NOTHING",
+        )
+        .bind(approval.project.as_str())
+        .bind(&approval.env)
+        .bind(&approval.sha)
+        .bind(&approval.approver)
+        .bind(approval.approved_at)
+        .execute(self.pool().await?)
+        .await?;
+        Ok(())
+    }
+
+    async fn approvals(
+        &self,
+        project: &ProjectId,
+        env: &str,
+        sha: &str,
+    ) -> Result<Vec<Approval>, StateStoreError> {
+        let rows: Vec<(String, DateTime<Utc>)> = sqlx::query_as(
+            "SELECT approver, approved_at FROM approvals \
+             WHERE project_id = ? AND env = ? AND sha = ? ORDER BY approved_at",
+        )
+        .bind(project.as_str())
+        .bind(env)
+        .bind(sha)
+        .fetch_all(self.pool().await?)
+        .await?;
+        Ok(rows
+            .into_iter()
+            .map(|(approver, approved_at)| Approval {
+                project: project.clone(),
+                env: env.to_string(),
+                sha: sha.to_string(),
+                approver,
+                approved_at,
+            })
+            .collect())
+    }
+
+    async fn set_pin(
+        &self,
+        project: &ProjectId,
+        env: &str,
+        pin: Option<&Pin>,
+    ) -> Result<(), StateStoreError> {
+        let pool = self.pool().await?;
+        match pin {
+            Some(pin) => sqlx::query(
+                "INSERT INTO pins (project_id, env, sha, reason, pinned_at) VALUES (?, ?, ?, ?, ?) \
+                 ON CONFLICT (project_id, env) DO UPDATE \
+                 SET sha = excluded.sha, reason = excluded.reason, pinned_at = excluded.pinned_at",
+            )
+            .bind(project.as_str())
+            .bind(env)
+            .bind(&pin.sha)
+            .bind(&pin.reason)
+            .bind(pin.pinned_at)
+            .execute(pool)
+            .await?,
+            None => sqlx::query("DELETE FROM pins WHERE project_id = ? AND env = ?")
+                .bind(project.as_str())
+                .bind(env)
+                .execute(pool)
+                .await?,
+        };
+        Ok(())
+    }
+
+    async fn pin(&self, project: &ProjectId, env: &str) -> Result<Option<Pin>, StateStoreError> {
+        let row: Option<(String, Option<String>, DateTime<Utc>)> = sqlx::query_as(
+            "SELECT sha, reason, pinned_at FROM pins WHERE project_id = ? AND env = ?",
+        )
+        .bind(project.as_str())
+        .bind(env)
+        .fetch_optional(self.pool().await?)
+        .await?;
+        Ok(row.map(|(sha, reason, pinned_at)| Pin {
+            sha,
+            reason,
+            pinned_at,
+        }))
+    }
+
+    async fn set_freeze(
+        &self,
+        scope: &FreezeScope,
+        freeze: Option<&Freeze>,
+    ) -> Result<(), StateStoreError> {
+        let pool = self.pool().await?;
+        match freeze {
+            Some(freeze) => {
+                sqlx::query(
+                    "INSERT INTO freezes (scope, reason, frozen_at) VALUES (?, ?, ?) \
+                 ON CONFLICT (scope) DO UPDATE \
+                 SET reason = excluded.reason, frozen_at = excluded.frozen_at",
+                )
+                .bind(scope.key())
+                .bind(&freeze.reason)
+                .bind(freeze.frozen_at)
+                .execute(pool)
+                .await?
+            }
+            None => {
+                sqlx::query("DELETE FROM freezes WHERE scope = ?")
+                    .bind(scope.key())
+                    .execute(pool)
+                    .await?
+            }
+        };
+        Ok(())
+    }
+
+    async fn freeze(&self, scope: &FreezeScope) -> Result<Option<Freeze>, StateStoreError> {
+        let row: Option<(Option<String>, DateTime<Utc>)> =
+            sqlx::query_as("SELECT reason, frozen_at FROM freezes WHERE scope = ?")
+                .bind(scope.key())
+                .fetch_optional(self.pool().await?)
+                .await?;
+        Ok(row.map(|(reason, frozen_at)| Freeze { reason, frozen_at }))
+    }
+
+    async fn insert_deletion(
+        &self,
+        project: &ProjectId,
+        deletion: &Deletion,
+    ) -> Result<bool, StateStoreError> {
+        let inserted = sqlx::query(
+            "INSERT INTO deleted_projects (project_id, reason, deleted_at, purged_at) \
+             VALUES (?, ?, ?, ?) \
+             ON CONFLICT (project_id) DO NOTHING",
+        )
+        .bind(project.as_str())
+        .bind(&deletion.reason)
+        .bind(deletion.deleted_at)
+        .bind(deletion.purged_at)
+        .execute(self.pool().await?)
+        .await?;
+        Ok(inserted.rows_affected() > 0)
+    }
+
+    async fn remove_deletion(&self, project: &ProjectId) -> Result<bool, StateStoreError> {
+        let removed = sqlx::query("DELETE FROM deleted_projects WHERE project_id = ?")
+            .bind(project.as_str())
+            .execute(self.pool().await?)
+            .await?;
+        Ok(removed.rows_affected() > 0)
+    }
+
+    async fn deletion(&self, project: &ProjectId) -> Result<Option<Deletion>, StateStoreError> {
+        let row: Option<DeletionRow> = sqlx::query_as(
+            "SELECT project_id, reason, deleted_at, purged_at FROM deleted_projects \
+             WHERE project_id = ?",
+        )
+        .bind(project.as_str())
+        .fetch_optional(self.pool().await?)
+        .await?;
+        Ok(row.map(|row| deletion_from_row(row).1))
+    }
+
+    async fn deletions(&self) -> Result<Vec<(ProjectId, Deletion)>, StateStoreError> {
+        let rows: Vec<DeletionRow> = sqlx::query_as(
+            "SELECT project_id, reason, deleted_at, purged_at FROM deleted_projects \
+             ORDER BY deleted_at",
+        )
+        .fetch_all(self.pool().await?)
+        .await?;
+        Ok(rows.into_iter().map(deletion_from_row).collect())
+    }
+
+    async fn purge_project(
+        &self,
+        project: &ProjectId,
+        at: DateTime<Utc>,
+    ) -> Result<(), StateStoreError> {
+        let mut transaction = self.pool().await?.begin().await?;
+        for table in [
+            "deployments",
+            "ref_states",
+            "promotions",
+            "approvals",
+            "pins",
+        ] {
+            sqlx::query(&format!("DELETE FROM {table} WHERE project_id = ?"))
+                .bind(project.as_str())
+                .execute(&mut *transaction)
+                .await?;
+        }
+        sqlx::query("DELETE FROM freezes WHERE scope = ?")
+            .bind(FreezeScope::Project(project.clone()).key())
+            .execute(&mut *transaction)
+            .await?;
+        sqlx::query("UPDATE deleted_projects SET purged_at = ? WHERE project_id = ?")
+            .bind(at)
+            .bind(project.as_str())
+            .execute(&mut *transaction)
+            .await?;
+        transaction.commit().await?;
+        Ok(())
+    }
+
+    async fn enqueue_outbox(
+        &self,
+        entries: &[OutboxEntry],
+    ) -> Result<Vec<OutboxId>, StateStoreError> {
+        let mut transaction = self.pool().await?.begin().await?;
+        let mut ids = Vec::with_capacity(entries.len());
+        for entry in entries {
+            let (id,): (i64,) = sqlx::query_as(
+                "INSERT INTO outbox (sink, event, attempts, next_attempt_at, last_error, pending) \
+                 VALUES (?, ?, ?, ?, ?, ?) RETURNING id",
+            )
+            .bind(&entry.sink)
+            .bind(Json(&entry.event))
+            .bind(i64::from(entry.attempts))
+            .bind(entry.next_attempt_at)
+            .bind(&entry.last_error)
+            .bind(entry.pending)
+            .fetch_one(&mut *transaction)
+            .await?;
+            ids.push(OutboxId(id));
+        }
+        transaction.commit().await?;
+        Ok(ids)
+    }
+
+    async fn pending_outbox(&self) -> Result<Vec<(OutboxId, OutboxEntry)>, StateStoreError> {
+        let rows: Vec<OutboxRow> = sqlx::query_as(&format!(
+            "SELECT {OUTBOX_COLUMNS} FROM outbox WHERE pending ORDER BY id"
+        ))
+        .fetch_all(self.pool().await?)
+        .await?;
+        Ok(rows.into_iter().map(outbox::from_row).collect())
+    }
+
+    async fn release_outbox(
+        &self,
+        ids: &[OutboxId],
+        at: DateTime<Utc>,
+    ) -> Result<(), StateStoreError> {
+        let mut transaction = self.pool().await?.begin().await?;
+        for id in ids {
+            sqlx::query(
+                "UPDATE outbox SET pending = FALSE, next_attempt_at = ? \
+                 WHERE id = ? AND pending",
+            )
+            .bind(at)
+            .bind(id.0)
+            .execute(&mut *transaction)
+            .await?;
+        }
+        transaction.commit().await?;
+        Ok(())
+    }
+
+    async fn due_outbox(
+        &self,
+        now: DateTime<Utc>,
+        limit: usize,
+    ) -> Result<Vec<(OutboxId, OutboxEntry)>, StateStoreError> {
+        let rows: Vec<OutboxRow> = sqlx::query_as(&format!(
+            "SELECT {OUTBOX_COLUMNS} FROM outbox \
+             WHERE next_attempt_at <= ? \
+             ORDER BY id LIMIT ?"
+        ))
+        .bind(now)
+        .bind(i64::try_from(limit).unwrap_or(i64::MAX))
+        .fetch_all(self.pool().await?)
+        .await?;
+        Ok(rows.into_iter().map(outbox::from_row).collect())
+    }
+
+    async fn complete_outbox(&self, id: OutboxId) -> Result<(), StateStoreError> {
+        sqlx::query("DELETE FROM outbox WHERE id = ?")
+            .bind(id.0)
+            .execute(self.pool().await?)
+            .await?;
+        Ok(())
+    }
+
+    async fn retry_outbox(
+        &self,
+        id: OutboxId,
+        next_attempt_at: Option<DateTime<Utc>>,
+        error: &str,
+    ) -> Result<(), StateStoreError> {
+        sqlx::query(
+            "UPDATE outbox SET attempts = attempts + 1, next_attempt_at = ?, last_error = ? \
+             WHERE id = ?",
+        )
+        .bind(next_attempt_at)
+        .bind(error)
+        .bind(id.0)
+        .execute(self.pool().await?)
+        .await?;
+        Ok(())
+    }
+
+    async fn enqueue_job(
+        &self,
+        target: &SyncTarget,
+        idempotency_key: Option<&str>,
+        at: DateTime<Utc>,
+    ) -> Result<JobId, StateStoreError> {
+        let pool = self.pool().await?;
+        let inserted: Option<(i64,)> = sqlx::query_as(
+            "INSERT INTO jobs (target, state, queued_at, idempotency_key) \
+             VALUES (?, ?, ?, ?) ON CONFLICT DO NOTHING RETURNING id",
+        )
+        .bind(Json(target))
+        .bind(JobState::Queued.key())
+        .bind(at)
+        .bind(idempotency_key)
+        .fetch_optional(pool)
+        .await?;
+        // Only a taken idempotency key keeps the row from being inserted
+        let (id,) = match inserted {
+            Some(id) => id,
+            None => {
+                sqlx::query_as("SELECT id FROM jobs WHERE idempotency_key = ?")
+                    .bind(idempotency_key)
+                    .fetch_one(pool)
+                    .await?
+            }
+        };
+        Ok(JobId(id))
+    }
+
+    async fn claim_job(
+        &self,
+        now: DateTime<Utc>,
+        stale_before: DateTime<Utc>,
+    ) -> Result<Option<(JobId, SyncJob)>, StateStoreError> {
+        // A single statement, so concurrent claims can't take the same job
+        let row: Option<JobRow> = sqlx::query_as(&format!(
+            "UPDATE jobs SET state = ?1, started_at = ?2, heartbeat_at = ?2 \
+             WHERE id = (SELECT id FROM jobs \
+             WHERE state = ?3 \
+             OR (state = ?1 AND COALESCE(heartbeat_at, started_at) < ?4) \
+             ORDER BY id LIMIT 1) \
+             RETURNING {JOB_COLUMNS}"
+        ))
+        .bind(JobState::Running.key())
+        .bind(now)
+        .bind(JobState::Queued.key())
+        .bind(stale_before)
+        .fetch_optional(self.pool().await?)
+        .await?;
+        Ok(row.map(jobs::from_row))
+    }
+
+    async fn heartbeat_job(&self, id: JobId, at: DateTime<Utc>) -> Result<(), StateStoreError> {
+        sqlx::query("UPDATE jobs SET heartbeat_at = ? WHERE id = ? AND state = ?")
+            .bind(at)
+            .bind(id.0)
+            .bind(JobState::Running.key())
+            .execute(self.pool().await?)
+            .await?;
+        Ok(())
+    }
+
+    async fn finish_job(
+        &self,
+        id: JobId,
+        run: Option<RunId>,
+        error: Option<&str>,
+        at: DateTime<Utc>,
+    ) -> Result<(), StateStoreError> {
+        let state = match error {
+            Some(_) => JobState::Failed,
+            None => JobState::Completed,
+        };
+        sqlx::query(
+            "UPDATE jobs SET state = ?, finished_at = ?, run_id = ?, error = ? WHERE id = ?",
+        )
+        .bind(state.key())
+        .bind(at)
+        .bind(run.map(|run| run.0))
+        .bind(error)
+        .bind(id.0)
+        .execute(self.pool().await?)
+        .await?;
+        Ok(())
+    }
+
+    async fn recent_jobs(&self, limit: usize) -> Result<Vec<(JobId, SyncJob)>, StateStoreError> {
+        let rows: Vec<JobRow> = sqlx::query_as(&format!(
+            "SELECT {JOB_COLUMNS} FROM jobs ORDER BY id DESC LIMIT ?"
+        ))
+        .bind(i64::try_from(limit).unwrap_or(i64::MAX))
+        .fetch_all(self.pool().await?)
+        .await?;
+        Ok(rows.into_iter().map(jobs::from_row).collect())
+    }
+
+    async fn export_state(&self) -> Result<StateSnapshot, StateStoreError> {
+        let pool = self.pool().await?;
+        let rows = SnapshotRows {
+            deployments: sqlx::query_as(
+                "SELECT project_id, env, sha, deployed_at FROM deployments",
+            )
+            .fetch_all(pool)
+            .await?,
+            refs: sqlx::query_as(
+                "SELECT project_id, git_ref, sha, etag, observed_at FROM ref_states",
+            )
+            .fetch_all(pool)
+            .await?,
+            runs: sqlx::query_as("SELECT id, report FROM runs ORDER BY id")
+                .fetch_all(pool)
+                .await?,
+            approvals: sqlx::query_as(
+                "SELECT project_id, env, sha, approver, approved_at FROM approvals",
+            )
+            .fetch_all(pool)
+            .await?,
+            pins: sqlx::query_as("SELECT project_id, env, sha, reason, pinned_at FROM pins")
+                .fetch_all(pool)
+                .await?,
+            freezes: sqlx::query_as("SELECT scope, reason, frozen_at FROM freezes")
+                .fetch_all(pool)
+                .await?,
+            promotions: sqlx::query_as("SELECT project_id, env, promoted_at FROM promotions")
+                .fetch_all(pool)
+                .await?,
+            jobs: sqlx::query_as(&format!("SELECT {JOB_COLUMNS} FROM jobs ORDER BY id"))
+                .fetch_all(pool)
+                .await?,
+            deletions: sqlx::query_as(
+                "SELECT project_id, reason, deleted_at, purged_at FROM deleted_projects",
+            )
+            .fetch_all(pool)
+            .await?,
+            outbox: sqlx::query_as(&format!("SELECT {OUTBOX_COLUMNS} FROM outbox ORDER BY id"))
+                .fetch_all(pool)
+                .await?,
+        };
+        Ok(rows.into())
+    }
+
+    async fn import_state(&self, snapshot: &StateSnapshot) -> Result<(), StateStoreError> {
+        snapshot.check_version()?;
+
+        let mut transaction = self.pool().await?.begin().await?;
+        for entry in &snapshot.deployments {
+            sqlx::query(
+                "INSERT INTO deployments (project_id, env, sha, deployed_at) VALUES (?, ?, ?, ?) \
+                 ON CONFLICT (project_id, env) DO UPDATE \
+                 SET sha = excluded.sha, deployed_at = excluded.deployed_at",
+            )
+            .bind(entry.project.as_str())
+            .bind(&entry.env)
+            .bind(&entry.deployment.sha)
+            .bind(entry.deployment.deployed_at)
+            .execute(&mut *transaction)
+            .await?;
+        }
+        for entry in &snapshot.refs {
+            sqlx::query(UPSERT_REF_STATE)
+                .bind(entry.project.as_str())
+                .bind(&entry.git_ref)
+                .bind(&entry.state.sha)
+                .bind(&entry.state.etag)
+                .bind(entry.state.observed_at)
+                .execute(&mut *transaction)
+                .await?;
+        }
+        for entry in &snapshot.runs {
+            sqlx::query(
+                "INSERT INTO runs (id, started_at, report) VALUES (?, ?, ?) \
+                 ON CONFLICT (id) DO UPDATE \
+                 SET started_at = excluded.started_at, report = excluded.report",
+            )
+            .bind(entry.id.0)
+            .bind(entry.report.started_at)
+            .bind(Json(&entry.report))
+            .execute(&mut *transaction)
+            .await?;
+        }
+        for approval in &snapshot.approvals {
+            sqlx::query(
+                "INSERT INTO approvals (project_id, env, sha, approver, approved_at) \
+                 VALUES (?, ?, ?, ?, ?) \
+                 ON CONFLICT (project_id, env, sha, approver) DO UPDATE \
+                 SET approved_at = excluded.approved_at",
+            )
+            .bind(approval.project.as_str())
+            .bind(&approval.env)
+            .bind(&approval.sha)
+            .bind(&approval.approver)
+            .bind(approval.approved_at)
+            .execute(&mut *transaction)
+            .await?;
+        }
+        for entry in &snapshot.pins {
+            sqlx::query(
+                "INSERT INTO pins (project_id, env, sha, reason, pinned_at) \
+                 VALUES (?, ?, ?, ?, ?) \
+                 ON CONFLICT (project_id, env) DO UPDATE \
+                 SET sha = excluded.sha, reason = excluded.reason, pinned_at = excluded.pinned_at",
+            )
+            .bind(entry.project.as_str())
+            .bind(&entry.env)
+            .bind(&entry.pin.sha)
+            .bind(&entry.pin.reason)
+            .bind(entry.pin.pinned_at)
+            .execute(&mut *transaction)
+            .await?;
+        }
+        for entry in &snapshot.freezes {
+            sqlx::query(
+                "INSERT INTO freezes (scope, reason, frozen_at) VALUES (?, ?, ?) \
+                 ON CONFLICT (scope) DO UPDATE \
+                 SET reason = excluded.reason, frozen_at = excluded.frozen_at",
+            )
+            .bind(entry.scope.key())
+            .bind(&entry.freeze.reason)
+            .bind(entry.freeze.frozen_at)
+            .execute(&mut *transaction)
+            .await?;
+        }
+        for OutboxSnapshotEntry { id, entry } in &snapshot.outbox {
+            sqlx::query(&format!(
+                "INSERT INTO outbox ({OUTBOX_COLUMNS}) VALUES (?, ?, ?, ?, ?, ?, ?) \
+                 ON CONFLICT (id) DO UPDATE \
+                 SET sink = excluded.sink, event = excluded.event, attempts = excluded.attempts, \
+                 next_attempt_at = excluded.next_attempt_at, last_error = excluded.last_error, \
+                 pending = excluded.pending"
+            ))
+            .bind(id.0)
+            .bind(&entry.sink)
+            .bind(Json(&entry.event))
+            .bind(i64::from(entry.attempts))
+            .bind(entry.next_attempt_at)
+            .bind(&entry.last_error)
+            .bind(entry.pending)
+            .execute(&mut *transaction)
+            .await?;
+        }
+        for entry in &snapshot.promotions {
+            sqlx::query(
+                "INSERT INTO promotions (project_id, env, promoted_at) VALUES (?, ?, ?) \
+                 ON CONFLICT DO NOTHING",
+            )
+            .bind(entry.project.as_str())
+            .bind(&entry.env)
+            .bind(entry.promoted_at)
+            .execute(&mut *transaction)
+            .await?;
+        }
+        for JobEntry { id, job } in &snapshot.jobs {
+            sqlx::query(&format!(
+                "INSERT INTO jobs ({JOB_COLUMNS}) VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?) \
+                 ON CONFLICT (id) DO UPDATE \
+                 SET target = excluded.target, state = excluded.state, \
+                 queued_at = excluded.queued_at, started_at = excluded.started_at, \
+                 finished_at = excluded.finished_at, run_id = excluded.run_id, \
+                 error = excluded.error, idempotency_key = excluded.idempotency_key, \
+                 heartbeat_at = excluded.heartbeat_at"
+            ))
+            .bind(id.0)
+            .bind(Json(&job.target))
+            .bind(job.state.key())
+            .bind(job.queued_at)
+            .bind(job.started_at)
+            .bind(job.finished_at)
+            .bind(job.run.map(|run| run.0))
+            .bind(&job.error)
+            .bind(&job.idempotency_key)
+            .bind(job.heartbeat_at)
+            .execute(&mut *transaction)
+            .await?;
+        }
+        for DeletionEntry { project, deletion } in &snapshot.deletions {
+            sqlx::query(UPSERT_DELETION)
+                .bind(project.as_str())
+                .bind(&deletion.reason)
+                .bind(deletion.deleted_at)
+                .bind(deletion.purged_at)
+                .execute(&mut *transaction)
+                .await?;
+        }
+        transaction.commit().await?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::TimeZone;
+
+    use super::*;
+
+    /// A store over a fresh database file of its own.
+    fn store(name: &str, migrations: MigrationMode) -> SqliteStateStore {
+        let path =
+            std::env::temp_dir().join(format!("hor-sqlite-{}-{name}.db", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let url = format!("sqlite://{}?mode=rwc", path.display());
+        SqliteStateStore::connect_lazy(&url, migrations).unwrap()
+    }
+
+    fn at(hour: u32) -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(2024, 1, 15, hour, 0, 0).unwrap()
+    }
+
+    #[tokio::test]
+    async fn checks_or_applies_migrations() {
+        let checked = store("check", MigrationMode::Check);
+        assert!(!checked.pending_migrations().await.unwrap().is_empty());
+        assert!(matches!(
+            checked.prepare().await,
+            Err(StateStoreError::PendingMigrations(_))
+        ));
+
+        let applied = store("apply", MigrationMode::Apply);
+        applied.prepare().await.unwrap();
+        assert!(applied.pending_migrations().await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn keeps_the_last_deployment() {
+        let store = store("deployments", MigrationMode::Apply);
+        let project = ProjectId::new("github/acme/api/prod");
+        assert_eq!(store.last_deployment(&project, "prod").await.unwrap(), None);
+        for (sha, hour) in [("abc", 9), ("def", 10)] {
+            let deployment = Deployment {
+                sha: sha.to_string(),
+                deployed_at: at(hour),
+            };
+            store
+                .record_deployment(&project, "prod", &deployment)
+                .await
+                .unwrap();
+        }
+        let deployment = store.last_deployment(&project, "prod").await.unwrap();
+        assert_eq!(
+            deployment,
+            Some(Deployment {
+                sha: "def".to_string(),
+                deployed_at: at(10),
+            })
+        );
+    }
+
+    #[tokio::test]
+    async fn project_freezes_are_never_global() {
+        let store = store("freezes", MigrationMode::Apply);
+        let freeze = Freeze {
+            reason: Some("incident".to_string()),
+            frozen_at: at(9),
+        };
+        let star = FreezeScope::Project(ProjectId::new("*"));
+        store.set_freeze(&star, Some(&freeze)).await.unwrap();
+        assert_eq!(store.freeze(&star).await.unwrap(), Some(freeze));
+        assert_eq!(store.freeze(&FreezeScope::Global).await.unwrap(), None);
+
+        store.set_freeze(&star, None).await.unwrap();
+        assert_eq!(store.freeze(&star).await.unwrap(), None);
+    }
+
+    #[tokio::test]
+    async fn keeps_the_first_deletion() {
+        let store = store("deletions", MigrationMode::Apply);
+        let project = ProjectId::new("github/acme/api/prod");
+        let deletion = |reason: &str, hour| Deletion {
+            reason: Some(reason.to_string()),
+            deleted_at: at(hour),
+            purged_at: None,
+        };
+        assert!(store
+            .insert_deletion(&project, &deletion("archived", 9))
+            .await
+            .unwrap());
+        assert!(!store
+            .insert_deletion(&project, &deletion("again", 10))
+            .await
+            .unwrap());
+        assert_eq!(
+            store.deletion(&project).await.unwrap(),
+            Some(deletion("archived", 9))
+        );
+
+        assert!(store.remove_deletion(&project).await.unwrap());
+        assert!(!store.remove_deletion(&project).await.unwrap());
+        assert!(store.deletions().await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn reclaims_jobs_without_a_recent_heartbeat() {
+        let store = store("jobs", MigrationMode::Apply);
+        let target = SyncTarget::Selector {
+            selector: String::new(),
+        };
+        let id = store.enqueue_job(&target, None, at(9)).await.unwrap();
+        let claimed = store.claim_job(at(9), at(8)).await.unwrap();
+        assert_eq!(claimed.map(|(id, _)| id), Some(id));
+
+        store.heartbeat_job(id, at(11)).await.unwrap();
+        assert_eq!(store.claim_job(at(12), at(10)).await.unwrap(), None);
+        let reclaimed = store.claim_job(at(13), at(12)).await.unwrap();
+        assert_eq!(reclaimed.map(|(id, _)| id), Some(id));
+
+        store
+            .finish_job(id, Some(RunId(1)), None, at(13))
+            .await
+            .unwrap();
+        assert_eq!(store.claim_job(at(20), at(19)).await.unwrap(), None);
+    }
+}
@@ -0,0 +1,59 @@
+//! Canned GitHub documents, trimmed to the fields octocrab requires and
+//! hands-off-release reads.
+
+use serde_json::{json, Value};
+
+/// The empty tree's hash, as good a commit sha as any.
+pub const SHA: &str = "4b825dc642cb6eb9a060e54bf8d69288fbee4904";
+/// A second commit, for releases that move something.
+pub const NEXT_SHA: &str = "8c1a5f2e0b7d4f6a9e3c2b1d0f9e8a7b6c5d4e3f";
+
+/// The repository `owner/repo` as `GET /repos/{owner}/{repo}` returns it.
+/// The caller always has push access.
+pub fn repository(
+    url: &str,
+    owner: &str,
+    repo: &str,
+    default_branch: Option<&str>,
+    archived: bool,
+) -> Value {
+    json!({
+        "id": 1,
+        "node_id": format!("R_{owner}_{repo}"),
+        "name": repo,
+        "full_name": format!("{owner}/{repo}"),
+        "url": format!("{url}/repos/{owner}/{repo}"),
+        "default_branch": default_branch,
+        "archived": archived,
+        // Zero until the first push
+        "size": default_branch.map_or(0, |_| 1),
+        "permissions": { "admin": false, "push": true, "pull": true },
+    })
+}
+
+/// The ref `name` (e.g. `tags/prod`) pointing at the commit `sha`, as the
+/// git refs endpoints return it.
+pub fn git_ref(url: &str, owner: &str, repo: &str, name: &str, sha: &str) -> Value {
+    json!({
+        "ref": format!("refs/{name}"),
+        "node_id": format!("REF_{owner}_{repo}"),
+        "url": format!("{url}/repos/{owner}/{repo}/git/refs/{name}"),
+        // Lightweight, as hands-off-release creates them
+        "object": {
+            "type": "commit",
+            "sha": sha,
+            "url": format!("{url}/repos/{owner}/{repo}/git/commits/{sha}"),
+        },
+    })
+}
+
+/// A budget that never runs out, so the scheduler never waits on it.
+pub fn rate_limit() -> Value {
+    let rate = json!({ "limit": 1_000_000, "used": 0, "remaining": 1_000_000, "reset": i32::MAX });
+    json!({ "resources": { "core": rate, "search": rate }, "rate": rate })
+}
+
+/// GitHub's error document.
+pub fn error(message: &str) -> Value {
+    json!({ "message": message, "documentation_url": "https://docs.github.com/rest" })
+}
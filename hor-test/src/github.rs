@@ -0,0 +1,230 @@
+//! A fake of the GitHub endpoints a sync reads and writes: repositories
+//! and their git refs. Refs are kept in memory, so a sync that moves a tag
+//! sees it moved on its next pass, just like against GitHub.
+
+use std::{
+    collections::{BTreeMap, HashMap},
+    sync::{Arc, Mutex, MutexGuard},
+};
+
+use serde_json::Value;
+use wiremock::{
+    matchers::{method, path, path_regex},
+    Mock, MockServer, Request, Respond, ResponseTemplate,
+};
+
+use crate::fixtures;
+
+pub struct MockGithub {
+    server: MockServer,
+    repos: Arc<Mutex<Repos>>,
+}
+
+type Repos = HashMap<(String, String), FakeRepo>;
+
+struct FakeRepo {
+    default_branch: Option<String>,
+    archived: bool,
+    /// Shas by ref name, e.g. `heads/main`
+    refs: BTreeMap<String, String>,
+}
+
+impl MockGithub {
+    /// Starts a server without any repositories.
+    pub async fn start() -> MockGithub {
+        let server = MockServer::start().await;
+        let repos = Arc::new(Mutex::new(Repos::new()));
+
+        Mock::given(method("GET"))
+            .and(path("/rate_limit"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(fixtures::rate_limit()))
+            .mount(&server)
+            .await;
+        Mock::given(path_regex("^/repos/"))
+            .respond_with(Fake {
+                url: server.uri(),
+                repos: repos.clone(),
+            })
+            .mount(&server)
+            .await;
+        MockGithub { server, repos }
+    }
+
+    /// The API URL to configure as `github-api-url`.
+    pub fn uri(&self) -> String {
+        self.server.uri()
+    }
+
+    /// The underlying server, to mount mocks of further endpoints on, e.g.
+    /// those policies read.
+    pub fn server(&self) -> &MockServer {
+        &self.server
+    }
+
+    /// Adds `owner/repo` with its `main` branch at `sha`, replacing any
+    /// repository of that name.
+    pub fn add_repo(&self, owner: &str, repo: &str, sha: &str) {
+        self.lock().insert(
+            (owner.to_string(), repo.to_string()),
+            FakeRepo {
+                default_branch: Some("main".to_string()),
+                archived: false,
+                refs: BTreeMap::from([("heads/main".to_string(), sha.to_string())]),
+            },
+        );
+    }
+
+    /// Adds `owner/repo` without any commits.
+    pub fn add_empty_repo(&self, owner: &str, repo: &str) {
+        self.lock().insert(
+            (owner.to_string(), repo.to_string()),
+            FakeRepo {
+                default_branch: None,
+                archived: false,
+                refs: BTreeMap::new(),
+            },
+        );
+    }
+
+    /// Marks `owner/repo` as archived.
+    pub fn archive(&self, owner: &str, repo: &str) {
+        self.with_repo(owner, repo, |repo| repo.archived = true);
+    }
+
+    /// Points the ref `name` (e.g. `tags/prod`) of `owner/repo` at `sha`,
+    /// or deletes it if `sha` is `None`.
+    pub fn set_ref(&self, owner: &str, repo: &str, name: &str, sha: Option<&str>) {
+        self.with_repo(owner, repo, |repo| match sha {
+            Some(sha) => {
+                repo.refs.insert(name.to_string(), sha.to_string());
+            }
+            None => {
+                repo.refs.remove(name);
+            }
+        });
+    }
+
+    /// Where the ref `name` of `owner/repo` points, `None` if it doesn't
+    /// exist.
+    pub fn git_ref(&self, owner: &str, repo: &str, name: &str) -> Option<String> {
+        self.lock()
+            .get(&(owner.to_string(), repo.to_string()))
+            .and_then(|repo| repo.refs.get(name).cloned())
+    }
+
+    /// Answers the next `reads` reads of the ref `name` of `owner/repo`
+    /// with `sha` whatever the ref is, e.g. to have it move between the
+    /// reads of a sync.
+    pub async fn stale_ref(&self, owner: &str, repo: &str, name: &str, sha: &str, reads: u64) {
+        Mock::given(method("GET"))
+            .and(path(format!("/repos/{owner}/{repo}/git/ref/{name}")))
+            .respond_with(ResponseTemplate::new(200).set_body_json(fixtures::git_ref(
+                &self.uri(),
+                owner,
+                repo,
+                name,
+                sha,
+            )))
+            .up_to_n_times(reads)
+            // Ahead of the fake repositories
+            .with_priority(1)
+            .mount(&self.server)
+            .await;
+    }
+
+    fn with_repo(&self, owner: &str, repo: &str, update: impl FnOnce(&mut FakeRepo)) {
+        let mut repos = self.lock();
+        let repo = repos
+            .get_mut(&(owner.to_string(), repo.to_string()))
+            .unwrap_or_else(|| panic!("{owner}/{repo} was never added"));
+        update(repo);
+    }
+
+    fn lock(&self) -> MutexGuard<'_, Repos> {
+        self.repos.lock().expect("mock repositories poisoned")
+    }
+}
+
+/// Serves everything under `/repos/` from the shared repositories.
+struct Fake {
+    url: String,
+    repos: Arc<Mutex<Repos>>,
+}
+
+impl Respond for Fake {
+    fn respond(&self, request: &Request) -> ResponseTemplate {
+        let url = self.url.as_str();
+        let segments: Vec<_> = request.url.path()[1..].split('/').collect();
+        let [_, owner, repo, rest @ ..] = segments.as_slice() else {
+            return not_found();
+        };
+        let mut repos = self.repos.lock().expect("mock repositories poisoned");
+        let Some(fake) = repos.get_mut(&(owner.to_string(), repo.to_string())) else {
+            return not_found();
+        };
+
+        match (request.method.to_string().as_str(), rest) {
+            ("GET", []) => ResponseTemplate::new(200).set_body_json(fixtures::repository(
+                url,
+                owner,
+                repo,
+                fake.default_branch.as_deref(),
+                fake.archived,
+            )),
+            ("GET", ["git", "ref", name @ ..]) => {
+                let name = name.join("/");
+                match fake.refs.get(&name) {
+                    Some(sha) => ResponseTemplate::new(200)
+                        .set_body_json(fixtures::git_ref(url, owner, repo, &name, sha)),
+                    None => not_found(),
+                }
+            }
+            ("POST", ["git", "refs"]) => {
+                let Some((name, sha)) = ref_update(request, Some("ref")) else {
+                    return unprocessable("Invalid request");
+                };
+                let Some(name) = name.strip_prefix("refs/") else {
+                    return unprocessable("Reference name must start with refs/");
+                };
+                if fake.refs.contains_key(name) {
+                    return unprocessable("Reference already exists");
+                }
+                fake.refs.insert(name.to_string(), sha.clone());
+                ResponseTemplate::new(201)
+                    .set_body_json(fixtures::git_ref(url, owner, repo, name, &sha))
+            }
+            ("PATCH", ["git", "refs", name @ ..]) => {
+                let name = name.join("/");
+                let Some((_, sha)) = ref_update(request, None) else {
+                    return unprocessable("Invalid request");
+                };
+                let Some(current) = fake.refs.get_mut(&name) else {
+                    return unprocessable("Reference does not exist");
+                };
+                *current = sha.clone();
+                ResponseTemplate::new(200)
+                    .set_body_json(fixtures::git_ref(url, owner, repo, &name, &sha))
+            }
+            _ => not_found(),
+        }
+    }
+}
+
+/// The ref name (if `name` is expected) and sha of a ref create or update.
+fn ref_update(request: &Request, name: Option<&str>) -> Option<(String, String)> {
+    let body: Value = request.body_json().ok()?;
+    let sha = body.get("sha")?.as_str()?.to_string();
+    let name = match name {
+        Some(name) => body.get(name)?.as_str()?.to_string(),
+        None => String::new(),
+    };
+    Some((name, sha))
+}
+
+fn not_found() -> ResponseTemplate {
+    ResponseTemplate::new(404).set_body_json(fixtures::error("Not Found"))
+}
+
+fn unprocessable(message: &str) -> ResponseTemplate {
+    ResponseTemplate::new(422).set_body_json(fixtures::error(message))
+}
@@ -0,0 +1,67 @@
+//! Support for testing syncs hermetically: a fake GitHub, canned fixtures
+//! and a system wired to both.
+//!
+//! Start a [`MockGithub`], add the repositories the projects release, sync
+//! a [`system`] over a [`StaticRegistry`] and look at where the fake's refs
+//! ended up.
+
+pub mod fixtures;
+mod github;
+
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use anyhow::Context;
+use hor_core::{HorSystem, InitializedState, RefType};
+use hor_registry::{Registry, SourceProject, SourceProjects};
+use serde_json::{json, Value};
+
+pub use github::MockGithub;
+
+/// A registry of fixed projects.
+pub struct StaticRegistry(pub SourceProjects);
+
+impl Registry for StaticRegistry {
+    fn get_projects(&self) -> &SourceProjects {
+        &self.0
+    }
+}
+
+/// The project releasing `owner/repo` to `env` with defaults otherwise.
+pub fn github_project(owner: &str, repo: &str, env: &str) -> anyhow::Result<SourceProject> {
+    Ok(serde_json::from_value(json!({
+        "github": { "owner": owner, "repo": repo, "env": env }
+    }))?)
+}
+
+/// Initializes a system over `registry` against `github`. `config` is
+/// merged into the `hor` configuration, e.g. for a state store. Any number
+/// of systems can be initialized in a process, each with its own state.
+pub fn system<R: Registry + Send + Sync + 'static>(
+    registry: R,
+    github: &MockGithub,
+    config: Value,
+) -> anyhow::Result<HorSystem<InitializedState>> {
+    static CONFIGS: AtomicUsize = AtomicUsize::new(0);
+
+    let mut hor = json!({
+        "github-personal-token": "test",
+        "github-api-url": github.uri(),
+    });
+    if let (Some(hor), Value::Object(config)) = (hor.as_object_mut(), config) {
+        hor.extend(config);
+    }
+    // Configuration is only read from files, all of it as the system is
+    // created, so the file can go right after
+    let path = std::env::temp_dir().join(format!(
+        "hor-test-{}-{}.json",
+        std::process::id(),
+        CONFIGS.fetch_add(1, Ordering::Relaxed)
+    ));
+    std::fs::write(&path, json!({ "hor": hor }).to_string())?;
+    let system = path
+        .to_str()
+        .context("Temporary directory is not UTF-8")
+        .and_then(|config| Ok(HorSystem::new(RefType::new(registry), config)?));
+    std::fs::remove_file(&path)?;
+    Ok(system?.init()?)
+}
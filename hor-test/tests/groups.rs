@@ -0,0 +1,155 @@
+//! All-or-nothing releases of a group of projects against the fake
+//! GitHub.
+
+use std::collections::HashMap;
+
+use chrono::Utc;
+use hor_core::{HorSystem, InitializedState};
+use hor_registry::{ProjectId, SourceProject};
+use hor_state::{BlockReason, Freeze, FreezeScope, ProjectOutcome, SkipReason};
+use hor_test::{
+    fixtures::{self, NEXT_SHA, SHA},
+    system, MockGithub, StaticRegistry,
+};
+use serde_json::json;
+use wiremock::{
+    matchers::{method, path},
+    Mock, ResponseTemplate,
+};
+
+const OWNER: &str = "acme";
+const REPOS: [&str; 2] = ["api", "web"];
+const TAG: &str = "tags/prod";
+
+struct Group {
+    github: MockGithub,
+    system: HorSystem<InitializedState>,
+    /// By repository
+    ids: HashMap<&'static str, ProjectId>,
+}
+
+/// `acme/api` and `acme/web` released together to `prod`, with `main` at
+/// [`NEXT_SHA`] and `prod` at [`SHA`].
+async fn setup() -> anyhow::Result<Group> {
+    let github = MockGithub::start().await;
+    let mut projects = Vec::new();
+    let mut ids = HashMap::new();
+    for repo in REPOS {
+        github.add_repo(OWNER, repo, NEXT_SHA);
+        github.set_ref(OWNER, repo, TAG, Some(SHA));
+        let project: SourceProject = serde_json::from_value(json!({
+            "github": { "owner": OWNER, "repo": repo, "env": "prod", "group": "checkout" }
+        }))?;
+        let SourceProject::Github(github_project) = &project else {
+            unreachable!("a GitHub project");
+        };
+        ids.insert(repo, github_project.id());
+        projects.push(project);
+    }
+    let system = system(StaticRegistry(projects), &github, json!({}))?;
+    Ok(Group {
+        github,
+        system,
+        ids,
+    })
+}
+
+impl Group {
+    /// Outcomes of a sync, by repository.
+    async fn sync(&self) -> anyhow::Result<HashMap<&'static str, ProjectOutcome>> {
+        let report = self.system.sync().await?;
+        Ok(REPOS
+            .into_iter()
+            .map(|repo| {
+                let outcome = report
+                    .projects
+                    .iter()
+                    .find(|project| project.id == self.ids[repo])
+                    .map(|project| project.outcome.clone())
+                    .expect("every member is reported");
+                (repo, outcome)
+            })
+            .collect())
+    }
+
+    fn tag(&self, repo: &str) -> Option<String> {
+        self.github.git_ref(OWNER, repo, TAG)
+    }
+}
+
+#[tokio::test]
+async fn members_with_nothing_to_release_dont_hold_the_group() -> anyhow::Result<()> {
+    let group = setup().await?;
+    group.github.archive(OWNER, "web");
+
+    let outcomes = group.sync().await?;
+    assert!(matches!(
+        outcomes["web"],
+        ProjectOutcome::Skipped {
+            reason: SkipReason::Archived,
+            ..
+        }
+    ));
+    assert!(matches!(outcomes["api"], ProjectOutcome::Updated { .. }));
+    assert_eq!(group.tag("api").as_deref(), Some(NEXT_SHA));
+    Ok(())
+}
+
+#[tokio::test]
+async fn a_frozen_member_holds_the_group() -> anyhow::Result<()> {
+    let group = setup().await?;
+    let freeze = Freeze {
+        reason: Some("incident".to_string()),
+        frozen_at: Utc::now(),
+    };
+    group
+        .system
+        .state_store()
+        .set_freeze(
+            &FreezeScope::Project(group.ids["web"].clone()),
+            Some(&freeze),
+        )
+        .await?;
+
+    let outcomes = group.sync().await?;
+    assert!(matches!(
+        outcomes["api"],
+        ProjectOutcome::Blocked {
+            reason: BlockReason::Group,
+            ..
+        }
+    ));
+    assert_eq!(group.tag("api").as_deref(), Some(SHA));
+    Ok(())
+}
+
+#[tokio::test]
+async fn a_member_failing_to_move_moves_the_group_back() -> anyhow::Result<()> {
+    let group = setup().await?;
+    Mock::given(method("PATCH"))
+        .and(path(format!("/repos/{OWNER}/web/git/refs/{TAG}")))
+        .respond_with(
+            ResponseTemplate::new(403)
+                .set_body_json(fixtures::error("Resource not accessible by integration")),
+        )
+        .with_priority(1)
+        .mount(group.github.server())
+        .await;
+
+    let outcomes = group.sync().await?;
+    assert!(
+        matches!(outcomes["web"], ProjectOutcome::Failed { .. }),
+        "{outcomes:?}"
+    );
+    assert_eq!(
+        outcomes["api"],
+        ProjectOutcome::RolledBack {
+            from: SHA.to_string(),
+            to: NEXT_SHA.to_string(),
+            reason: format!("{} of group checkout did not move", group.ids["web"]),
+        }
+    );
+    assert_eq!(group.tag("api").as_deref(), Some(SHA));
+    assert_eq!(group.tag("web").as_deref(), Some(SHA));
+    Ok(())
+}
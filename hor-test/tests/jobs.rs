@@ -0,0 +1,89 @@
+//! Queued syncs claimed from the system's state store, as instances
+//! sharing it do: claimed once, kept by heartbeats and claimed again once
+//! the instance running them went quiet.
+
+use std::{sync::Arc, time::Duration};
+
+use chrono::Utc;
+use hor_core::{Clock, HorSystem, InitializedState, ManualClock};
+use hor_state::{JobId, JobState, RunId, SyncTarget};
+use hor_test::{fixtures::NEXT_SHA, github_project, system, MockGithub, StaticRegistry};
+use serde_json::json;
+
+/// How long a claimed job may go without a heartbeat in these tests.
+const TIMEOUT: chrono::Duration = chrono::Duration::minutes(5);
+
+async fn setup() -> anyhow::Result<(MockGithub, HorSystem<InitializedState>, Arc<ManualClock>)> {
+    let github = MockGithub::start().await;
+    github.add_repo("acme", "api", NEXT_SHA);
+    let project = github_project("acme", "api", "prod")?;
+    let clock = Arc::new(ManualClock::new(Utc::now()));
+    let system =
+        system(StaticRegistry(vec![project]), &github, json!({}))?.with_clock(clock.clone());
+    Ok((github, system, clock))
+}
+
+/// The job claimed at the clock's time, if any.
+async fn claim(
+    system: &HorSystem<InitializedState>,
+    clock: &ManualClock,
+) -> anyhow::Result<Option<JobId>> {
+    let now = clock.now();
+    let claimed = system.state_store().claim_job(now, now - TIMEOUT).await?;
+    Ok(claimed.map(|(id, _)| id))
+}
+
+fn everything() -> SyncTarget {
+    SyncTarget::Selector {
+        selector: String::new(),
+    }
+}
+
+#[tokio::test]
+async fn a_job_is_queued_and_claimed_once() -> anyhow::Result<()> {
+    let (_github, system, clock) = setup().await?;
+    let id = system.queue_sync(&everything(), Some("retried")).await?;
+    assert_eq!(system.queue_sync(&everything(), Some("retried")).await?, id);
+
+    assert_eq!(claim(&system, &clock).await?, Some(id));
+    assert_eq!(claim(&system, &clock).await?, None);
+    Ok(())
+}
+
+#[tokio::test]
+async fn heartbeats_keep_a_job_claimed() -> anyhow::Result<()> {
+    let (_github, system, clock) = setup().await?;
+    let store = system.state_store();
+    let id = system.queue_sync(&everything(), None).await?;
+    assert_eq!(claim(&system, &clock).await?, Some(id));
+
+    for _ in 0..3 {
+        clock.advance(Duration::from_secs(4 * 60));
+        store.heartbeat_job(id, clock.now()).await?;
+        assert_eq!(claim(&system, &clock).await?, None);
+    }
+    Ok(())
+}
+
+#[tokio::test]
+async fn a_job_gone_quiet_is_claimed_again() -> anyhow::Result<()> {
+    let (_github, system, clock) = setup().await?;
+    let store = system.state_store();
+    let id = system.queue_sync(&everything(), None).await?;
+    assert_eq!(claim(&system, &clock).await?, Some(id));
+
+    clock.advance(Duration::from_secs(6 * 60));
+    assert_eq!(claim(&system, &clock).await?, Some(id));
+    store
+        .finish_job(id, Some(RunId(1)), None, clock.now())
+        .await?;
+
+    // Finished jobs stay finished however long ago that was
+    clock.advance(Duration::from_secs(60 * 60));
+    assert_eq!(claim(&system, &clock).await?, None);
+    let jobs = system.recent_jobs(10).await?;
+    assert_eq!(jobs.len(), 1);
+    assert_eq!(jobs[0].1.state, JobState::Completed);
+    assert_eq!(jobs[0].1.run, Some(RunId(1)));
+    Ok(())
+}
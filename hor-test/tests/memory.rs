@@ -0,0 +1,155 @@
+//! Syncs against the fake GitHub with the default in-memory state store,
+//! each test with a system of its own.
+
+use chrono::Utc;
+use hor_core::{HorSystem, InitializedState};
+use hor_registry::{ProjectId, SourceProject};
+use hor_state::{Freeze, FreezeScope, Pin, ProjectOutcome, SkipReason};
+use hor_test::{
+    fixtures::{NEXT_SHA, SHA},
+    github_project, system, MockGithub, StaticRegistry,
+};
+use serde_json::json;
+
+const OWNER: &str = "acme";
+const REPO: &str = "api";
+const ENV: &str = "prod";
+const TAG: &str = "tags/prod";
+
+/// A system releasing `acme/api`, whose `main` is at [`NEXT_SHA`] and
+/// `prod` tag at [`SHA`], and the id of its project.
+async fn setup() -> anyhow::Result<(MockGithub, HorSystem<InitializedState>, ProjectId)> {
+    let github = MockGithub::start().await;
+    github.add_repo(OWNER, REPO, NEXT_SHA);
+    github.set_ref(OWNER, REPO, TAG, Some(SHA));
+    let project = github_project(OWNER, REPO, ENV)?;
+    let SourceProject::Github(github_project) = &project else {
+        unreachable!("github_project is a GitHub project");
+    };
+    let id = github_project.id();
+    let system = system(StaticRegistry(vec![project]), &github, json!({}))?;
+    Ok((github, system, id))
+}
+
+/// The outcome of the only project of a sync.
+async fn sync(system: &HorSystem<InitializedState>) -> anyhow::Result<ProjectOutcome> {
+    let report = system.sync().await?;
+    assert_eq!(report.projects.len(), 1, "{report:?}");
+    Ok(report.projects[0].outcome.clone())
+}
+
+fn moved() -> ProjectOutcome {
+    ProjectOutcome::Updated {
+        from: SHA.to_string(),
+        to: NEXT_SHA.to_string(),
+    }
+}
+
+fn skipped(outcome: &ProjectOutcome, expected: SkipReason) -> bool {
+    matches!(outcome, ProjectOutcome::Skipped { reason, .. } if *reason == expected)
+}
+
+#[tokio::test]
+async fn sync_moves_the_env_tag_to_main() -> anyhow::Result<()> {
+    let (github, system, _) = setup().await?;
+
+    assert_eq!(sync(&system).await?, moved());
+    assert_eq!(github.git_ref(OWNER, REPO, TAG).as_deref(), Some(NEXT_SHA));
+    assert_eq!(
+        sync(&system).await?,
+        ProjectOutcome::Unchanged {
+            sha: NEXT_SHA.to_string()
+        }
+    );
+    Ok(())
+}
+
+#[tokio::test]
+async fn sync_creates_a_missing_env_tag() -> anyhow::Result<()> {
+    let (github, system, _) = setup().await?;
+    github.set_ref(OWNER, REPO, TAG, None);
+
+    assert_eq!(
+        sync(&system).await?,
+        ProjectOutcome::Created {
+            sha: NEXT_SHA.to_string()
+        }
+    );
+    assert_eq!(github.git_ref(OWNER, REPO, TAG).as_deref(), Some(NEXT_SHA));
+    Ok(())
+}
+
+#[tokio::test]
+async fn freezes_hold_the_env_tag_until_lifted() -> anyhow::Result<()> {
+    let (github, system, id) = setup().await?;
+    let store = system.state_store();
+    let freeze = Freeze {
+        reason: Some("incident".to_string()),
+        frozen_at: Utc::now(),
+    };
+
+    for scope in [FreezeScope::Global, FreezeScope::Project(id)] {
+        store.set_freeze(&scope, Some(&freeze)).await?;
+        assert!(skipped(&sync(&system).await?, SkipReason::Frozen));
+        assert_eq!(github.git_ref(OWNER, REPO, TAG).as_deref(), Some(SHA));
+        store.set_freeze(&scope, None).await?;
+    }
+    assert_eq!(sync(&system).await?, moved());
+    Ok(())
+}
+
+#[tokio::test]
+async fn pins_hold_the_env_tag_at_their_commit() -> anyhow::Result<()> {
+    let (github, system, id) = setup().await?;
+    let store = system.state_store();
+    let pin = Pin {
+        sha: SHA.to_string(),
+        reason: Some("bisecting".to_string()),
+        pinned_at: Utc::now(),
+    };
+
+    store.set_pin(&id, ENV, Some(&pin)).await?;
+    assert_eq!(
+        sync(&system).await?,
+        ProjectOutcome::Unchanged {
+            sha: SHA.to_string()
+        }
+    );
+    assert_eq!(github.git_ref(OWNER, REPO, TAG).as_deref(), Some(SHA));
+
+    store.set_pin(&id, ENV, None).await?;
+    assert_eq!(sync(&system).await?, moved());
+    Ok(())
+}
+
+#[tokio::test]
+async fn deleted_projects_are_skipped_until_restored() -> anyhow::Result<()> {
+    let (github, system, id) = setup().await?;
+
+    assert!(system.delete_project(&id, Some("archived")).await?);
+    // The first deletion is kept
+    assert!(!system.delete_project(&id, None).await?);
+    let deleted = system.deleted_projects().await?;
+    assert_eq!(deleted.len(), 1);
+    assert_eq!(deleted[0].0, id);
+    assert_eq!(deleted[0].1.reason.as_deref(), Some("archived"));
+
+    assert!(skipped(&sync(&system).await?, SkipReason::Deleted));
+    assert_eq!(github.git_ref(OWNER, REPO, TAG).as_deref(), Some(SHA));
+
+    assert!(system.restore_project(&id).await?);
+    assert!(!system.restore_project(&id).await?);
+    assert!(system.deleted_projects().await?.is_empty());
+    assert_eq!(sync(&system).await?, moved());
+    Ok(())
+}
+
+#[tokio::test]
+async fn only_registered_projects_can_be_deleted() -> anyhow::Result<()> {
+    let (_github, system, _) = setup().await?;
+
+    let unknown = ProjectId::new("github/acme/unknown/prod");
+    assert!(system.delete_project(&unknown, None).await.is_err());
+    assert!(system.deleted_projects().await?.is_empty());
+    Ok(())
+}
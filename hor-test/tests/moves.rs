@@ -0,0 +1,68 @@
+//! Env tag moves racing other writers of the tag, against the fake
+//! GitHub.
+
+use hor_core::{HorSystem, InitializedState};
+use hor_state::ProjectOutcome;
+use hor_test::{
+    fixtures::{NEXT_SHA, SHA},
+    github_project, system, MockGithub, StaticRegistry,
+};
+use serde_json::json;
+
+const OWNER: &str = "acme";
+const REPO: &str = "api";
+const TAG: &str = "tags/prod";
+/// Where someone else moved the tag
+const OTHER_SHA: &str = "0f1e2d3c4b5a69788796a5b4c3d2e1f00f1e2d3c";
+
+/// A system releasing `acme/api`, whose `main` is at [`NEXT_SHA`], with
+/// the `prod` tag left to the test.
+async fn setup() -> anyhow::Result<(MockGithub, HorSystem<InitializedState>)> {
+    let github = MockGithub::start().await;
+    github.add_repo(OWNER, REPO, NEXT_SHA);
+    let project = github_project(OWNER, REPO, "prod")?;
+    let system = system(StaticRegistry(vec![project]), &github, json!({}))?;
+    Ok((github, system))
+}
+
+async fn sync(system: &HorSystem<InitializedState>) -> anyhow::Result<ProjectOutcome> {
+    let report = system.sync().await?;
+    assert_eq!(report.projects.len(), 1, "{report:?}");
+    Ok(report.projects[0].outcome.clone())
+}
+
+#[tokio::test]
+async fn a_tag_moved_since_it_was_read_is_left_alone() -> anyhow::Result<()> {
+    let (github, system) = setup().await?;
+    github.set_ref(OWNER, REPO, TAG, Some(OTHER_SHA));
+    // Planned from SHA, moved to OTHER_SHA by the time the move re-reads it
+    github.stale_ref(OWNER, REPO, TAG, SHA, 1).await;
+
+    assert_eq!(
+        sync(&system).await?,
+        ProjectOutcome::Conflict {
+            expected: Some(SHA.to_string()),
+            actual: Some(OTHER_SHA.to_string()),
+        }
+    );
+    assert_eq!(github.git_ref(OWNER, REPO, TAG).as_deref(), Some(OTHER_SHA));
+    Ok(())
+}
+
+#[tokio::test]
+async fn a_tag_deleted_before_it_moved_is_recreated() -> anyhow::Result<()> {
+    let (github, system) = setup().await?;
+    // Read at SHA by the plan and the move, gone once GitHub is asked to
+    // move it, so the update is refused
+    github.stale_ref(OWNER, REPO, TAG, SHA, 2).await;
+
+    assert_eq!(
+        sync(&system).await?,
+        ProjectOutcome::Recreated {
+            from: SHA.to_string(),
+            to: NEXT_SHA.to_string(),
+        }
+    );
+    assert_eq!(github.git_ref(OWNER, REPO, TAG).as_deref(), Some(NEXT_SHA));
+    Ok(())
+}
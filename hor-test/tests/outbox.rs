@@ -0,0 +1,205 @@
+//! Announcements of env tag moves held in the outbox around the move, and
+//! settled by the next sync when a crash left them pending.
+
+use std::sync::Arc;
+
+use chrono::{DateTime, Duration, Utc};
+use hor_core::{Clock, HorSystem, InitializedState, ManualClock};
+use hor_registry::{ProjectId, SourceProject};
+use hor_state::{Deployment, Event, OutboxEntry, ProjectOutcome};
+use hor_test::{
+    fixtures::{NEXT_SHA, SHA},
+    github_project, system, MockGithub, StaticRegistry,
+};
+use serde_json::{json, Value};
+
+const OWNER: &str = "acme";
+const REPO: &str = "api";
+const ENV: &str = "prod";
+const TAG: &str = "tags/prod";
+const SINK: &str = "hook";
+
+struct Outbox {
+    github: MockGithub,
+    system: HorSystem<InitializedState>,
+    clock: Arc<ManualClock>,
+    id: ProjectId,
+}
+
+/// A system releasing `acme/api`, whose `main` is at [`NEXT_SHA`] and
+/// `prod` tag at [`SHA`], announcing to a webhook.
+async fn setup(config: Value) -> anyhow::Result<Outbox> {
+    let github = MockGithub::start().await;
+    github.add_repo(OWNER, REPO, NEXT_SHA);
+    github.set_ref(OWNER, REPO, TAG, Some(SHA));
+    let project = github_project(OWNER, REPO, ENV)?;
+    let SourceProject::Github(github_project) = &project else {
+        unreachable!("github_project is a GitHub project");
+    };
+    let id = github_project.id();
+    let mut config = config;
+    config["notifications"] = json!([{
+        "name": SINK,
+        "kind": "webhook",
+        "url": format!("{}/hook", github.uri()),
+    }]);
+    let clock = Arc::new(ManualClock::new(Utc::now()));
+    let system = system(StaticRegistry(vec![project]), &github, config)?.with_clock(clock.clone());
+    Ok(Outbox {
+        github,
+        system,
+        clock,
+        id,
+    })
+}
+
+impl Outbox {
+    fn ref_moved(&self, to: &str, at: DateTime<Utc>) -> Event {
+        Event::RefMoved {
+            project: self.id.clone(),
+            owner: OWNER.to_string(),
+            repo: REPO.to_string(),
+            env: ENV.to_string(),
+            from: Some(SHA.to_string()),
+            to: to.to_string(),
+            at,
+            simulated: false,
+            risk: None,
+        }
+    }
+
+    /// Leaves a move announced as if a crash followed, `ago` back.
+    async fn hold(&self, to: &str, ago: Duration) -> anyhow::Result<()> {
+        let event = self.ref_moved(to, self.clock.now() - ago);
+        self.system
+            .state_store()
+            .enqueue_outbox(&[OutboxEntry::pending(SINK, event)])
+            .await?;
+        Ok(())
+    }
+
+    /// `(to, at)` of the moves due to be announced.
+    async fn announced(&self) -> anyhow::Result<Vec<(String, DateTime<Utc>)>> {
+        let due = self
+            .system
+            .state_store()
+            .due_outbox(DateTime::<Utc>::MAX_UTC, 100)
+            .await?;
+        Ok(due
+            .into_iter()
+            .filter_map(|(_, entry)| match entry.event {
+                Event::RefMoved { to, at, .. } => Some((to, at)),
+                _ => None,
+            })
+            .collect())
+    }
+
+    async fn pending(&self) -> anyhow::Result<usize> {
+        Ok(self.system.state_store().pending_outbox().await?.len())
+    }
+
+    async fn deployed(&self) -> anyhow::Result<Option<Deployment>> {
+        Ok(self
+            .system
+            .state_store()
+            .last_deployment(&self.id, ENV)
+            .await?)
+    }
+}
+
+#[tokio::test]
+async fn a_move_is_announced_once_it_happened() -> anyhow::Result<()> {
+    let outbox = setup(json!({})).await?;
+    let report = outbox.system.sync().await?;
+    assert!(matches!(
+        report.projects[0].outcome,
+        ProjectOutcome::Updated { .. }
+    ));
+    let announced = outbox.announced().await?;
+    assert_eq!(announced, vec![(NEXT_SHA.to_string(), outbox.clock.now())]);
+    assert_eq!(outbox.pending().await?, 0);
+    Ok(())
+}
+
+#[tokio::test]
+async fn a_failed_move_is_not_announced() -> anyhow::Result<()> {
+    let outbox = setup(json!({ "protected-refs": [ENV] })).await?;
+    let report = outbox.system.sync().await?;
+    assert!(matches!(
+        report.projects[0].outcome,
+        ProjectOutcome::Failed { .. }
+    ));
+    assert_eq!(
+        outbox.github.git_ref(OWNER, REPO, TAG).as_deref(),
+        Some(SHA)
+    );
+    assert!(outbox.announced().await?.is_empty());
+    assert_eq!(outbox.pending().await?, 0);
+    Ok(())
+}
+
+#[tokio::test]
+async fn only_the_latest_interrupted_move_is_settled() -> anyhow::Result<()> {
+    let outbox = setup(json!({})).await?;
+    // The tag got to `main` before the crash, moved away and back since
+    outbox.github.set_ref(OWNER, REPO, TAG, Some(NEXT_SHA));
+    outbox.hold(NEXT_SHA, Duration::hours(3)).await?;
+    outbox.hold(NEXT_SHA, Duration::hours(2)).await?;
+    outbox.hold(SHA, Duration::hours(1)).await?;
+    let latest = outbox.clock.now() - Duration::hours(2);
+
+    outbox.system.sync().await?;
+    assert_eq!(
+        outbox.announced().await?,
+        vec![(NEXT_SHA.to_string(), latest)]
+    );
+    assert_eq!(outbox.pending().await?, 0);
+    let deployed = outbox.deployed().await?.expect("settled move is recorded");
+    assert_eq!(
+        (deployed.sha.as_str(), deployed.deployed_at),
+        (NEXT_SHA, latest)
+    );
+    Ok(())
+}
+
+#[tokio::test]
+async fn a_move_deployed_again_since_is_dropped() -> anyhow::Result<()> {
+    let outbox = setup(json!({})).await?;
+    outbox.github.set_ref(OWNER, REPO, TAG, Some(NEXT_SHA));
+    let redeployed = Deployment {
+        sha: NEXT_SHA.to_string(),
+        deployed_at: outbox.clock.now() - Duration::minutes(30),
+    };
+    outbox
+        .system
+        .state_store()
+        .record_deployment(&outbox.id, ENV, &redeployed)
+        .await?;
+    outbox.hold(NEXT_SHA, Duration::hours(1)).await?;
+
+    outbox.system.sync().await?;
+    assert!(outbox.announced().await?.is_empty());
+    assert_eq!(outbox.pending().await?, 0);
+    let deployed = outbox.deployed().await?.expect("deployment is kept");
+    assert_eq!(deployed.deployed_at, redeployed.deployed_at);
+    Ok(())
+}
+
+#[tokio::test]
+async fn a_move_still_in_flight_is_left_pending() -> anyhow::Result<()> {
+    let outbox = setup(json!({})).await?;
+    outbox.github.set_ref(OWNER, REPO, TAG, Some(NEXT_SHA));
+    outbox.hold(NEXT_SHA, Duration::minutes(1)).await?;
+
+    outbox.system.sync().await?;
+    assert!(outbox.announced().await?.is_empty());
+    assert_eq!(outbox.pending().await?, 1);
+
+    outbox
+        .clock
+        .advance(std::time::Duration::from_secs(15 * 60));
+    outbox.system.sync().await?;
+    assert_eq!(outbox.announced().await?.len(), 1);
+    assert_eq!(outbox.pending().await?, 0);
+    Ok(())
+}
@@ -0,0 +1,202 @@
+//! Region-by-region rollouts against the fake GitHub, stepping a manual
+//! clock through bake times.
+
+use std::{collections::HashSet, sync::Arc, time::Duration};
+
+use chrono::Utc;
+use hor_core::{HorSystem, InitializedState, ManualClock, Priority};
+use hor_registry::{ProjectId, SourceProject};
+use hor_state::{BlockReason, ProjectOutcome};
+use hor_test::{
+    fixtures::{NEXT_SHA, SHA},
+    system, MockGithub, StaticRegistry,
+};
+use serde_json::{json, Value};
+
+const OWNER: &str = "acme";
+const REPO: &str = "api";
+const BAKE: Duration = Duration::from_secs(60 * 60);
+
+struct Rollout {
+    github: MockGithub,
+    system: HorSystem<InitializedState>,
+    clock: Arc<ManualClock>,
+    /// By region, in rollout order
+    ids: Vec<ProjectId>,
+}
+
+/// `acme/api` rolling out to `prod-eu` then `prod-us`, both at [`SHA`]
+/// while `main` is at [`NEXT_SHA`].
+async fn setup(rollout: Value, config: Value) -> anyhow::Result<Rollout> {
+    let github = MockGithub::start().await;
+    github.add_repo(OWNER, REPO, NEXT_SHA);
+    github.set_ref(OWNER, REPO, "tags/prod-eu", Some(SHA));
+    github.set_ref(OWNER, REPO, "tags/prod-us", Some(SHA));
+    let project: SourceProject = serde_json::from_value(json!({
+        "github": {
+            "owner": OWNER,
+            "repo": REPO,
+            "env": "prod",
+            "regions": ["eu", "us"],
+            "rollout": rollout,
+        }
+    }))?;
+    let SourceProject::Github(github_project) = &project else {
+        unreachable!("a GitHub project");
+    };
+    let ids = github_project
+        .expand_regions()
+        .iter()
+        .map(|region| region.id())
+        .collect();
+    let clock = Arc::new(ManualClock::new(Utc::now()));
+    let system = system(StaticRegistry(vec![project]), &github, config)?.with_clock(clock.clone());
+    Ok(Rollout {
+        github,
+        system,
+        clock,
+        ids,
+    })
+}
+
+impl Rollout {
+    /// The outcome of every region of a sync, in rollout order.
+    async fn sync(&self) -> anyhow::Result<Vec<ProjectOutcome>> {
+        outcomes(&self.ids, self.system.sync().await?)
+    }
+
+    async fn sync_ids(&self, ids: &[&ProjectId]) -> anyhow::Result<Vec<ProjectOutcome>> {
+        let ids: HashSet<_> = ids.iter().map(|id| (*id).clone()).collect();
+        let report = self.system.sync_ids(&ids, Priority::Triggered).await?;
+        outcomes(&self.ids, report)
+    }
+
+    fn tag(&self, region: &str) -> Option<String> {
+        self.github
+            .git_ref(OWNER, REPO, &format!("tags/prod-{region}"))
+    }
+}
+
+fn outcomes(
+    ids: &[ProjectId],
+    report: hor_state::SyncReport,
+) -> anyhow::Result<Vec<ProjectOutcome>> {
+    Ok(ids
+        .iter()
+        .map(|id| {
+            let report = report.projects.iter().find(|report| &report.id == id);
+            report
+                .unwrap_or_else(|| panic!("no report of {id}"))
+                .outcome
+                .clone()
+        })
+        .collect())
+}
+
+fn moved() -> ProjectOutcome {
+    ProjectOutcome::Updated {
+        from: SHA.to_string(),
+        to: NEXT_SHA.to_string(),
+    }
+}
+
+fn unchanged() -> ProjectOutcome {
+    ProjectOutcome::Unchanged {
+        sha: NEXT_SHA.to_string(),
+    }
+}
+
+fn blocked(outcome: &ProjectOutcome, expected: BlockReason) -> bool {
+    matches!(outcome, ProjectOutcome::Blocked { reason, .. } if *reason == expected)
+}
+
+#[tokio::test]
+async fn regions_release_once_the_previous_one_baked() -> anyhow::Result<()> {
+    let rollout = setup(json!({ "bake-secs": BAKE.as_secs() }), json!({})).await?;
+
+    let outcomes = rollout.sync().await?;
+    assert_eq!(outcomes[0], moved());
+    assert!(blocked(&outcomes[1], BlockReason::Baking), "{outcomes:?}");
+    assert_eq!(rollout.tag("us").as_deref(), Some(SHA));
+
+    rollout.clock.advance(BAKE / 2);
+    let outcomes = rollout.sync().await?;
+    assert_eq!(outcomes[0], unchanged());
+    assert!(blocked(&outcomes[1], BlockReason::Baking), "{outcomes:?}");
+
+    rollout.clock.advance(BAKE / 2);
+    assert_eq!(rollout.sync().await?, [unchanged(), moved()]);
+    assert_eq!(rollout.tag("us").as_deref(), Some(NEXT_SHA));
+    Ok(())
+}
+
+#[tokio::test]
+async fn selecting_a_later_region_walks_the_earlier_ones() -> anyhow::Result<()> {
+    let rollout = setup(json!({ "bake-secs": BAKE.as_secs() }), json!({})).await?;
+
+    let outcomes = rollout.sync_ids(&[&rollout.ids[1]]).await?;
+    assert_eq!(outcomes[0], moved());
+    assert!(blocked(&outcomes[1], BlockReason::Baking), "{outcomes:?}");
+    assert_eq!(rollout.tag("us").as_deref(), Some(SHA));
+    Ok(())
+}
+
+#[tokio::test]
+async fn failing_region_halts_and_rolls_back_the_rollout() -> anyhow::Result<()> {
+    let rollout = setup(
+        json!({ "roll-back": true }),
+        json!({ "protected-refs": ["prod-us"] }),
+    )
+    .await?;
+
+    let outcomes = rollout.sync().await?;
+    assert!(
+        matches!(&outcomes[0], ProjectOutcome::RolledBack { from, to, .. }
+            if from == SHA && to == NEXT_SHA),
+        "{outcomes:?}"
+    );
+    assert!(
+        matches!(outcomes[1], ProjectOutcome::Failed { .. }),
+        "{outcomes:?}"
+    );
+    assert_eq!(rollout.tag("eu").as_deref(), Some(SHA));
+    assert_eq!(rollout.tag("us").as_deref(), Some(SHA));
+    Ok(())
+}
+
+#[tokio::test]
+async fn rolls_back_regions_released_by_earlier_syncs() -> anyhow::Result<()> {
+    let rollout = setup(
+        json!({ "bake-secs": BAKE.as_secs(), "roll-back": true }),
+        json!({ "protected-refs": ["prod-us"] }),
+    )
+    .await?;
+
+    let outcomes = rollout.sync().await?;
+    assert_eq!(outcomes[0], moved());
+    assert_eq!(rollout.tag("eu").as_deref(), Some(NEXT_SHA));
+
+    rollout.clock.advance(BAKE);
+    let outcomes = rollout.sync().await?;
+    assert!(
+        matches!(&outcomes[0], ProjectOutcome::RolledBack { from, to, .. }
+            if from == SHA && to == NEXT_SHA),
+        "{outcomes:?}"
+    );
+    assert_eq!(rollout.tag("eu").as_deref(), Some(SHA));
+    Ok(())
+}
+
+#[tokio::test]
+async fn halting_without_roll_back_keeps_earlier_regions() -> anyhow::Result<()> {
+    let rollout = setup(json!({}), json!({ "protected-refs": ["prod-us"] })).await?;
+
+    let outcomes = rollout.sync().await?;
+    assert_eq!(outcomes[0], moved());
+    assert!(
+        matches!(outcomes[1], ProjectOutcome::Failed { .. }),
+        "{outcomes:?}"
+    );
+    assert_eq!(rollout.tag("eu").as_deref(), Some(NEXT_SHA));
+    Ok(())
+}
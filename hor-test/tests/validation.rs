@@ -0,0 +1,46 @@
+//! Checks of registered projects against the fake GitHub.
+
+use hor_core::ProjectValidationError;
+use hor_registry::SourceProject;
+use hor_test::{fixtures::SHA, github_project, system, MockGithub, StaticRegistry};
+use serde_json::json;
+
+const OWNER: &str = "acme";
+
+#[tokio::test]
+async fn reachable_projects_are_valid() -> anyhow::Result<()> {
+    let github = MockGithub::start().await;
+    github.add_repo(OWNER, "api", SHA);
+    let project = github_project(OWNER, "api", "prod")?;
+    let system = system(StaticRegistry(vec![project]), &github, json!({}))?;
+
+    let errors = system.validate_projects().await;
+    assert!(errors.is_empty(), "{errors:?}");
+    Ok(())
+}
+
+#[tokio::test]
+async fn every_failing_check_is_reported() -> anyhow::Result<()> {
+    let github = MockGithub::start().await;
+    github.add_empty_repo(OWNER, "web");
+    let regional: SourceProject = serde_json::from_value(json!({
+        "github": { "owner": OWNER, "repo": "api", "env": "prod", "regions": ["eu", "us"] }
+    }))?;
+    let empty = github_project(OWNER, "web", "prod")?;
+    let system = system(StaticRegistry(vec![regional, empty]), &github, json!({}))?;
+
+    let errors = system.validate_projects().await;
+    let missing = errors
+        .iter()
+        .filter(|error| matches!(error, ProjectValidationError::RepositoryNotFound { .. }))
+        .count();
+    assert_eq!(missing, 2, "one per region: {errors:?}");
+    assert!(
+        errors
+            .iter()
+            .any(|error| matches!(error, ProjectValidationError::NoDefaultBranch { .. })),
+        "{errors:?}"
+    );
+    assert_eq!(errors.len(), 3, "{errors:?}");
+    Ok(())
+}
@@ -1,9 +1,268 @@
+use std::{fs::File, path::PathBuf};
+
+use anyhow::{bail, Context};
+use clap::{Parser, Subcommand};
 use hor_core::{HorSystem, RefType};
-use hor_registry::file::FileBasedRegistry;
+use hor_registry::{file::FileBasedRegistry, ProjectId, Registry, RegistryDiff};
+use hor_state::{RunId, StateSnapshot, SyncTarget};
+use tracing::{info, warn};
+
+#[derive(Parser)]
+struct Cli {
+    #[command(subcommand)]
+    command: Option<Command>,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Run the sync scheduler until interrupted (the default)
+    Run,
+    /// Check the registered projects against GitHub, failing if any is
+    /// misconfigured
+    Validate,
+    /// Evaluate the policies of a past run again from its recorded inputs,
+    /// without touching GitHub
+    Replay { run: i64 },
+    /// Write the state store's contents to a JSON file
+    ExportState { path: PathBuf },
+    /// Load a JSON file written by `export-state` into the state store
+    ImportState { path: PathBuf },
+    /// Show what replacing the registry with another file would add,
+    /// remove and modify, without applying it
+    Diff {
+        /// Registry file to compare with, e.g. the one proposed in a pull
+        /// request
+        path: String,
+        /// Fail if there are any differences
+        #[arg(long)]
+        check: bool,
+    },
+    /// Queue a sync for whichever running instance claims it first
+    QueueSync {
+        /// Label selector of the projects to sync, e.g. `team=payments`;
+        /// every project if left out
+        selector: Option<String>,
+        /// Sync only these projects instead
+        #[arg(long = "project", conflicts_with = "selector")]
+        projects: Vec<String>,
+        /// Queue at most one sync under this key, so a retried invocation
+        /// doesn't sync twice
+        #[arg(long)]
+        idempotency_key: Option<String>,
+    },
+    /// List the most recently queued syncs
+    Jobs {
+        #[arg(long, default_value_t = 20)]
+        limit: usize,
+    },
+    /// Stop syncing a project but keep its history, until it's restored or
+    /// purged after the retention period
+    DeleteProject {
+        id: String,
+        #[arg(long)]
+        reason: Option<String>,
+    },
+    /// Sync a deleted project again
+    RestoreProject { id: String },
+    /// List the deleted projects
+    DeletedProjects,
+    /// Show which projects drifted from where their releases should have
+    /// taken them, going by the last day's runs
+    DriftReport,
+    /// Apply pending state store migrations
+    Migrate {
+        /// Only report pending migrations, failing if there are any
+        #[arg(long)]
+        check: bool,
+    },
+}
+
+impl Command {
+    /// Whether the command only makes sense against state that outlives
+    /// it, rather than the in-memory store gone once it exits.
+    fn needs_persistent_state(&self) -> bool {
+        matches!(
+            self,
+            Command::ExportState { .. }
+                | Command::ImportState { .. }
+                | Command::QueueSync { .. }
+                | Command::DeleteProject { .. }
+                | Command::RestoreProject { .. }
+                | Command::Migrate { .. }
+        )
+    }
+}
 
 #[tokio::main]
 async fn main() -> anyhow::Result<()> {
+    let cli = Cli::parse();
     let registry = RefType::new(FileBasedRegistry::from_file("examples/example")?);
-    HorSystem::new(registry, "local")?.init()?;
+    let system = HorSystem::new(registry.clone(), "local")?.init()?;
+
+    let command = cli.command.unwrap_or(Command::Run);
+    if command.needs_persistent_state() && !system.state_store().persistent() {
+        bail!("The state store is in memory, configure a sqlite or postgres `state-store` instead");
+    }
+    match command {
+        Command::Run => {
+            system
+                .state_store()
+                .prepare()
+                .await
+                .context("State store is not ready")?;
+            // Reported rather than fatal, so a GitHub hiccup at startup
+            // doesn't keep every other project from syncing
+            let deadline = system.startup_validation_timeout();
+            let total = registry.get_projects().len();
+            info!(total, "Validating projects");
+            match tokio::time::timeout(deadline, system.validate_projects()).await {
+                Ok(errors) => {
+                    info!(total, invalid = errors.len(), "Validated projects");
+                    for error in errors {
+                        let error = format!("{:#}", anyhow::Error::new(error));
+                        warn!(error = system.redactor().redact(&error), "Invalid project");
+                    }
+                }
+                // Syncs report whatever was left unchecked as they go
+                Err(_) => warn!(?deadline, "Project validation timed out, starting anyway"),
+            }
+            let system = system.start();
+            tokio::signal::ctrl_c().await?;
+            system.shutdown().await;
+        }
+        Command::Validate => {
+            let errors = system.validate_projects().await;
+            let count = errors.len();
+            for error in errors {
+                let error = format!("{:#}", anyhow::Error::new(error));
+                eprintln!("{}", system.redactor().redact(&error));
+            }
+            if count > 0 {
+                bail!("{count} invalid project(s)");
+            }
+        }
+        Command::Replay { run } => {
+            for project in system.replay(RunId(run)).await? {
+                println!("{} ({}): {:?}", project.id, project.env, project.outcome);
+                if let Some(inputs) = &project.inputs {
+                    println!(
+                        "  {:?} -> {} from {:?}",
+                        inputs.from, inputs.to, inputs.source
+                    );
+                }
+                for decision in &project.replayed {
+                    let verdict = if decision.passed { "pass" } else { "FAIL" };
+                    println!("  {verdict} {}: {}", decision.rule, decision.detail);
+                }
+                if project.diverged() {
+                    println!("  recorded decisions differ: {:?}", project.recorded);
+                }
+            }
+        }
+        Command::ExportState { path } => {
+            let snapshot = system.state_store().export_state().await?;
+            let file = File::create(&path).with_context(|| format!("Unable to create {path:?}"))?;
+            serde_json::to_writer_pretty(file, &snapshot)?;
+        }
+        Command::ImportState { path } => {
+            let file = File::open(&path).with_context(|| format!("Unable to open {path:?}"))?;
+            let snapshot: StateSnapshot = serde_json::from_reader(file)?;
+            system.state_store().import_state(&snapshot).await?;
+        }
+        Command::Diff { path, check } => {
+            let proposed = FileBasedRegistry::from_file(&path)?;
+            let diff = RegistryDiff::between(registry.get_projects(), proposed.get_projects());
+            for (sign, entries) in [
+                ('+', &diff.added),
+                ('-', &diff.removed),
+                ('~', &diff.modified),
+            ] {
+                for entry in entries {
+                    println!("{sign} {entry}");
+                }
+            }
+            if check && !diff.is_empty() {
+                bail!("Registry differs from {path}");
+            }
+        }
+        Command::QueueSync {
+            selector,
+            projects,
+            idempotency_key,
+        } => {
+            let target = if projects.is_empty() {
+                SyncTarget::Selector {
+                    selector: selector.unwrap_or_default(),
+                }
+            } else {
+                SyncTarget::Projects {
+                    ids: projects.into_iter().map(ProjectId::new).collect(),
+                }
+            };
+            println!(
+                "Queued sync {}",
+                system
+                    .queue_sync(&target, idempotency_key.as_deref())
+                    .await?
+            );
+        }
+        Command::Jobs { limit } => {
+            for (id, job) in system.recent_jobs(limit).await? {
+                println!(
+                    "{id} {:?} queued {}: {:?}",
+                    job.state, job.queued_at, job.target
+                );
+                if let Some(run) = job.run {
+                    println!("  run {run}");
+                }
+                if let Some(error) = &job.error {
+                    println!("  error: {}", system.redactor().redact(error));
+                }
+            }
+        }
+        Command::DeleteProject { id, reason } => {
+            let id = ProjectId::new(id);
+            if !system.delete_project(&id, reason.as_deref()).await? {
+                println!("{id} was already deleted");
+            }
+        }
+        Command::RestoreProject { id } => {
+            let id = ProjectId::new(id);
+            if !system.restore_project(&id).await? {
+                bail!("{id} isn't deleted");
+            }
+        }
+        Command::DeletedProjects => {
+            for (id, deletion) in system.deleted_projects().await? {
+                let reason = deletion.reason.as_deref().unwrap_or("no reason given");
+                println!("{id} deleted {}: {reason}", deletion.deleted_at);
+                if let Some(purged_at) = deletion.purged_at {
+                    println!("  purged {purged_at}");
+                }
+            }
+        }
+        Command::DriftReport => {
+            let report = system.drift_report().await?;
+            println!(
+                "{} converged, {} drifted since {}",
+                report.converged,
+                report.drifted.len(),
+                report.since
+            );
+            for project in &report.drifted {
+                println!(
+                    "{} ({}) since {}: {:?}",
+                    project.id, project.env, project.drifted_since, project.outcome
+                );
+            }
+        }
+        Command::Migrate { check: true } => {
+            let pending = system.state_store().pending_migrations().await?;
+            if !pending.is_empty() {
+                bail!("Pending state store migrations: {pending:?}");
+            }
+        }
+        Command::Migrate { check: false } => system.state_store().apply_migrations().await?,
+    }
     Ok(())
 }